@@ -0,0 +1,98 @@
+use commonlib::{
+    compute::{storage_texture_layout_entry, workgroup_count, StorageBuffer, StorageTexture},
+    renderer::RendererBuilder,
+};
+use wgpu::{Backends, BufferUsages, ShaderStages, StorageTextureAccess, TextureFormat};
+
+const IMAGE_WIDTH: u32 = 64;
+const IMAGE_HEIGHT: u32 = 24;
+
+// Runs without a window. Pass `fallback` to use a software adapter.
+fn main() {
+    env_logger::init();
+
+    let force_fallback_adapter = std::env::args().any(|arg| arg == "fallback");
+
+    let renderer = RendererBuilder::headless()
+        .set_backends(Backends::all())
+        .set_force_fallback_adapter(force_fallback_adapter)
+        .create_instance()
+        .get_adapter()
+        .get_device(Some("Device"))
+        .create_compute_shader_module(Some("Compute Shader"), include_str!("compute.wgsl"))
+        .add_compute_bind_group_layout(
+            Some("Numbers Bind Group Layout"),
+            &[StorageBuffer::<u32>::bind_group_layout_entry(
+                0,
+                ShaderStages::COMPUTE,
+                false,
+            )],
+        )
+        .add_compute_bind_group_layout(
+            Some("Image Bind Group Layout"),
+            &[storage_texture_layout_entry(
+                0,
+                ShaderStages::COMPUTE,
+                TextureFormat::R32Float,
+                StorageTextureAccess::WriteOnly,
+            )],
+        )
+        .create_compute_pipeline(Some("Collatz Pipeline"), "cs_collatz")
+        .create_compute_pipeline(Some("Mandelbrot Pipeline"), "cs_mandelbrot")
+        .build();
+
+    let device = renderer.device().unwrap();
+    let queue = renderer.queue().unwrap();
+    println!("{:?}", renderer.adapter().unwrap().get_info());
+
+    let inputs: Vec<u32> = (1..=16).chain([27, 97, 871, 6171]).collect();
+    let numbers = StorageBuffer::new(device, Some("Numbers"), &inputs, BufferUsages::empty());
+    let image = StorageTexture::new(
+        device,
+        Some("Mandelbrot"),
+        IMAGE_WIDTH,
+        IMAGE_HEIGHT,
+        TextureFormat::R32Float,
+    );
+
+    let numbers_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Numbers Bind Group"),
+        layout: &renderer.compute_bind_group_layouts()[0],
+        entries: &[numbers.bind_group_entry(0)],
+    });
+    let image_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Image Bind Group"),
+        layout: &renderer.compute_bind_group_layouts()[1],
+        entries: &[image.bind_group_entry(0)],
+    });
+    let bind_groups = [&numbers_bind_group, &image_bind_group];
+
+    renderer.dispatch(0, &bind_groups, [workgroup_count(numbers.len(), 64), 1, 1]);
+    renderer.dispatch(
+        1,
+        &bind_groups,
+        [
+            workgroup_count(IMAGE_WIDTH, 8),
+            workgroup_count(IMAGE_HEIGHT, 8),
+            1,
+        ],
+    );
+
+    println!("Collatz steps:");
+    for (input, steps) in inputs.iter().zip(numbers.read(device, queue)) {
+        println!("{:>6}: {}", input, steps);
+    }
+
+    println!("Mandelbrot:");
+    let shades = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+    let pixels: Vec<f32> = bytemuck::pod_collect_to_vec(&image.read(device, queue));
+    for row in pixels.chunks(IMAGE_WIDTH as usize) {
+        let line: String = row
+            .iter()
+            .map(|value| {
+                shades[((value * (shades.len() - 1) as f32) as usize).min(shades.len() - 1)]
+            })
+            .collect();
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,44 @@
+@binding(0) @group(0) var<storage, read_write> numbers : array<u32>;
+@binding(0) @group(1) var image : texture_storage_2d<r32float, write>;
+
+// Steps for the Collatz sequence starting at `n` to reach 1.
+fn collatzSteps(start: u32) -> u32 {
+    var n = start;
+    var steps = 0u;
+    while (n > 1u) {
+        if (n % 2u == 0u) {
+            n = n / 2u;
+        } else {
+            n = 3u * n + 1u;
+        }
+        steps = steps + 1u;
+    }
+    return steps;
+}
+
+@compute @workgroup_size(64)
+fn cs_collatz(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x < arrayLength(&numbers)) {
+        numbers[id.x] = collatzSteps(numbers[id.x]);
+    }
+}
+
+// Iterations before escaping, scaled to [0, 1].
+@compute @workgroup_size(8, 8)
+fn cs_mandelbrot(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(image);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let c = vec2<f32>(
+        f32(id.x) / f32(size.x) * 3.0 - 2.2,
+        f32(id.y) / f32(size.y) * 2.4 - 1.2,
+    );
+    var z = vec2<f32>(0.0);
+    var iterations = 0u;
+    while (iterations < 64u && dot(z, z) < 4.0) {
+        z = vec2<f32>(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        iterations = iterations + 1u;
+    }
+    textureStore(image, vec2<i32>(id.xy), vec4<f32>(f32(iterations) / 64.0, 0.0, 0.0, 1.0));
+}
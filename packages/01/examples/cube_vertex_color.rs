@@ -5,12 +5,12 @@ use cgmath::Point3;
 use commonlib::{
     renderer::RendererBuilder,
     transform::{create_projection, create_transforms, create_view_projection},
+    uniforms::{ModelUniforms, SceneUniforms},
     vertices::{vertex_data::cube_data_index, Vertex4DColored},
 };
 use wgpu::{
-    util::DeviceExt, vertex_attr_array, BindGroupEntry, BindGroupLayoutEntry, BufferAddress,
-    BufferUsages, CommandEncoderDescriptor, Extent3d, ShaderStages, TextureDescriptor,
-    TextureDimension, TextureFormat, TextureUsages, VertexAttribute,
+    util::DeviceExt, BindGroupEntry, BufferUsages, CommandEncoderDescriptor, Extent3d,
+    ShaderStages, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
 };
 use winit::{
     event::{Event, WindowEvent},
@@ -39,8 +39,6 @@ fn create_vertices() -> (Vec<Vertex4DColored>, Vec<u16>) {
     (vertices, indices)
 }
 
-const VERTEX_ATTRIBUTE: [VertexAttribute; 2] = vertex_attr_array![0=>Float32x4, 1=>Float32x4];
-
 fn main() {
     env_logger::init();
 
@@ -48,10 +46,7 @@ fn main() {
 
     let args: Vec<String> = std::env::args().collect();
     if args.len() > 1 {
-        is_perspective = match args[1].as_str() {
-            "ortho" => false,
-            _ => true,
-        }
+        is_perspective = args[1].as_str() != "ortho";
     }
 
     let event_loop = EventLoop::new();
@@ -70,23 +65,20 @@ fn main() {
             Some("Shader Module"),
             include_str!("cube_vertex_color.wgsl"),
         )
-        .add_vertex_buffer_layout(wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex4DColored>() as BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &VERTEX_ATTRIBUTE,
-        })
+        .add_vertex_buffer_layout(Vertex4DColored::LAYOUT)
         .add_bind_group_layout(
-            Some("Uniform Bind Group Layout"),
-            &[BindGroupLayoutEntry {
-                binding: 0,
-                count: None,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                visibility: ShaderStages::VERTEX,
-            }],
+            Some("Scene Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX,
+            )],
+        )
+        .add_bind_group_layout(
+            Some("Model Bind Group Layout"),
+            &[ModelUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX,
+            )],
         )
         .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
         .set_depth_stencil_state(wgpu::DepthStencilState {
@@ -107,7 +99,7 @@ fn main() {
     let up_direction = cgmath::Vector3::<f32>::unit_y();
 
     let model_matrix = create_transforms([0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]);
-    let (view_matrix, _projection_matrix, view_projection_matrix) = create_view_projection(
+    let (view_matrix, projection_matrix, _view_projection_matrix) = create_view_projection(
         camera_position,
         look_direction,
         up_direction,
@@ -115,7 +107,6 @@ fn main() {
             / renderer.surface_configuration().unwrap().height as f32,
         is_perspective,
     );
-    let mvp_matrix = view_projection_matrix * model_matrix;
 
     let vertex_buffer =
         renderer
@@ -137,26 +128,57 @@ fn main() {
                 usage: BufferUsages::INDEX,
             });
 
-    let uniform_buffer =
+    let scene_buffer =
+        renderer
+            .device()
+            .unwrap()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Scene Uniform Buffer"),
+                contents: bytemuck::bytes_of(&SceneUniforms::new(
+                    view_matrix,
+                    projection_matrix,
+                    camera_position,
+                    [
+                        renderer.surface_configuration().unwrap().width as f32,
+                        renderer.surface_configuration().unwrap().height as f32,
+                    ],
+                    0.0,
+                )),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+    let model_buffer =
         renderer
             .device()
             .unwrap()
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Uniform Buffer"),
-                contents: cast_slice(mvp_matrix.as_ref() as &[f32; 16]),
+                label: Some("Model Uniform Buffer"),
+                contents: bytemuck::bytes_of(&ModelUniforms::new(model_matrix)),
                 usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
             });
 
-    let uniform_bind_group =
+    let scene_bind_group =
         renderer
             .device()
             .unwrap()
             .create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Uniform Bind Group"),
-                layout: renderer.bind_group_layouts().get(0).unwrap(),
+                label: Some("Scene Bind Group"),
+                layout: &renderer.bind_group_layouts()[0],
                 entries: &[BindGroupEntry {
                     binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
+                    resource: scene_buffer.as_entire_binding(),
+                }],
+            });
+
+    let model_bind_group =
+        renderer
+            .device()
+            .unwrap()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Model Bind Group"),
+                layout: &renderer.bind_group_layouts()[1],
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: model_buffer.as_entire_binding(),
                 }],
             });
 
@@ -184,13 +206,16 @@ fn main() {
 
                 let new_projection_matrix =
                     create_projection(size.width as f32 / size.height as f32, is_perspective);
-                let mvp_matrix = new_projection_matrix * view_matrix * model_matrix;
-
-                let queue = renderer.queue().unwrap();
-                queue.write_buffer(
-                    &uniform_buffer,
+                renderer.queue().unwrap().write_buffer(
+                    &scene_buffer,
                     0,
-                    cast_slice(mvp_matrix.as_ref() as &[f32; 16]),
+                    bytemuck::bytes_of(&SceneUniforms::new(
+                        view_matrix,
+                        new_projection_matrix,
+                        camera_position,
+                        [size.width as f32, size.height as f32],
+                        0.0,
+                    )),
                 );
             }
             Event::RedrawRequested(_) => {
@@ -250,10 +275,11 @@ fn main() {
                             ),
                         });
 
-                    render_pass.set_pipeline(&render_pipeline);
+                    render_pass.set_pipeline(render_pipeline);
                     render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
                     render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                    render_pass.set_bind_group(0, &uniform_bind_group, &[]);
+                    render_pass.set_bind_group(0, &scene_bind_group, &[]);
+                    render_pass.set_bind_group(1, &model_bind_group, &[]);
                     render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
                 }
 
@@ -0,0 +1,334 @@
+use cgmath::{Deg, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+use commonlib::{
+    camera::{Camera, CameraController},
+    loaders::gltf::GltfScene,
+    mesh::Mesh,
+    projection::Projection,
+    renderer::RendererBuilder,
+    uniforms::{ModelUniforms, SceneUniforms},
+    vertices::Vertex3DNormalColored,
+};
+use wgpu::{
+    util::DeviceExt, BindGroupEntry, BufferUsages, Extent3d, ShaderStages, TextureDescriptor,
+    TextureUsages,
+};
+use winit::{
+    event::{DeviceEvent, ElementState, Event, WindowEvent},
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+    let model_path = args
+        .get(1)
+        .expect("a path to a .gltf or .glb file as the first argument");
+    let scene = GltfScene::load(model_path).unwrap_or_else(|error| panic!("{}", error));
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("glTF Viewer")
+        .build(&event_loop)
+        .expect("to create window");
+
+    let mut renderer = RendererBuilder::new(window)
+        .create_instance()
+        .create_surface()
+        .get_adapter()
+        .get_device(Some("Device"))
+        .create_surface_configuration()
+        .create_shader_module(Some("Shader"), include_str!("gltf_viewer.wgsl"))
+        .add_vertex_buffer_layout(Vertex3DNormalColored::LAYOUT)
+        .add_bind_group_layout(
+            Some("Scene Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX,
+            )],
+        )
+        .add_bind_group_layout(
+            Some("Model Bind Group Layout"),
+            &[ModelUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX,
+            )],
+        )
+        .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
+        .set_depth_stencil_state(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth24Plus,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        })
+        .create_pipeline_layout(Some("Pipeline Layout"))
+        .create_render_pipeline(Some("Render Pipeline"))
+        .build();
+
+    let mut camera = Camera::new([0.0, 1.5, 3.0], Deg(-90.0), Deg(-25.0));
+    let mut camera_controller = CameraController::new(0.005);
+    let mut projection = Projection::new(
+        renderer.surface_configuration().unwrap().width as f32,
+        renderer.surface_configuration().unwrap().height as f32,
+    );
+    projection.replace_view_matrix(camera.view_matrix());
+
+    // Node transforms are baked into the vertices so every primitive shares one uniform.
+    let materials = &scene.materials;
+    let meshes: Vec<Mesh<Vertex3DNormalColored>> = scene
+        .mesh_instances()
+        .flat_map(|(gltf_mesh, world_transform)| {
+            let normal_matrix = normal_matrix(world_transform);
+            gltf_mesh
+                .primitives
+                .iter()
+                .filter(|primitive| {
+                    primitive.data.topology == wgpu::PrimitiveTopology::TriangleList
+                })
+                .map(move |primitive| {
+                    let base_color = primitive.material.map_or([1.0, 1.0, 1.0, 1.0], |material| {
+                        materials[material].base_color_factor
+                    });
+                    primitive
+                        .colored_data()
+                        .map_vertices(|vertex| Vertex3DNormalColored {
+                            position: world_transform
+                                .transform_point(Point3::from(vertex.position))
+                                .into(),
+                            normal: (normal_matrix * Vector3::from(vertex.normal)).into(),
+                            color: [0, 1, 2, 3]
+                                .map(|channel| base_color[channel] * vertex.color[channel]),
+                        })
+                })
+        })
+        .map(|mesh_data| Mesh::new(renderer.device().unwrap(), Some("glTF Mesh"), &mesh_data))
+        .collect();
+
+    let mut bounding_box = meshes
+        .iter()
+        .filter_map(|mesh| mesh.bounding_box())
+        .copied();
+    if let Some(mut model_bounds) = bounding_box.next() {
+        for mesh_bounds in bounding_box {
+            model_bounds.extend(mesh_bounds.min);
+            model_bounds.extend(mesh_bounds.max);
+        }
+        let size = model_bounds.size();
+        let scale = 2.0 / size.x.max(size.y).max(size.z).max(f32::EPSILON);
+        let center = model_bounds.center();
+        projection.set_model_matrix(
+            [-center.x * scale, -center.y * scale, -center.z * scale],
+            [0.0, 0.0, 0.0],
+            [scale, scale, scale],
+        );
+    }
+
+    let viewport_size = [
+        renderer.surface_configuration().unwrap().width as f32,
+        renderer.surface_configuration().unwrap().height as f32,
+    ];
+    let scene_buffer =
+        renderer
+            .device()
+            .unwrap()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Scene Uniform Buffer"),
+                contents: bytemuck::bytes_of(&SceneUniforms::from_camera(
+                    &camera,
+                    &projection,
+                    viewport_size,
+                    0.0,
+                )),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+    let model_buffer =
+        renderer
+            .device()
+            .unwrap()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Model Uniform Buffer"),
+                contents: bytemuck::bytes_of(&ModelUniforms::from_projection(&projection)),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+
+    let scene_bind_group =
+        renderer
+            .device()
+            .unwrap()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Scene Bind Group"),
+                layout: &renderer.bind_group_layouts()[0],
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: scene_buffer.as_entire_binding(),
+                }],
+            });
+
+    let model_bind_group =
+        renderer
+            .device()
+            .unwrap()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Model Bind Group"),
+                layout: &renderer.bind_group_layouts()[1],
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: model_buffer.as_entire_binding(),
+                }],
+            });
+
+    let mut mouse_pressed = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_poll();
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                control_flow.set_exit();
+            }
+            Event::DeviceEvent { event, .. } => match event {
+                DeviceEvent::Button { button: 1, state } => {
+                    mouse_pressed = state == ElementState::Pressed;
+                }
+                DeviceEvent::MouseMotion { delta } if mouse_pressed => {
+                    camera_controller.set_rotation(delta.0 as f32, delta.1 as f32);
+                }
+                _ => (),
+            },
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                {
+                    let surface_configuration = renderer.mut_surface_configuration().unwrap();
+                    surface_configuration.width = size.width;
+                    surface_configuration.height = size.height;
+                }
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                surface.configure(device, surface_configuration);
+
+                projection.set_aspect_ratio(size.width as f32 / size.height as f32);
+
+                renderer.queue().unwrap().write_buffer(
+                    &scene_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_camera(
+                        &camera,
+                        &projection,
+                        [size.width as f32, size.height as f32],
+                        0.0,
+                    )),
+                );
+            }
+            Event::RedrawRequested(_) => {
+                camera_controller.update_camera(&mut camera);
+                projection.replace_view_matrix(camera.view_matrix());
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let viewport_size = [
+                    surface_configuration.width as f32,
+                    surface_configuration.height as f32,
+                ];
+                renderer.queue().unwrap().write_buffer(
+                    &scene_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_camera(
+                        &camera,
+                        &projection,
+                        viewport_size,
+                        0.0,
+                    )),
+                );
+
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                let queue = renderer.queue().unwrap();
+                let render_pipeline = renderer.render_pipeline().unwrap();
+                let surface_texture = surface.get_current_texture().unwrap();
+
+                let texture_view = surface_texture.texture.create_view(&Default::default());
+                let depth_texture = renderer
+                    .device()
+                    .unwrap()
+                    .create_texture(&TextureDescriptor {
+                        label: Some("Texture"),
+                        size: Extent3d {
+                            depth_or_array_layers: 1,
+                            width: surface_configuration.width,
+                            height: surface_configuration.height,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: wgpu::TextureFormat::Depth24Plus,
+                        usage: TextureUsages::RENDER_ATTACHMENT,
+                        view_formats: &[],
+                    });
+                let depth_view = depth_texture.create_view(&Default::default());
+
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+                {
+                    let mut render_pass =
+                        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: None,
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: &texture_view,
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                                        r: 0.5,
+                                        g: 0.5,
+                                        b: 0.5,
+                                        a: 1.0,
+                                    }),
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: Some(
+                                wgpu::RenderPassDepthStencilAttachment {
+                                    view: &depth_view,
+                                    depth_ops: Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(1.0),
+                                        store: false,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                        });
+                    render_pass.set_pipeline(render_pipeline);
+                    render_pass.set_bind_group(0, &scene_bind_group, &[]);
+                    render_pass.set_bind_group(1, &model_bind_group, &[]);
+                    for mesh in &meshes {
+                        mesh.draw(&mut render_pass);
+                    }
+                }
+                queue.submit(Some(command_encoder.finish()));
+                surface_texture.present();
+            }
+            Event::MainEventsCleared => {
+                renderer.window().unwrap().request_redraw();
+            }
+            _ => (),
+        }
+    })
+}
+
+fn normal_matrix(world_transform: &Matrix4<f32>) -> Matrix3<f32> {
+    let linear = Matrix3::from_cols(
+        world_transform.x.truncate(),
+        world_transform.y.truncate(),
+        world_transform.z.truncate(),
+    );
+    linear
+        .invert()
+        .map_or(linear, |inverse| inverse.transpose())
+}
@@ -0,0 +1,366 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::Deg;
+use commonlib::{
+    camera::{Camera, CameraController},
+    hdr::{Exposure, HdrPipeline},
+    mesh::Mesh,
+    projection::Projection,
+    renderer::RendererBuilder,
+    uniform_arena::UniformArena,
+    uniforms::SceneUniforms,
+    vertices::{primitives, Vertex3DNormalUv},
+};
+use wgpu::{
+    util::DeviceExt, BindGroupEntry, BufferUsages, Extent3d, ShaderStages, TextureDescriptor,
+    TextureUsages,
+};
+use winit::{
+    event::{DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ObjectUniforms {
+    model_matrix: [[f32; 4]; 4],
+    normal_matrix: [[f32; 4]; 4],
+    color: [f32; 4],
+    emissive: [f32; 4],
+}
+
+struct SceneObject {
+    mesh: Mesh<Vertex3DNormalUv>,
+    translation: [f32; 3],
+    spin: f32,
+    color: [f32; 4],
+    emissive: [f32; 4],
+}
+
+const ANIMATION_SPEED: f32 = 1.0;
+
+fn main() {
+    env_logger::init();
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("HDR Exposure")
+        .build(&event_loop)
+        .expect("to create window");
+
+    let mut renderer = RendererBuilder::new(window)
+        .create_instance()
+        .create_surface()
+        .get_adapter()
+        .get_device(Some("Device"))
+        .create_surface_configuration()
+        .create_shader_module(Some("Shader"), include_str!("hdr_exposure.wgsl"))
+        .add_vertex_buffer_layout(Vertex3DNormalUv::LAYOUT)
+        .add_bind_group_layout(
+            Some("Scene Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .add_bind_group_layout(
+            Some("Object Bind Group Layout"),
+            &[UniformArena::<ObjectUniforms>::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
+        .set_depth_stencil_state(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth24Plus,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        })
+        .create_hdr_pipeline()
+        .create_pipeline_layout(Some("Pipeline Layout"))
+        .create_render_pipeline(Some("Render Pipeline"))
+        .build();
+
+    let surface_configuration = renderer.surface_configuration().unwrap();
+    let device = renderer.device().unwrap();
+
+    // A switches between auto and manual exposure, up and down change the
+    // manual exposure or the auto exposure compensation by half a stop.
+    renderer
+        .window()
+        .unwrap()
+        .set_title(&exposure_title(renderer.hdr_pipeline().unwrap()));
+
+    let mut camera = Camera::new([0.0, 3.0, 8.0], Deg(-90.0), Deg(-20.0));
+    let mut camera_controller = CameraController::new(0.005);
+    let mut projection = Projection::new(
+        surface_configuration.width as f32,
+        surface_configuration.height as f32,
+    );
+    projection.replace_view_matrix(camera.view_matrix());
+
+    let viewport_size = [
+        surface_configuration.width as f32,
+        surface_configuration.height as f32,
+    ];
+    let scene_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Scene Uniform Buffer"),
+        contents: bytemuck::bytes_of(&SceneUniforms::from_camera(
+            &camera,
+            &projection,
+            viewport_size,
+            0.0,
+        )),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let scene_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Scene Bind Group"),
+        layout: &renderer.bind_group_layouts()[0],
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: scene_buffer.as_entire_binding(),
+        }],
+    });
+
+    let objects = vec![
+        SceneObject {
+            mesh: Mesh::new(device, Some("Floor"), &primitives::plane(12.0, 12.0, 1, 1)),
+            translation: [0.0, -1.0, 0.0],
+            spin: 0.0,
+            color: [0.3, 0.3, 0.35, 1.0],
+            emissive: [0.0; 4],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Cube"), &primitives::cube(0.8)),
+            translation: [-2.0, 0.0, 0.0],
+            spin: 1.0,
+            color: [0.8, 0.4, 0.2, 1.0],
+            emissive: [0.0; 4],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Sphere"), &primitives::uv_sphere(0.6, 48, 24)),
+            translation: [0.0, 0.5, 0.0],
+            spin: 0.0,
+            color: [1.0, 0.9, 0.6, 1.0],
+            emissive: [40.0, 30.0, 15.0, 0.0],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Torus"), &primitives::torus(0.7, 0.25, 48, 16)),
+            translation: [2.0, 0.0, 0.0],
+            spin: -0.7,
+            color: [0.2, 0.5, 1.0, 1.0],
+            emissive: [0.0; 4],
+        },
+    ];
+
+    let mut object_arena: UniformArena<ObjectUniforms> =
+        UniformArena::new(device, Some("Object Arena"), objects.len() as u32);
+    object_arena.create_bind_group(device, &renderer.bind_group_layouts()[1], 0);
+
+    let render_start_time = std::time::Instant::now();
+    let mut last_frame_time = render_start_time;
+
+    let mut mouse_pressed = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_poll();
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                control_flow.set_exit();
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let hdr_pipeline = renderer.mut_hdr_pipeline().unwrap();
+                let step = match keycode {
+                    VirtualKeyCode::Up => 0.5,
+                    VirtualKeyCode::Down => -0.5,
+                    _ => 0.0,
+                };
+                match (keycode, hdr_pipeline.exposure()) {
+                    (VirtualKeyCode::A, Exposure::Auto) => {
+                        hdr_pipeline.set_exposure(Exposure::Manual(1.0));
+                    }
+                    (VirtualKeyCode::A, Exposure::Manual(_)) => {
+                        hdr_pipeline.set_exposure(Exposure::Auto);
+                    }
+                    (_, Exposure::Auto) => {
+                        let compensation = hdr_pipeline.exposure_compensation();
+                        hdr_pipeline.set_exposure_compensation(compensation + step);
+                    }
+                    (_, Exposure::Manual(exposure)) => {
+                        hdr_pipeline.set_exposure(Exposure::Manual(exposure * 2f32.powf(step)));
+                    }
+                }
+                let title = exposure_title(renderer.hdr_pipeline().unwrap());
+                renderer.window().unwrap().set_title(&title);
+            }
+            Event::DeviceEvent { event, .. } => match event {
+                DeviceEvent::Button { button: 1, state } => {
+                    mouse_pressed = state == ElementState::Pressed;
+                }
+                DeviceEvent::MouseMotion { delta } if mouse_pressed => {
+                    camera_controller.set_rotation(delta.0 as f32, delta.1 as f32);
+                }
+                _ => (),
+            },
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                renderer.resize(size.width, size.height);
+                projection.set_aspect_ratio(size.width as f32 / size.height as f32);
+            }
+            Event::RedrawRequested(_) => {
+                let current_time = std::time::Instant::now();
+                let current_duration = current_time - render_start_time;
+                let delta_time = (current_time - last_frame_time).as_secs_f32();
+                last_frame_time = current_time;
+                let animated_duration = ANIMATION_SPEED * current_duration.as_secs_f32();
+
+                camera_controller.update_camera(&mut camera);
+                projection.replace_view_matrix(camera.view_matrix());
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let viewport_size = [
+                    surface_configuration.width as f32,
+                    surface_configuration.height as f32,
+                ];
+                renderer.queue().unwrap().write_buffer(
+                    &scene_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_camera(
+                        &camera,
+                        &projection,
+                        viewport_size,
+                        animated_duration,
+                    )),
+                );
+
+                object_arena.clear();
+                for object in &objects {
+                    projection.set_model_matrix(
+                        object.translation,
+                        [0.0, object.spin * animated_duration, 0.0],
+                        [1.0, 1.0, 1.0],
+                    );
+                    object_arena.push(&ObjectUniforms {
+                        model_matrix: (*projection.model_matrix()).into(),
+                        normal_matrix: (*projection.normal_matrix()).into(),
+                        color: object.color,
+                        emissive: object.emissive,
+                    });
+                }
+                if object_arena.upload(renderer.device().unwrap(), renderer.queue().unwrap()) {
+                    object_arena.create_bind_group(
+                        renderer.device().unwrap(),
+                        &renderer.bind_group_layouts()[1],
+                        0,
+                    );
+                }
+
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                let queue = renderer.queue().unwrap();
+                let render_pipeline = renderer.render_pipeline().unwrap();
+                let hdr_pipeline = renderer.hdr_pipeline().unwrap();
+                let surface_texture = surface.get_current_texture().unwrap();
+
+                let texture_view = surface_texture.texture.create_view(&Default::default());
+                let depth_texture = device.create_texture(&TextureDescriptor {
+                    label: Some("Texture"),
+                    size: Extent3d {
+                        depth_or_array_layers: 1,
+                        width: surface_configuration.width,
+                        height: surface_configuration.height,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Depth24Plus,
+                    usage: TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                });
+                let depth_view = depth_texture.create_view(&Default::default());
+
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+                {
+                    let mut render_pass =
+                        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: None,
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: hdr_pipeline.view(),
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                                        r: 0.02,
+                                        g: 0.02,
+                                        b: 0.03,
+                                        a: 1.0,
+                                    }),
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: Some(
+                                wgpu::RenderPassDepthStencilAttachment {
+                                    view: &depth_view,
+                                    depth_ops: Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(1.0),
+                                        store: false,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                        });
+                    render_pass.set_pipeline(render_pipeline);
+                    render_pass.set_bind_group(0, &scene_bind_group, &[]);
+                    let object_bind_group = object_arena.bind_group().unwrap();
+                    for (index, object) in objects.iter().enumerate() {
+                        render_pass.set_bind_group(
+                            1,
+                            object_bind_group,
+                            &[object_arena.offset(index as u32)],
+                        );
+                        object.mesh.draw(&mut render_pass);
+                    }
+                }
+
+                hdr_pipeline.render(queue, &mut command_encoder, &texture_view, delta_time);
+
+                queue.submit(Some(command_encoder.finish()));
+                surface_texture.present();
+            }
+            Event::MainEventsCleared => {
+                renderer.window().unwrap().request_redraw();
+            }
+            _ => (),
+        }
+    })
+}
+
+fn exposure_title(hdr_pipeline: &HdrPipeline) -> String {
+    match hdr_pipeline.exposure() {
+        Exposure::Auto => format!(
+            "HDR Exposure - auto, {:+.1} EV",
+            hdr_pipeline.exposure_compensation()
+        ),
+        Exposure::Manual(exposure) => format!("HDR Exposure - manual, {:.3}", exposure),
+    }
+}
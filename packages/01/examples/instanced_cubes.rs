@@ -0,0 +1,287 @@
+use bytemuck::cast_slice;
+use commonlib::{
+    instance::{InstanceBuffer, InstanceData},
+    projection::Projection,
+    renderer::RendererBuilder,
+    uniforms::SceneUniforms,
+    vertices::{vertex_data, Vertex4DColored},
+};
+use wgpu::{
+    util::DeviceExt, BindGroupEntry, BufferUsages, Extent3d, ShaderStages, TextureDescriptor,
+    TextureUsages,
+};
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+fn create_vertex(position: [i8; 3], color: [i8; 3]) -> Vertex4DColored {
+    Vertex4DColored {
+        position: [
+            position[0] as f32,
+            position[1] as f32,
+            position[2] as f32,
+            1.0,
+        ],
+        color: [color[0] as f32, color[1] as f32, color[2] as f32, 1.0],
+    }
+}
+
+fn create_vertices() -> Vec<Vertex4DColored> {
+    let (pos, col, _uv, _normal) = vertex_data::cube_data();
+    let mut data: Vec<Vertex4DColored> = Vec::with_capacity(pos.len());
+    for i in 0..pos.len() {
+        data.push(create_vertex(pos[i], col[i]));
+    }
+    data
+}
+
+const ANIMATION_SPEED: f32 = 1.0;
+const GRID_SIZE: i32 = 50;
+const GRID_SPACING: f32 = 1.5;
+
+fn create_instances(animated_duration: f32) -> Vec<InstanceData> {
+    let offset = (GRID_SIZE - 1) as f32 * GRID_SPACING / 2.0;
+    let mut instances = Vec::with_capacity((GRID_SIZE * GRID_SIZE) as usize);
+    for x in 0..GRID_SIZE {
+        for z in 0..GRID_SIZE {
+            let phase = animated_duration + (x + z) as f32 * 0.2;
+            let u = x as f32 / (GRID_SIZE - 1) as f32;
+            let v = z as f32 / (GRID_SIZE - 1) as f32;
+            instances.push(InstanceData::new(
+                [
+                    x as f32 * GRID_SPACING - offset,
+                    phase.sin(),
+                    z as f32 * GRID_SPACING - offset,
+                ],
+                [0.0, phase, 0.0],
+                [0.4, 0.4, 0.4],
+                [u, 1.0 - 0.5 * (u + v), v, 1.0],
+            ));
+        }
+    }
+    instances
+}
+
+fn main() {
+    env_logger::init();
+
+    let mut is_perspective = true;
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 {
+        is_perspective = args[1].as_str() != "ortho";
+    }
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Instanced Cubes")
+        .build(&event_loop)
+        .expect("to create window");
+
+    let mut renderer = RendererBuilder::new(window)
+        .create_instance()
+        .create_surface()
+        .get_adapter()
+        .get_device(Some("Device"))
+        .create_surface_configuration()
+        .create_shader_module(Some("Shader"), include_str!("instanced_cubes.wgsl"))
+        .add_vertex_buffer_layout(Vertex4DColored::LAYOUT)
+        .add_vertex_buffer_layout(InstanceData::LAYOUT)
+        .add_bind_group_layout(
+            Some("Uniform Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX,
+            )],
+        )
+        .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
+        .set_depth_stencil_state(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth24Plus,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        })
+        .create_pipeline_layout(Some("Pipeline Layout"))
+        .create_render_pipeline(Some("Render Pipeline"))
+        .build();
+
+    let mut projection = Projection::new(
+        renderer.surface_configuration().unwrap().width as f32,
+        renderer.surface_configuration().unwrap().height as f32,
+    );
+    projection.set_is_perspective(is_perspective);
+    projection.set_camera_position([0.0, 30.0, 50.0]);
+    let mut viewport_size = [
+        renderer.surface_configuration().unwrap().width as f32,
+        renderer.surface_configuration().unwrap().height as f32,
+    ];
+
+    let uniform_buffer =
+        renderer
+            .device()
+            .unwrap()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Uniform Buffer"),
+                contents: bytemuck::bytes_of(&SceneUniforms::from_projection(
+                    &projection,
+                    viewport_size,
+                    0.0,
+                )),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+
+    let uniform_bind_group =
+        renderer
+            .device()
+            .unwrap()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Uniform Bind Group"),
+                layout: renderer.bind_group_layouts().first().unwrap(),
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+            });
+
+    let vertex_buffer =
+        renderer
+            .device()
+            .unwrap()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Vertex Buffer"),
+                contents: cast_slice(&create_vertices()),
+                usage: BufferUsages::VERTEX,
+            });
+
+    let mut instance_buffer = InstanceBuffer::new(
+        renderer.device().unwrap(),
+        Some("Instance Buffer"),
+        &create_instances(0.0),
+    );
+
+    let render_start_time = std::time::Instant::now();
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_poll();
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                control_flow.set_exit();
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                {
+                    let surface_configuration = renderer.mut_surface_configuration().unwrap();
+                    surface_configuration.width = size.width;
+                    surface_configuration.height = size.height;
+                }
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                surface.configure(device, surface_configuration);
+
+                projection.set_aspect_ratio(size.width as f32 / size.height as f32);
+                viewport_size = [size.width as f32, size.height as f32];
+            }
+            Event::RedrawRequested(_) => {
+                let current_time = std::time::Instant::now();
+                let current_duration = current_time - render_start_time;
+                let animated_duration = ANIMATION_SPEED * current_duration.as_secs_f32();
+
+                renderer.queue().unwrap().write_buffer(
+                    &uniform_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_projection(
+                        &projection,
+                        viewport_size,
+                        current_duration.as_secs_f32(),
+                    )),
+                );
+                instance_buffer.update(
+                    renderer.device().unwrap(),
+                    renderer.queue().unwrap(),
+                    &create_instances(animated_duration),
+                );
+
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                let queue = renderer.queue().unwrap();
+                let render_pipeline = renderer.render_pipeline().unwrap();
+                let surface_texture = surface.get_current_texture().unwrap();
+                let surface_configuration = renderer.surface_configuration().unwrap();
+
+                let texture_view = surface_texture.texture.create_view(&Default::default());
+                let depth_texture = renderer
+                    .device()
+                    .unwrap()
+                    .create_texture(&TextureDescriptor {
+                        label: Some("Texture"),
+                        size: Extent3d {
+                            depth_or_array_layers: 1,
+                            width: surface_configuration.width,
+                            height: surface_configuration.height,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: wgpu::TextureFormat::Depth24Plus,
+                        usage: TextureUsages::RENDER_ATTACHMENT,
+                        view_formats: &[],
+                    });
+                let depth_view = depth_texture.create_view(&Default::default());
+
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+                {
+                    let mut render_pass =
+                        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: None,
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: &texture_view,
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                                        r: 0.5,
+                                        g: 0.5,
+                                        b: 0.5,
+                                        a: 1.0,
+                                    }),
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: Some(
+                                wgpu::RenderPassDepthStencilAttachment {
+                                    view: &depth_view,
+                                    depth_ops: Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(1.0),
+                                        store: false,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                        });
+                    render_pass.set_pipeline(render_pipeline);
+                    render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                    render_pass.set_vertex_buffer(1, instance_buffer.slice());
+                    render_pass.set_bind_group(0, &uniform_bind_group, &[]);
+                    render_pass.draw(0..36, instance_buffer.instances())
+                }
+                queue.submit(Some(command_encoder.finish()));
+                surface_texture.present();
+            }
+            Event::MainEventsCleared => {
+                renderer.window().unwrap().request_redraw();
+            }
+            _ => (),
+        }
+    })
+}
@@ -0,0 +1,30 @@
+#include "common/scene_uniforms.wgsl"
+
+@binding(0) @group(0) var<uniform> scene : SceneUniforms;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vColor: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) pos: vec4<f32>,
+    @location(1) color: vec4<f32>,
+    @location(5) model0: vec4<f32>,
+    @location(6) model1: vec4<f32>,
+    @location(7) model2: vec4<f32>,
+    @location(8) model3: vec4<f32>,
+    @location(9) instanceColor: vec4<f32>,
+) -> Output {
+    let modelMatrix = mat4x4<f32>(model0, model1, model2, model3);
+    var output: Output;
+    output.Position = scene.viewProjectionMatrix * modelMatrix * pos;
+    output.vColor = color * instanceColor;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vColor: vec4<f32>) -> @location(0) vec4<f32> {
+    return vColor;
+}
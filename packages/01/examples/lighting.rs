@@ -0,0 +1,385 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::Deg;
+use commonlib::{
+    camera::{Camera, CameraController},
+    lighting::{BlinnPhongMaterial, Light, LightBuffer},
+    mesh::Mesh,
+    projection::Projection,
+    renderer::RendererBuilder,
+    uniform_arena::UniformArena,
+    uniforms::SceneUniforms,
+    vertices::{primitives, Vertex3DNormalUv},
+};
+use wgpu::{
+    util::DeviceExt, BindGroupEntry, BufferUsages, Extent3d, ShaderStages, TextureDescriptor,
+    TextureUsages,
+};
+use winit::{
+    event::{DeviceEvent, ElementState, Event, WindowEvent},
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ObjectUniforms {
+    model_matrix: [[f32; 4]; 4],
+    normal_matrix: [[f32; 4]; 4],
+    material: BlinnPhongMaterial,
+}
+
+struct SceneObject {
+    mesh: Mesh<Vertex3DNormalUv>,
+    translation: [f32; 3],
+    scale: [f32; 3],
+    spin: f32,
+    material: BlinnPhongMaterial,
+}
+
+const ANIMATION_SPEED: f32 = 1.0;
+
+fn main() {
+    env_logger::init();
+
+    let mut is_perspective = true;
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 {
+        is_perspective = args[1].as_str() != "ortho";
+    }
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Blinn-Phong Lighting")
+        .build(&event_loop)
+        .expect("to create window");
+
+    let mut renderer = RendererBuilder::new(window)
+        .create_instance()
+        .create_surface()
+        .get_adapter()
+        .get_device(Some("Device"))
+        .create_surface_configuration()
+        .create_shader_module(Some("Shader"), include_str!("lighting.wgsl"))
+        .add_vertex_buffer_layout(Vertex3DNormalUv::LAYOUT)
+        .add_bind_group_layout(
+            Some("Scene Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .add_bind_group_layout(
+            Some("Light Bind Group Layout"),
+            &[LightBuffer::bind_group_layout_entry(
+                0,
+                ShaderStages::FRAGMENT,
+            )],
+        )
+        .add_bind_group_layout(
+            Some("Object Bind Group Layout"),
+            &[UniformArena::<ObjectUniforms>::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
+        .set_depth_stencil_state(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth24Plus,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        })
+        .create_pipeline_layout(Some("Pipeline Layout"))
+        .create_render_pipeline(Some("Render Pipeline"))
+        .build();
+
+    let mut camera = Camera::new([0.0, 4.0, 8.0], Deg(-90.0), Deg(-25.0));
+    let mut camera_controller = CameraController::new(0.005);
+    let mut projection = Projection::new(
+        renderer.surface_configuration().unwrap().width as f32,
+        renderer.surface_configuration().unwrap().height as f32,
+    );
+    projection.replace_view_matrix(camera.view_matrix());
+    projection.set_is_perspective(is_perspective);
+
+    let viewport_size = [
+        renderer.surface_configuration().unwrap().width as f32,
+        renderer.surface_configuration().unwrap().height as f32,
+    ];
+    let scene_buffer =
+        renderer
+            .device()
+            .unwrap()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Scene Uniform Buffer"),
+                contents: bytemuck::bytes_of(&SceneUniforms::from_camera(
+                    &camera,
+                    &projection,
+                    viewport_size,
+                    0.0,
+                )),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+    let scene_bind_group =
+        renderer
+            .device()
+            .unwrap()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Scene Bind Group"),
+                layout: &renderer.bind_group_layouts()[0],
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: scene_buffer.as_entire_binding(),
+                }],
+            });
+
+    let mut light_buffer = LightBuffer::new(renderer.device().unwrap(), Some("Lights"), 4);
+    light_buffer.set_ambient_color([0.08, 0.08, 0.1]);
+    light_buffer.add_light(Light::directional(
+        [-0.4, -1.0, -0.3],
+        [1.0, 0.95, 0.85],
+        0.6,
+    ));
+    let point_light =
+        light_buffer.add_light(Light::point([2.0, 1.5, 0.0], [1.0, 0.4, 0.2], 4.0, 8.0));
+    light_buffer.add_light(Light::spot(
+        [0.0, 5.0, 0.0],
+        [0.0, -1.0, 0.0],
+        [0.3, 0.6, 1.0],
+        6.0,
+        12.0,
+        Deg(15.0),
+        Deg(25.0),
+    ));
+    if light_buffer.upload(renderer.device().unwrap(), renderer.queue().unwrap())
+        || light_buffer.bind_group().is_none()
+    {
+        light_buffer.create_bind_group(
+            renderer.device().unwrap(),
+            &renderer.bind_group_layouts()[1],
+            0,
+        );
+    }
+
+    let device = renderer.device().unwrap();
+    let objects = vec![
+        SceneObject {
+            mesh: Mesh::new(device, Some("Floor"), &primitives::plane(12.0, 12.0, 1, 1)),
+            translation: [0.0, -1.0, 0.0],
+            scale: [1.0, 1.0, 1.0],
+            spin: 0.0,
+            material: BlinnPhongMaterial::new([0.6, 0.6, 0.6, 1.0], 0.1, 8.0),
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Cube"), &primitives::cube(1.0)),
+            translation: [-1.8, 0.0, 0.0],
+            scale: [1.0, 1.0, 1.0],
+            spin: 1.0,
+            material: BlinnPhongMaterial::new([0.9, 0.2, 0.2, 1.0], 0.5, 32.0),
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Sphere"), &primitives::uv_sphere(1.0, 48, 24)),
+            translation: [1.8, 0.0, 0.0],
+            scale: [1.0, 1.0, 1.0],
+            spin: 0.0,
+            material: BlinnPhongMaterial::new([0.2, 0.5, 0.9, 1.0], 1.0, 96.0),
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Torus"), &primitives::torus(0.6, 0.2, 48, 16)),
+            translation: [0.0, 0.0, 2.0],
+            scale: [1.0, 2.0, 1.0],
+            spin: -0.7,
+            material: BlinnPhongMaterial::new([0.9, 0.8, 0.3, 1.0], 0.8, 64.0),
+        },
+    ];
+
+    let mut object_arena: UniformArena<ObjectUniforms> = UniformArena::new(
+        renderer.device().unwrap(),
+        Some("Object Arena"),
+        objects.len() as u32,
+    );
+    object_arena.create_bind_group(
+        renderer.device().unwrap(),
+        &renderer.bind_group_layouts()[2],
+        0,
+    );
+
+    let render_start_time = std::time::Instant::now();
+
+    let mut mouse_pressed = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_poll();
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                control_flow.set_exit();
+            }
+            Event::DeviceEvent { event, .. } => match event {
+                DeviceEvent::Button { button: 1, state } => {
+                    mouse_pressed = state == ElementState::Pressed;
+                }
+                DeviceEvent::MouseMotion { delta } if mouse_pressed => {
+                    camera_controller.set_rotation(delta.0 as f32, delta.1 as f32);
+                }
+                _ => (),
+            },
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                {
+                    let surface_configuration = renderer.mut_surface_configuration().unwrap();
+                    surface_configuration.width = size.width;
+                    surface_configuration.height = size.height;
+                }
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                surface.configure(device, surface_configuration);
+
+                projection.set_aspect_ratio(size.width as f32 / size.height as f32);
+            }
+            Event::RedrawRequested(_) => {
+                let current_time = std::time::Instant::now();
+                let current_duration = current_time - render_start_time;
+                let animated_duration = ANIMATION_SPEED * current_duration.as_secs_f32();
+
+                camera_controller.update_camera(&mut camera);
+                projection.replace_view_matrix(camera.view_matrix());
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let viewport_size = [
+                    surface_configuration.width as f32,
+                    surface_configuration.height as f32,
+                ];
+                renderer.queue().unwrap().write_buffer(
+                    &scene_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_camera(
+                        &camera,
+                        &projection,
+                        viewport_size,
+                        animated_duration,
+                    )),
+                );
+
+                light_buffer.mut_lights()[point_light].position = [
+                    3.0 * animated_duration.cos(),
+                    1.5,
+                    3.0 * animated_duration.sin(),
+                ];
+                if light_buffer.upload(renderer.device().unwrap(), renderer.queue().unwrap()) {
+                    light_buffer.create_bind_group(
+                        renderer.device().unwrap(),
+                        &renderer.bind_group_layouts()[1],
+                        0,
+                    );
+                }
+
+                object_arena.clear();
+                for object in &objects {
+                    projection.set_model_matrix(
+                        object.translation,
+                        [0.0, object.spin * animated_duration, 0.0],
+                        object.scale,
+                    );
+                    object_arena.push(&ObjectUniforms {
+                        model_matrix: (*projection.model_matrix()).into(),
+                        normal_matrix: (*projection.normal_matrix()).into(),
+                        material: object.material,
+                    });
+                }
+                if object_arena.upload(renderer.device().unwrap(), renderer.queue().unwrap()) {
+                    object_arena.create_bind_group(
+                        renderer.device().unwrap(),
+                        &renderer.bind_group_layouts()[2],
+                        0,
+                    );
+                }
+
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                let queue = renderer.queue().unwrap();
+                let render_pipeline = renderer.render_pipeline().unwrap();
+                let surface_texture = surface.get_current_texture().unwrap();
+
+                let texture_view = surface_texture.texture.create_view(&Default::default());
+                let depth_texture = renderer
+                    .device()
+                    .unwrap()
+                    .create_texture(&TextureDescriptor {
+                        label: Some("Texture"),
+                        size: Extent3d {
+                            depth_or_array_layers: 1,
+                            width: surface_configuration.width,
+                            height: surface_configuration.height,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: wgpu::TextureFormat::Depth24Plus,
+                        usage: TextureUsages::RENDER_ATTACHMENT,
+                        view_formats: &[],
+                    });
+                let depth_view = depth_texture.create_view(&Default::default());
+
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+                {
+                    let mut render_pass =
+                        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: None,
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: &texture_view,
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                                        r: 0.02,
+                                        g: 0.02,
+                                        b: 0.03,
+                                        a: 1.0,
+                                    }),
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: Some(
+                                wgpu::RenderPassDepthStencilAttachment {
+                                    view: &depth_view,
+                                    depth_ops: Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(1.0),
+                                        store: false,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                        });
+                    render_pass.set_pipeline(render_pipeline);
+                    render_pass.set_bind_group(0, &scene_bind_group, &[]);
+                    render_pass.set_bind_group(1, light_buffer.bind_group().unwrap(), &[]);
+                    let object_bind_group = object_arena.bind_group().unwrap();
+                    for (index, object) in objects.iter().enumerate() {
+                        render_pass.set_bind_group(
+                            2,
+                            object_bind_group,
+                            &[object_arena.offset(index as u32)],
+                        );
+                        object.mesh.draw(&mut render_pass);
+                    }
+                }
+                queue.submit(Some(command_encoder.finish()));
+                surface_texture.present();
+            }
+            Event::MainEventsCleared => {
+                renderer.window().unwrap().request_redraw();
+            }
+            _ => (),
+        }
+    })
+}
@@ -0,0 +1,42 @@
+#include "common/scene_uniforms.wgsl"
+#include "common/lighting.wgsl"
+
+struct ObjectUniforms {
+    modelMatrix: mat4x4<f32>,
+    normalMatrix: mat4x4<f32>,
+    material: BlinnPhongMaterial,
+};
+
+@binding(0) @group(0) var<uniform> scene : SceneUniforms;
+@binding(0) @group(1) var<storage, read> lights : Lights;
+@binding(0) @group(2) var<uniform> object : ObjectUniforms;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vPosition: vec3<f32>,
+    @location(1) vNormal: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>, @location(2) uv: vec2<f32>) -> Output {
+    let worldPosition = object.modelMatrix * vec4<f32>(pos, 1.0);
+    var output: Output;
+    output.Position = scene.viewProjectionMatrix * worldPosition;
+    output.vPosition = worldPosition.xyz;
+    output.vNormal = (object.normalMatrix * vec4<f32>(normal, 0.0)).xyz;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vPosition: vec3<f32>, @location(1) vNormal: vec3<f32>) -> @location(0) vec4<f32> {
+    let normal = normalize(vNormal);
+    let viewDirection = normalize(scene.cameraPosition.xyz - vPosition);
+    let material = object.material;
+    let baseColor = material.color.rgb;
+
+    var color = blinnPhongAmbient(lights.ambientColor, material, baseColor);
+    for (var i = 0u; i < lights.count; i = i + 1u) {
+        color = color + blinnPhongLight(lights.lights[i], material, baseColor, vPosition, normal, viewDirection);
+    }
+    return vec4<f32>(color, material.color.a);
+}
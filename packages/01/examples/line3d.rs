@@ -2,13 +2,11 @@ use bytemuck::cast_slice;
 use cgmath::{Point3, Vector3};
 use commonlib::{
     renderer::RendererBuilder,
-    transform::{create_projection, create_transforms, create_view_projection},
+    transform::{create_projection, create_view_projection},
+    uniforms::SceneUniforms,
     vertices::Vertex3D,
 };
-use wgpu::{
-    util::DeviceExt, vertex_attr_array, BindGroupEntry, BindGroupLayoutEntry, BufferAddress,
-    BufferUsages, ShaderStages, VertexAttribute, VertexBufferLayout,
-};
+use wgpu::{util::DeviceExt, BindGroupEntry, BufferUsages, ShaderStages};
 use winit::{
     dpi::PhysicalSize,
     event::{Event, WindowEvent},
@@ -20,20 +18,18 @@ fn create_vertices() -> [Vertex3D; 300] {
     let mut vertices = [Vertex3D {
         position: [0.0, 0.0, 0.0],
     }; 300];
-    for i in 0..300 {
+    for (i, vertex) in vertices.iter_mut().enumerate() {
         let t = 0.1 * (i as f32) / 30.0;
         let x = (-t).exp() * (30.0 * t).sin();
         let z = (-t).exp() * (30.0 * t).cos();
         let y = 2.0 * t - 1.0;
-        vertices[i] = Vertex3D {
+        *vertex = Vertex3D {
             position: [x, y, z],
         };
     }
     vertices
 }
 
-const VERTEX_ATTRIBUTE: [VertexAttribute; 1] = vertex_attr_array![0=>Float32x3];
-
 fn main() {
     env_logger::init();
 
@@ -41,10 +37,7 @@ fn main() {
 
     let args: Vec<String> = std::env::args().collect();
     if args.len() > 1 {
-        is_perspective = match args[1].as_str() {
-            "ortho" => false,
-            _ => true,
-        }
+        is_perspective = args[1].as_str() != "ortho";
     }
 
     let event_loop = EventLoop::new();
@@ -66,23 +59,13 @@ fn main() {
             Some(wgpu::IndexFormat::Uint32),
         )
         .create_shader_module(Some("Shader Module"), include_str!("line3d.wgsl"))
-        .add_vertex_buffer_layout(VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex3D>() as BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &VERTEX_ATTRIBUTE,
-        })
+        .add_vertex_buffer_layout(Vertex3D::LAYOUT)
         .add_bind_group_layout(
-            Some("Uniform Bind Group Layout"),
-            &[BindGroupLayoutEntry {
-                binding: 0,
-                count: None,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                visibility: ShaderStages::VERTEX,
-            }],
+            Some("Scene Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX,
+            )],
         )
         .create_pipeline_layout(Some("Pipeline Layout"))
         .create_render_pipeline(Some("Render Pipeline"))
@@ -92,36 +75,40 @@ fn main() {
     let look_direction: Point3<f32> = (0.0, 0.0, 0.0).into();
     let up_direction: Vector3<f32> = Vector3::unit_y();
 
-    let model_matrix = create_transforms([0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]);
-    let (view_matrix, _projection_matrix, view_projection_matrix) = create_view_projection(
+    let (view_matrix, projection_matrix, _view_projection_matrix) = create_view_projection(
         camera_position,
         look_direction,
         up_direction,
         window_size.width as f32 / window_size.height as f32,
         is_perspective,
     );
-    let mvp_matrix = view_projection_matrix * model_matrix;
 
-    let uniform_buffer =
+    let scene_buffer =
         renderer
             .device()
             .unwrap()
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Uniform Buffer"),
-                contents: cast_slice(mvp_matrix.as_ref() as &[f32; 16]),
+                label: Some("Scene Uniform Buffer"),
+                contents: bytemuck::bytes_of(&SceneUniforms::new(
+                    view_matrix,
+                    projection_matrix,
+                    camera_position,
+                    [window_size.width as f32, window_size.height as f32],
+                    0.0,
+                )),
                 usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
             });
 
-    let uniform_bind_group =
+    let scene_bind_group =
         renderer
             .device()
             .unwrap()
             .create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Uniform Bind"),
-                layout: renderer.bind_group_layouts().get(0).unwrap(),
+                label: Some("Scene Bind Group"),
+                layout: &renderer.bind_group_layouts()[0],
                 entries: &[BindGroupEntry {
                     binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
+                    resource: scene_buffer.as_entire_binding(),
                 }],
             });
 
@@ -159,12 +146,16 @@ fn main() {
 
                 let new_projection_matrix =
                     create_projection(size.width as f32 / size.height as f32, is_perspective);
-                let mvp_mat = new_projection_matrix * view_matrix * model_matrix;
-                let mvp_ref: &[f32; 16] = mvp_mat.as_ref();
                 renderer.queue().unwrap().write_buffer(
-                    &uniform_buffer,
+                    &scene_buffer,
                     0,
-                    bytemuck::cast_slice(mvp_ref),
+                    bytemuck::bytes_of(&SceneUniforms::new(
+                        view_matrix,
+                        new_projection_matrix,
+                        camera_position,
+                        [size.width as f32, size.height as f32],
+                        0.0,
+                    )),
                 );
             }
             Event::RedrawRequested(_) => {
@@ -197,9 +188,9 @@ fn main() {
                             })],
                             depth_stencil_attachment: None,
                         });
-                    render_pass.set_pipeline(&render_pipeline);
+                    render_pass.set_pipeline(render_pipeline);
                     render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                    render_pass.set_bind_group(0, &uniform_bind_group, &[]);
+                    render_pass.set_bind_group(0, &scene_bind_group, &[]);
                     render_pass.draw(0..300, 0..1)
                 }
                 queue.submit(Some(command_encoder.finish()));
@@ -1,12 +1,8 @@
-struct Uniforms {
-mvpMatrix: mat4x4<f32>,
-};
-
-@binding(0) @group(0) var<uniform> uniforms : Uniforms;
+#include "common/uniforms.wgsl"
 
 @vertex
 fn vs_main(@location(0) pos: vec4<f32>) -> @builtin(position) vec4<f32> {
-    return uniforms.mvpMatrix * pos;
+    return scene.viewProjectionMatrix * pos;
 }
 
 @fragment
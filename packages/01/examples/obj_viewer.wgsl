@@ -0,0 +1,19 @@
+#define VERTEX_NORMAL
+#include "common/uniforms.wgsl"
+#include "common/color_output.wgsl"
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>, @location(2) color: vec4<f32>) -> Output {
+    var output: Output;
+    output.Position = scene.viewProjectionMatrix * model.modelMatrix * vec4<f32>(pos, 1.0);
+    output.vNormal = (model.normalMatrix * vec4<f32>(normal, 0.0)).xyz;
+    output.vColor = color;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vNormal: vec3<f32>, @location(1) vColor: vec4<f32>) -> @location(0) vec4<f32> {
+    let lightDirection = normalize(vec3<f32>(0.5, 1.0, 0.3));
+    let diffuse = abs(dot(normalize(vNormal), lightDirection));
+    return vec4<f32>(vColor.rgb * (0.3 + 0.7 * diffuse), vColor.a);
+}
@@ -0,0 +1,360 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::Deg;
+use commonlib::{
+    camera::{Camera, CameraController},
+    hdr::HdrPipeline,
+    mesh::Mesh,
+    particles::{Emitter, ParticleRenderMode, ParticleSystem},
+    projection::Projection,
+    renderer::RendererBuilder,
+    uniform_arena::UniformArena,
+    uniforms::SceneUniforms,
+    vertices::{primitives, Vertex3DNormalUv},
+};
+use wgpu::{
+    util::DeviceExt, BindGroupEntry, BufferUsages, Extent3d, ShaderStages, TextureDescriptor,
+    TextureUsages,
+};
+use winit::{
+    event::{DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ObjectUniforms {
+    model_matrix: [[f32; 4]; 4],
+    normal_matrix: [[f32; 4]; 4],
+    color: [f32; 4],
+}
+
+struct SceneObject {
+    mesh: Mesh<Vertex3DNormalUv>,
+    translation: [f32; 3],
+    color: [f32; 4],
+}
+
+const ANIMATION_SPEED: f32 = 1.0;
+const MAX_PARTICLES: u32 = 20_000;
+const ORBIT_RADIUS: f32 = 3.0;
+
+fn main() {
+    env_logger::init();
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Particles - billboards")
+        .build(&event_loop)
+        .expect("to create window");
+
+    let mut renderer = RendererBuilder::new(window)
+        .create_instance()
+        .create_surface()
+        .get_adapter()
+        .get_device(Some("Device"))
+        .create_surface_configuration()
+        .create_shader_module(Some("Shader"), include_str!("particles.wgsl"))
+        .add_vertex_buffer_layout(Vertex3DNormalUv::LAYOUT)
+        .add_bind_group_layout(
+            Some("Scene Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .add_bind_group_layout(
+            Some("Object Bind Group Layout"),
+            &[UniformArena::<ObjectUniforms>::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
+        .set_depth_stencil_state(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth24Plus,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        })
+        .create_hdr_pipeline()
+        .create_pipeline_layout(Some("Pipeline Layout"))
+        .create_render_pipeline(Some("Render Pipeline"))
+        .build();
+
+    let surface_configuration = renderer.surface_configuration().unwrap();
+    let device = renderer.device().unwrap();
+
+    // P switches between billboards and points.
+    let mut particles = ParticleSystem::new(
+        device,
+        MAX_PARTICLES,
+        HdrPipeline::FORMAT,
+        Some(wgpu::TextureFormat::Depth24Plus),
+    );
+    particles.set_drag(0.3);
+    particles.add_emitter(
+        Emitter::cone([0.0, -0.4, 0.0], [0.0, 1.0, 0.0], Deg(12.0), 0.1, 3000.0)
+            .set_speed(5.0, 6.0)
+            .set_lifetime(1.6, 2.0)
+            .set_colors([0.4, 0.8, 3.0, 1.0], [0.1, 0.2, 1.0, 0.0])
+            .set_sizes(0.08, 0.03),
+    );
+    particles.add_emitter(
+        Emitter::sphere([-3.0, 0.5, 0.0], 0.3, 400.0)
+            .set_speed(0.2, 0.8)
+            .set_lifetime(1.0, 1.5)
+            .set_colors([6.0, 2.5, 0.5, 1.0], [1.0, 0.1, 0.0, 0.0])
+            .set_sizes(0.12, 0.02),
+    );
+    let orbiting_emitter = particles.add_emitter(
+        Emitter::point([ORBIT_RADIUS, 1.0, 0.0], 800.0)
+            .set_speed(0.3, 0.6)
+            .set_lifetime(1.0, 1.5)
+            .set_colors([0.5, 4.0, 1.0, 1.0], [0.0, 0.5, 0.2, 0.0])
+            .set_sizes(0.06, 0.0),
+    );
+
+    let mut camera = Camera::new([0.0, 3.0, 8.0], Deg(-90.0), Deg(-20.0));
+    let mut camera_controller = CameraController::new(0.005);
+    let mut projection = Projection::new(
+        surface_configuration.width as f32,
+        surface_configuration.height as f32,
+    );
+    projection.replace_view_matrix(camera.view_matrix());
+
+    let viewport_size = [
+        surface_configuration.width as f32,
+        surface_configuration.height as f32,
+    ];
+    let scene_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Scene Uniform Buffer"),
+        contents: bytemuck::bytes_of(&SceneUniforms::from_camera(
+            &camera,
+            &projection,
+            viewport_size,
+            0.0,
+        )),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let scene_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Scene Bind Group"),
+        layout: &renderer.bind_group_layouts()[0],
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: scene_buffer.as_entire_binding(),
+        }],
+    });
+
+    let objects = vec![
+        SceneObject {
+            mesh: Mesh::new(device, Some("Floor"), &primitives::plane(12.0, 12.0, 1, 1)),
+            translation: [0.0, -1.0, 0.0],
+            color: [0.3, 0.3, 0.35, 1.0],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Fountain"), &primitives::cube(0.6)),
+            translation: [0.0, -0.7, 0.0],
+            color: [0.5, 0.5, 0.55, 1.0],
+        },
+    ];
+
+    let mut object_arena: UniformArena<ObjectUniforms> =
+        UniformArena::new(device, Some("Object Arena"), objects.len() as u32);
+    object_arena.create_bind_group(device, &renderer.bind_group_layouts()[1], 0);
+
+    let render_start_time = std::time::Instant::now();
+    let mut last_frame_time = render_start_time;
+
+    let mut mouse_pressed = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_poll();
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                control_flow.set_exit();
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::P),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let (render_mode, title) = match particles.render_mode() {
+                    ParticleRenderMode::Billboards => {
+                        (ParticleRenderMode::Points, "Particles - points")
+                    }
+                    ParticleRenderMode::Points => {
+                        (ParticleRenderMode::Billboards, "Particles - billboards")
+                    }
+                };
+                particles.set_render_mode(render_mode);
+                renderer.window().unwrap().set_title(title);
+            }
+            Event::DeviceEvent { event, .. } => match event {
+                DeviceEvent::Button { button: 1, state } => {
+                    mouse_pressed = state == ElementState::Pressed;
+                }
+                DeviceEvent::MouseMotion { delta } if mouse_pressed => {
+                    camera_controller.set_rotation(delta.0 as f32, delta.1 as f32);
+                }
+                _ => (),
+            },
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                renderer.resize(size.width, size.height);
+                projection.set_aspect_ratio(size.width as f32 / size.height as f32);
+            }
+            Event::RedrawRequested(_) => {
+                let current_time = std::time::Instant::now();
+                let current_duration = current_time - render_start_time;
+                let delta_time = (current_time - last_frame_time).as_secs_f32();
+                last_frame_time = current_time;
+                let animated_duration = ANIMATION_SPEED * current_duration.as_secs_f32();
+
+                camera_controller.update_camera(&mut camera);
+                projection.replace_view_matrix(camera.view_matrix());
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let viewport_size = [
+                    surface_configuration.width as f32,
+                    surface_configuration.height as f32,
+                ];
+                renderer.queue().unwrap().write_buffer(
+                    &scene_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_camera(
+                        &camera,
+                        &projection,
+                        viewport_size,
+                        animated_duration,
+                    )),
+                );
+
+                object_arena.clear();
+                for object in &objects {
+                    projection.set_model_matrix(
+                        object.translation,
+                        [0.0, 0.0, 0.0],
+                        [1.0, 1.0, 1.0],
+                    );
+                    object_arena.push(&ObjectUniforms {
+                        model_matrix: (*projection.model_matrix()).into(),
+                        normal_matrix: (*projection.normal_matrix()).into(),
+                        color: object.color,
+                    });
+                }
+                if object_arena.upload(renderer.device().unwrap(), renderer.queue().unwrap()) {
+                    object_arena.create_bind_group(
+                        renderer.device().unwrap(),
+                        &renderer.bind_group_layouts()[1],
+                        0,
+                    );
+                }
+
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                let queue = renderer.queue().unwrap();
+                let render_pipeline = renderer.render_pipeline().unwrap();
+                let hdr_pipeline = renderer.hdr_pipeline().unwrap();
+                let surface_texture = surface.get_current_texture().unwrap();
+
+                let texture_view = surface_texture.texture.create_view(&Default::default());
+                let depth_texture = device.create_texture(&TextureDescriptor {
+                    label: Some("Texture"),
+                    size: Extent3d {
+                        depth_or_array_layers: 1,
+                        width: surface_configuration.width,
+                        height: surface_configuration.height,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Depth24Plus,
+                    usage: TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                });
+                let depth_view = depth_texture.create_view(&Default::default());
+
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+                let orbit = *particles.emitter(orbiting_emitter).unwrap();
+                particles.set_emitter(
+                    orbiting_emitter,
+                    orbit.set_position([
+                        ORBIT_RADIUS * animated_duration.cos(),
+                        1.0 + 0.5 * (2.0 * animated_duration).sin(),
+                        ORBIT_RADIUS * animated_duration.sin(),
+                    ]),
+                );
+                particles.update_from_camera(queue, &camera, &projection);
+                particles.simulate(queue, &mut command_encoder, delta_time);
+
+                {
+                    let mut render_pass =
+                        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: None,
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: hdr_pipeline.view(),
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                                        r: 0.02,
+                                        g: 0.02,
+                                        b: 0.03,
+                                        a: 1.0,
+                                    }),
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: Some(
+                                wgpu::RenderPassDepthStencilAttachment {
+                                    view: &depth_view,
+                                    depth_ops: Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(1.0),
+                                        store: false,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                        });
+                    render_pass.set_pipeline(render_pipeline);
+                    render_pass.set_bind_group(0, &scene_bind_group, &[]);
+                    let object_bind_group = object_arena.bind_group().unwrap();
+                    for (index, object) in objects.iter().enumerate() {
+                        render_pass.set_bind_group(
+                            1,
+                            object_bind_group,
+                            &[object_arena.offset(index as u32)],
+                        );
+                        object.mesh.draw(&mut render_pass);
+                    }
+                    particles.draw(&mut render_pass);
+                }
+
+                hdr_pipeline.render(queue, &mut command_encoder, &texture_view, delta_time);
+
+                queue.submit(Some(command_encoder.finish()));
+                surface_texture.present();
+            }
+            Event::MainEventsCleared => {
+                renderer.window().unwrap().request_redraw();
+            }
+            _ => (),
+        }
+    })
+}
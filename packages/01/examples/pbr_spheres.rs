@@ -0,0 +1,358 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::Deg;
+use commonlib::{
+    camera::{Camera, CameraController},
+    lighting::{Light, LightBuffer},
+    mesh::Mesh,
+    pbr::{ibl::EnvironmentLighting, PbrMaterial, PbrMaterialBuilder},
+    projection::Projection,
+    renderer::RendererBuilder,
+    skybox::Skybox,
+    texture::{compressed::CompressedImage, cubemap::face_direction, TextureBuilder},
+    uniform_arena::UniformArena,
+    uniforms::SceneUniforms,
+    vertices::{primitives, Vertex3DNormalUv},
+};
+use wgpu::{
+    util::DeviceExt, BindGroupEntry, BindGroupLayoutEntry, BufferUsages, Extent3d, ShaderStages,
+    TextureDescriptor, TextureUsages,
+};
+use winit::{
+    event::{DeviceEvent, ElementState, Event, WindowEvent},
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ObjectUniforms {
+    model_matrix: [[f32; 4]; 4],
+    normal_matrix: [[f32; 4]; 4],
+}
+
+// A sky fading from a deep blue zenith to a pale horizon over a brown ground,
+// used when no panorama is given.
+fn gradient_cubemap(size: u32) -> CompressedImage {
+    let mut pixels = Vec::with_capacity((size * size * 4 * 6) as usize);
+    for face in 0..6 {
+        for y in 0..size {
+            for x in 0..size {
+                let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let height = face_direction(face, s, t).y;
+                let color = if height >= 0.0 {
+                    let blend = height.powf(0.5);
+                    [0.85 - 0.7 * blend, 0.9 - 0.5 * blend, 1.0 - 0.2 * blend]
+                } else {
+                    let blend = (-height).powf(0.5);
+                    [0.45 - 0.2 * blend, 0.38 - 0.2 * blend, 0.3 - 0.15 * blend]
+                };
+                pixels.extend(color.map(|channel| (channel * 255.0) as u8));
+                pixels.push(255);
+            }
+        }
+    }
+    CompressedImage {
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        width: size,
+        height: size,
+        array_layers: 6,
+        is_cubemap: true,
+        levels: vec![pixels],
+    }
+}
+
+const GRID_SIZE: usize = 5;
+const GRID_SPACING: f32 = 2.5;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+    let panorama_path = args.get(1);
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("PBR Spheres")
+        .build(&event_loop)
+        .expect("to create window");
+
+    let mut scene_entries = vec![SceneUniforms::bind_group_layout_entry(
+        0,
+        ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+    )];
+    scene_entries.extend(EnvironmentLighting::bind_group_layout_entries(
+        1,
+        ShaderStages::FRAGMENT,
+    ));
+    let light_entries: [BindGroupLayoutEntry; 1] = [LightBuffer::bind_group_layout_entry(
+        0,
+        ShaderStages::FRAGMENT,
+    )];
+
+    let mut renderer = RendererBuilder::new(window)
+        .create_instance()
+        .create_surface()
+        .get_adapter()
+        .get_device(Some("Device"))
+        .create_surface_configuration()
+        .create_shader_module(Some("Shader"), include_str!("pbr_spheres.wgsl"))
+        .add_vertex_buffer_layout(Vertex3DNormalUv::LAYOUT)
+        .add_bind_group_layout(Some("Scene Bind Group Layout"), &scene_entries)
+        .add_bind_group_layout(Some("Light Bind Group Layout"), &light_entries)
+        .add_bind_group_layout(
+            Some("Object Bind Group Layout"),
+            &[UniformArena::<ObjectUniforms>::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX,
+            )],
+        )
+        .add_bind_group_layout(
+            Some("Material Bind Group Layout"),
+            &PbrMaterial::bind_group_layout_entries(ShaderStages::FRAGMENT),
+        )
+        .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
+        .set_depth_stencil_state(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth24Plus,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        })
+        .create_pipeline_layout(Some("Pipeline Layout"))
+        .create_render_pipeline(Some("Render Pipeline"))
+        .build();
+
+    let device = renderer.device().unwrap();
+    let queue = renderer.queue().unwrap();
+
+    let environment_builder = TextureBuilder::new().set_label("Environment");
+    let environment = match panorama_path {
+        Some(panorama_path) => environment_builder
+            .build_cubemap_from_equirectangular(device, queue, panorama_path)
+            .unwrap_or_else(|error| panic!("{}", error)),
+        None => environment_builder
+            .build_from_compressed(device, queue, &gradient_cubemap(256))
+            .unwrap_or_else(|error| panic!("{}", error)),
+    };
+    let environment_lighting = EnvironmentLighting::new(device, queue, &environment);
+    let skybox = Skybox::new(
+        device,
+        environment,
+        renderer.surface_configuration().unwrap().format,
+        Some(wgpu::TextureFormat::Depth24Plus),
+    );
+
+    let mut camera = Camera::new([0.0, 0.0, 12.0], Deg(-90.0), Deg(0.0));
+    let mut camera_controller = CameraController::new(0.005);
+    let mut projection = Projection::new(
+        renderer.surface_configuration().unwrap().width as f32,
+        renderer.surface_configuration().unwrap().height as f32,
+    );
+    projection.replace_view_matrix(camera.view_matrix());
+
+    let viewport_size = [
+        renderer.surface_configuration().unwrap().width as f32,
+        renderer.surface_configuration().unwrap().height as f32,
+    ];
+    let scene_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Scene Uniform Buffer"),
+        contents: bytemuck::bytes_of(&SceneUniforms::from_camera(
+            &camera,
+            &projection,
+            viewport_size,
+            0.0,
+        )),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let mut scene_bind_group_entries = vec![BindGroupEntry {
+        binding: 0,
+        resource: scene_buffer.as_entire_binding(),
+    }];
+    scene_bind_group_entries.extend(environment_lighting.bind_group_entries(1));
+    let scene_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Scene Bind Group"),
+        layout: &renderer.bind_group_layouts()[0],
+        entries: &scene_bind_group_entries,
+    });
+
+    let mut light_buffer = LightBuffer::new(device, Some("Lights"), 2);
+    light_buffer.set_ambient_color([0.0, 0.0, 0.0]);
+    light_buffer.add_light(Light::point([-6.0, 6.0, 8.0], [1.0, 1.0, 1.0], 60.0, 0.0));
+    light_buffer.add_light(Light::point([6.0, -4.0, 8.0], [1.0, 0.9, 0.8], 40.0, 0.0));
+    if light_buffer.upload(device, queue) || light_buffer.bind_group().is_none() {
+        light_buffer.create_bind_group(device, &renderer.bind_group_layouts()[1], 0);
+    }
+
+    // Metalness grows from bottom to top, roughness from left to right.
+    let mut object_arena: UniformArena<ObjectUniforms> =
+        UniformArena::new(device, Some("Object Arena"), (GRID_SIZE * GRID_SIZE) as u32);
+    let mut materials = Vec::with_capacity(GRID_SIZE * GRID_SIZE);
+    let offset = (GRID_SIZE - 1) as f32 * GRID_SPACING / 2.0;
+    for row in 0..GRID_SIZE {
+        for column in 0..GRID_SIZE {
+            projection.set_model_matrix(
+                [
+                    column as f32 * GRID_SPACING - offset,
+                    row as f32 * GRID_SPACING - offset,
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0],
+                [1.0, 1.0, 1.0],
+            );
+            object_arena.push(&ObjectUniforms {
+                model_matrix: (*projection.model_matrix()).into(),
+                normal_matrix: (*projection.normal_matrix()).into(),
+            });
+            materials.push(
+                PbrMaterialBuilder::new()
+                    .set_label("Sphere Material")
+                    .set_base_color_factor([0.9, 0.3, 0.2, 1.0])
+                    .set_metallic_factor(row as f32 / (GRID_SIZE - 1) as f32)
+                    .set_roughness_factor((column as f32 / (GRID_SIZE - 1) as f32).max(0.05))
+                    .build(device, queue, &renderer.bind_group_layouts()[3]),
+            );
+        }
+    }
+    if object_arena.upload(device, queue) || object_arena.bind_group().is_none() {
+        object_arena.create_bind_group(device, &renderer.bind_group_layouts()[2], 0);
+    }
+
+    let sphere = Mesh::new(device, Some("Sphere"), &primitives::uv_sphere(1.0, 64, 32));
+
+    let render_start_time = std::time::Instant::now();
+
+    let mut mouse_pressed = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_poll();
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                control_flow.set_exit();
+            }
+            Event::DeviceEvent { event, .. } => match event {
+                DeviceEvent::Button { button: 1, state } => {
+                    mouse_pressed = state == ElementState::Pressed;
+                }
+                DeviceEvent::MouseMotion { delta } if mouse_pressed => {
+                    camera_controller.set_rotation(delta.0 as f32, delta.1 as f32);
+                }
+                _ => (),
+            },
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                {
+                    let surface_configuration = renderer.mut_surface_configuration().unwrap();
+                    surface_configuration.width = size.width;
+                    surface_configuration.height = size.height;
+                }
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                surface.configure(device, surface_configuration);
+
+                projection.set_aspect_ratio(size.width as f32 / size.height as f32);
+            }
+            Event::RedrawRequested(_) => {
+                let current_duration = std::time::Instant::now() - render_start_time;
+
+                camera_controller.update_camera(&mut camera);
+                projection.replace_view_matrix(camera.view_matrix());
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let viewport_size = [
+                    surface_configuration.width as f32,
+                    surface_configuration.height as f32,
+                ];
+                let queue = renderer.queue().unwrap();
+                queue.write_buffer(
+                    &scene_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_camera(
+                        &camera,
+                        &projection,
+                        viewport_size,
+                        current_duration.as_secs_f32(),
+                    )),
+                );
+                skybox.update_from_camera(queue, &camera, &projection);
+
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                let render_pipeline = renderer.render_pipeline().unwrap();
+                let surface_texture = surface.get_current_texture().unwrap();
+
+                let texture_view = surface_texture.texture.create_view(&Default::default());
+                let depth_texture = device.create_texture(&TextureDescriptor {
+                    label: Some("Texture"),
+                    size: Extent3d {
+                        depth_or_array_layers: 1,
+                        width: surface_configuration.width,
+                        height: surface_configuration.height,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Depth24Plus,
+                    usage: TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                });
+                let depth_view = depth_texture.create_view(&Default::default());
+
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+                {
+                    let mut render_pass =
+                        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: None,
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: &texture_view,
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: Some(
+                                wgpu::RenderPassDepthStencilAttachment {
+                                    view: &depth_view,
+                                    depth_ops: Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(1.0),
+                                        store: false,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                        });
+                    render_pass.set_pipeline(render_pipeline);
+                    render_pass.set_bind_group(0, &scene_bind_group, &[]);
+                    render_pass.set_bind_group(1, light_buffer.bind_group().unwrap(), &[]);
+                    let object_bind_group = object_arena.bind_group().unwrap();
+                    for (index, material) in materials.iter().enumerate() {
+                        render_pass.set_bind_group(
+                            2,
+                            object_bind_group,
+                            &[object_arena.offset(index as u32)],
+                        );
+                        render_pass.set_bind_group(3, material.bind_group(), &[]);
+                        sphere.draw(&mut render_pass);
+                    }
+                    skybox.draw(&mut render_pass);
+                }
+                queue.submit(Some(command_encoder.finish()));
+                surface_texture.present();
+            }
+            Event::MainEventsCleared => {
+                renderer.window().unwrap().request_redraw();
+            }
+            _ => (),
+        }
+    })
+}
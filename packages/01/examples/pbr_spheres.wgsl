@@ -0,0 +1,76 @@
+#include "common/scene_uniforms.wgsl"
+#include "common/lighting.wgsl"
+#include "common/pbr.wgsl"
+
+struct ObjectUniforms {
+    modelMatrix: mat4x4<f32>,
+    normalMatrix: mat4x4<f32>,
+};
+
+@binding(0) @group(0) var<uniform> scene : SceneUniforms;
+@binding(1) @group(0) var irradianceTexture: texture_cube<f32>;
+@binding(2) @group(0) var irradianceSampler: sampler;
+@binding(3) @group(0) var prefilteredTexture: texture_cube<f32>;
+@binding(4) @group(0) var prefilteredSampler: sampler;
+@binding(5) @group(0) var brdfLutTexture: texture_2d<f32>;
+@binding(6) @group(0) var brdfLutSampler: sampler;
+
+@binding(0) @group(1) var<storage, read> lights : Lights;
+
+@binding(0) @group(2) var<uniform> object : ObjectUniforms;
+
+@binding(0) @group(3) var<uniform> material : PbrMaterialUniforms;
+@binding(1) @group(3) var albedoTexture: texture_2d<f32>;
+@binding(2) @group(3) var albedoSampler: sampler;
+@binding(3) @group(3) var normalTexture: texture_2d<f32>;
+@binding(4) @group(3) var normalSampler: sampler;
+@binding(5) @group(3) var metallicRoughnessTexture: texture_2d<f32>;
+@binding(6) @group(3) var metallicRoughnessSampler: sampler;
+@binding(7) @group(3) var occlusionTexture: texture_2d<f32>;
+@binding(8) @group(3) var occlusionSampler: sampler;
+@binding(9) @group(3) var emissiveTexture: texture_2d<f32>;
+@binding(10) @group(3) var emissiveSampler: sampler;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vPosition: vec3<f32>,
+    @location(1) vNormal: vec3<f32>,
+    @location(2) vUv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>, @location(2) uv: vec2<f32>) -> Output {
+    let worldPosition = object.modelMatrix * vec4<f32>(pos, 1.0);
+    var output: Output;
+    output.Position = scene.viewProjectionMatrix * worldPosition;
+    output.vPosition = worldPosition.xyz;
+    output.vNormal = (object.normalMatrix * vec4<f32>(normal, 0.0)).xyz;
+    output.vUv = uv;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vPosition: vec3<f32>, @location(1) vNormal: vec3<f32>, @location(2) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let baseColor = textureSample(albedoTexture, albedoSampler, vUv) * material.baseColorFactor;
+    let metallicRoughness = textureSample(metallicRoughnessTexture, metallicRoughnessSampler, vUv);
+    let metallic = clamp(material.metallicFactor * metallicRoughness.b, 0.0, 1.0);
+    let roughness = clamp(material.roughnessFactor * metallicRoughness.g, 0.04, 1.0);
+    let occlusion = mix(1.0, textureSample(occlusionTexture, occlusionSampler, vUv).r, material.occlusionStrength);
+    let emissive = textureSample(emissiveTexture, emissiveSampler, vUv).rgb * material.emissiveFactor;
+
+    var normal = normalize(vNormal);
+    var tangentNormal = textureSample(normalTexture, normalSampler, vUv).xyz * 2.0 - 1.0;
+    tangentNormal = normalize(vec3<f32>(tangentNormal.xy * material.normalScale, tangentNormal.z));
+    let perturbed = perturbNormal(normal, vPosition, vUv, tangentNormal);
+    normal = select(normal, perturbed, (material.flags & PBR_NORMAL_TEXTURE) != 0u);
+
+    let viewDirection = normalize(scene.cameraPosition.xyz - vPosition);
+    var color = pbrAmbient(irradianceTexture, prefilteredTexture, prefilteredSampler, brdfLutTexture, brdfLutSampler, baseColor.rgb, metallic, roughness, occlusion, normal, viewDirection);
+    for (var i = 0u; i < lights.count; i = i + 1u) {
+        color = color + pbrLight(lights.lights[i], baseColor.rgb, metallic, roughness, vPosition, normal, viewDirection);
+    }
+    color = color + emissive;
+
+    // Reinhard tone mapping, the sRGB surface applies the gamma curve.
+    return vec4<f32>(color / (color + vec3<f32>(1.0)), baseColor.a);
+}
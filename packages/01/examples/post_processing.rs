@@ -0,0 +1,402 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::Deg;
+use commonlib::{
+    camera::{Camera, CameraController},
+    mesh::Mesh,
+    post_process::{PostEffect, PostProcessStack, TonemapOperator},
+    projection::Projection,
+    renderer::RendererBuilder,
+    uniform_arena::UniformArena,
+    uniforms::SceneUniforms,
+    vertices::{primitives, Vertex3DNormalUv},
+};
+use wgpu::{
+    util::DeviceExt, BindGroupEntry, BufferUsages, Extent3d, ShaderStages, TextureDescriptor,
+    TextureUsages,
+};
+use winit::{
+    event::{DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ObjectUniforms {
+    model_matrix: [[f32; 4]; 4],
+    normal_matrix: [[f32; 4]; 4],
+    color: [f32; 4],
+    emissive: [f32; 4],
+}
+
+struct SceneObject {
+    mesh: Mesh<Vertex3DNormalUv>,
+    translation: [f32; 3],
+    spin: f32,
+    color: [f32; 4],
+    emissive: [f32; 4],
+}
+
+const ANIMATION_SPEED: f32 = 1.0;
+
+fn main() {
+    env_logger::init();
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Post Processing")
+        .build(&event_loop)
+        .expect("to create window");
+
+    let mut renderer = RendererBuilder::new(window)
+        .create_instance()
+        .create_surface()
+        .get_adapter()
+        .get_device(Some("Device"))
+        .create_surface_configuration()
+        .create_shader_module(Some("Shader"), include_str!("post_processing.wgsl"))
+        .add_vertex_buffer_layout(Vertex3DNormalUv::LAYOUT)
+        .add_bind_group_layout(
+            Some("Scene Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .add_bind_group_layout(
+            Some("Object Bind Group Layout"),
+            &[UniformArena::<ObjectUniforms>::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
+        .set_depth_stencil_state(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth24Plus,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        })
+        .set_color_target_format(PostProcessStack::HDR_FORMAT)
+        .create_pipeline_layout(Some("Pipeline Layout"))
+        .create_render_pipeline(Some("Render Pipeline"))
+        .build();
+
+    let surface_configuration = renderer.surface_configuration().unwrap();
+    let device = renderer.device().unwrap();
+
+    // Keys 1 to 6 toggle the effects, T switches the tonemapping operator. The
+    // surface is sRGB, so gamma starts disabled.
+    let mut post_process = PostProcessStack::new(
+        device,
+        surface_configuration.width,
+        surface_configuration.height,
+        surface_configuration.format,
+    );
+    post_process.add_effect(device, PostEffect::bloom());
+    post_process.add_effect(
+        device,
+        PostEffect::Custom {
+            label: "Chromatic Aberration".to_string(),
+            source: include_str!("post_processing_effect.wgsl").to_string(),
+            params: [6.0, 0.0, 0.0, 0.0],
+        },
+    );
+    let tonemap = post_process.add_effect(device, PostEffect::aces(1.0));
+    post_process.add_effect(device, PostEffect::Fxaa);
+    post_process.add_effect(device, PostEffect::vignette());
+    let gamma = post_process.add_effect(device, PostEffect::Gamma(2.2));
+    post_process.set_enabled(gamma, false);
+    renderer
+        .window()
+        .unwrap()
+        .set_title(&effects_title(&post_process));
+
+    let mut camera = Camera::new([0.0, 3.0, 8.0], Deg(-90.0), Deg(-20.0));
+    let mut camera_controller = CameraController::new(0.005);
+    let mut projection = Projection::new(
+        surface_configuration.width as f32,
+        surface_configuration.height as f32,
+    );
+    projection.replace_view_matrix(camera.view_matrix());
+
+    let viewport_size = [
+        surface_configuration.width as f32,
+        surface_configuration.height as f32,
+    ];
+    let scene_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Scene Uniform Buffer"),
+        contents: bytemuck::bytes_of(&SceneUniforms::from_camera(
+            &camera,
+            &projection,
+            viewport_size,
+            0.0,
+        )),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let scene_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Scene Bind Group"),
+        layout: &renderer.bind_group_layouts()[0],
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: scene_buffer.as_entire_binding(),
+        }],
+    });
+
+    let objects = vec![
+        SceneObject {
+            mesh: Mesh::new(device, Some("Floor"), &primitives::plane(12.0, 12.0, 1, 1)),
+            translation: [0.0, -1.0, 0.0],
+            spin: 0.0,
+            color: [0.3, 0.3, 0.35, 1.0],
+            emissive: [0.0; 4],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Cube"), &primitives::cube(0.8)),
+            translation: [-2.0, 0.0, 0.0],
+            spin: 1.0,
+            color: [0.8, 0.4, 0.2, 1.0],
+            emissive: [0.0; 4],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Sphere"), &primitives::uv_sphere(0.6, 48, 24)),
+            translation: [0.0, 0.5, 0.0],
+            spin: 0.0,
+            color: [1.0, 0.9, 0.6, 1.0],
+            emissive: [4.0, 3.0, 1.5, 0.0],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Torus"), &primitives::torus(0.7, 0.25, 48, 16)),
+            translation: [2.0, 0.0, 0.0],
+            spin: -0.7,
+            color: [0.2, 0.5, 1.0, 1.0],
+            emissive: [0.2, 0.8, 3.0, 0.0],
+        },
+    ];
+
+    let mut object_arena: UniformArena<ObjectUniforms> =
+        UniformArena::new(device, Some("Object Arena"), objects.len() as u32);
+    object_arena.create_bind_group(device, &renderer.bind_group_layouts()[1], 0);
+
+    let render_start_time = std::time::Instant::now();
+
+    let mut mouse_pressed = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_poll();
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                control_flow.set_exit();
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let toggled = match keycode {
+                    VirtualKeyCode::Key1 => Some(0),
+                    VirtualKeyCode::Key2 => Some(1),
+                    VirtualKeyCode::Key3 => Some(2),
+                    VirtualKeyCode::Key4 => Some(3),
+                    VirtualKeyCode::Key5 => Some(4),
+                    VirtualKeyCode::Key6 => Some(5),
+                    _ => None,
+                };
+                if let Some(index) = toggled {
+                    post_process.toggle(index);
+                }
+                if keycode == VirtualKeyCode::T {
+                    let effect = match post_process.effect(tonemap) {
+                        Some(PostEffect::Tonemap {
+                            operator: TonemapOperator::Aces,
+                            exposure,
+                        }) => PostEffect::reinhard(*exposure),
+                        Some(PostEffect::Tonemap { exposure, .. }) => PostEffect::aces(*exposure),
+                        _ => PostEffect::aces(1.0),
+                    };
+                    post_process.set_effect(renderer.device().unwrap(), tonemap, effect);
+                }
+                renderer
+                    .window()
+                    .unwrap()
+                    .set_title(&effects_title(&post_process));
+            }
+            Event::DeviceEvent { event, .. } => match event {
+                DeviceEvent::Button { button: 1, state } => {
+                    mouse_pressed = state == ElementState::Pressed;
+                }
+                DeviceEvent::MouseMotion { delta } if mouse_pressed => {
+                    camera_controller.set_rotation(delta.0 as f32, delta.1 as f32);
+                }
+                _ => (),
+            },
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                {
+                    let surface_configuration = renderer.mut_surface_configuration().unwrap();
+                    surface_configuration.width = size.width;
+                    surface_configuration.height = size.height;
+                }
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                surface.configure(device, surface_configuration);
+                post_process.resize(device, size.width, size.height);
+
+                projection.set_aspect_ratio(size.width as f32 / size.height as f32);
+            }
+            Event::RedrawRequested(_) => {
+                let current_time = std::time::Instant::now();
+                let current_duration = current_time - render_start_time;
+                let animated_duration = ANIMATION_SPEED * current_duration.as_secs_f32();
+
+                camera_controller.update_camera(&mut camera);
+                projection.replace_view_matrix(camera.view_matrix());
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let viewport_size = [
+                    surface_configuration.width as f32,
+                    surface_configuration.height as f32,
+                ];
+                renderer.queue().unwrap().write_buffer(
+                    &scene_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_camera(
+                        &camera,
+                        &projection,
+                        viewport_size,
+                        animated_duration,
+                    )),
+                );
+
+                object_arena.clear();
+                for object in &objects {
+                    projection.set_model_matrix(
+                        object.translation,
+                        [0.0, object.spin * animated_duration, 0.0],
+                        [1.0, 1.0, 1.0],
+                    );
+                    object_arena.push(&ObjectUniforms {
+                        model_matrix: (*projection.model_matrix()).into(),
+                        normal_matrix: (*projection.normal_matrix()).into(),
+                        color: object.color,
+                        emissive: object.emissive,
+                    });
+                }
+                if object_arena.upload(renderer.device().unwrap(), renderer.queue().unwrap()) {
+                    object_arena.create_bind_group(
+                        renderer.device().unwrap(),
+                        &renderer.bind_group_layouts()[1],
+                        0,
+                    );
+                }
+
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                let queue = renderer.queue().unwrap();
+                let render_pipeline = renderer.render_pipeline().unwrap();
+                let surface_texture = surface.get_current_texture().unwrap();
+
+                let texture_view = surface_texture.texture.create_view(&Default::default());
+                let depth_texture = device.create_texture(&TextureDescriptor {
+                    label: Some("Texture"),
+                    size: Extent3d {
+                        depth_or_array_layers: 1,
+                        width: surface_configuration.width,
+                        height: surface_configuration.height,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Depth24Plus,
+                    usage: TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                });
+                let depth_view = depth_texture.create_view(&Default::default());
+
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+                {
+                    let mut render_pass =
+                        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: None,
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: post_process.hdr_view(),
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                                        r: 0.02,
+                                        g: 0.02,
+                                        b: 0.03,
+                                        a: 1.0,
+                                    }),
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: Some(
+                                wgpu::RenderPassDepthStencilAttachment {
+                                    view: &depth_view,
+                                    depth_ops: Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(1.0),
+                                        store: false,
+                                    }),
+                                    stencil_ops: None,
+                                },
+                            ),
+                        });
+                    render_pass.set_pipeline(render_pipeline);
+                    render_pass.set_bind_group(0, &scene_bind_group, &[]);
+                    let object_bind_group = object_arena.bind_group().unwrap();
+                    for (index, object) in objects.iter().enumerate() {
+                        render_pass.set_bind_group(
+                            1,
+                            object_bind_group,
+                            &[object_arena.offset(index as u32)],
+                        );
+                        object.mesh.draw(&mut render_pass);
+                    }
+                }
+
+                post_process.set_time(animated_duration);
+                post_process.render(device, queue, &mut command_encoder, &texture_view);
+
+                queue.submit(Some(command_encoder.finish()));
+                surface_texture.present();
+            }
+            Event::MainEventsCleared => {
+                renderer.window().unwrap().request_redraw();
+            }
+            _ => (),
+        }
+    })
+}
+
+fn effects_title(post_process: &PostProcessStack) -> String {
+    let effects: Vec<String> = (0..post_process.effect_count())
+        .map(|index| {
+            let label = post_process.effect(index).unwrap().label();
+            if post_process.is_enabled(index) {
+                format!("{} {}", index + 1, label)
+            } else {
+                format!("{} ({})", index + 1, label)
+            }
+        })
+        .collect();
+    format!("Post Processing - {}", effects.join(", "))
+}
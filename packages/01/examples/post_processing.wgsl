@@ -0,0 +1,33 @@
+#include "common/scene_uniforms.wgsl"
+
+struct ObjectUniforms {
+    modelMatrix: mat4x4<f32>,
+    normalMatrix: mat4x4<f32>,
+    color: vec4<f32>,
+    // Added on top of the lit color, above 1 to make the object glow.
+    emissive: vec4<f32>,
+};
+
+@binding(0) @group(0) var<uniform> scene : SceneUniforms;
+@binding(0) @group(1) var<uniform> object : ObjectUniforms;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vNormal: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>, @location(2) uv: vec2<f32>) -> Output {
+    var output: Output;
+    output.Position = scene.viewProjectionMatrix * object.modelMatrix * vec4<f32>(pos, 1.0);
+    output.vNormal = (object.normalMatrix * vec4<f32>(normal, 0.0)).xyz;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vNormal: vec3<f32>) -> @location(0) vec4<f32> {
+    let lightDirection = normalize(vec3<f32>(0.4, 1.0, 0.3));
+    let diffuse = max(dot(normalize(vNormal), lightDirection), 0.0);
+    let color = object.color.rgb * (0.1 + 2.0 * diffuse) + object.emissive.rgb;
+    return vec4<f32>(color, object.color.a);
+}
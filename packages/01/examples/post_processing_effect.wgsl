@@ -0,0 +1,12 @@
+#include "common/post_process.wgsl"
+
+// Chromatic aberration growing towards the edges, params.x is the strength in
+// texels.
+@fragment
+fn fs_main(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let offset = (vUv - vec2<f32>(0.5)) * post.params.x * post.texelSize * 2.0;
+    let red = textureSample(sourceTexture, sourceSampler, vUv + offset).r;
+    let center = textureSample(sourceTexture, sourceSampler, vUv);
+    let blue = textureSample(sourceTexture, sourceSampler, vUv - offset).b;
+    return vec4<f32>(red, center.g, blue, center.a);
+}
@@ -0,0 +1,529 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::Deg;
+use commonlib::{
+    camera::{Camera, CameraController},
+    mesh::Mesh,
+    projection::Projection,
+    render_graph::{GraphPass, GraphTextureDescriptor, RenderGraph, TextureHandle, TextureSize},
+    renderer::RendererBuilder,
+    texture::Texture,
+    uniform_arena::UniformArena,
+    uniforms::SceneUniforms,
+    vertices::{primitives, Vertex3DNormalUv},
+};
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupEntry, BindGroupLayout, BufferUsages, Device, LoadOp,
+    PipelineLayout, RenderPipeline, Sampler, ShaderModule, ShaderStages, TextureFormat,
+};
+use winit::{
+    event::{DeviceEvent, ElementState, Event, WindowEvent},
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ObjectUniforms {
+    model_matrix: [[f32; 4]; 4],
+    normal_matrix: [[f32; 4]; 4],
+    color: [f32; 4],
+}
+
+struct SceneObject {
+    mesh: Mesh<Vertex3DNormalUv>,
+    translation: [f32; 3],
+    spin: f32,
+    color: [f32; 4],
+}
+
+struct PostBindGroups {
+    blur_x: BindGroup,
+    blur_y: BindGroup,
+    composite: BindGroup,
+    overlay: BindGroup,
+}
+
+struct GraphTextures {
+    scene_color: TextureHandle,
+    blur_x: TextureHandle,
+    blur_y: TextureHandle,
+}
+
+const ANIMATION_SPEED: f32 = 1.0;
+
+fn main() {
+    env_logger::init();
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Render Graph")
+        .build(&event_loop)
+        .expect("to create window");
+
+    let mut renderer = RendererBuilder::new(window)
+        .create_instance()
+        .create_surface()
+        .get_adapter()
+        .get_device(Some("Device"))
+        .create_surface_configuration()
+        .create_shader_module(Some("Shader"), include_str!("render_graph.wgsl"))
+        .add_vertex_buffer_layout(Vertex3DNormalUv::LAYOUT)
+        .add_bind_group_layout(
+            Some("Scene Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .add_bind_group_layout(
+            Some("Object Bind Group Layout"),
+            &[UniformArena::<ObjectUniforms>::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            )],
+        )
+        .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
+        .set_depth_stencil_state(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth24Plus,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        })
+        .create_pipeline_layout(Some("Pipeline Layout"))
+        .create_render_pipeline(Some("Render Pipeline"))
+        .build();
+
+    let surface_configuration = renderer.surface_configuration().unwrap();
+    let format = surface_configuration.format;
+
+    // The scene renders offscreen, a bright pass is blurred at half resolution and
+    // composited onto the surface, then an overlay shows the glow in a corner.
+    let mut graph = RenderGraph::new(surface_configuration.width, surface_configuration.height);
+    let surface_target = graph.import_texture("Surface");
+    let textures = GraphTextures {
+        scene_color: graph.create_texture(GraphTextureDescriptor::new(
+            "Scene Color",
+            format,
+            TextureSize::Relative(1.0),
+        )),
+        blur_x: graph.create_texture(GraphTextureDescriptor::new(
+            "Blur X",
+            format,
+            TextureSize::Relative(0.5),
+        )),
+        blur_y: graph.create_texture(GraphTextureDescriptor::new(
+            "Blur Y",
+            format,
+            TextureSize::Relative(0.5),
+        )),
+    };
+    let depth = graph.create_texture(GraphTextureDescriptor::new(
+        "Depth",
+        TextureFormat::Depth24Plus,
+        TextureSize::Relative(1.0),
+    ));
+    let clear = LoadOp::Clear(wgpu::Color {
+        r: 0.02,
+        g: 0.02,
+        b: 0.03,
+        a: 1.0,
+    });
+    let scene_pass = graph.add_pass(
+        GraphPass::new("Scene")
+            .add_color_attachment(textures.scene_color, clear)
+            .set_depth_attachment(depth, LoadOp::Clear(1.0)),
+    );
+    let blur_x_pass = graph.add_pass(
+        GraphPass::new("Blur X")
+            .add_read(textures.scene_color)
+            .add_color_attachment(textures.blur_x, clear),
+    );
+    let blur_y_pass = graph.add_pass(
+        GraphPass::new("Blur Y")
+            .add_read(textures.blur_x)
+            .add_color_attachment(textures.blur_y, clear),
+    );
+    let composite_pass = graph.add_pass(
+        GraphPass::new("Composite")
+            .add_read(textures.scene_color)
+            .add_read(textures.blur_y)
+            .add_color_attachment(surface_target, clear),
+    );
+    let overlay_pass = graph.add_pass(
+        GraphPass::new("Overlay")
+            .add_read(textures.blur_y)
+            .add_color_attachment(surface_target, LoadOp::Load),
+    );
+    graph.compile(renderer.device().unwrap());
+
+    let device = renderer.device().unwrap();
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Post Sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    let post_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Post Bind Group Layout"),
+        entries: &Texture::bind_group_layout_entries(0, ShaderStages::FRAGMENT),
+    });
+    let [texture_entry, sampler_entry] =
+        Texture::bind_group_layout_entries(0, ShaderStages::FRAGMENT);
+    let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Composite Bind Group Layout"),
+        entries: &[
+            texture_entry,
+            sampler_entry,
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                ..texture_entry
+            },
+        ],
+    });
+    let post_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Post Pipeline Layout"),
+        bind_group_layouts: &[&post_layout],
+        push_constant_ranges: &[],
+    });
+    let composite_pipeline_layout =
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Composite Pipeline Layout"),
+            bind_group_layouts: &[&composite_layout],
+            push_constant_ranges: &[],
+        });
+    let shader = renderer.shader().unwrap();
+    let blur_x_pipeline =
+        create_post_pipeline(device, shader, &post_pipeline_layout, "fs_blur_x", format);
+    let blur_y_pipeline =
+        create_post_pipeline(device, shader, &post_pipeline_layout, "fs_blur_y", format);
+    let composite_pipeline = create_post_pipeline(
+        device,
+        shader,
+        &composite_pipeline_layout,
+        "fs_composite",
+        format,
+    );
+    let overlay_pipeline =
+        create_post_pipeline(device, shader, &post_pipeline_layout, "fs_overlay", format);
+    let mut post_bind_groups = create_post_bind_groups(
+        device,
+        &graph,
+        &textures,
+        &post_layout,
+        &composite_layout,
+        &sampler,
+    );
+
+    let mut camera = Camera::new([0.0, 3.0, 8.0], Deg(-90.0), Deg(-20.0));
+    let mut camera_controller = CameraController::new(0.005);
+    let mut projection = Projection::new(
+        surface_configuration.width as f32,
+        surface_configuration.height as f32,
+    );
+    projection.replace_view_matrix(camera.view_matrix());
+
+    let viewport_size = [
+        surface_configuration.width as f32,
+        surface_configuration.height as f32,
+    ];
+    let scene_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Scene Uniform Buffer"),
+        contents: bytemuck::bytes_of(&SceneUniforms::from_camera(
+            &camera,
+            &projection,
+            viewport_size,
+            0.0,
+        )),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let scene_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Scene Bind Group"),
+        layout: &renderer.bind_group_layouts()[0],
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: scene_buffer.as_entire_binding(),
+        }],
+    });
+
+    let objects = vec![
+        SceneObject {
+            mesh: Mesh::new(device, Some("Floor"), &primitives::plane(12.0, 12.0, 1, 1)),
+            translation: [0.0, -1.0, 0.0],
+            spin: 0.0,
+            color: [0.3, 0.3, 0.35, 1.0],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Cube"), &primitives::cube(0.8)),
+            translation: [-2.0, 0.0, 0.0],
+            spin: 1.0,
+            color: [1.0, 0.5, 0.2, 1.0],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Sphere"), &primitives::uv_sphere(1.0, 48, 24)),
+            translation: [0.0, 0.0, 0.0],
+            spin: 0.0,
+            color: [0.9, 0.95, 1.0, 1.0],
+        },
+        SceneObject {
+            mesh: Mesh::new(device, Some("Torus"), &primitives::torus(0.7, 0.25, 48, 16)),
+            translation: [2.0, 0.0, 0.0],
+            spin: -0.7,
+            color: [0.2, 0.6, 1.0, 1.0],
+        },
+    ];
+
+    let mut object_arena: UniformArena<ObjectUniforms> =
+        UniformArena::new(device, Some("Object Arena"), objects.len() as u32);
+    object_arena.create_bind_group(device, &renderer.bind_group_layouts()[1], 0);
+
+    let render_start_time = std::time::Instant::now();
+
+    let mut mouse_pressed = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        control_flow.set_poll();
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                control_flow.set_exit();
+            }
+            Event::DeviceEvent { event, .. } => match event {
+                DeviceEvent::Button { button: 1, state } => {
+                    mouse_pressed = state == ElementState::Pressed;
+                }
+                DeviceEvent::MouseMotion { delta } if mouse_pressed => {
+                    camera_controller.set_rotation(delta.0 as f32, delta.1 as f32);
+                }
+                _ => (),
+            },
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                {
+                    let surface_configuration = renderer.mut_surface_configuration().unwrap();
+                    surface_configuration.width = size.width;
+                    surface_configuration.height = size.height;
+                }
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                surface.configure(device, surface_configuration);
+
+                // The size-relative attachments are recreated, so are the bind groups
+                // sampling them.
+                graph.resize(device, size.width, size.height);
+                post_bind_groups = create_post_bind_groups(
+                    device,
+                    &graph,
+                    &textures,
+                    &post_layout,
+                    &composite_layout,
+                    &sampler,
+                );
+
+                projection.set_aspect_ratio(size.width as f32 / size.height as f32);
+            }
+            Event::RedrawRequested(_) => {
+                let current_time = std::time::Instant::now();
+                let current_duration = current_time - render_start_time;
+                let animated_duration = ANIMATION_SPEED * current_duration.as_secs_f32();
+
+                camera_controller.update_camera(&mut camera);
+                projection.replace_view_matrix(camera.view_matrix());
+
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let viewport_size = [
+                    surface_configuration.width as f32,
+                    surface_configuration.height as f32,
+                ];
+                renderer.queue().unwrap().write_buffer(
+                    &scene_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_camera(
+                        &camera,
+                        &projection,
+                        viewport_size,
+                        animated_duration,
+                    )),
+                );
+
+                object_arena.clear();
+                for object in &objects {
+                    projection.set_model_matrix(
+                        object.translation,
+                        [0.0, object.spin * animated_duration, 0.0],
+                        [1.0, 1.0, 1.0],
+                    );
+                    object_arena.push(&ObjectUniforms {
+                        model_matrix: (*projection.model_matrix()).into(),
+                        normal_matrix: (*projection.normal_matrix()).into(),
+                        color: object.color,
+                    });
+                }
+                if object_arena.upload(renderer.device().unwrap(), renderer.queue().unwrap()) {
+                    object_arena.create_bind_group(
+                        renderer.device().unwrap(),
+                        &renderer.bind_group_layouts()[1],
+                        0,
+                    );
+                }
+
+                let surface = renderer.surface().unwrap();
+                let device = renderer.device().unwrap();
+                let queue = renderer.queue().unwrap();
+                let render_pipeline = renderer.render_pipeline().unwrap();
+                let surface_texture = surface.get_current_texture().unwrap();
+                let texture_view = surface_texture.texture.create_view(&Default::default());
+                let object_bind_group = object_arena.bind_group().unwrap();
+
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+                graph.execute(
+                    &mut command_encoder,
+                    &[(surface_target, &texture_view)],
+                    |pass, _, render_pass| {
+                        if pass == scene_pass {
+                            render_pass.set_pipeline(render_pipeline);
+                            render_pass.set_bind_group(0, &scene_bind_group, &[]);
+                            for (index, object) in objects.iter().enumerate() {
+                                render_pass.set_bind_group(
+                                    1,
+                                    object_bind_group,
+                                    &[object_arena.offset(index as u32)],
+                                );
+                                object.mesh.draw(render_pass);
+                            }
+                        } else if pass == blur_x_pass {
+                            render_pass.set_pipeline(&blur_x_pipeline);
+                            render_pass.set_bind_group(0, &post_bind_groups.blur_x, &[]);
+                            render_pass.draw(0..3, 0..1);
+                        } else if pass == blur_y_pass {
+                            render_pass.set_pipeline(&blur_y_pipeline);
+                            render_pass.set_bind_group(0, &post_bind_groups.blur_y, &[]);
+                            render_pass.draw(0..3, 0..1);
+                        } else if pass == composite_pass {
+                            render_pass.set_pipeline(&composite_pipeline);
+                            render_pass.set_bind_group(0, &post_bind_groups.composite, &[]);
+                            render_pass.draw(0..3, 0..1);
+                        } else if pass == overlay_pass {
+                            let width = viewport_size[0] * 0.25;
+                            let height = viewport_size[1] * 0.25;
+                            render_pass.set_viewport(
+                                viewport_size[0] - width - 16.0,
+                                16.0,
+                                width,
+                                height,
+                                0.0,
+                                1.0,
+                            );
+                            render_pass.set_pipeline(&overlay_pipeline);
+                            render_pass.set_bind_group(0, &post_bind_groups.overlay, &[]);
+                            render_pass.draw(0..3, 0..1);
+                        }
+                    },
+                );
+
+                queue.submit(Some(command_encoder.finish()));
+                surface_texture.present();
+            }
+            Event::MainEventsCleared => {
+                renderer.window().unwrap().request_redraw();
+            }
+            _ => (),
+        }
+    })
+}
+
+fn create_post_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    layout: &PipelineLayout,
+    fragment_entry_point: &str,
+    format: TextureFormat,
+) -> RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(fragment_entry_point),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_fullscreen",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: fragment_entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: None,
+    })
+}
+
+fn create_post_bind_groups(
+    device: &Device,
+    graph: &RenderGraph,
+    textures: &GraphTextures,
+    post_layout: &BindGroupLayout,
+    composite_layout: &BindGroupLayout,
+    sampler: &Sampler,
+) -> PostBindGroups {
+    let view = |texture: TextureHandle| {
+        wgpu::BindingResource::TextureView(
+            graph
+                .view(texture)
+                .expect("render graph to have allocated the texture"),
+        )
+    };
+    let post_bind_group = |label: &str, texture: TextureHandle| {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout: post_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: view(texture),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    };
+
+    PostBindGroups {
+        blur_x: post_bind_group("Blur X Bind Group", textures.scene_color),
+        blur_y: post_bind_group("Blur Y Bind Group", textures.blur_x),
+        composite: device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Composite Bind Group"),
+            layout: composite_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: view(textures.scene_color),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: view(textures.blur_y),
+                },
+            ],
+        }),
+        overlay: post_bind_group("Overlay Bind Group", textures.blur_y),
+    }
+}
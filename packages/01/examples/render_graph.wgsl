@@ -0,0 +1,89 @@
+#include "common/scene_uniforms.wgsl"
+
+struct ObjectUniforms {
+    modelMatrix: mat4x4<f32>,
+    normalMatrix: mat4x4<f32>,
+    color: vec4<f32>,
+};
+
+@binding(0) @group(0) var<uniform> scene : SceneUniforms;
+@binding(0) @group(1) var<uniform> object : ObjectUniforms;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vNormal: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>, @location(2) uv: vec2<f32>) -> Output {
+    var output: Output;
+    output.Position = scene.viewProjectionMatrix * object.modelMatrix * vec4<f32>(pos, 1.0);
+    output.vNormal = (object.normalMatrix * vec4<f32>(normal, 0.0)).xyz;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vNormal: vec3<f32>) -> @location(0) vec4<f32> {
+    let lightDirection = normalize(vec3<f32>(0.4, 1.0, 0.3));
+    let diffuse = max(dot(normalize(vNormal), lightDirection), 0.0);
+    return vec4<f32>(object.color.rgb * (0.15 + 0.85 * diffuse), object.color.a);
+}
+
+// The post-processing passes run on a fullscreen triangle with their own pipeline
+// layouts, so their bindings do not clash with the scene's.
+@binding(0) @group(0) var sourceTexture : texture_2d<f32>;
+@binding(1) @group(0) var sourceSampler : sampler;
+@binding(2) @group(0) var glowTexture : texture_2d<f32>;
+
+struct FullscreenOutput {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vUv: vec2<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var output: FullscreenOutput;
+    output.Position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.vUv = uv;
+    return output;
+}
+
+fn blur(uv: vec2<f32>, direction: vec2<f32>) -> vec3<f32> {
+    let texel = direction / vec2<f32>(textureDimensions(sourceTexture));
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    var color = textureSample(sourceTexture, sourceSampler, uv).rgb * weights[0];
+    for (var i = 1; i < 5; i = i + 1) {
+        let offset = texel * f32(i) * 2.0;
+        color = color + textureSample(sourceTexture, sourceSampler, uv + offset).rgb * weights[i];
+        color = color + textureSample(sourceTexture, sourceSampler, uv - offset).rgb * weights[i];
+    }
+    return color;
+}
+
+@fragment
+fn fs_blur_x(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    // Only the bright parts of the scene glow.
+    let color = blur(vUv, vec2<f32>(1.0, 0.0));
+    let brightness = max(color.r, max(color.g, color.b));
+    return vec4<f32>(color * smoothstep(0.5, 0.9, brightness), 1.0);
+}
+
+@fragment
+fn fs_blur_y(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    return vec4<f32>(blur(vUv, vec2<f32>(0.0, 1.0)), 1.0);
+}
+
+@fragment
+fn fs_composite(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(sourceTexture, sourceSampler, vUv).rgb;
+    let glow = textureSample(glowTexture, sourceSampler, vUv).rgb;
+    let centered = vUv - vec2<f32>(0.5);
+    let vignette = 1.0 - 0.6 * dot(centered, centered);
+    return vec4<f32>((color + glow) * vignette, 1.0);
+}
+
+@fragment
+fn fs_overlay(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSample(sourceTexture, sourceSampler, vUv).rgb, 1.0);
+}
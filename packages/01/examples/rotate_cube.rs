@@ -2,11 +2,12 @@ use bytemuck::cast_slice;
 use commonlib::{
     projection::Projection,
     renderer::RendererBuilder,
+    uniforms::{ModelUniforms, SceneUniforms},
     vertices::{vertex_data, Vertex4DColored},
 };
 use wgpu::{
-    util::DeviceExt, vertex_attr_array, BindGroupEntry, BindGroupLayoutEntry, BufferAddress,
-    BufferUsages, Extent3d, ShaderStages, TextureDescriptor, TextureUsages, VertexAttribute,
+    util::DeviceExt, BindGroupEntry, BufferUsages, Extent3d, ShaderStages, TextureDescriptor,
+    TextureUsages,
 };
 use winit::{
     event::{Event, WindowEvent},
@@ -36,7 +37,6 @@ fn create_vertices() -> Vec<Vertex4DColored> {
 }
 
 const ANIMATION_SPEED: f32 = 1.0;
-const VERTEX_ATTRIBUTE: [VertexAttribute; 2] = vertex_attr_array![0=>Float32x4,1=>Float32x4];
 
 fn main() {
     env_logger::init();
@@ -45,10 +45,7 @@ fn main() {
 
     let args: Vec<String> = std::env::args().collect();
     if args.len() > 1 {
-        is_perspective = match args[1].as_str() {
-            "ortho" => false,
-            _ => true,
-        }
+        is_perspective = args[1].as_str() != "ortho";
     }
 
     let event_loop = EventLoop::new();
@@ -64,23 +61,20 @@ fn main() {
         .get_device(Some("Device"))
         .create_surface_configuration()
         .create_shader_module(Some("Shader"), include_str!("rotate_cube.wgsl"))
-        .add_vertex_buffer_layout(wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex4DColored>() as BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &VERTEX_ATTRIBUTE,
-        })
+        .add_vertex_buffer_layout(Vertex4DColored::LAYOUT)
+        .add_bind_group_layout(
+            Some("Scene Bind Group Layout"),
+            &[SceneUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX,
+            )],
+        )
         .add_bind_group_layout(
-            Some("Uniform Bind Group Layout"),
-            &[BindGroupLayoutEntry {
-                binding: 0,
-                count: None,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                visibility: ShaderStages::VERTEX,
-            }],
+            Some("Model Bind Group Layout"),
+            &[ModelUniforms::bind_group_layout_entry(
+                0,
+                ShaderStages::VERTEX,
+            )],
         )
         .set_primitive_state(wgpu::PrimitiveTopology::TriangleList, None)
         .set_depth_stencil_state(wgpu::DepthStencilState {
@@ -100,26 +94,56 @@ fn main() {
     );
     projection.set_is_perspective(is_perspective);
 
-    let uniform_buffer =
+    let viewport_size = [
+        renderer.surface_configuration().unwrap().width as f32,
+        renderer.surface_configuration().unwrap().height as f32,
+    ];
+    let scene_buffer =
+        renderer
+            .device()
+            .unwrap()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Scene Uniform Buffer"),
+                contents: bytemuck::bytes_of(&SceneUniforms::from_projection(
+                    &projection,
+                    viewport_size,
+                    0.0,
+                )),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+    let model_buffer =
         renderer
             .device()
             .unwrap()
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Uniform Buffer"),
-                contents: cast_slice(projection.mvp_matrix_slice()),
+                label: Some("Model Uniform Buffer"),
+                contents: bytemuck::bytes_of(&ModelUniforms::from_projection(&projection)),
                 usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
             });
 
-    let uniform_bind_group =
+    let scene_bind_group =
         renderer
             .device()
             .unwrap()
             .create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Uniform Bind Group"),
-                layout: renderer.bind_group_layouts().get(0).unwrap(),
+                label: Some("Scene Bind Group"),
+                layout: &renderer.bind_group_layouts()[0],
                 entries: &[BindGroupEntry {
                     binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
+                    resource: scene_buffer.as_entire_binding(),
+                }],
+            });
+
+    let model_bind_group =
+        renderer
+            .device()
+            .unwrap()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Model Bind Group"),
+                layout: &renderer.bind_group_layouts()[1],
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: model_buffer.as_entire_binding(),
                 }],
             });
 
@@ -162,9 +186,13 @@ fn main() {
                 projection.set_aspect_ratio(size.width as f32 / size.height as f32);
 
                 renderer.queue().unwrap().write_buffer(
-                    &uniform_buffer,
+                    &scene_buffer,
                     0,
-                    bytemuck::cast_slice(projection.mvp_matrix_slice()),
+                    bytemuck::bytes_of(&SceneUniforms::from_projection(
+                        &projection,
+                        [size.width as f32, size.height as f32],
+                        0.0,
+                    )),
                 );
             }
             Event::RedrawRequested(_) => {
@@ -178,10 +206,24 @@ fn main() {
                     0.0,
                 ]);
 
+                let surface_configuration = renderer.surface_configuration().unwrap();
+                let viewport_size = [
+                    surface_configuration.width as f32,
+                    surface_configuration.height as f32,
+                ];
+                renderer.queue().unwrap().write_buffer(
+                    &scene_buffer,
+                    0,
+                    bytemuck::bytes_of(&SceneUniforms::from_projection(
+                        &projection,
+                        viewport_size,
+                        current_duration.as_secs_f32(),
+                    )),
+                );
                 renderer.queue().unwrap().write_buffer(
-                    &uniform_buffer,
+                    &model_buffer,
                     0,
-                    bytemuck::cast_slice(projection.mvp_matrix_slice()),
+                    bytemuck::bytes_of(&ModelUniforms::from_projection(&projection)),
                 );
 
                 let surface = renderer.surface().unwrap();
@@ -189,7 +231,6 @@ fn main() {
                 let queue = renderer.queue().unwrap();
                 let render_pipeline = renderer.render_pipeline().unwrap();
                 let surface_texture = surface.get_current_texture().unwrap();
-                let surface_configuration = renderer.surface_configuration().unwrap();
 
                 let texture_view = surface_texture.texture.create_view(&Default::default());
                 let depth_texture = renderer
@@ -242,9 +283,10 @@ fn main() {
                                 },
                             ),
                         });
-                    render_pass.set_pipeline(&render_pipeline);
+                    render_pass.set_pipeline(render_pipeline);
                     render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                    render_pass.set_bind_group(0, &uniform_bind_group, &[]);
+                    render_pass.set_bind_group(0, &scene_bind_group, &[]);
+                    render_pass.set_bind_group(1, &model_bind_group, &[]);
                     render_pass.draw(0..36, 0..1)
                 }
                 queue.submit(Some(command_encoder.finish()));
@@ -0,0 +1,63 @@
+#include "common/scene_uniforms.wgsl"
+#include "common/lighting.wgsl"
+#include "common/shadow.wgsl"
+
+struct ObjectUniforms {
+    modelMatrix: mat4x4<f32>,
+    normalMatrix: mat4x4<f32>,
+    material: BlinnPhongMaterial,
+};
+
+@binding(0) @group(0) var<uniform> scene : SceneUniforms;
+@binding(1) @group(0) var<uniform> shadow : ShadowUniforms;
+@binding(2) @group(0) var shadowMap : texture_depth_2d_array;
+@binding(3) @group(0) var shadowSampler : sampler_comparison;
+@binding(0) @group(1) var<storage, read> lights : Lights;
+@binding(0) @group(2) var<uniform> object : ObjectUniforms;
+
+// Only used by the depth-only pipeline, whose layout puts the cascade's light
+// matrix in group 0 and the objects in group 1.
+@binding(0) @group(0) var<uniform> lightViewProjection : mat4x4<f32>;
+@binding(0) @group(1) var<uniform> shadowObject : ObjectUniforms;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vPosition: vec3<f32>,
+    @location(1) vNormal: vec3<f32>,
+    @location(2) vViewDepth: f32,
+};
+
+@vertex
+fn vs_shadow(@location(0) pos: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return lightViewProjection * shadowObject.modelMatrix * vec4<f32>(pos, 1.0);
+}
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>, @location(2) uv: vec2<f32>) -> Output {
+    let worldPosition = object.modelMatrix * vec4<f32>(pos, 1.0);
+    var output: Output;
+    output.Position = scene.viewProjectionMatrix * worldPosition;
+    output.vPosition = worldPosition.xyz;
+    output.vNormal = (object.normalMatrix * vec4<f32>(normal, 0.0)).xyz;
+    output.vViewDepth = -(scene.viewMatrix * worldPosition).z;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vPosition: vec3<f32>, @location(1) vNormal: vec3<f32>, @location(2) vViewDepth: f32) -> @location(0) vec4<f32> {
+    let normal = normalize(vNormal);
+    let viewDirection = normalize(scene.cameraPosition.xyz - vPosition);
+    let material = object.material;
+    let baseColor = material.color.rgb;
+
+    var color = blinnPhongAmbient(lights.ambientColor, material, baseColor);
+    for (var i = 0u; i < lights.count; i = i + 1u) {
+        var light = blinnPhongLight(lights.lights[i], material, baseColor, vPosition, normal, viewDirection);
+        // The first light is the one the shadow map was rendered from.
+        if (i == 0u) {
+            light = light * shadowFactor(shadowMap, shadowSampler, shadow, vPosition, normal, vViewDepth);
+        }
+        color = color + light;
+    }
+    return vec4<f32>(color, material.color.a);
+}
@@ -0,0 +1,27 @@
+#include "common/uniforms.wgsl"
+
+@binding(0) @group(2) var environmentTexture: texture_cube<f32>;
+@binding(1) @group(2) var environmentSampler: sampler;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vPosition: vec3<f32>,
+    @location(1) vNormal: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>, @location(2) uv: vec2<f32>) -> Output {
+    var output: Output;
+    output.Position = scene.viewProjectionMatrix * model.modelMatrix * vec4<f32>(pos, 1.0);
+    output.vPosition = (model.modelMatrix * vec4<f32>(pos, 1.0)).xyz;
+    output.vNormal = (model.normalMatrix * vec4<f32>(normal, 0.0)).xyz;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vPosition: vec3<f32>, @location(1) vNormal: vec3<f32>) -> @location(0) vec4<f32> {
+    let viewDirection = normalize(vPosition - scene.cameraPosition.xyz);
+    let reflected = reflect(viewDirection, normalize(vNormal));
+    let color = textureSample(environmentTexture, environmentSampler, vec3<f32>(reflected.x, reflected.y, -reflected.z));
+    return vec4<f32>(color.rgb * 0.9 + vec3<f32>(0.05), 1.0);
+}
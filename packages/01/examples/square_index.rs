@@ -1,8 +1,8 @@
-use std::mem::size_of;
-
-use bytemuck::cast_slice;
-use commonlib::{renderer::RendererBuilder, vertices::Vertex2DColored};
-use wgpu::{util::DeviceExt, vertex_attr_array, BufferUsages, VertexBufferLayout};
+use commonlib::{
+    mesh::{Mesh, MeshData},
+    renderer::RendererBuilder,
+    vertices::Vertex2DColored,
+};
 use winit::{
     event::{Event, WindowEvent},
     event_loop::EventLoop,
@@ -28,23 +28,7 @@ const VERTICES: &[Vertex2DColored] = &[
     },
 ];
 
-const INDICES: &[u16] = &[0, 1, 3, 3, 1, 2];
-
-trait Describable {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 2];
-    fn desc<'a>() -> VertexBufferLayout<'a>;
-}
-
-impl Describable for Vertex2DColored {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = vertex_attr_array![0=>Float32x2, 1=>Float32x3];
-    fn desc<'a>() -> VertexBufferLayout<'a> {
-        wgpu::VertexBufferLayout {
-            array_stride: size_of::<Vertex2DColored>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBUTES,
-        }
-    }
-}
+const INDICES: &[u32] = &[0, 1, 3, 3, 1, 2];
 
 fn main() {
     env_logger::init();
@@ -63,27 +47,19 @@ fn main() {
         .create_surface_configuration()
         .create_pipeline_layout(Some("Pipeline Layout"))
         .create_shader_module(Some("Shader Module"), include_str!("square_index.wgsl"))
-        .add_vertex_buffer_layout(Vertex2DColored::desc())
+        .add_vertex_buffer_layout(Vertex2DColored::LAYOUT)
         .create_render_pipeline(Some("Render Pipeline"))
         .build();
 
-    let vertex_buffer = renderer
-        .device()
-        .expect("renderer to have a device")
-        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Buffer"),
-            contents: cast_slice(VERTICES),
-            usage: BufferUsages::VERTEX,
-        });
-
-    let index_buffer = renderer
-        .device()
-        .expect("renderer to have a device")
-        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Buffer"),
-            contents: cast_slice(INDICES),
-            usage: BufferUsages::INDEX,
-        });
+    let mesh = Mesh::new(
+        renderer.device().expect("renderer to have a device"),
+        Some("Mesh"),
+        &MeshData::indexed(
+            VERTICES.to_vec(),
+            INDICES.to_vec(),
+            wgpu::PrimitiveTopology::TriangleList,
+        ),
+    );
 
     event_loop.run(move |event, _, control_flow| {
         control_flow.set_poll();
@@ -137,10 +113,8 @@ fn main() {
                             })],
                             depth_stencil_attachment: None,
                         });
-                    render_pass.set_pipeline(&render_pipeline);
-                    render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                    render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-                    render_pass.draw_indexed(0..(INDICES.len() as u32), 0, 0..1)
+                    render_pass.set_pipeline(render_pipeline);
+                    mesh.draw(&mut render_pass);
                 }
                 queue.submit(Some(command_encoder.finish()));
                 surface_texture.present();
@@ -0,0 +1,22 @@
+#include "common/uniforms.wgsl"
+
+@binding(0) @group(2) var cubeTexture: texture_2d<f32>;
+@binding(1) @group(2) var cubeSampler: sampler;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vUv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>, @location(1) normal: vec3<f32>, @location(2) uv: vec2<f32>) -> Output {
+    var output: Output;
+    output.Position = scene.viewProjectionMatrix * model.modelMatrix * vec4<f32>(pos, 1.0);
+    output.vUv = uv;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    return textureSample(cubeTexture, cubeSampler, vUv);
+}
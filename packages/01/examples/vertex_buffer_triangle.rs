@@ -1,8 +1,6 @@
-use std::mem::size_of;
-
 use bytemuck::cast_slice;
 use commonlib::{renderer::RendererBuilder, vertices::Vertex2DColored};
-use wgpu::{util::DeviceExt, vertex_attr_array, BufferUsages, VertexBufferLayout};
+use wgpu::{util::DeviceExt, BufferUsages};
 use winit::{
     event::{Event, WindowEvent},
     event_loop::EventLoop,
@@ -24,22 +22,6 @@ const VERTICES: &[Vertex2DColored] = &[
     },
 ];
 
-trait Describable {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 2];
-    fn desc<'a>() -> VertexBufferLayout<'a>;
-}
-
-impl Describable for Vertex2DColored {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = vertex_attr_array![0=>Float32x2, 1=>Float32x3];
-    fn desc<'a>() -> VertexBufferLayout<'a> {
-        wgpu::VertexBufferLayout {
-            array_stride: size_of::<Vertex2DColored>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBUTES,
-        }
-    }
-}
-
 fn main() {
     env_logger::init();
 
@@ -57,7 +39,7 @@ fn main() {
         .create_surface_configuration()
         .create_pipeline_layout(Some("Pipeline Layout"))
         .create_shader_module(Some("Shader"), include_str!("vertex_buffer_triangle.wgsl"))
-        .add_vertex_buffer_layout(Vertex2DColored::desc())
+        .add_vertex_buffer_layout(Vertex2DColored::LAYOUT)
         .create_render_pipeline(Some("Create Render Pipeline"))
         .build();
 
@@ -138,7 +120,7 @@ fn main() {
                             })],
                             depth_stencil_attachment: None,
                         });
-                    render_pass.set_pipeline(&render_pipeline);
+                    render_pass.set_pipeline(render_pipeline);
                     render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
                     render_pass.draw(0..3, 0..1)
                 }
@@ -0,0 +1,270 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Expr, ExprLit,
+    Fields, Ident, Lit, LitInt, Type,
+};
+
+#[proc_macro_derive(Vertex, attributes(vertex, location, format))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_vertex(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct FieldAttribute {
+    ident: Ident,
+    ty: Type,
+    formats: Vec<Ident>,
+    element: Option<Type>,
+    location: u32,
+}
+
+fn expand_vertex(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "`Vertex` cannot be derived for generic types",
+        ));
+    }
+    if !has_repr_c(&input.attrs)? {
+        return Err(Error::new(
+            name.span(),
+            "`Vertex` requires the struct to be `#[repr(C)]`",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    name.span(),
+                    "`Vertex` can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                name.span(),
+                "`Vertex` can only be derived for structs",
+            ))
+        }
+    };
+
+    let step_mode = parse_step_mode(&input.attrs)?;
+
+    let mut next_location = 0;
+    let mut field_attributes = Vec::with_capacity(fields.len());
+    for field in fields {
+        let ident = field.ident.clone().expect("named field to have an ident");
+        let location = parse_location(&field.attrs)?.unwrap_or(next_location);
+        let (formats, element) = match parse_format(&field.attrs)? {
+            Some(format) => (vec![format], None),
+            None => infer_formats(&field.ty)?,
+        };
+        next_location = location + formats.len() as u32;
+        field_attributes.push(FieldAttribute {
+            ident,
+            ty: field.ty.clone(),
+            formats,
+            element,
+            location,
+        });
+    }
+
+    let field_types: Vec<&Type> = field_attributes.iter().map(|field| &field.ty).collect();
+    let attribute_count: usize = field_attributes
+        .iter()
+        .map(|field| field.formats.len())
+        .sum();
+
+    let mut attributes = Vec::with_capacity(attribute_count);
+    for field in &field_attributes {
+        let ident = &field.ident;
+        for (index, format) in field.formats.iter().enumerate() {
+            let location = field.location + index as u32;
+            let element_offset = match &field.element {
+                Some(element) => quote! { #index * ::core::mem::size_of::<#element>() },
+                None => quote! { 0 },
+            };
+            attributes.push(quote! {
+                __derive::wgpu::VertexAttribute {
+                    format: __derive::wgpu::VertexFormat::#format,
+                    offset: (::core::mem::offset_of!(#name, #ident) + #element_offset)
+                        as __derive::wgpu::BufferAddress,
+                    shader_location: #location,
+                }
+            });
+        }
+    }
+
+    let step_mode = format_ident!("{}", step_mode);
+    let position_impl = expand_position(name, &field_attributes);
+
+    Ok(quote! {
+        const _: () = {
+            use ::commonlib::__derive;
+
+            fn assert_pod<T: __derive::bytemuck::Pod>() {}
+            #[allow(dead_code)]
+            fn assert_fields_are_pod() {
+                #(assert_pod::<#field_types>();)*
+            }
+
+            assert!(
+                ::core::mem::size_of::<#name>() == 0 #(+ ::core::mem::size_of::<#field_types>())*,
+                "`Vertex` types must not contain padding",
+            );
+
+            unsafe impl __derive::bytemuck::Zeroable for #name {}
+            unsafe impl __derive::bytemuck::Pod for #name {}
+
+            impl #name {
+                pub const ATTRIBUTES: [__derive::wgpu::VertexAttribute; #attribute_count] =
+                    [#(#attributes),*];
+                pub const LAYOUT: __derive::wgpu::VertexBufferLayout<'static> =
+                    __derive::wgpu::VertexBufferLayout {
+                        array_stride: ::core::mem::size_of::<#name>()
+                            as __derive::wgpu::BufferAddress,
+                        step_mode: __derive::wgpu::VertexStepMode::#step_mode,
+                        attributes: &Self::ATTRIBUTES,
+                    };
+            }
+
+            impl ::commonlib::vertices::Vertex for #name {
+                const LAYOUT: __derive::wgpu::VertexBufferLayout<'static> = #name::LAYOUT;
+            }
+
+            #position_impl
+        };
+    })
+}
+
+fn expand_position(name: &Ident, fields: &[FieldAttribute]) -> TokenStream2 {
+    let Some(field) = fields.iter().find(|field| field.ident == "position") else {
+        return quote! {};
+    };
+    let Type::Array(array) = &field.ty else {
+        return quote! {};
+    };
+    let is_f32 = matches!(array.elem.as_ref(), Type::Path(path) if path.path.is_ident("f32"));
+    let components = match (is_f32, array_len(&array.len)) {
+        (true, Ok(2)) => quote! { [self.position[0], self.position[1], 0.0] },
+        (true, Ok(3 | 4)) => quote! { [self.position[0], self.position[1], self.position[2]] },
+        _ => return quote! {},
+    };
+
+    quote! {
+        impl ::commonlib::vertices::VertexPosition for #name {
+            fn position(&self) -> [f32; 3] {
+                #components
+            }
+        }
+    }
+}
+
+fn has_repr_c(attrs: &[Attribute]) -> Result<bool, Error> {
+    let mut is_repr_c = false;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                is_repr_c = true;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(is_repr_c)
+}
+
+fn parse_step_mode(attrs: &[Attribute]) -> Result<&'static str, Error> {
+    let mut step_mode = "Vertex";
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("vertex")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("instance") {
+                step_mode = "Instance";
+                Ok(())
+            } else {
+                Err(meta.error("expected `instance`"))
+            }
+        })?;
+    }
+    Ok(step_mode)
+}
+
+fn parse_location(attrs: &[Attribute]) -> Result<Option<u32>, Error> {
+    match attrs.iter().find(|attr| attr.path().is_ident("location")) {
+        Some(attr) => Ok(Some(attr.parse_args::<LitInt>()?.base10_parse()?)),
+        None => Ok(None),
+    }
+}
+
+fn parse_format(attrs: &[Attribute]) -> Result<Option<Ident>, Error> {
+    match attrs.iter().find(|attr| attr.path().is_ident("format")) {
+        Some(attr) => Ok(Some(attr.parse_args::<Ident>()?)),
+        None => Ok(None),
+    }
+}
+
+fn infer_formats(ty: &Type) -> Result<(Vec<Ident>, Option<Type>), Error> {
+    if let Some(format) = scalar_format(ty, 1) {
+        return Ok((vec![format], None));
+    }
+
+    if let Type::Array(array) = ty {
+        let len = array_len(&array.len)?;
+        if let Some(format) = scalar_format(&array.elem, len) {
+            return Ok((vec![format], None));
+        }
+        if let Type::Array(column) = array.elem.as_ref() {
+            let column_len = array_len(&column.len)?;
+            if let Some(format) = scalar_format(&column.elem, column_len) {
+                return Ok((vec![format; len], Some(array.elem.as_ref().clone())));
+            }
+        }
+    }
+
+    Err(Error::new(
+        ty.span(),
+        "cannot infer a vertex format for this type, add `#[format(...)]`",
+    ))
+}
+
+fn scalar_format(ty: &Type, len: usize) -> Option<Ident> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let scalar = path.path.get_ident()?.to_string();
+    let format = match (scalar.as_str(), len) {
+        ("f32", 1) => "Float32".to_string(),
+        ("u32", 1) => "Uint32".to_string(),
+        ("i32", 1) => "Sint32".to_string(),
+        ("f64", 1) => "Float64".to_string(),
+        ("f32", 2..=4) => format!("Float32x{}", len),
+        ("u32", 2..=4) => format!("Uint32x{}", len),
+        ("i32", 2..=4) => format!("Sint32x{}", len),
+        ("f64", 2..=4) => format!("Float64x{}", len),
+        ("u16", 2 | 4) => format!("Uint16x{}", len),
+        ("i16", 2 | 4) => format!("Sint16x{}", len),
+        ("u8", 2 | 4) => format!("Uint8x{}", len),
+        ("i8", 2 | 4) => format!("Sint8x{}", len),
+        _ => return None,
+    };
+    Some(Ident::new(&format, Span::call_site()))
+}
+
+fn array_len(len: &Expr) -> Result<usize, Error> {
+    match len {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit), ..
+        }) => lit.base10_parse(),
+        _ => Err(Error::new(
+            len.span(),
+            "vertex array lengths must be integer literals",
+        )),
+    }
+}
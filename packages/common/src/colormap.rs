@@ -0,0 +1,259 @@
+use wgpu::{util::DeviceExt, Device, Queue, Texture, TextureUsages};
+
+#[rustfmt::skip]
+const VIRIDIS: [[u8; 3]; 11] = [
+    [0x44, 0x01, 0x54], [0x48, 0x24, 0x75], [0x41, 0x44, 0x87], [0x35, 0x5f, 0x8d],
+    [0x2a, 0x78, 0x8e], [0x21, 0x91, 0x8c], [0x22, 0xa8, 0x84], [0x44, 0xbf, 0x70],
+    [0x7a, 0xd1, 0x51], [0xbd, 0xdf, 0x26], [0xfd, 0xe7, 0x25],
+];
+
+#[rustfmt::skip]
+const PLASMA: [[u8; 3]; 11] = [
+    [0x0d, 0x08, 0x87], [0x41, 0x04, 0x9d], [0x6a, 0x00, 0xa8], [0x8f, 0x0d, 0xa4],
+    [0xb1, 0x2a, 0x90], [0xcc, 0x47, 0x78], [0xe1, 0x64, 0x62], [0xf2, 0x84, 0x4b],
+    [0xfc, 0xa6, 0x36], [0xfc, 0xce, 0x25], [0xf0, 0xf9, 0x21],
+];
+
+#[rustfmt::skip]
+const RD_BU: [[u8; 3]; 11] = [
+    [0x67, 0x00, 0x1f], [0xb2, 0x18, 0x2b], [0xd6, 0x60, 0x4d], [0xf4, 0xa5, 0x82],
+    [0xfd, 0xdb, 0xc7], [0xf7, 0xf7, 0xf7], [0xd1, 0xe5, 0xf0], [0x92, 0xc5, 0xde],
+    [0x43, 0x93, 0xc3], [0x21, 0x66, 0xac], [0x05, 0x30, 0x61],
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Colormap {
+    Viridis,
+    Plasma,
+    Jet,
+    Hot,
+    Cool,
+    Grayscale,
+    RdBu,
+}
+
+impl Colormap {
+    pub fn sample(&self, t: f32) -> [f32; 4] {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let [r, g, b] = match self {
+            Colormap::Viridis => interpolate(&VIRIDIS, t),
+            Colormap::Plasma => interpolate(&PLASMA, t),
+            Colormap::RdBu => interpolate(&RD_BU, t),
+            Colormap::Jet => [
+                (1.5 - (4.0 * t - 3.0).abs()).clamp(0.0, 1.0),
+                (1.5 - (4.0 * t - 2.0).abs()).clamp(0.0, 1.0),
+                (1.5 - (4.0 * t - 1.0).abs()).clamp(0.0, 1.0),
+            ],
+            Colormap::Hot => [
+                (3.0 * t).clamp(0.0, 1.0),
+                (3.0 * t - 1.0).clamp(0.0, 1.0),
+                (3.0 * t - 2.0).clamp(0.0, 1.0),
+            ],
+            Colormap::Cool => [t, 1.0 - t, 1.0],
+            Colormap::Grayscale => [t, t, t],
+        };
+        [r, g, b, 1.0]
+    }
+
+    pub fn sample_linear(&self, t: f32) -> [f32; 4] {
+        let [r, g, b, a] = self.sample(t);
+        [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+    }
+
+    pub fn lookup_table(&self, resolution: u32) -> Vec<[u8; 4]> {
+        let resolution = resolution.max(2);
+        (0..resolution)
+            .map(|index| {
+                self.sample(index as f32 / (resolution - 1) as f32)
+                    .map(|channel| (channel * 255.0).round() as u8)
+            })
+            .collect()
+    }
+
+    pub fn create_texture(&self, device: &Device, queue: &Queue, resolution: u32) -> Texture {
+        // 1D textures can't be wider than the device allows
+        let resolution = resolution.min(device.limits().max_texture_dimension_1d);
+        let lookup_table = self.lookup_table(resolution);
+        device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Colormap Texture"),
+                size: wgpu::Extent3d {
+                    width: lookup_table.len() as u32,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D1,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            bytemuck::cast_slice(&lookup_table),
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Normalization {
+    Linear { min: f32, max: f32 },
+    Log { min: f32, max: f32 },
+}
+
+impl Normalization {
+    pub fn linear_from_data(values: &[f32]) -> Self {
+        let (min, max) = data_range(values.iter().copied());
+        Normalization::Linear { min, max }
+    }
+
+    pub fn log_from_data(values: &[f32]) -> Self {
+        let (min, max) = data_range(values.iter().copied().filter(|value| *value > 0.0));
+        Normalization::Log { min, max }
+    }
+
+    pub fn normalize(&self, value: f32) -> f32 {
+        let t = match *self {
+            Normalization::Linear { min, max } => (value - min) / (max - min),
+            Normalization::Log { min, max } => {
+                if value <= 0.0 || min <= 0.0 {
+                    0.0
+                } else {
+                    (value.ln() - min.ln()) / (max.ln() - min.ln())
+                }
+            }
+        };
+        if t.is_finite() {
+            t.clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScalarColorMapper {
+    pub colormap: Colormap,
+    pub normalization: Normalization,
+}
+
+impl ScalarColorMapper {
+    pub fn new(colormap: Colormap, normalization: Normalization) -> Self {
+        Self {
+            colormap,
+            normalization,
+        }
+    }
+
+    pub fn map(&self, value: f32) -> [f32; 4] {
+        self.colormap.sample(self.normalization.normalize(value))
+    }
+
+    pub fn map_all(&self, values: &[f32]) -> Vec<[f32; 4]> {
+        values.iter().map(|value| self.map(*value)).collect()
+    }
+}
+
+fn interpolate(stops: &[[u8; 3]], t: f32) -> [f32; 3] {
+    let position = t * (stops.len() - 1) as f32;
+    let index = (position.floor() as usize).min(stops.len() - 2);
+    let fraction = position - index as f32;
+    let (from, to) = (stops[index], stops[index + 1]);
+    [0, 1, 2].map(|channel| {
+        let from = from[channel] as f32 / 255.0;
+        let to = to[channel] as f32 / 255.0;
+        from + (to - from) * fraction
+    })
+}
+
+fn data_range<I: Iterator<Item = f32>>(values: I) -> (f32, f32) {
+    let (min, max) = values
+        .filter(|value| value.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+    if min > max {
+        (0.0, 1.0)
+    } else {
+        (min, max)
+    }
+}
+
+pub fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_normalization_maps_the_range_to_unit_interval() {
+        let normalization = Normalization::linear_from_data(&[2.0, 4.0, 6.0]);
+        assert_eq!(normalization, Normalization::Linear { min: 2.0, max: 6.0 });
+        assert_eq!(normalization.normalize(2.0), 0.0);
+        assert_eq!(normalization.normalize(4.0), 0.5);
+        assert_eq!(normalization.normalize(6.0), 1.0);
+        assert_eq!(normalization.normalize(-10.0), 0.0);
+        assert_eq!(normalization.normalize(10.0), 1.0);
+    }
+
+    #[test]
+    fn nan_values_are_ignored_and_map_to_zero() {
+        let normalization = Normalization::linear_from_data(&[f32::NAN, 1.0, f32::INFINITY, 3.0]);
+        assert_eq!(normalization, Normalization::Linear { min: 1.0, max: 3.0 });
+        assert_eq!(normalization.normalize(f32::NAN), 0.0);
+        assert_eq!(Colormap::Grayscale.sample(f32::NAN), [0.0, 0.0, 0.0, 1.0]);
+
+        let empty = Normalization::linear_from_data(&[f32::NAN]);
+        assert_eq!(empty, Normalization::Linear { min: 0.0, max: 1.0 });
+    }
+
+    #[test]
+    fn log_normalization_skips_non_positive_values() {
+        let normalization = Normalization::log_from_data(&[-5.0, 0.0, 1.0, 10.0, 100.0]);
+        assert_eq!(
+            normalization,
+            Normalization::Log {
+                min: 1.0,
+                max: 100.0
+            }
+        );
+        assert_eq!(normalization.normalize(0.0), 0.0);
+        assert_eq!(normalization.normalize(-5.0), 0.0);
+        assert!((normalization.normalize(10.0) - 0.5).abs() < 1e-6);
+        assert_eq!(normalization.normalize(100.0), 1.0);
+
+        let invalid = Normalization::Log {
+            min: -1.0,
+            max: 10.0,
+        };
+        assert_eq!(invalid.normalize(5.0), 0.0);
+    }
+
+    #[test]
+    fn equal_min_and_max_map_to_zero() {
+        let normalization = Normalization::linear_from_data(&[3.0, 3.0]);
+        assert_eq!(normalization, Normalization::Linear { min: 3.0, max: 3.0 });
+        assert_eq!(normalization.normalize(3.0), 0.0);
+        assert_eq!(normalization.normalize(4.0), 0.0);
+
+        let normalization = Normalization::log_from_data(&[5.0]);
+        assert_eq!(normalization.normalize(5.0), 0.0);
+    }
+
+    #[test]
+    fn lookup_table_hits_both_endpoints() {
+        let table = Colormap::Viridis.lookup_table(256);
+        assert_eq!(table.len(), 256);
+        assert_eq!(table[0], [0x44, 0x01, 0x54, 0xff]);
+        assert_eq!(table[255], [0xfd, 0xe7, 0x25, 0xff]);
+
+        let table = Colormap::Grayscale.lookup_table(0);
+        assert_eq!(table, vec![[0, 0, 0, 0xff], [0xff, 0xff, 0xff, 0xff]]);
+    }
+}
@@ -0,0 +1,305 @@
+use std::marker::PhantomData;
+
+use bytemuck::Pod;
+use wgpu::{
+    BindGroupEntry, BindGroupLayoutEntry, Buffer, BufferAddress, BufferSize, BufferUsages, Device,
+    Queue, ShaderStages, StorageTextureAccess, Texture, TextureFormat, TextureView,
+};
+
+// Workgroups needed to cover `size` invocations.
+pub fn workgroup_count(size: u32, workgroup_size: u32) -> u32 {
+    size.div_ceil(workgroup_size.max(1))
+}
+
+pub fn storage_buffer_layout_entry(
+    binding: u32,
+    visibility: ShaderStages,
+    read_only: bool,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub fn storage_texture_layout_entry(
+    binding: u32,
+    visibility: ShaderStages,
+    format: TextureFormat,
+    access: StorageTextureAccess,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::StorageTexture {
+            access,
+            format,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+// Copies the first `len` elements of `buffer` into a mappable one and blocks
+// until they can be read. The buffer needs `COPY_SRC` usage.
+pub fn read_buffer<T: Pod>(device: &Device, queue: &Queue, buffer: &Buffer, len: u32) -> Vec<T> {
+    // Copies have to be aligned, the padding is dropped after reading
+    let size = (len as BufferAddress * std::mem::size_of::<T>() as BufferAddress)
+        .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+        .min(buffer.size());
+    let bytes = read_bytes(device, queue, size, |command_encoder, staging| {
+        command_encoder.copy_buffer_to_buffer(buffer, 0, staging, 0, size);
+    });
+    let mut values: Vec<T> = bytemuck::pod_collect_to_vec(&bytes);
+    values.truncate(len as usize);
+    values
+}
+
+fn read_bytes(
+    device: &Device,
+    queue: &Queue,
+    size: BufferAddress,
+    copy: impl FnOnce(&mut wgpu::CommandEncoder, &Buffer),
+) -> Vec<u8> {
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut command_encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    copy(&mut command_encoder, &staging);
+    queue.submit(Some(command_encoder.finish()));
+
+    let slice = staging.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .expect("readback to finish")
+        .expect("readback buffer to map");
+
+    let bytes = slice.get_mapped_range().to_vec();
+    staging.unmap();
+    bytes
+}
+
+// A typed storage buffer. It can always be written from the CPU and read back,
+// extra usages such as `VERTEX` are added on top.
+pub struct StorageBuffer<T: Pod> {
+    buffer: Buffer,
+    len: u32,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> StorageBuffer<T> {
+    pub fn new(device: &Device, label: Option<&str>, contents: &[T], usage: BufferUsages) -> Self {
+        let buffer = Self::create_buffer(device, label, contents.len() as u32, usage, true);
+        let bytes: &[u8] = bytemuck::cast_slice(contents);
+        buffer.slice(..).get_mapped_range_mut()[..bytes.len()].copy_from_slice(bytes);
+        buffer.unmap();
+
+        Self {
+            buffer,
+            len: contents.len() as u32,
+            _marker: PhantomData,
+        }
+    }
+
+    // Zero initialized.
+    pub fn with_len(device: &Device, label: Option<&str>, len: u32, usage: BufferUsages) -> Self {
+        Self {
+            buffer: Self::create_buffer(device, label, len, usage, false),
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bind_group_layout_entry(
+        binding: u32,
+        visibility: ShaderStages,
+        read_only: bool,
+    ) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(std::mem::size_of::<T>() as BufferAddress),
+            },
+            count: None,
+        }
+    }
+
+    pub fn bind_group_entry(&self, binding: u32) -> BindGroupEntry<'_> {
+        BindGroupEntry {
+            binding,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+
+    // Writes `values` starting at element `index`.
+    pub fn write(&self, queue: &Queue, index: u32, values: &[T]) {
+        queue.write_buffer(
+            &self.buffer,
+            index as BufferAddress * std::mem::size_of::<T>() as BufferAddress,
+            bytemuck::cast_slice(values),
+        );
+    }
+
+    pub fn read(&self, device: &Device, queue: &Queue) -> Vec<T> {
+        read_buffer(device, queue, &self.buffer, self.len)
+    }
+
+    fn create_buffer(
+        device: &Device,
+        label: Option<&str>,
+        len: u32,
+        usage: BufferUsages,
+        mapped_at_creation: bool,
+    ) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label,
+            size: ((len.max(1) as usize * std::mem::size_of::<T>()) as BufferAddress)
+                .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage: usage | BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            mapped_at_creation,
+        })
+    }
+}
+
+// A 2D texture compute shaders write to, which can also be sampled and read
+// back.
+pub struct StorageTexture {
+    texture: Texture,
+    view: TextureView,
+    format: TextureFormat,
+    size: (u32, u32),
+}
+
+impl StorageTexture {
+    pub fn new(
+        device: &Device,
+        label: Option<&str>,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+
+        Self {
+            texture,
+            view,
+            format,
+            size: (width, height),
+        }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn bind_group_layout_entry(
+        &self,
+        binding: u32,
+        visibility: ShaderStages,
+        access: StorageTextureAccess,
+    ) -> BindGroupLayoutEntry {
+        storage_texture_layout_entry(binding, visibility, self.format, access)
+    }
+
+    pub fn bind_group_entry(&self, binding: u32) -> BindGroupEntry<'_> {
+        BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureView(&self.view),
+        }
+    }
+
+    // Tightly packed rows, top to bottom.
+    pub fn read(&self, device: &Device, queue: &Queue) -> Vec<u8> {
+        let bytes_per_pixel = self
+            .format
+            .block_size(None)
+            .expect("storage texture to have a color format");
+        let (width, height) = self.size;
+        let unpadded_row = width * bytes_per_pixel;
+        let padded_row = unpadded_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let bytes = read_bytes(
+            device,
+            queue,
+            (padded_row * height) as BufferAddress,
+            |command_encoder, staging| {
+                command_encoder.copy_texture_to_buffer(
+                    self.texture.as_image_copy(),
+                    wgpu::ImageCopyBuffer {
+                        buffer: staging,
+                        layout: wgpu::ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: Some(padded_row),
+                            rows_per_image: Some(height),
+                        },
+                    },
+                    wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            },
+        );
+        bytes
+            .chunks(padded_row as usize)
+            .flat_map(|row| &row[..unpadded_row as usize])
+            .copied()
+            .collect()
+    }
+}
@@ -0,0 +1,444 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, ComputePipeline, Device, Queue,
+    RenderPipeline, Sampler, ShaderStages, Texture, TextureFormat, TextureView,
+};
+
+use crate::{
+    compute::{storage_buffer_layout_entry, workgroup_count},
+    shader_preprocessor::create_library_shader_module,
+};
+
+const HDR_WGSL: &str = include_str!("shaders/hdr.wgsl");
+const HISTOGRAM_BINS: u64 = 256;
+const HISTOGRAM_WORKGROUP_SIZE: u32 = 16;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct HdrParams {
+    pub min_log_luminance: f32,
+    pub log_luminance_range: f32,
+    pub adaptation: f32,
+    pub compensation: f32,
+    pub pixel_count: u32,
+    pub manual_exposure: f32,
+    pub mode: u32,
+    pub _padding: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Exposure {
+    // Adapts to the average scene luminance measured by the histogram.
+    Auto,
+    // A fixed multiplier applied before tonemapping.
+    Manual(f32),
+}
+
+// Render the scene into `view`, then `render` measures its luminance with a
+// compute histogram, adapts the exposure over time and tonemaps to the output.
+pub struct HdrPipeline {
+    output_format: TextureFormat,
+    size: (u32, u32),
+    exposure: Exposure,
+    compensation: f32,
+    adaptation_speed: f32,
+    luminance_range: (f32, f32),
+    texture: Texture,
+    view: TextureView,
+    sampler: Sampler,
+    params_buffer: Buffer,
+    histogram_buffer: Buffer,
+    exposure_buffer: Buffer,
+    compute_bind_group_layout: BindGroupLayout,
+    compute_bind_group: BindGroup,
+    histogram_pipeline: ComputePipeline,
+    average_pipeline: ComputePipeline,
+    tonemap_bind_group_layout: BindGroupLayout,
+    tonemap_bind_group: BindGroup,
+    tonemap_pipeline: RenderPipeline,
+}
+
+impl HdrPipeline {
+    pub const FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+    pub fn new(device: &Device, width: u32, height: u32, output_format: TextureFormat) -> Self {
+        let module =
+            create_library_shader_module(device, "HDR Shader", &[("shaders/hdr.wgsl", HDR_WGSL)]);
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HDR Params Buffer"),
+            size: std::mem::size_of::<HdrParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HDR Histogram Buffer"),
+            size: HISTOGRAM_BINS * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        // The adapted average luminance and the exposure derived from it.
+        let exposure_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HDR Exposure Buffer"),
+            size: 8,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let compute_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("HDR Compute Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    storage_buffer_layout_entry(1, ShaderStages::COMPUTE, false),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    storage_buffer_layout_entry(3, ShaderStages::COMPUTE, false),
+                ],
+            });
+        let tonemap_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("HDR Tonemap Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    storage_buffer_layout_entry(2, ShaderStages::FRAGMENT, true),
+                ],
+            });
+
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("HDR Compute Pipeline Layout"),
+                bind_group_layouts: &[&compute_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let histogram_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("HDR Histogram Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &module,
+            entry_point: "cs_histogram",
+        });
+        let average_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("HDR Average Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &module,
+            entry_point: "cs_average",
+        });
+
+        let tonemap_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("HDR Tonemap Pipeline Layout"),
+                bind_group_layouts: &[&tonemap_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let tonemap_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("HDR Tonemap Pipeline"),
+            layout: Some(&tonemap_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_tonemap",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: output_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("HDR Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let (width, height) = (width.max(1), height.max(1));
+        let (texture, view) = create_target(device, width, height);
+        let compute_bind_group = create_compute_bind_group(
+            device,
+            &compute_bind_group_layout,
+            &view,
+            &histogram_buffer,
+            &params_buffer,
+            &exposure_buffer,
+        );
+        let tonemap_bind_group = create_tonemap_bind_group(
+            device,
+            &tonemap_bind_group_layout,
+            &view,
+            &sampler,
+            &exposure_buffer,
+        );
+
+        Self {
+            output_format,
+            size: (width, height),
+            exposure: Exposure::Auto,
+            compensation: 0.0,
+            adaptation_speed: 1.5,
+            luminance_range: (-10.0, 6.0),
+            texture,
+            view,
+            sampler,
+            params_buffer,
+            histogram_buffer,
+            exposure_buffer,
+            compute_bind_group_layout,
+            compute_bind_group,
+            histogram_pipeline,
+            average_pipeline,
+            tonemap_bind_group_layout,
+            tonemap_bind_group,
+            tonemap_pipeline,
+        }
+    }
+
+    // The scene's color attachment, in `FORMAT`.
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+    pub fn output_format(&self) -> TextureFormat {
+        self.output_format
+    }
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+    pub fn exposure(&self) -> Exposure {
+        self.exposure
+    }
+    pub fn exposure_compensation(&self) -> f32 {
+        self.compensation
+    }
+    // Two f32s, the adapted average luminance and the exposure of the last
+    // frame.
+    pub fn exposure_buffer(&self) -> &Buffer {
+        &self.exposure_buffer
+    }
+
+    pub fn set_exposure(&mut self, exposure: Exposure) {
+        self.exposure = exposure;
+    }
+
+    // In stops, added to the auto exposure.
+    pub fn set_exposure_compensation(&mut self, compensation: f32) {
+        self.compensation = compensation;
+    }
+
+    // How quickly the auto exposure follows the scene, per second.
+    pub fn set_adaptation_speed(&mut self, speed: f32) {
+        self.adaptation_speed = speed.max(0.0);
+    }
+
+    // The log2 luminance range the histogram covers, brighter and darker pixels
+    // land in the outermost bins.
+    pub fn set_luminance_range(&mut self, min_log_luminance: f32, max_log_luminance: f32) {
+        self.luminance_range = (
+            min_log_luminance,
+            max_log_luminance.max(min_log_luminance + 0.001),
+        );
+    }
+
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        if self.size == (width, height) {
+            return;
+        }
+        self.size = (width, height);
+        (self.texture, self.view) = create_target(device, width, height);
+        self.compute_bind_group = create_compute_bind_group(
+            device,
+            &self.compute_bind_group_layout,
+            &self.view,
+            &self.histogram_buffer,
+            &self.params_buffer,
+            &self.exposure_buffer,
+        );
+        self.tonemap_bind_group = create_tonemap_bind_group(
+            device,
+            &self.tonemap_bind_group_layout,
+            &self.view,
+            &self.sampler,
+            &self.exposure_buffer,
+        );
+    }
+
+    // `delta_time` is in seconds and drives the adaptation.
+    pub fn render(
+        &self,
+        queue: &Queue,
+        command_encoder: &mut CommandEncoder,
+        output_view: &TextureView,
+        delta_time: f32,
+    ) {
+        let (mode, manual_exposure) = match self.exposure {
+            Exposure::Auto => (0, 1.0),
+            Exposure::Manual(exposure) => (1, exposure),
+        };
+        let params = HdrParams {
+            min_log_luminance: self.luminance_range.0,
+            log_luminance_range: self.luminance_range.1 - self.luminance_range.0,
+            adaptation: 1.0 - (-delta_time.max(0.0) * self.adaptation_speed).exp(),
+            compensation: self.compensation,
+            pixel_count: self.size.0 * self.size.1,
+            manual_exposure,
+            mode,
+            _padding: 0,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("HDR Exposure Pass"),
+                });
+            compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+            compute_pass.set_pipeline(&self.histogram_pipeline);
+            compute_pass.dispatch_workgroups(
+                workgroup_count(self.size.0, HISTOGRAM_WORKGROUP_SIZE),
+                workgroup_count(self.size.1, HISTOGRAM_WORKGROUP_SIZE),
+                1,
+            );
+            compute_pass.set_pipeline(&self.average_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("HDR Tonemap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.tonemap_pipeline);
+        render_pass.set_bind_group(0, &self.tonemap_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_target(device: &Device, width: u32, height: u32) -> (Texture, TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("HDR Target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HdrPipeline::FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&Default::default());
+    (texture, view)
+}
+
+fn create_compute_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view: &TextureView,
+    histogram_buffer: &Buffer,
+    params_buffer: &Buffer,
+    exposure_buffer: &Buffer,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("HDR Compute Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: histogram_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: params_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: exposure_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+fn create_tonemap_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view: &TextureView,
+    sampler: &Sampler,
+    exposure_buffer: &Buffer,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("HDR Tonemap Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: exposure_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
@@ -0,0 +1,202 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use wgpu::{Device, ShaderModule};
+
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            modified: modified_time(&path),
+            path,
+        }
+    }
+}
+
+struct WatchedShader {
+    path: PathBuf,
+    // The file itself followed by the files it includes.
+    files: Vec<WatchedFile>,
+}
+
+// Polls the modification time of shader files and of the files they include.
+pub struct ShaderWatcher {
+    shaders: Vec<WatchedShader>,
+    poll_interval: Duration,
+    last_poll: Instant,
+}
+
+impl Default for ShaderWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderWatcher {
+    pub fn new() -> Self {
+        Self {
+            shaders: Vec::new(),
+            poll_interval: Duration::from_millis(250),
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.shaders.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.shaders.is_empty()
+    }
+    pub fn path(&self, index: usize) -> &Path {
+        &self.shaders[index].path
+    }
+    pub fn files(&self, index: usize) -> impl Iterator<Item = &Path> {
+        self.shaders[index]
+            .files
+            .iter()
+            .map(|file| file.path.as_path())
+    }
+
+    // `poll` does nothing until this much time has passed since the last check.
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) -> usize {
+        let path = path.as_ref().to_path_buf();
+        self.shaders.push(WatchedShader {
+            files: vec![WatchedFile::new(path.clone())],
+            path,
+        });
+        self.shaders.len() - 1
+    }
+
+    // Replaces the files the shader includes, such as the `files()` of the
+    // `SourceMap` it was last preprocessed into. Built-in sources have no file
+    // and are left out.
+    pub fn set_includes(&mut self, index: usize, files: &[String]) {
+        let shader = &mut self.shaders[index];
+        let includes = files
+            .iter()
+            .map(PathBuf::from)
+            .filter(|file| *file != shader.path && file.is_file())
+            .map(WatchedFile::new);
+        shader.files = std::iter::once(WatchedFile::new(shader.path.clone()))
+            .chain(includes)
+            .collect();
+    }
+
+    // The indices of the shaders whose files changed since the last poll.
+    pub fn poll(&mut self) -> Vec<usize> {
+        if self.last_poll.elapsed() < self.poll_interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+        for (index, shader) in self.shaders.iter_mut().enumerate() {
+            let mut shader_changed = false;
+            for file in shader.files.iter_mut() {
+                let modified = modified_time(&file.path);
+                if modified.is_some() && modified != file.modified {
+                    file.modified = modified;
+                    shader_changed = true;
+                }
+            }
+            if shader_changed {
+                changed.push(index);
+            }
+        }
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Runs `create` and returns the validation error it raised instead of letting
+// the device's error handler panic.
+pub fn capture_validation_error<T>(
+    device: &Device,
+    create: impl FnOnce() -> T,
+) -> Result<T, String> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => Err(error.to_string()),
+        None => Ok(value),
+    }
+}
+
+// The error carries the parser's message with the line and column of the
+// problem.
+pub fn compile_shader(
+    device: &Device,
+    label: Option<&str>,
+    source: &str,
+) -> Result<ShaderModule, String> {
+    capture_validation_error(device, || {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader_preprocessor::ShaderPreprocessor;
+
+    fn touch(path: &Path, seconds: u64) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn editing_an_include_changes_the_shader() {
+        let directory = std::env::temp_dir().join(format!("shader-watcher-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("shader.wgsl");
+        let include = directory.join("color.wgsl");
+        std::fs::write(
+            &path,
+            "#include \"common/scene_uniforms.wgsl\"\n#include \"color.wgsl\"\n",
+        )
+        .unwrap();
+        std::fs::write(&include, "const color = vec4<f32>(1.0);\n").unwrap();
+
+        let mut shader_watcher = ShaderWatcher::new();
+        shader_watcher.set_poll_interval(Duration::ZERO);
+        let index = shader_watcher.watch(&path);
+        let shader = ShaderPreprocessor::new().process_file(&path).unwrap();
+        shader_watcher.set_includes(index, shader.source_map().files());
+        let files: Vec<_> = shader_watcher.files(index).map(Path::to_path_buf).collect();
+        let unchanged = shader_watcher.poll();
+        touch(&include, 10);
+        let include_changed = shader_watcher.poll();
+        let polled_again = shader_watcher.poll();
+        touch(&path, 20);
+        let file_changed = shader_watcher.poll();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(files, vec![path, include]);
+        assert!(unchanged.is_empty());
+        assert_eq!(include_changed, vec![index]);
+        assert!(polled_again.is_empty());
+        assert_eq!(file_changed, vec![index]);
+    }
+}
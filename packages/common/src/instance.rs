@@ -0,0 +1,118 @@
+use cgmath::Matrix4;
+use wgpu::{util::DeviceExt, Buffer, BufferSlice, BufferUsages, Device, Queue};
+
+use crate::{transform::create_transforms, vertices::Vertex};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Vertex)]
+#[vertex(instance)]
+pub struct InstanceData {
+    #[location(5)]
+    pub model_matrix: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+impl InstanceData {
+    pub fn new(
+        translation: [f32; 3],
+        rotation: [f32; 3],
+        scaling: [f32; 3],
+        color: [f32; 4],
+    ) -> Self {
+        Self::from_matrix(create_transforms(translation, rotation, scaling), color)
+    }
+
+    pub fn from_matrix(model_matrix: Matrix4<f32>, color: [f32; 4]) -> Self {
+        Self {
+            model_matrix: model_matrix.into(),
+            color,
+        }
+    }
+}
+
+pub struct InstanceBuffer {
+    label: Option<String>,
+    buffer: Buffer,
+    capacity: usize,
+    len: usize,
+}
+
+impl InstanceBuffer {
+    pub fn new(device: &Device, label: Option<&str>, instances: &[InstanceData]) -> Self {
+        Self {
+            label: label.map(String::from),
+            buffer: Self::create_buffer(device, label, instances),
+            capacity: instances.len(),
+            len: instances.len(),
+        }
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn slice(&self) -> BufferSlice<'_> {
+        self.buffer.slice(..)
+    }
+    pub fn instances(&self) -> std::ops::Range<u32> {
+        0..self.len as u32
+    }
+
+    pub fn update(&mut self, device: &Device, queue: &Queue, instances: &[InstanceData]) {
+        if instances.len() > self.capacity {
+            self.buffer = Self::create_buffer(device, self.label.as_deref(), instances);
+            self.capacity = instances.len();
+        } else {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        }
+        self.len = instances.len();
+    }
+
+    fn create_buffer(device: &Device, label: Option<&str>, instances: &[InstanceData]) -> Buffer {
+        let contents: &[u8] = if instances.is_empty() {
+            &[0; std::mem::size_of::<InstanceData>()]
+        } else {
+            bytemuck::cast_slice(instances)
+        };
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::{VertexFormat, VertexStepMode};
+
+    #[test]
+    fn instance_data_steps_per_instance() {
+        assert_eq!(InstanceData::LAYOUT.step_mode, VertexStepMode::Instance);
+        assert_eq!(InstanceData::LAYOUT.array_stride, 80);
+    }
+
+    #[test]
+    fn model_matrix_is_split_into_columns_from_its_location() {
+        let attributes = InstanceData::ATTRIBUTES;
+        assert_eq!(attributes.len(), 5);
+        for (column, attribute) in attributes[..4].iter().enumerate() {
+            assert_eq!(attribute.format, VertexFormat::Float32x4);
+            assert_eq!(attribute.offset, column as u64 * 16);
+            assert_eq!(attribute.shader_location, 5 + column as u32);
+        }
+        // Fields after an explicit location keep counting from it
+        assert_eq!(attributes[4].format, VertexFormat::Float32x4);
+        assert_eq!(attributes[4].offset, 64);
+        assert_eq!(attributes[4].shader_location, 9);
+    }
+}
@@ -1,5 +1,34 @@
+extern crate self as commonlib;
+
 pub mod renderer;
 pub mod vertices;
 pub mod transform;
 pub mod projection;
-pub mod camera;
\ No newline at end of file
+pub mod camera;
+pub mod uniforms;
+pub mod uniform_arena;
+pub mod instance;
+
+#[doc(hidden)]
+pub mod __derive {
+    pub use bytemuck;
+    pub use wgpu;
+}
+
+pub mod mesh;
+pub mod surface;
+pub mod colormap;
+pub mod loaders;
+pub mod texture;
+pub mod skybox;
+pub mod lighting;
+pub mod pbr;
+pub mod shadow;
+pub mod render_graph;
+pub mod post_process;
+pub mod hdr;
+pub mod compute;
+pub mod particles;
+pub mod hot_reload;
+pub mod shader_reflection;
+pub mod shader_preprocessor;
\ No newline at end of file
@@ -0,0 +1,292 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Deg, InnerSpace, Rad, Vector3};
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutEntry, Buffer, BufferAddress, BufferSize,
+    BufferUsages, Device, Queue, ShaderStages,
+};
+
+pub const LIGHTING_WGSL: &str = include_str!("shaders/lighting.wgsl");
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LightKind {
+    Directional = 0,
+    Point = 1,
+    Spot = 2,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct Light {
+    pub position: [f32; 3],
+    pub kind: u32,
+    pub direction: [f32; 3],
+    pub range: f32,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub attenuation: [f32; 3],
+    pub inner_cone_cos: f32,
+    pub outer_cone_cos: f32,
+    pub _padding: [f32; 3],
+}
+
+impl Light {
+    pub const SIZE: BufferAddress = std::mem::size_of::<Self>() as BufferAddress;
+
+    pub fn directional(direction: [f32; 3], color: [f32; 3], intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional as u32,
+            direction: normalize(direction),
+            color,
+            intensity,
+            ..Self::zeroed()
+        }
+    }
+
+    // A `range` of zero leaves only the attenuation factors to fade the light out.
+    pub fn point(position: [f32; 3], color: [f32; 3], intensity: f32, range: f32) -> Self {
+        Self {
+            position,
+            kind: LightKind::Point as u32,
+            range,
+            color,
+            intensity,
+            attenuation: [1.0, 0.0, 1.0],
+            ..Self::zeroed()
+        }
+    }
+
+    // The cone is fully lit inside `inner_angle` and fades out towards `outer_angle`,
+    // both measured from the axis.
+    pub fn spot(
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+        inner_angle: Deg<f32>,
+        outer_angle: Deg<f32>,
+    ) -> Self {
+        let outer_angle = Rad::from(outer_angle);
+        let inner_angle = Rad::from(inner_angle).0.min(outer_angle.0);
+        Self {
+            position,
+            kind: LightKind::Spot as u32,
+            direction: normalize(direction),
+            range,
+            color,
+            intensity,
+            attenuation: [1.0, 0.0, 1.0],
+            inner_cone_cos: inner_angle.cos(),
+            outer_cone_cos: outer_angle.0.cos(),
+            _padding: [0.0; 3],
+        }
+    }
+
+    pub fn kind(&self) -> LightKind {
+        match self.kind {
+            0 => LightKind::Directional,
+            1 => LightKind::Point,
+            _ => LightKind::Spot,
+        }
+    }
+
+    pub fn set_position(mut self, position: [f32; 3]) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn set_direction(mut self, direction: [f32; 3]) -> Self {
+        self.direction = normalize(direction);
+        self
+    }
+
+    // Intensity is divided by `constant + linear * d + quadratic * d^2`.
+    pub fn set_attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> Self {
+        self.attenuation = [constant, linear, quadratic];
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct BlinnPhongMaterial {
+    pub color: [f32; 4],
+    pub ambient: f32,
+    pub diffuse: f32,
+    pub specular: f32,
+    pub shininess: f32,
+}
+
+impl BlinnPhongMaterial {
+    pub const SIZE: BufferAddress = std::mem::size_of::<Self>() as BufferAddress;
+
+    pub fn new(color: [f32; 4], specular: f32, shininess: f32) -> Self {
+        Self {
+            color,
+            specular,
+            shininess,
+            ..Self::default()
+        }
+    }
+
+    pub fn bind_group_layout_entry(binding: u32, visibility: ShaderStages) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(Self::SIZE),
+            },
+            count: None,
+        }
+    }
+}
+
+impl Default for BlinnPhongMaterial {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0, 1.0],
+            ambient: 1.0,
+            diffuse: 1.0,
+            specular: 0.5,
+            shininess: 32.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct LightsHeader {
+    ambient_color: [f32; 3],
+    count: u32,
+}
+
+const HEADER_SIZE: BufferAddress = std::mem::size_of::<LightsHeader>() as BufferAddress;
+
+// Mirrors the `Lights` struct of `LIGHTING_WGSL`: the ambient color and light
+// count followed by a runtime sized array, kept in a read-only storage buffer.
+pub struct LightBuffer {
+    label: Option<String>,
+    lights: Vec<Light>,
+    ambient_color: [f32; 3],
+    buffer: Buffer,
+    bind_group: Option<BindGroup>,
+    capacity: u32,
+}
+
+impl LightBuffer {
+    pub fn new(device: &Device, label: Option<&str>, capacity: u32) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            label: label.map(String::from),
+            lights: Vec::new(),
+            ambient_color: [0.1, 0.1, 0.1],
+            buffer: Self::create_buffer(device, label, capacity),
+            bind_group: None,
+            capacity,
+        }
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+    pub fn mut_lights(&mut self) -> &mut Vec<Light> {
+        &mut self.lights
+    }
+    pub fn ambient_color(&self) -> [f32; 3] {
+        self.ambient_color
+    }
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+    pub fn bind_group(&self) -> Option<&BindGroup> {
+        self.bind_group.as_ref()
+    }
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn set_ambient_color(&mut self, ambient_color: [f32; 3]) {
+        self.ambient_color = ambient_color;
+    }
+
+    pub fn add_light(&mut self, light: Light) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear();
+    }
+
+    pub fn bind_group_layout_entry(binding: u32, visibility: ShaderStages) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(HEADER_SIZE + Light::SIZE),
+            },
+            count: None,
+        }
+    }
+
+    pub fn create_bind_group(&mut self, device: &Device, layout: &BindGroupLayout, binding: u32) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: self.label.as_deref(),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding,
+                resource: self.buffer.as_entire_binding(),
+            }],
+        }));
+    }
+
+    // Returns true when the buffer had to grow, in which case the bind group is
+    // dropped and has to be created again.
+    #[must_use]
+    pub fn upload(&mut self, device: &Device, queue: &Queue) -> bool {
+        let count = self.lights.len() as u32;
+        let reallocated = count > self.capacity;
+        if reallocated {
+            self.capacity = count.next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.label.as_deref(), self.capacity);
+            self.bind_group = None;
+        }
+
+        let header = LightsHeader {
+            ambient_color: self.ambient_color,
+            count,
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&header));
+        if !self.lights.is_empty() {
+            queue.write_buffer(
+                &self.buffer,
+                HEADER_SIZE,
+                bytemuck::cast_slice(&self.lights),
+            );
+        }
+        reallocated
+    }
+
+    fn create_buffer(device: &Device, label: Option<&str>, capacity: u32) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label,
+            size: HEADER_SIZE + Light::SIZE * capacity as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+}
+
+fn normalize(direction: [f32; 3]) -> [f32; 3] {
+    let direction = Vector3::from(direction);
+    if direction.magnitude2() > 0.0 {
+        direction.normalize().into()
+    } else {
+        [0.0, -1.0, 0.0]
+    }
+}
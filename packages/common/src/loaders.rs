@@ -0,0 +1,51 @@
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub struct LoadError {
+    file: PathBuf,
+    line: Option<usize>,
+    message: String,
+}
+
+impl LoadError {
+    pub fn new(file: impl AsRef<Path>, line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            file: file.as_ref().to_path_buf(),
+            line,
+            message: message.into(),
+        }
+    }
+
+    pub fn io(file: impl AsRef<Path>, error: std::io::Error) -> Self {
+        Self::new(file, None, error.to_string())
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file.display(), line, self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
@@ -0,0 +1,773 @@
+use std::path::Path;
+
+use cgmath::{Deg, InnerSpace, Matrix4, Rad, SquareMatrix, Vector3};
+use wgpu::PrimitiveTopology;
+
+use crate::{
+    camera::Camera,
+    loaders::LoadError,
+    mesh::{compute_vertex_normals, MeshData},
+    transform::OPENGL_TO_WGPU_MATRIX,
+    vertices::{Vertex3DNormalColored, Vertex3DNormalUv},
+};
+
+// Only the first texture coordinate set is kept, so textures that use another set have no
+// coordinates to sample with.
+#[derive(Clone, Debug)]
+pub struct GltfPrimitive {
+    pub data: MeshData<Vertex3DNormalUv>,
+    pub colors: Option<Vec<[f32; 4]>>,
+    pub material: Option<usize>,
+}
+
+impl GltfPrimitive {
+    // Primitives without COLOR_0 are white, as glTF specifies.
+    pub fn colored_data(&self) -> MeshData<Vertex3DNormalColored> {
+        let mut colors = self.colors.iter().flatten();
+        self.data.map_vertices(|vertex| Vertex3DNormalColored {
+            position: vertex.position,
+            normal: vertex.normal,
+            color: colors.next().copied().unwrap_or([1.0, 1.0, 1.0, 1.0]),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextureRef {
+    pub texture: usize,
+    pub tex_coord: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PbrMaterial {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: [f32; 3],
+    pub emissive_texture: Option<TextureRef>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            name: None,
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_factor: [0.0, 0.0, 0.0],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfTexture {
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+    pub is_srgb: bool,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GltfProjection {
+    Perspective {
+        yfov: f32,
+        aspect_ratio: Option<f32>,
+        znear: f32,
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfCamera {
+    pub name: Option<String>,
+    pub projection: GltfProjection,
+}
+
+impl GltfCamera {
+    pub fn projection_matrix(&self, viewport_aspect_ratio: f32) -> Matrix4<f32> {
+        let projection = match self.projection {
+            GltfProjection::Perspective {
+                yfov,
+                aspect_ratio,
+                znear,
+                zfar,
+            } => {
+                let aspect_ratio = aspect_ratio.unwrap_or(viewport_aspect_ratio);
+                match zfar {
+                    Some(zfar) => cgmath::perspective(Rad(yfov), aspect_ratio, znear, zfar),
+                    None => infinite_perspective(yfov, aspect_ratio, znear),
+                }
+            }
+            GltfProjection::Orthographic {
+                xmag,
+                ymag,
+                znear,
+                zfar,
+            } => cgmath::ortho(-xmag, xmag, -ymag, ymag, znear, zfar),
+        };
+        OPENGL_TO_WGPU_MATRIX * projection
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SceneNode {
+    pub name: Option<String>,
+    pub local_transform: Matrix4<f32>,
+    pub world_transform: Matrix4<f32>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<PbrMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub cameras: Vec<GltfCamera>,
+    pub nodes: Vec<SceneNode>,
+    pub roots: Vec<usize>,
+}
+
+impl GltfScene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let (document, buffers, images) =
+            gltf::import(path).map_err(|error| LoadError::new(path, None, error.to_string()))?;
+
+        let materials: Vec<PbrMaterial> = document.materials().map(convert_material).collect();
+
+        let mut srgb_textures = vec![false; document.textures().len()];
+        for material in &materials {
+            for texture in [material.base_color_texture, material.emissive_texture]
+                .into_iter()
+                .flatten()
+            {
+                srgb_textures[texture.texture] = true;
+            }
+        }
+
+        // A texture that cannot be decoded only costs its material the detail, the scene still
+        // loads with a white texture in its place.
+        let textures = document
+            .textures()
+            .map(|texture| {
+                let image = &images[texture.source().index()];
+                let is_srgb = srgb_textures[texture.index()];
+                convert_texture(&texture, image, is_srgb).unwrap_or_else(|message| {
+                    log::warn!(
+                        "{}: texture {}: {}, using a white texture",
+                        path.display(),
+                        texture.index(),
+                        message
+                    );
+                    GltfTexture {
+                        width: 1,
+                        height: 1,
+                        rgba: vec![255; 4],
+                        ..sampled_texture(&texture, is_srgb)
+                    }
+                })
+            })
+            .collect();
+
+        let meshes = document
+            .meshes()
+            .map(|mesh| {
+                let primitives = mesh
+                    .primitives()
+                    .map(|primitive| convert_primitive(&primitive, &buffers))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|message| {
+                        let name = mesh.name().unwrap_or("unnamed");
+                        LoadError::new(path, None, format!("mesh `{}`: {}", name, message))
+                    })?;
+                Ok(GltfMesh {
+                    name: mesh.name().map(String::from),
+                    primitives,
+                })
+            })
+            .collect::<Result<Vec<_>, LoadError>>()?;
+
+        let cameras = document
+            .cameras()
+            .map(|camera| GltfCamera {
+                name: camera.name().map(String::from),
+                projection: match camera.projection() {
+                    gltf::camera::Projection::Perspective(perspective) => {
+                        GltfProjection::Perspective {
+                            yfov: perspective.yfov(),
+                            aspect_ratio: perspective.aspect_ratio(),
+                            znear: perspective.znear(),
+                            zfar: perspective.zfar(),
+                        }
+                    }
+                    gltf::camera::Projection::Orthographic(orthographic) => {
+                        GltfProjection::Orthographic {
+                            xmag: orthographic.xmag(),
+                            ymag: orthographic.ymag(),
+                            znear: orthographic.znear(),
+                            zfar: orthographic.zfar(),
+                        }
+                    }
+                },
+            })
+            .collect();
+
+        let mut nodes: Vec<SceneNode> = document
+            .nodes()
+            .map(|node| SceneNode {
+                name: node.name().map(String::from),
+                local_transform: node.transform().matrix().into(),
+                world_transform: Matrix4::identity(),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                camera: node.camera().map(|camera| camera.index()),
+                parent: None,
+                children: node.children().map(|child| child.index()).collect(),
+            })
+            .collect();
+        for index in 0..nodes.len() {
+            for child in nodes[index].children.clone() {
+                nodes[child].parent = Some(index);
+            }
+        }
+
+        let roots: Vec<usize> = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            None => (0..nodes.len())
+                .filter(|index| nodes[*index].parent.is_none())
+                .collect(),
+        };
+
+        let mut visited = vec![false; nodes.len()];
+        let mut stack: Vec<(usize, Matrix4<f32>)> = roots
+            .iter()
+            .map(|root| (*root, Matrix4::identity()))
+            .collect();
+        while let Some((index, parent_transform)) = stack.pop() {
+            // glTF nodes form disjoint trees, anything else would be walked forever.
+            if std::mem::replace(&mut visited[index], true) {
+                return Err(LoadError::new(
+                    path,
+                    None,
+                    format!("node {} is reached more than once from the scene", index),
+                ));
+            }
+            let world_transform = parent_transform * nodes[index].local_transform;
+            nodes[index].world_transform = world_transform;
+            stack.extend(
+                nodes[index]
+                    .children
+                    .iter()
+                    .map(|child| (*child, world_transform)),
+            );
+        }
+
+        Ok(Self {
+            meshes,
+            materials,
+            textures,
+            cameras,
+            nodes,
+            roots,
+        })
+    }
+
+    // The nodes under `roots` in depth-first order. Nodes outside the scene keep an identity
+    // world transform and are never drawn.
+    pub fn scene_nodes(&self) -> impl Iterator<Item = &SceneNode> {
+        let mut stack: Vec<usize> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let index = stack.pop()?;
+            stack.extend(self.nodes[index].children.iter().rev());
+            Some(&self.nodes[index])
+        })
+    }
+
+    pub fn mesh_instances(&self) -> impl Iterator<Item = (&GltfMesh, &Matrix4<f32>)> {
+        self.scene_nodes().filter_map(|node| {
+            node.mesh
+                .map(|mesh| (&self.meshes[mesh], &node.world_transform))
+        })
+    }
+
+    pub fn camera_nodes(&self) -> impl Iterator<Item = &SceneNode> {
+        self.scene_nodes().filter(|node| node.camera.is_some())
+    }
+
+    pub fn view_matrix(node: &SceneNode) -> Matrix4<f32> {
+        node.world_transform
+            .invert()
+            .unwrap_or_else(Matrix4::identity)
+    }
+
+    // `Camera` has no roll, so only the node's position and viewing direction carry over.
+    pub fn camera(node: &SceneNode) -> Camera {
+        let position = node.world_transform.w.truncate();
+        let forward = -node.world_transform.z.truncate();
+        let forward = if forward.magnitude2() > 0.0 {
+            forward.normalize()
+        } else {
+            -Vector3::unit_z()
+        };
+        Camera::new(
+            position.into(),
+            Deg::from(Rad(forward.z.atan2(forward.x))),
+            Deg::from(Rad(forward.y.clamp(-1.0, 1.0).asin())),
+        )
+    }
+}
+
+fn infinite_perspective(yfov: f32, aspect_ratio: f32, znear: f32) -> Matrix4<f32> {
+    let f = 1.0 / (yfov / 2.0).tan();
+    #[rustfmt::skip]
+    let matrix = Matrix4::new(
+        f / aspect_ratio, 0.0, 0.0, 0.0,
+        0.0, f, 0.0, 0.0,
+        0.0, 0.0, -1.0, -1.0,
+        0.0, 0.0, -2.0 * znear, 0.0,
+    );
+    matrix
+}
+
+fn texture_ref(texture: Option<gltf::texture::Info>) -> Option<TextureRef> {
+    texture.map(|info| TextureRef {
+        texture: info.texture().index(),
+        tex_coord: info.tex_coord(),
+    })
+}
+
+fn convert_material(material: gltf::Material) -> PbrMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    let normal_texture = material.normal_texture();
+    let occlusion_texture = material.occlusion_texture();
+
+    PbrMaterial {
+        name: material.name().map(String::from),
+        base_color_factor: pbr.base_color_factor(),
+        base_color_texture: texture_ref(pbr.base_color_texture()),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: texture_ref(pbr.metallic_roughness_texture()),
+        normal_texture: normal_texture.as_ref().map(|texture| TextureRef {
+            texture: texture.texture().index(),
+            tex_coord: texture.tex_coord(),
+        }),
+        normal_scale: normal_texture
+            .as_ref()
+            .map_or(1.0, |texture| texture.scale()),
+        occlusion_texture: occlusion_texture.as_ref().map(|texture| TextureRef {
+            texture: texture.texture().index(),
+            tex_coord: texture.tex_coord(),
+        }),
+        occlusion_strength: occlusion_texture
+            .as_ref()
+            .map_or(1.0, |texture| texture.strength()),
+        emissive_factor: material.emissive_factor(),
+        emissive_texture: texture_ref(material.emissive_texture()),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        double_sided: material.double_sided(),
+    }
+}
+
+fn sampled_texture(texture: &gltf::Texture, is_srgb: bool) -> GltfTexture {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let sampler = texture.sampler();
+    let address_mode = |mode: WrappingMode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => {
+            (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest)
+        }
+        Some(MinFilter::NearestMipmapLinear) => {
+            (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear)
+        }
+        Some(MinFilter::LinearMipmapNearest) => {
+            (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest)
+        }
+        _ => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear),
+    };
+
+    GltfTexture {
+        name: texture.name().map(String::from),
+        width: 0,
+        height: 0,
+        rgba: Vec::new(),
+        is_srgb,
+        mag_filter: match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+            _ => wgpu::FilterMode::Linear,
+        },
+        min_filter,
+        mipmap_filter,
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+    }
+}
+
+fn convert_texture(
+    texture: &gltf::Texture,
+    image: &gltf::image::Data,
+    is_srgb: bool,
+) -> Result<GltfTexture, String> {
+    Ok(GltfTexture {
+        width: image.width,
+        height: image.height,
+        rgba: image_to_rgba8(image.format, image.width, image.height, &image.pixels)?,
+        ..sampled_texture(texture, is_srgb)
+    })
+}
+
+// Wider channels are narrowed to 8 bits, float images are clamped to [0, 1] first. The decoder
+// stores pixels in native byte order and gives two channel images as luminance and alpha.
+fn image_to_rgba8(
+    format: gltf::image::Format,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> Result<Vec<u8>, String> {
+    use gltf::image::Format;
+
+    let (channels, channel_size) = match format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let expected = width as usize * height as usize * channels * channel_size;
+    if pixels.len() != expected {
+        return Err(format!(
+            "{:?} image is {}x{} but holds {} bytes instead of {}",
+            format,
+            width,
+            height,
+            pixels.len(),
+            expected
+        ));
+    }
+
+    let values: Vec<u8> = pixels
+        .chunks_exact(channel_size)
+        .map(|channel| match *channel {
+            [value] => value,
+            [low, high] => {
+                let value = u16::from_ne_bytes([low, high]) as u32;
+                ((value * 255 + 32767) / 65535) as u8
+            }
+            [a, b, c, d] => {
+                let value = f32::from_ne_bytes([a, b, c, d]);
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+            _ => unreachable!(),
+        })
+        .collect();
+
+    Ok(values
+        .chunks_exact(channels)
+        .flat_map(|pixel| match *pixel {
+            [value] => [value, value, value, 255],
+            [value, alpha] => [value, value, value, alpha],
+            [r, g, b] => [r, g, b, 255],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!(),
+        })
+        .collect())
+}
+
+fn convert_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+) -> Result<GltfPrimitive, String> {
+    use gltf::mesh::Mode;
+
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .ok_or("primitive has no POSITION attribute")?
+        .collect();
+    let vertex_count = positions.len();
+
+    let mut indices: Option<Vec<u32>> = reader
+        .read_indices()
+        .map(|indices| indices.into_u32().collect());
+    if let Some(index) = indices
+        .iter()
+        .flatten()
+        .find(|index| **index as usize >= vertex_count)
+    {
+        return Err(format!(
+            "index {} is out of range for {} vertices",
+            index, vertex_count
+        ));
+    }
+
+    let (topology, indices) = match primitive.mode() {
+        Mode::Points => (PrimitiveTopology::PointList, indices),
+        Mode::Lines => (PrimitiveTopology::LineList, indices),
+        Mode::LineStrip => (PrimitiveTopology::LineStrip, indices),
+        Mode::Triangles => (PrimitiveTopology::TriangleList, indices),
+        Mode::LineLoop => {
+            let mut indices = indices.unwrap_or_else(|| (0..vertex_count as u32).collect());
+            if let Some(first) = indices.first().copied() {
+                indices.push(first);
+            }
+            (PrimitiveTopology::LineStrip, Some(indices))
+        }
+        Mode::TriangleStrip => {
+            let strip = indices
+                .take()
+                .unwrap_or_else(|| (0..vertex_count as u32).collect());
+            let list = (0..strip.len().saturating_sub(2))
+                .flat_map(|index| {
+                    if index % 2 == 0 {
+                        [strip[index], strip[index + 1], strip[index + 2]]
+                    } else {
+                        [strip[index + 1], strip[index], strip[index + 2]]
+                    }
+                })
+                .collect();
+            (PrimitiveTopology::TriangleList, Some(list))
+        }
+        Mode::TriangleFan => {
+            let fan = indices
+                .take()
+                .unwrap_or_else(|| (0..vertex_count as u32).collect());
+            let list = (1..fan.len().saturating_sub(1))
+                .flat_map(|index| [fan[0], fan[index], fan[index + 1]])
+                .collect();
+            (PrimitiveTopology::TriangleList, Some(list))
+        }
+    };
+
+    let check_count = |name: &str, count: usize| {
+        if count == vertex_count {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} has {} elements but POSITION has {}",
+                name, count, vertex_count
+            ))
+        }
+    };
+
+    let normals: Vec<[f32; 3]> = match reader.read_normals() {
+        Some(normals) => normals.collect(),
+        None if topology == PrimitiveTopology::TriangleList => {
+            let triangle_indices = indices
+                .clone()
+                .unwrap_or_else(|| (0..vertex_count as u32).collect());
+            compute_vertex_normals(&positions, &triangle_indices)
+        }
+        None => vec![[0.0, 0.0, 1.0]; vertex_count],
+    };
+    check_count("NORMAL", normals.len())?;
+    let uvs: Vec<[f32; 2]> = reader
+        .read_tex_coords(0)
+        .map(|uvs| uvs.into_f32().collect())
+        .unwrap_or_else(|| vec![[0.0, 0.0]; vertex_count]);
+    check_count("TEXCOORD_0", uvs.len())?;
+    let colors: Option<Vec<[f32; 4]>> = reader
+        .read_colors(0)
+        .map(|colors| colors.into_rgba_f32().collect());
+    if let Some(colors) = &colors {
+        check_count("COLOR_0", colors.len())?;
+    }
+
+    let vertices = positions
+        .iter()
+        .zip(&normals)
+        .zip(&uvs)
+        .map(|((position, normal), uv)| Vertex3DNormalUv {
+            position: *position,
+            normal: *normal,
+            uv: *uv,
+        })
+        .collect();
+
+    Ok(GltfPrimitive {
+        data: MeshData {
+            vertices,
+            indices,
+            topology,
+        },
+        colors,
+        material: primitive.material().index(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // Writes a scene with one triangle and `normal_count` normals next to its buffer.
+    fn write_scene(name: &str, nodes: &str, normal_count: usize) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("gltf-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let positions = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let normals = vec![[0.0f32, 0.0, 1.0]; normal_count];
+        let mut buffer: Vec<u8> = bytemuck::cast_slice(&positions).to_vec();
+        buffer.extend_from_slice(bytemuck::cast_slice(&normals));
+        std::fs::write(directory.join("scene.bin"), &buffer).unwrap();
+
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "uri": "scene.bin", "byteLength": {} }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": {} }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": {}, "type": "VEC3" }}
+                ],
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1 }} }}] }}],
+                "nodes": {},
+                "scenes": [{{ "nodes": [0] }}],
+                "scene": 0
+            }}"#,
+            buffer.len(),
+            normal_count * 12,
+            normal_count,
+            nodes
+        );
+        let path = directory.join("scene.gltf");
+        std::fs::write(&path, json).unwrap();
+        path
+    }
+
+    #[test]
+    fn only_nodes_under_the_scene_are_instanced() {
+        let path = write_scene(
+            "scene-roots",
+            r#"[
+                { "children": [2], "translation": [0, 1, 0] },
+                { "mesh": 0, "translation": [5, 0, 0] },
+                { "mesh": 0, "translation": [1, 0, 0] }
+            ]"#,
+            3,
+        );
+        let scene = GltfScene::load(&path);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        let scene = scene.unwrap();
+        assert_eq!(scene.roots, vec![0]);
+        let instances: Vec<_> = scene.mesh_instances().collect();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].1.w.truncate(), Vector3::new(1.0, 1.0, 0.0));
+        assert_eq!(scene.meshes[0].primitives[0].data.vertices.len(), 3);
+        assert!(scene.meshes[0].primitives[0].colors.is_none());
+    }
+
+    #[test]
+    fn attribute_count_mismatch_is_an_error() {
+        let path = write_scene("attribute-count", r#"[{ "mesh": 0 }]"#, 2);
+        let scene = GltfScene::load(&path);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        let error = scene.unwrap_err();
+        assert!(error.message().contains("NORMAL"), "{}", error);
+    }
+
+    #[test]
+    fn wide_and_float_images_convert_to_rgba8() {
+        use gltf::image::Format;
+
+        let luma_alpha: Vec<u8> = [u16::MAX, 0x8000]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        assert_eq!(
+            image_to_rgba8(Format::R16G16, 1, 1, &luma_alpha).unwrap(),
+            vec![255, 255, 255, 128]
+        );
+
+        let rgb: Vec<u8> = [0u16, 0x0101, 0xffff]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        assert_eq!(
+            image_to_rgba8(Format::R16G16B16, 1, 1, &rgb).unwrap(),
+            vec![0, 1, 255, 255]
+        );
+
+        let float: Vec<u8> = [-1.0f32, 0.5, 2.0, 1.0]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        assert_eq!(
+            image_to_rgba8(Format::R32G32B32A32FLOAT, 1, 1, &float).unwrap(),
+            vec![0, 128, 255, 255]
+        );
+
+        assert!(image_to_rgba8(Format::R16, 2, 2, &[0; 6]).is_err());
+    }
+}
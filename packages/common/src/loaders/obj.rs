@@ -0,0 +1,646 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use wgpu::PrimitiveTopology;
+
+use crate::{
+    loaders::LoadError,
+    mesh::{compute_vertex_normals, MeshData},
+    vertices::Vertex3DNormalUv,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub emissive: [f32; 3],
+    pub shininess: f32,
+    pub dissolve: f32,
+    pub optical_density: f32,
+    pub illumination_model: Option<u32>,
+    pub ambient_texture: Option<PathBuf>,
+    pub diffuse_texture: Option<PathBuf>,
+    pub specular_texture: Option<PathBuf>,
+    pub normal_texture: Option<PathBuf>,
+    pub dissolve_texture: Option<PathBuf>,
+}
+
+impl ObjMaterial {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: [0.0, 0.0, 0.0],
+            diffuse: [1.0, 1.0, 1.0],
+            specular: [0.0, 0.0, 0.0],
+            emissive: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            dissolve: 1.0,
+            optical_density: 1.0,
+            illumination_model: None,
+            ambient_texture: None,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+            dissolve_texture: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjMesh {
+    pub name: String,
+    pub material: Option<usize>,
+    pub data: MeshData<Vertex3DNormalUv>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+impl ObjModel {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|error| LoadError::io(path, error))?;
+        let directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        Self::parse(&source, path, |library| {
+            let library = directory.join(library);
+            // Models are often shared without their material library, which should not keep the
+            // geometry from loading.
+            if !library.is_file() {
+                log::warn!(
+                    "{}: material library `{}` not found, using the default material",
+                    path.display(),
+                    library.display()
+                );
+                return Ok(Vec::new());
+            }
+            load_mtl(library)
+        })
+    }
+
+    pub fn parse<F>(
+        source: &str,
+        file: impl AsRef<Path>,
+        mut load_library: F,
+    ) -> Result<Self, LoadError>
+    where
+        F: FnMut(&str) -> Result<Vec<ObjMaterial>, LoadError>,
+    {
+        let file = file.as_ref();
+        let error = |line: usize, message: String| LoadError::new(file, Some(line), message);
+
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+        let mut uvs: Vec<[f32; 2]> = Vec::new();
+
+        let mut model = ObjModel::default();
+        let mut builder = MeshBuilder::new("default", None);
+        let mut smoothing_group = 0;
+        let mut face_count: u64 = 0;
+
+        for (line_number, line) in logical_lines(source) {
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            let arguments: Vec<&str> = tokens.collect();
+
+            match keyword {
+                "v" => positions.push(
+                    parse_floats::<3>(&arguments).map_err(|message| error(line_number, message))?,
+                ),
+                "vn" => normals.push(
+                    parse_floats::<3>(&arguments).map_err(|message| error(line_number, message))?,
+                ),
+                "vt" => {
+                    let [u, v] = parse_floats::<2>(&arguments)
+                        .or_else(|_| parse_floats::<1>(&arguments).map(|[u]| [u, 0.0]))
+                        .map_err(|message| error(line_number, message))?;
+                    // OBJ puts the texture origin at the bottom left, wgpu at the top left.
+                    uvs.push([u, 1.0 - v]);
+                }
+                "f" => {
+                    if arguments.len() < 3 {
+                        return Err(error(
+                            line_number,
+                            format!("face needs at least 3 vertices, found {}", arguments.len()),
+                        ));
+                    }
+                    let mut corners = Vec::with_capacity(arguments.len());
+                    for argument in &arguments {
+                        let corner =
+                            parse_corner(argument, positions.len(), uvs.len(), normals.len())
+                                .map_err(|message| error(line_number, message))?;
+                        let smoothing = match (corner.normal, smoothing_group) {
+                            (Some(_), _) => Smoothing::Explicit,
+                            (None, 0) => Smoothing::Flat(face_count),
+                            (None, group) => Smoothing::Group(group),
+                        };
+                        corners.push(builder.vertex(corner, smoothing, &positions, &uvs, &normals));
+                    }
+                    for index in 1..corners.len() - 1 {
+                        builder.indices.extend_from_slice(&[
+                            corners[0],
+                            corners[index],
+                            corners[index + 1],
+                        ]);
+                    }
+                    face_count += 1;
+                }
+                "g" | "o" => {
+                    let name = if arguments.is_empty() {
+                        "default".to_string()
+                    } else {
+                        arguments.join(" ")
+                    };
+                    let material = builder.material;
+                    builder.flush_into(&mut model.meshes);
+                    builder = MeshBuilder::new(&name, material);
+                }
+                "usemtl" => {
+                    let name = arguments.join(" ");
+                    let material = model
+                        .materials
+                        .iter()
+                        .position(|material| material.name == name);
+                    if material.is_none() {
+                        log::warn!(
+                            "{}:{}: unknown material `{}`",
+                            file.display(),
+                            line_number,
+                            name
+                        );
+                    }
+                    if material != builder.material {
+                        let group = builder.name.clone();
+                        builder.flush_into(&mut model.meshes);
+                        builder = MeshBuilder::new(&group, material);
+                    }
+                }
+                // Like texture names, the library name keeps its spaces.
+                "mtllib" => {
+                    let library = arguments.join(" ");
+                    if !library.is_empty() {
+                        model.materials.extend(load_library(&library)?);
+                    }
+                }
+                "s" => {
+                    smoothing_group = match arguments.first().copied() {
+                        None | Some("off") => 0,
+                        Some(group) => group.parse().map_err(|_| {
+                            error(line_number, format!("invalid smoothing group `{}`", group))
+                        })?,
+                    };
+                }
+                _ => (),
+            }
+        }
+        builder.flush_into(&mut model.meshes);
+
+        Ok(model)
+    }
+}
+
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<Vec<ObjMaterial>, LoadError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path).map_err(|error| LoadError::io(path, error))?;
+    parse_mtl(&source, path)
+}
+
+pub fn parse_mtl(source: &str, file: impl AsRef<Path>) -> Result<Vec<ObjMaterial>, LoadError> {
+    let file = file.as_ref();
+    let directory = file.parent().unwrap_or(Path::new(""));
+    let error = |line: usize, message: String| LoadError::new(file, Some(line), message);
+
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    for (line_number, line) in logical_lines(source) {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            materials.push(ObjMaterial::new(&arguments.join(" ")));
+            continue;
+        }
+        let Some(material) = materials.last_mut() else {
+            return Err(error(
+                line_number,
+                format!("`{}` appears before any `newmtl`", keyword),
+            ));
+        };
+
+        let color = || parse_floats::<3>(&arguments).map_err(|message| error(line_number, message));
+        let scalar = || {
+            parse_floats::<1>(&arguments)
+                .map(|[value]| value)
+                .map_err(|message| error(line_number, message))
+        };
+        let texture = || texture_file_name(&arguments).map(|name| directory.join(name));
+
+        match keyword {
+            "Ka" => material.ambient = color()?,
+            "Kd" => material.diffuse = color()?,
+            "Ks" => material.specular = color()?,
+            "Ke" => material.emissive = color()?,
+            "Ns" => material.shininess = scalar()?,
+            "Ni" => material.optical_density = scalar()?,
+            "d" => material.dissolve = scalar()?,
+            "Tr" => material.dissolve = 1.0 - scalar()?,
+            "illum" => material.illumination_model = Some(scalar()? as u32),
+            "map_Ka" => material.ambient_texture = texture(),
+            "map_Kd" => material.diffuse_texture = texture(),
+            "map_Ks" => material.specular_texture = texture(),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_texture = texture(),
+            "map_d" => material.dissolve_texture = texture(),
+            _ => (),
+        }
+    }
+
+    Ok(materials)
+}
+
+// Texture statements may carry options such as `-bm 1.0` before the file name, which itself may
+// contain spaces.
+fn texture_file_name(arguments: &[&str]) -> Option<String> {
+    let mut index = 0;
+    while let Some(option) = arguments
+        .get(index)
+        .filter(|argument| argument.starts_with('-'))
+    {
+        index += 1;
+        match *option {
+            "-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan"
+            | "-texres" | "-type" => index += 1,
+            "-mm" => index += 2,
+            // Offset, scale and turbulence take one to three numbers.
+            "-o" | "-s" | "-t" => {
+                let numbers = arguments[index..]
+                    .iter()
+                    .take(3)
+                    .take_while(|argument| argument.parse::<f32>().is_ok())
+                    .count();
+                index += numbers;
+            }
+            _ => (),
+        }
+    }
+
+    let name = arguments.get(index..)?.join(" ");
+    (!name.is_empty()).then_some(name)
+}
+
+#[derive(Copy, Clone)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum Smoothing {
+    Explicit,
+    Group(u32),
+    Flat(u64),
+}
+
+struct MeshBuilder {
+    name: String,
+    material: Option<usize>,
+    vertices: Vec<Vertex3DNormalUv>,
+    indices: Vec<u32>,
+    needs_normal: Vec<bool>,
+    lookup: HashMap<(usize, Option<usize>, Option<usize>, Smoothing), u32>,
+}
+
+impl MeshBuilder {
+    fn new(name: &str, material: Option<usize>) -> Self {
+        Self {
+            name: name.to_string(),
+            material,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            needs_normal: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    fn vertex(
+        &mut self,
+        corner: Corner,
+        smoothing: Smoothing,
+        positions: &[[f32; 3]],
+        uvs: &[[f32; 2]],
+        normals: &[[f32; 3]],
+    ) -> u32 {
+        let key = (corner.position, corner.uv, corner.normal, smoothing);
+        *self.lookup.entry(key).or_insert_with(|| {
+            self.vertices.push(Vertex3DNormalUv {
+                position: positions[corner.position],
+                normal: corner
+                    .normal
+                    .map_or([0.0, 0.0, 0.0], |index| normals[index]),
+                uv: corner.uv.map_or([0.0, 0.0], |index| uvs[index]),
+            });
+            self.needs_normal.push(corner.normal.is_none());
+            self.vertices.len() as u32 - 1
+        })
+    }
+
+    fn flush_into(mut self, meshes: &mut Vec<ObjMesh>) {
+        if self.indices.is_empty() {
+            return;
+        }
+
+        if self.needs_normal.iter().any(|needs_normal| *needs_normal) {
+            let positions: Vec<[f32; 3]> =
+                self.vertices.iter().map(|vertex| vertex.position).collect();
+            let computed = compute_vertex_normals(&positions, &self.indices);
+            for ((vertex, normal), needs_normal) in self
+                .vertices
+                .iter_mut()
+                .zip(computed)
+                .zip(self.needs_normal.iter())
+            {
+                if *needs_normal {
+                    vertex.normal = normal;
+                }
+            }
+        }
+
+        meshes.push(ObjMesh {
+            name: self.name,
+            material: self.material,
+            data: MeshData::indexed(self.vertices, self.indices, PrimitiveTopology::TriangleList),
+        });
+    }
+}
+
+fn logical_lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let (start, mut joined) = pending.take().unwrap_or((index + 1, String::new()));
+        match line.trim_end().strip_suffix('\\') {
+            Some(continued) => {
+                joined.push_str(continued);
+                joined.push(' ');
+                pending = Some((start, joined));
+            }
+            None => {
+                joined.push_str(line);
+                lines.push((start, joined));
+            }
+        }
+    }
+    lines.extend(pending);
+    lines
+}
+
+fn parse_floats<const N: usize>(arguments: &[&str]) -> Result<[f32; N], String> {
+    if arguments.len() < N {
+        return Err(format!("expected {} numbers, found {}", N, arguments.len()));
+    }
+    let mut values = [0.0; N];
+    for (value, argument) in values.iter_mut().zip(arguments) {
+        *value = argument
+            .parse()
+            .map_err(|_| format!("invalid number `{}`", argument))?;
+    }
+    Ok(values)
+}
+
+fn resolve_index(token: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let index: i64 = token
+        .parse()
+        .map_err(|_| format!("invalid {} index `{}`", kind, token))?;
+    let resolved = match index {
+        0 => None,
+        index if index > 0 => Some(index - 1),
+        index => Some(count as i64 + index),
+    };
+    match resolved {
+        Some(resolved) if resolved >= 0 && (resolved as usize) < count => Ok(resolved as usize),
+        _ => Err(format!(
+            "{} index {} is out of range, {} defined so far",
+            kind, index, count
+        )),
+    }
+}
+
+fn parse_corner(
+    token: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<Corner, String> {
+    let mut parts = token.split('/');
+    let position = resolve_index(parts.next().unwrap_or(""), position_count, "position")?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        Some(uv) => Some(resolve_index(uv, uv_count, "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        None | Some("") => None,
+        Some(normal) => Some(resolve_index(normal, normal_count, "normal")?),
+    };
+    Ok(Corner {
+        position,
+        uv,
+        normal,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<ObjModel, LoadError> {
+        ObjModel::parse(source, "model.obj", |library| {
+            assert_eq!(library, "my materials.mtl");
+            Ok(vec![ObjMaterial::new("red"), ObjMaterial::new("blue")])
+        })
+    }
+
+    fn positions(mesh: &ObjMesh) -> Vec<[f32; 3]> {
+        mesh.data
+            .indices
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|&index| mesh.data.vertices[index as usize].position)
+            .collect()
+    }
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (actual_value, expected_value) in actual.iter().zip(expected) {
+            assert!(
+                (actual_value - expected_value).abs() < 1e-5,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_element() {
+        let model = parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.5 0.25\nvn 0 0 1\nf -3/-1/-1 -2/-1/-1 -1/-1/-1\nv 0 0 1\nf 1 -3 -1\n",
+        )
+        .unwrap();
+
+        let mesh = &model.meshes[0];
+        assert_eq!(
+            positions(mesh),
+            [
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0],
+            ]
+        );
+        assert_eq!(mesh.data.vertices[0].uv, [0.5, 0.75]);
+        assert_eq!(mesh.data.vertices[0].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn polygons_are_triangulated_as_fans() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv -1 0.5 0\nf 1 2 3 4 5\n").unwrap();
+
+        assert_eq!(
+            model.meshes[0].data.indices.as_deref(),
+            Some(&[0, 1, 2, 0, 2, 3, 0, 3, 4][..])
+        );
+    }
+
+    #[test]
+    fn shared_corners_are_welded_within_a_smoothing_group() {
+        let quad = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\n";
+        let vertex_count = |faces: &str| {
+            parse(&format!("{}{}", quad, faces)).unwrap().meshes[0]
+                .data
+                .vertices
+                .len()
+        };
+
+        assert_eq!(vertex_count("s 1\nf 1 2 3\nf 1 3 4\n"), 4);
+        assert_eq!(vertex_count("s off\nf 1 2 3\nf 1 3 4\n"), 6);
+        assert_eq!(vertex_count("f 1//1 2//1 3//1\nf 1//1 3//1 4//1\n"), 4);
+        // The same position with another texture coordinate is a new vertex.
+        assert_eq!(
+            vertex_count("vt 0 0\nvt 1 1\nf 1//1 2//1 3//1\nf 1/1/1 3/2/1 4/2/1\n"),
+            6
+        );
+    }
+
+    #[test]
+    fn smoothing_groups_average_normals_and_flat_faces_keep_their_own() {
+        let fold = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\n";
+
+        let smooth = parse(&format!("{}s 1\nf 1 2 3\nf 1 3 4\n", fold)).unwrap();
+        let vertices = &smooth.meshes[0].data.vertices;
+        let shared = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(vertices[0].normal, [shared, 0.0, shared]);
+        assert_close(vertices[1].normal, [0.0, 0.0, 1.0]);
+        assert_close(vertices[3].normal, [1.0, 0.0, 0.0]);
+
+        let flat = parse(&format!("{}f 1 2 3\nf 1 3 4\n", fold)).unwrap();
+        let vertices = &flat.meshes[0].data.vertices;
+        for vertex in &vertices[..3] {
+            assert_close(vertex.normal, [0.0, 0.0, 1.0]);
+        }
+        for vertex in &vertices[3..] {
+            assert_close(vertex.normal, [1.0, 0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn groups_and_materials_split_meshes() {
+        let model = parse(
+            "mtllib my materials.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\ng empty\ng first\nusemtl red\nf 1 2 3\nusemtl blue\nf 1 2 3\ng second part\nf 1 2 3\nusemtl blue\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        assert_eq!(model.materials.len(), 2);
+        let meshes: Vec<_> = model
+            .meshes
+            .iter()
+            .map(|mesh| (mesh.name.as_str(), mesh.material, mesh.data.vertices.len()))
+            .collect();
+        assert_eq!(
+            meshes,
+            [
+                ("first", Some(0), 3),
+                ("first", Some(1), 3),
+                ("second part", Some(1), 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_carry_the_file_and_line() {
+        let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 \\\n  4\n")
+            .expect_err("index 4 to be out of range");
+        assert_eq!(error.file(), Path::new("model.obj"));
+        assert_eq!(error.line(), Some(5));
+        assert_eq!(
+            error.to_string(),
+            "model.obj:5: position index 4 is out of range, 3 defined so far"
+        );
+
+        let error = parse("v 0 zero 0\n").expect_err("`zero` to be invalid");
+        assert_eq!(error.to_string(), "model.obj:1: invalid number `zero`");
+
+        let error = parse("v 0 0 0\nv 1 0 0\nf 1 2\n").expect_err("face to be too small");
+        assert_eq!(error.line(), Some(3));
+
+        let error = parse("s smooth\n").expect_err("group to be invalid");
+        assert_eq!(error.message(), "invalid smoothing group `smooth`");
+    }
+
+    #[test]
+    fn texture_file_name_skips_options_and_keeps_spaces() {
+        let name = |line: &str| texture_file_name(&line.split_whitespace().collect::<Vec<_>>());
+        assert_eq!(name("brick wall.png").as_deref(), Some("brick wall.png"));
+        assert_eq!(
+            name("-bm 0.5 -o 0.1 0.2 -clamp on my bump.png").as_deref(),
+            Some("my bump.png")
+        );
+        assert_eq!(
+            name("-s 2 2 1 -mm 0 1 wood.png").as_deref(),
+            Some("wood.png")
+        );
+        assert_eq!(name("-bm 0.5"), None);
+    }
+
+    #[test]
+    fn missing_material_library_falls_back_to_default_material() {
+        let directory = std::env::temp_dir().join(format!("obj-mtllib-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("triangle.obj");
+        std::fs::write(
+            &path,
+            "mtllib missing.mtl\nusemtl red\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let model = ObjModel::load(&path);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let model = model.unwrap();
+        assert!(model.materials.is_empty());
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].material, None);
+    }
+}
@@ -0,0 +1,597 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use wgpu::PrimitiveTopology;
+
+use crate::{
+    loaders::LoadError,
+    mesh::MeshData,
+    vertices::{Vertex4DColored, VertexPosition},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+// Faces are triangulated on load, so `indices` is empty for point clouds.
+#[derive(Clone, Debug, Default)]
+pub struct PlyModel {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub colors: Option<Vec<[f32; 4]>>,
+    pub indices: Vec<u32>,
+}
+
+impl PlyModel {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| LoadError::io(path, error))?;
+        Self::parse(&bytes, path)
+    }
+
+    pub fn parse(bytes: &[u8], file: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let file = file.as_ref();
+        let (header, body_start) =
+            parse_header(bytes).map_err(|(line, message)| LoadError::new(file, line, message))?;
+        read_body(&header, &bytes[body_start..])
+            .map_err(|message| LoadError::new(file, None, message))
+    }
+
+    pub fn from_mesh<V: VertexPosition>(mesh: &MeshData<V>) -> Self {
+        Self {
+            positions: mesh.vertices.iter().map(VertexPosition::position).collect(),
+            normals: None,
+            colors: None,
+            indices: mesh.triangles().unwrap_or_default().concat(),
+        }
+    }
+
+    pub fn from_colored_mesh(mesh: &MeshData<Vertex4DColored>) -> Self {
+        Self {
+            colors: Some(mesh.vertices.iter().map(|vertex| vertex.color).collect()),
+            ..Self::from_mesh(mesh)
+        }
+    }
+
+    pub fn to_mesh_data(&self) -> MeshData<Vertex4DColored> {
+        let vertices = self
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(index, [x, y, z])| Vertex4DColored {
+                position: [*x, *y, *z, 1.0],
+                color: self
+                    .colors
+                    .as_ref()
+                    .map_or([1.0, 1.0, 1.0, 1.0], |colors| colors[index]),
+            })
+            .collect();
+        if self.indices.is_empty() {
+            MeshData::new(vertices, PrimitiveTopology::PointList)
+        } else {
+            MeshData::indexed(
+                vertices,
+                self.indices.clone(),
+                PrimitiveTopology::TriangleList,
+            )
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, format: PlyFormat) -> io::Result<()> {
+        let format_name = match format {
+            PlyFormat::Ascii => "ascii",
+            PlyFormat::BinaryLittleEndian => "binary_little_endian",
+            PlyFormat::BinaryBigEndian => "binary_big_endian",
+        };
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format {} 1.0", format_name)?;
+        writeln!(writer, "element vertex {}", self.positions.len())?;
+        let mut properties = vec!["float x", "float y", "float z"];
+        if self.normals.is_some() {
+            properties.extend(["float nx", "float ny", "float nz"]);
+        }
+        if self.colors.is_some() {
+            properties.extend(["uchar red", "uchar green", "uchar blue", "uchar alpha"]);
+        }
+        for property in properties {
+            writeln!(writer, "property {}", property)?;
+        }
+        writeln!(writer, "element face {}", self.indices.len() / 3)?;
+        writeln!(writer, "property list uchar uint vertex_indices")?;
+        writeln!(writer, "end_header")?;
+
+        let mut body = BodyWriter { writer, format };
+        for (index, position) in self.positions.iter().enumerate() {
+            let mut values: Vec<Value> =
+                position.iter().map(|value| Value::Float(*value)).collect();
+            if let Some(normals) = &self.normals {
+                values.extend(normals[index].map(Value::Float));
+            }
+            if let Some(colors) = &self.colors {
+                values.extend(
+                    colors[index].map(|channel| {
+                        Value::UChar((channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    }),
+                );
+            }
+            body.write_row(&values)?;
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            let mut values = vec![Value::UChar(3)];
+            values.extend(triangle.iter().map(|index| Value::UInt(*index)));
+            body.write_row(&values)?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: PlyFormat) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, format)?;
+        writer.flush()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ScalarType {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Char),
+            "uchar" | "uint8" => Some(ScalarType::UChar),
+            "short" | "int16" => Some(ScalarType::Short),
+            "ushort" | "uint16" => Some(ScalarType::UShort),
+            "int" | "int32" => Some(ScalarType::Int),
+            "uint" | "uint32" => Some(ScalarType::UInt),
+            "float" | "float32" => Some(ScalarType::Float),
+            "double" | "float64" => Some(ScalarType::Double),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::Char | ScalarType::UChar => 1,
+            ScalarType::Short | ScalarType::UShort => 2,
+            ScalarType::Int | ScalarType::UInt | ScalarType::Float => 4,
+            ScalarType::Double => 8,
+        }
+    }
+
+    // Integer colors are stored as 0..=MAX, floating point colors as 0.0..=1.0.
+    fn color_scale(&self) -> f64 {
+        match self {
+            ScalarType::Char => i8::MAX as f64,
+            ScalarType::UChar => u8::MAX as f64,
+            ScalarType::Short => i16::MAX as f64,
+            ScalarType::UShort => u16::MAX as f64,
+            ScalarType::Int => i32::MAX as f64,
+            ScalarType::UInt => u32::MAX as f64,
+            ScalarType::Float | ScalarType::Double => 1.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PropertyType {
+    Scalar(ScalarType),
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Clone, Debug)]
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<(usize, ScalarType)> {
+        self.properties
+            .iter()
+            .enumerate()
+            .find_map(|(index, property)| match property.ty {
+                PropertyType::Scalar(ty) if names.contains(&property.name.as_str()) => {
+                    Some((index, ty))
+                }
+                _ => None,
+            })
+    }
+}
+
+struct Header {
+    format: PlyFormat,
+    elements: Vec<Element>,
+}
+
+fn parse_header(bytes: &[u8]) -> Result<(Header, usize), (Option<usize>, String)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut offset = 0;
+
+    for line_number in 1.. {
+        let Some(length) = bytes[offset..].iter().position(|byte| *byte == b'\n') else {
+            return Err((Some(line_number), "header has no `end_header`".to_string()));
+        };
+        let line = String::from_utf8_lossy(&bytes[offset..offset + length]);
+        offset += length + 1;
+        let error = |message: String| Err((Some(line_number), message));
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["ply"] if line_number == 1 => (),
+            _ if line_number == 1 => return error("missing `ply` magic number".to_string()),
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                    name => return error(format!("unknown format `{}`", name)),
+                });
+            }
+            ["element", name, count] => {
+                let Ok(count) = count.parse() else {
+                    return error(format!("invalid element count `{}`", count));
+                };
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            ["property", "list", count_type, item_type, name] => {
+                let (Some(count_type), Some(item_type)) =
+                    (ScalarType::parse(count_type), ScalarType::parse(item_type))
+                else {
+                    return error(format!("invalid list property `{}`", name));
+                };
+                let Some(element) = elements.last_mut() else {
+                    return error("property declared before any element".to_string());
+                };
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::List(count_type, item_type),
+                });
+            }
+            ["property", ty, name] => {
+                let Some(ty) = ScalarType::parse(ty) else {
+                    return error(format!("unknown property type `{}`", ty));
+                };
+                let Some(element) = elements.last_mut() else {
+                    return error("property declared before any element".to_string());
+                };
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::Scalar(ty),
+                });
+            }
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => (),
+            _ => return error(format!("unexpected header line `{}`", line.trim())),
+        }
+    }
+
+    let Some(format) = format else {
+        return Err((None, "header has no `format` line".to_string()));
+    };
+    Ok((Header { format, elements }, offset))
+}
+
+struct BodyReader<'a> {
+    format: PlyFormat,
+    bytes: &'a [u8],
+    position: usize,
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> BodyReader<'a> {
+    fn new(format: PlyFormat, bytes: &'a [u8]) -> Result<Self, String> {
+        let text = match format {
+            PlyFormat::Ascii => std::str::from_utf8(bytes).map_err(|error| error.to_string())?,
+            _ => "",
+        };
+        Ok(Self {
+            format,
+            bytes,
+            position: 0,
+            tokens: text.split_ascii_whitespace(),
+        })
+    }
+
+    fn read(&mut self, ty: ScalarType) -> Result<f64, String> {
+        if self.format == PlyFormat::Ascii {
+            let token = self.tokens.next().ok_or("unexpected end of data")?;
+            return token
+                .parse()
+                .map_err(|_| format!("invalid number `{}`", token));
+        }
+
+        let size = ty.size();
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + size)
+            .ok_or("unexpected end of data")?;
+        self.position += size;
+        let mut buffer = [0u8; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.format == PlyFormat::BinaryBigEndian {
+            buffer[..size].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = buffer;
+        Ok(match ty {
+            ScalarType::Char => b0 as i8 as f64,
+            ScalarType::UChar => b0 as f64,
+            ScalarType::Short => i16::from_le_bytes([b0, b1]) as f64,
+            ScalarType::UShort => u16::from_le_bytes([b0, b1]) as f64,
+            ScalarType::Int => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            ScalarType::UInt => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            ScalarType::Float => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            ScalarType::Double => f64::from_le_bytes(buffer),
+        })
+    }
+
+    fn read_property(&mut self, ty: PropertyType) -> Result<Vec<f64>, String> {
+        match ty {
+            PropertyType::Scalar(ty) => Ok(vec![self.read(ty)?]),
+            PropertyType::List(count_type, item_type) => {
+                let count = self.read(count_type)? as usize;
+                (0..count).map(|_| self.read(item_type)).collect()
+            }
+        }
+    }
+}
+
+// List values are read as floats, which a cast to `u32` would saturate or
+// truncate into a valid looking index.
+fn vertex_index(value: f64) -> Result<u32, String> {
+    if value < 0.0 || value.fract() != 0.0 || value > u32::MAX as f64 {
+        return Err(format!("face index {} is not a vertex index", value));
+    }
+    Ok(value as u32)
+}
+
+fn read_body(header: &Header, bytes: &[u8]) -> Result<PlyModel, String> {
+    let mut reader = BodyReader::new(header.format, bytes)?;
+    let mut model = PlyModel::default();
+
+    for element in &header.elements {
+        let position = element
+            .property(&["x"])
+            .zip(element.property(&["y"]))
+            .zip(element.property(&["z"]));
+        let normal = element
+            .property(&["nx"])
+            .zip(element.property(&["ny"]))
+            .zip(element.property(&["nz"]));
+        let color = [
+            element.property(&["red", "diffuse_red", "r"]),
+            element.property(&["green", "diffuse_green", "g"]),
+            element.property(&["blue", "diffuse_blue", "b"]),
+        ];
+        let alpha = element.property(&["alpha", "a"]);
+        let face = element.properties.iter().position(|property| {
+            matches!(property.ty, PropertyType::List(..))
+                && (property.name == "vertex_indices" || property.name == "vertex_index")
+        });
+
+        let is_vertex = element.name == "vertex";
+        if is_vertex {
+            if position.is_none() {
+                return Err("vertex element has no x, y and z properties".to_string());
+            }
+            if normal.is_some() {
+                model.normals = Some(Vec::new());
+            }
+            if color.iter().all(Option::is_some) {
+                model.colors = Some(Vec::new());
+            }
+        }
+
+        for _ in 0..element.count {
+            let values = element
+                .properties
+                .iter()
+                .map(|property| reader.read_property(property.ty))
+                .collect::<Result<Vec<_>, _>>()?;
+            let scalar = |(index, _): (usize, ScalarType)| values[index][0] as f32;
+
+            if is_vertex {
+                if let Some(((x, y), z)) = position {
+                    model.positions.push([scalar(x), scalar(y), scalar(z)]);
+                }
+                if let (Some(normals), Some(((x, y), z))) = (&mut model.normals, normal) {
+                    normals.push([scalar(x), scalar(y), scalar(z)]);
+                }
+                if let Some(colors) = &mut model.colors {
+                    let channel = |(index, ty): (usize, ScalarType)| {
+                        (values[index][0] / ty.color_scale()) as f32
+                    };
+                    let [red, green, blue] =
+                        color.map(|channel_property| channel_property.map_or(1.0, channel));
+                    colors.push([red, green, blue, alpha.map_or(1.0, channel)]);
+                }
+            } else if element.name == "face" {
+                if let Some(face) = face {
+                    let indices = values[face]
+                        .iter()
+                        .map(|&value| vertex_index(value))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    for index in 1..indices.len().saturating_sub(1) {
+                        model.indices.extend_from_slice(&[
+                            indices[0],
+                            indices[index],
+                            indices[index + 1],
+                        ]);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(index) = model
+        .indices
+        .iter()
+        .find(|index| **index as usize >= model.positions.len())
+    {
+        return Err(format!(
+            "face index {} is out of range for {} vertices",
+            index,
+            model.positions.len()
+        ));
+    }
+
+    Ok(model)
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Value {
+    UChar(u8),
+    UInt(u32),
+    Float(f32),
+}
+
+struct BodyWriter<'a, W: Write> {
+    writer: &'a mut W,
+    format: PlyFormat,
+}
+
+impl<W: Write> BodyWriter<'_, W> {
+    fn write_row(&mut self, values: &[Value]) -> io::Result<()> {
+        if self.format == PlyFormat::Ascii {
+            let row: Vec<String> = values
+                .iter()
+                .map(|value| match value {
+                    Value::UChar(value) => value.to_string(),
+                    Value::UInt(value) => value.to_string(),
+                    Value::Float(value) => value.to_string(),
+                })
+                .collect();
+            return writeln!(self.writer, "{}", row.join(" "));
+        }
+
+        let big_endian = self.format == PlyFormat::BinaryBigEndian;
+        for value in values {
+            match value {
+                Value::UChar(value) => self.writer.write_all(&[*value])?,
+                Value::UInt(value) if big_endian => self.writer.write_all(&value.to_be_bytes())?,
+                Value::UInt(value) => self.writer.write_all(&value.to_le_bytes())?,
+                Value::Float(value) if big_endian => self.writer.write_all(&value.to_be_bytes())?,
+                Value::Float(value) => self.writer.write_all(&value.to_le_bytes())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source_model() -> PlyModel {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.5, 0.0, -0.25],
+            [1.5, 2.0, 0.125],
+            [0.0, 2.0, 1.0e-3],
+        ];
+        let vertices = positions
+            .iter()
+            .enumerate()
+            .map(|(index, [x, y, z])| Vertex4DColored {
+                position: [*x, *y, *z, 1.0],
+                // Multiples of 1/255 survive the trip through `uchar` channels.
+                color: [index as f32 * 60.0, 255.0, 17.0, 128.0 + index as f32].map(|c| c / 255.0),
+            })
+            .collect();
+        let mesh = MeshData::indexed(
+            vertices,
+            vec![0, 1, 2, 0, 2, 3],
+            PrimitiveTopology::TriangleList,
+        );
+        PlyModel {
+            normals: Some(vec![
+                [0.0, 0.0, 1.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.6, 0.8, 0.0],
+            ]),
+            ..PlyModel::from_colored_mesh(&mesh)
+        }
+    }
+
+    fn assert_round_trip(format: PlyFormat) {
+        let model = source_model();
+        let mut bytes = Vec::new();
+        model.write(&mut bytes, format).unwrap();
+        let parsed = PlyModel::parse(&bytes, "round_trip.ply").unwrap();
+
+        assert_eq!(parsed.positions, model.positions);
+        assert_eq!(parsed.normals, model.normals);
+        assert_eq!(parsed.indices, model.indices);
+        let colors = parsed.colors.unwrap();
+        for (parsed, original) in colors.iter().zip(model.colors.as_ref().unwrap()) {
+            for channel in 0..4 {
+                assert!((parsed[channel] - original[channel]).abs() < 1.0e-6);
+            }
+        }
+        assert_eq!(colors.len(), model.positions.len());
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        assert_round_trip(PlyFormat::Ascii);
+    }
+
+    #[test]
+    fn binary_little_endian_round_trip() {
+        assert_round_trip(PlyFormat::BinaryLittleEndian);
+    }
+
+    #[test]
+    fn binary_big_endian_round_trip() {
+        assert_round_trip(PlyFormat::BinaryBigEndian);
+    }
+
+    #[test]
+    fn oversized_element_count_is_an_error() {
+        let bytes = b"ply\nformat binary_little_endian 1.0\nelement vertex 4000000000\n\
+property float x\nproperty float y\nproperty float z\nproperty float nx\nproperty float ny\n\
+property float nz\nend_header\n";
+        assert!(PlyModel::parse(bytes, "oversized.ply").is_err());
+    }
+
+    #[test]
+    fn malformed_face_index_is_an_error() {
+        let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\n\
+property float y\nproperty float z\nelement face 1\nproperty list uchar float vertex_indices\n\
+end_header\n0 0 0\n1 0 0\n0 1 0\n";
+        for face in ["3 0 1 -1", "3 0 1.5 2", "3 0 1 3"] {
+            let source = format!("{}{}\n", header, face);
+            let error = PlyModel::parse(source.as_bytes(), "face.ply")
+                .expect_err("face index to be rejected");
+            assert_eq!(error.file(), Path::new("face.ply"));
+            assert!(error.message().starts_with("face index"), "{}", error);
+        }
+    }
+}
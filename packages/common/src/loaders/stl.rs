@@ -0,0 +1,320 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use cgmath::{InnerSpace, Vector3};
+use wgpu::PrimitiveTopology;
+
+use crate::{
+    loaders::LoadError,
+    mesh::MeshData,
+    vertices::{Vertex3DNormalUv, VertexPosition},
+};
+
+const HEADER_SIZE: usize = 80;
+const FACET_SIZE: usize = 50;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StlFormat {
+    Ascii,
+    Binary,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StlFacet {
+    pub normal: [f32; 3],
+    pub vertices: [[f32; 3]; 3],
+}
+
+impl StlFacet {
+    // Normals are rebuilt from the winding order, since exporters often write zeroes
+    // or stale values. The stored normal is only kept for degenerate facets.
+    pub fn new(vertices: [[f32; 3]; 3], fallback_normal: [f32; 3]) -> Self {
+        let normal = face_normal(&vertices).unwrap_or(fallback_normal);
+        Self { normal, vertices }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StlModel {
+    pub name: Option<String>,
+    pub facets: Vec<StlFacet>,
+}
+
+impl StlModel {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| LoadError::io(path, error))?;
+        Self::parse(&bytes, path)
+    }
+
+    pub fn parse(bytes: &[u8], file: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let file = file.as_ref();
+        // Binary files may also start with "solid", so the size check goes first.
+        if let Some(count) = binary_facet_count(bytes) {
+            if bytes.len() == HEADER_SIZE + 4 + count * FACET_SIZE {
+                return Ok(parse_binary(bytes, count));
+            }
+        }
+        if bytes.trim_ascii_start().starts_with(b"solid") {
+            let source = std::str::from_utf8(bytes)
+                .map_err(|error| LoadError::new(file, None, error.to_string()))?;
+            return parse_ascii(source, file);
+        }
+        match binary_facet_count(bytes) {
+            Some(count) => Err(LoadError::new(
+                file,
+                None,
+                format!(
+                    "binary STL declares {} facets ({} bytes) but the file is {} bytes",
+                    count,
+                    HEADER_SIZE + 4 + count * FACET_SIZE,
+                    bytes.len()
+                ),
+            )),
+            None => Err(LoadError::new(file, None, "file is too short to be an STL")),
+        }
+    }
+
+    pub fn from_mesh<V: VertexPosition>(name: Option<&str>, mesh: &MeshData<V>) -> Option<Self> {
+        let facets = mesh
+            .triangles()?
+            .into_iter()
+            .map(|triangle| {
+                let vertices = triangle.map(|index| mesh.vertices[index as usize].position());
+                StlFacet::new(vertices, [0.0, 0.0, 0.0])
+            })
+            .collect();
+        Some(Self {
+            name: name.map(String::from),
+            facets,
+        })
+    }
+
+    pub fn to_mesh_data(&self) -> MeshData<Vertex3DNormalUv> {
+        let vertices = self
+            .facets
+            .iter()
+            .flat_map(|facet| {
+                facet.vertices.map(|position| Vertex3DNormalUv {
+                    position,
+                    normal: facet.normal,
+                    uv: [0.0, 0.0],
+                })
+            })
+            .collect();
+        MeshData::new(vertices, PrimitiveTopology::TriangleList)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, format: StlFormat) -> io::Result<()> {
+        match format {
+            StlFormat::Ascii => self.write_ascii(writer),
+            StlFormat::Binary => self.write_binary(writer),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: StlFormat) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, format)?;
+        writer.flush()
+    }
+
+    fn write_ascii<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let name = self.name.as_deref().unwrap_or("");
+        writeln!(writer, "solid {}", name)?;
+        for facet in &self.facets {
+            let [nx, ny, nz] = facet.normal;
+            writeln!(writer, "  facet normal {:e} {:e} {:e}", nx, ny, nz)?;
+            writeln!(writer, "    outer loop")?;
+            for [x, y, z] in facet.vertices {
+                writeln!(writer, "      vertex {:e} {:e} {:e}", x, y, z)?;
+            }
+            writeln!(writer, "    endloop")?;
+            writeln!(writer, "  endfacet")?;
+        }
+        writeln!(writer, "endsolid {}", name)
+    }
+
+    fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut header = [0u8; HEADER_SIZE];
+        // A binary header must not start with "solid" or readers mistake it for ASCII.
+        let name = format!("binary {}", self.name.as_deref().unwrap_or(""));
+        let length = name.len().min(HEADER_SIZE);
+        header[..length].copy_from_slice(&name.as_bytes()[..length]);
+        writer.write_all(&header)?;
+
+        let count = u32::try_from(self.facets.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many facets"))?;
+        writer.write_all(&count.to_le_bytes())?;
+        for facet in &self.facets {
+            for value in facet.normal.iter().chain(facet.vertices.iter().flatten()) {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(&0u16.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+fn face_normal(vertices: &[[f32; 3]; 3]) -> Option<[f32; 3]> {
+    let [a, b, c] = vertices.map(Vector3::from);
+    let normal = (b - a).cross(c - a);
+    if normal.magnitude2() > f32::EPSILON * f32::EPSILON {
+        Some(normal.normalize().into())
+    } else {
+        None
+    }
+}
+
+fn binary_facet_count(bytes: &[u8]) -> Option<usize> {
+    let count = bytes.get(HEADER_SIZE..HEADER_SIZE + 4)?;
+    Some(u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize)
+}
+
+fn parse_binary(bytes: &[u8], count: usize) -> StlModel {
+    let facets = bytes[HEADER_SIZE + 4..]
+        .chunks_exact(FACET_SIZE)
+        .take(count)
+        .map(|facet| {
+            let mut values = facet[..48]
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]));
+            let mut next = || [(); 3].map(|_| values.next().unwrap_or_default());
+            let normal = next();
+            StlFacet::new([next(), next(), next()], normal)
+        })
+        .collect();
+    StlModel { name: None, facets }
+}
+
+fn parse_ascii(source: &str, file: &Path) -> Result<StlModel, LoadError> {
+    let error = |line: usize, message: String| LoadError::new(file, Some(line), message);
+
+    let mut model = StlModel::default();
+    let mut normal = [0.0; 3];
+    let mut vertices: Vec<[f32; 3]> = Vec::with_capacity(3);
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "solid" => {
+                if !arguments.is_empty() {
+                    model.name = Some(arguments.join(" "));
+                }
+            }
+            "facet" => {
+                let values = match arguments.split_first() {
+                    Some((&"normal", values)) => values,
+                    _ => return Err(error(line_number, "expected `facet normal`".to_string())),
+                };
+                normal = parse_vector(values).map_err(|message| error(line_number, message))?;
+                vertices.clear();
+            }
+            "vertex" => {
+                if vertices.len() == 3 {
+                    return Err(error(
+                        line_number,
+                        "facet has more than 3 vertices".to_string(),
+                    ));
+                }
+                vertices
+                    .push(parse_vector(&arguments).map_err(|message| error(line_number, message))?);
+            }
+            "endfacet" => {
+                let facet: [[f32; 3]; 3] = vertices.as_slice().try_into().map_err(|_| {
+                    error(
+                        line_number,
+                        format!("facet needs 3 vertices, found {}", vertices.len()),
+                    )
+                })?;
+                model.facets.push(StlFacet::new(facet, normal));
+            }
+            "outer" | "endloop" | "endsolid" => (),
+            keyword => {
+                return Err(error(
+                    line_number,
+                    format!("unexpected keyword `{}`", keyword),
+                ))
+            }
+        }
+    }
+
+    Ok(model)
+}
+
+fn parse_vector(arguments: &[&str]) -> Result<[f32; 3], String> {
+    if arguments.len() != 3 {
+        return Err(format!("expected 3 numbers, found {}", arguments.len()));
+    }
+    let mut values = [0.0; 3];
+    for (value, argument) in values.iter_mut().zip(arguments) {
+        *value = argument
+            .parse()
+            .map_err(|_| format!("invalid number `{}`", argument))?;
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vertices::primitives;
+
+    fn round_trip(model: &StlModel, format: StlFormat) -> StlModel {
+        let mut bytes = Vec::new();
+        model.write(&mut bytes, format).unwrap();
+        StlModel::parse(&bytes, "round_trip.stl").unwrap()
+    }
+
+    fn source_model() -> (MeshData<Vertex3DNormalUv>, StlModel) {
+        let mesh = primitives::icosphere(1.5, 1);
+        let model = StlModel::from_mesh(Some("sphere"), &mesh).unwrap();
+        (mesh, model)
+    }
+
+    fn assert_matches_mesh(model: &StlModel, mesh: &MeshData<Vertex3DNormalUv>) {
+        let triangles = mesh.triangles().unwrap();
+        assert_eq!(model.facets.len(), triangles.len());
+        for (facet, triangle) in model.facets.iter().zip(&triangles) {
+            let positions = triangle.map(|index| mesh.vertices[index as usize].position);
+            assert_eq!(facet.vertices, positions);
+            assert_eq!(Some(facet.normal), face_normal(&positions));
+        }
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        let (mesh, model) = source_model();
+        let parsed = round_trip(&model, StlFormat::Ascii);
+        assert_eq!(parsed.name.as_deref(), Some("sphere"));
+        assert_matches_mesh(&parsed, &mesh);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let (mesh, model) = source_model();
+        let parsed = round_trip(&model, StlFormat::Binary);
+        assert_eq!(parsed.name, None);
+        assert_matches_mesh(&parsed, &mesh);
+    }
+
+    #[test]
+    fn binary_header_starting_with_solid() {
+        let (mesh, model) = source_model();
+        let mut bytes = Vec::new();
+        model.write(&mut bytes, StlFormat::Binary).unwrap();
+        bytes[..HEADER_SIZE].fill(b' ');
+        bytes[..11].copy_from_slice(b"solid model");
+
+        let parsed = StlModel::parse(&bytes, "solid_header.stl").unwrap();
+        assert_matches_mesh(&parsed, &mesh);
+    }
+}
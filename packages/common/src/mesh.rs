@@ -0,0 +1,247 @@
+use std::{marker::PhantomData, ops::Range};
+
+use cgmath::{InnerSpace, Point3, Vector3};
+use wgpu::{
+    util::DeviceExt, Buffer, BufferUsages, Device, IndexFormat, PrimitiveTopology, RenderPass,
+};
+
+use crate::vertices::{Vertex, VertexPosition};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl BoundingBox {
+    pub fn from_points<I: IntoIterator<Item = [f32; 3]>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first: Point3<f32> = points.next()?.into();
+        let mut bounding_box = Self {
+            min: first,
+            max: first,
+        };
+        for point in points {
+            bounding_box.extend(point.into());
+        }
+        Some(bounding_box)
+    }
+
+    pub fn extend(&mut self, point: Point3<f32>) {
+        self.min = Point3::new(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Point3::new(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        Point3::new(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0,
+        )
+    }
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MeshData<V> {
+    pub vertices: Vec<V>,
+    pub indices: Option<Vec<u32>>,
+    pub topology: PrimitiveTopology,
+}
+
+impl<V> MeshData<V> {
+    pub fn new(vertices: Vec<V>, topology: PrimitiveTopology) -> Self {
+        Self {
+            vertices,
+            indices: None,
+            topology,
+        }
+    }
+
+    pub fn indexed(vertices: Vec<V>, indices: Vec<u32>, topology: PrimitiveTopology) -> Self {
+        Self {
+            vertices,
+            indices: Some(indices),
+            topology,
+        }
+    }
+
+    pub fn map_vertices<W, F: FnMut(&V) -> W>(&self, f: F) -> MeshData<W> {
+        MeshData {
+            vertices: self.vertices.iter().map(f).collect(),
+            indices: self.indices.clone(),
+            topology: self.topology,
+        }
+    }
+
+    pub fn element_count(&self) -> u32 {
+        match &self.indices {
+            Some(indices) => indices.len() as u32,
+            None => self.vertices.len() as u32,
+        }
+    }
+
+    pub fn triangles(&self) -> Option<Vec<[u32; 3]>> {
+        if self.topology != PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let triangles = match &self.indices {
+            Some(indices) => indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+            None => (0..self.vertices.len() as u32 / 3)
+                .map(|triangle| [triangle * 3, triangle * 3 + 1, triangle * 3 + 2])
+                .collect(),
+        };
+        Some(triangles)
+    }
+}
+
+impl<V: VertexPosition> MeshData<V> {
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::from_points(self.vertices.iter().map(VertexPosition::position))
+    }
+}
+
+pub fn compute_vertex_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+        let pa = Vector3::from(positions[a]);
+        // Left unnormalized so larger faces weigh more in the average.
+        let face_normal =
+            (Vector3::from(positions[b]) - pa).cross(Vector3::from(positions[c]) - pa);
+        normals[a] += face_normal;
+        normals[b] += face_normal;
+        normals[c] += face_normal;
+    }
+    normals
+        .into_iter()
+        .map(|normal| {
+            if normal.magnitude2() > f32::EPSILON {
+                normal.normalize().into()
+            } else {
+                [0.0, 1.0, 0.0]
+            }
+        })
+        .collect()
+}
+
+struct IndexBuffer {
+    buffer: Buffer,
+    format: IndexFormat,
+    count: u32,
+}
+
+pub struct Mesh<V> {
+    vertex_buffer: Buffer,
+    index_buffer: Option<IndexBuffer>,
+    vertex_count: u32,
+    topology: PrimitiveTopology,
+    bounding_box: Option<BoundingBox>,
+    _marker: PhantomData<V>,
+}
+
+impl<V: Vertex + VertexPosition> Mesh<V> {
+    pub fn new(device: &Device, label: Option<&str>, data: &MeshData<V>) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: bytemuck::cast_slice(&data.vertices),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+
+        let index_buffer = data.indices.as_ref().map(|indices| {
+            let fits_u16 = indices.iter().all(|index| *index < u16::MAX as u32);
+            let (format, buffer) = if fits_u16 {
+                let indices: Vec<u16> = indices.iter().map(|index| *index as u16).collect();
+                (
+                    IndexFormat::Uint16,
+                    Self::create_index_buffer(device, label, bytemuck::cast_slice(&indices)),
+                )
+            } else {
+                (
+                    IndexFormat::Uint32,
+                    Self::create_index_buffer(device, label, bytemuck::cast_slice(indices)),
+                )
+            };
+            IndexBuffer {
+                buffer,
+                format,
+                count: indices.len() as u32,
+            }
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            vertex_count: data.vertices.len() as u32,
+            topology: data.topology,
+            bounding_box: data.bounding_box(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn create_index_buffer(device: &Device, label: Option<&str>, contents: &[u8]) -> Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+        })
+    }
+}
+
+impl<V> Mesh<V> {
+    pub fn vertex_buffer(&self) -> &Buffer {
+        &self.vertex_buffer
+    }
+    pub fn index_buffer(&self) -> Option<&Buffer> {
+        self.index_buffer
+            .as_ref()
+            .map(|index_buffer| &index_buffer.buffer)
+    }
+    pub fn index_format(&self) -> Option<IndexFormat> {
+        self.index_buffer
+            .as_ref()
+            .map(|index_buffer| index_buffer.format)
+    }
+    pub fn index_count(&self) -> Option<u32> {
+        self.index_buffer
+            .as_ref()
+            .map(|index_buffer| index_buffer.count)
+    }
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+    pub fn topology(&self) -> PrimitiveTopology {
+        self.topology
+    }
+    pub fn bounding_box(&self) -> Option<&BoundingBox> {
+        self.bounding_box.as_ref()
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        self.draw_instanced(render_pass, 0..1);
+    }
+
+    pub fn draw_instanced<'a>(&'a self, render_pass: &mut RenderPass<'a>, instances: Range<u32>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        match &self.index_buffer {
+            Some(index_buffer) => {
+                render_pass.set_index_buffer(index_buffer.buffer.slice(..), index_buffer.format);
+                render_pass.draw_indexed(0..index_buffer.count, 0, instances);
+            }
+            None => render_pass.draw(0..self.vertex_count, instances),
+        }
+    }
+}
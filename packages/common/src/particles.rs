@@ -0,0 +1,566 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Deg, InnerSpace, Matrix4, Rad, Vector3};
+use wgpu::{
+    util::DeviceExt, BindGroup, Buffer, BufferAddress, BufferUsages, CommandEncoder,
+    ComputePipeline, Device, Queue, RenderPass, RenderPipeline, ShaderStages, TextureFormat,
+    VertexBufferLayout,
+};
+
+use crate::{
+    camera::Camera,
+    compute::{workgroup_count, StorageBuffer},
+    projection::Projection,
+    shader_preprocessor::create_library_shader_module,
+};
+
+const PARTICLES_WGSL: &str = include_str!("shaders/particles.wgsl");
+const WORKGROUP_SIZE: u32 = 64;
+// Frames longer than this are simulated as this long, so a stall does not
+// throw every particle across the scene.
+const MAX_TIME_STEP: f32 = 0.1;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct Particle {
+    pub position: [f32; 3],
+    pub age: f32,
+    pub velocity: [f32; 3],
+    // Zero for dead particles.
+    pub lifetime: f32,
+    pub emitter: u32,
+    pub _padding: [u32; 3],
+}
+
+impl Particle {
+    pub const LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: std::mem::size_of::<Self>() as BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Uint32],
+    };
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EmitterShape {
+    Point = 0,
+    Sphere = 1,
+    Cone = 2,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct Emitter {
+    pub position: [f32; 3],
+    pub shape: u32,
+    pub direction: [f32; 3],
+    pub cone_cos: f32,
+    pub start_color: [f32; 4],
+    pub end_color: [f32; 4],
+    pub speed: [f32; 2],
+    pub lifetime: [f32; 2],
+    pub radius: f32,
+    pub start_size: f32,
+    pub end_size: f32,
+    // Particles per second.
+    pub spawn_rate: f32,
+    // Filled in by the `ParticleSystem`.
+    pub first_slot: u32,
+    pub slot_count: u32,
+    pub spawn_offset: u32,
+    pub spawn_count: u32,
+}
+
+impl Emitter {
+    // Emits in every direction from a single point.
+    pub fn point(position: [f32; 3], spawn_rate: f32) -> Self {
+        Self {
+            position,
+            shape: EmitterShape::Point as u32,
+            direction: [0.0, 1.0, 0.0],
+            start_color: [1.0; 4],
+            end_color: [1.0, 1.0, 1.0, 0.0],
+            speed: [1.0, 2.0],
+            lifetime: [1.0, 2.0],
+            start_size: 0.1,
+            end_size: 0.0,
+            spawn_rate,
+            ..Self::zeroed()
+        }
+    }
+
+    // Spawns anywhere inside the sphere, moving outwards.
+    pub fn sphere(position: [f32; 3], radius: f32, spawn_rate: f32) -> Self {
+        Self {
+            shape: EmitterShape::Sphere as u32,
+            radius,
+            ..Self::point(position, spawn_rate)
+        }
+    }
+
+    // Spawns on a disc of `radius` facing `direction` and moves within `angle`
+    // of it.
+    pub fn cone(
+        position: [f32; 3],
+        direction: [f32; 3],
+        angle: Deg<f32>,
+        radius: f32,
+        spawn_rate: f32,
+    ) -> Self {
+        Self {
+            shape: EmitterShape::Cone as u32,
+            direction: normalize(direction),
+            cone_cos: Rad::from(angle).0.cos(),
+            radius,
+            ..Self::point(position, spawn_rate)
+        }
+    }
+
+    pub fn shape(&self) -> EmitterShape {
+        match self.shape {
+            0 => EmitterShape::Point,
+            1 => EmitterShape::Sphere,
+            _ => EmitterShape::Cone,
+        }
+    }
+
+    pub fn set_position(mut self, position: [f32; 3]) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn set_direction(mut self, direction: [f32; 3]) -> Self {
+        self.direction = normalize(direction);
+        self
+    }
+
+    pub fn set_spawn_rate(mut self, spawn_rate: f32) -> Self {
+        self.spawn_rate = spawn_rate.max(0.0);
+        self
+    }
+
+    // Each particle picks a speed and a lifetime between `min` and `max`.
+    pub fn set_speed(mut self, min: f32, max: f32) -> Self {
+        self.speed = [min, max.max(min)];
+        self
+    }
+
+    pub fn set_lifetime(mut self, min: f32, max: f32) -> Self {
+        self.lifetime = [min, max.max(min)];
+        self
+    }
+
+    // Interpolated over each particle's lifetime.
+    pub fn set_colors(mut self, start_color: [f32; 4], end_color: [f32; 4]) -> Self {
+        self.start_color = start_color;
+        self.end_color = end_color;
+        self
+    }
+
+    pub fn set_sizes(mut self, start_size: f32, end_size: f32) -> Self {
+        self.start_size = start_size;
+        self.end_size = end_size;
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct SimulationUniforms {
+    gravity: [f32; 3],
+    drag: f32,
+    delta_time: f32,
+    time: f32,
+    seed: u32,
+    emitter_count: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParticleRenderMode {
+    // Camera facing quads sized by the emitter.
+    Billboards,
+    // One pixel each.
+    Points,
+}
+
+// Particles live in a storage buffer that a compute pass updates every frame
+// and the render pipelines read as instances. Every emitter owns a fixed range
+// of slots, sized when it is added from its spawn rate and longest lifetime.
+pub struct ParticleSystem {
+    emitters: Vec<Emitter>,
+    spawn_remainders: Vec<f32>,
+    next_slot: u32,
+    gravity: [f32; 3],
+    drag: f32,
+    time: f32,
+    frame: u32,
+    render_mode: ParticleRenderMode,
+    particles: StorageBuffer<Particle>,
+    simulation_buffer: Buffer,
+    emitter_buffer: Buffer,
+    camera_buffer: Buffer,
+    compute_bind_group: BindGroup,
+    render_bind_group: BindGroup,
+    simulate_pipeline: ComputePipeline,
+    billboard_pipeline: RenderPipeline,
+    point_pipeline: RenderPipeline,
+}
+
+impl ParticleSystem {
+    pub const MAX_EMITTERS: usize = 16;
+
+    pub fn new(
+        device: &Device,
+        capacity: u32,
+        color_format: TextureFormat,
+        depth_format: Option<TextureFormat>,
+    ) -> Self {
+        let particles = StorageBuffer::with_len(
+            device,
+            Some("Particle Buffer"),
+            capacity.max(1),
+            BufferUsages::VERTEX,
+        );
+        let simulation_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Simulation Buffer"),
+            contents: bytemuck::bytes_of(&SimulationUniforms::zeroed()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let emitter_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Emitter Buffer"),
+            contents: bytemuck::cast_slice(&[Emitter::zeroed(); Self::MAX_EMITTERS]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Camera Buffer"),
+            contents: bytemuck::cast_slice(&[[[0.0f32; 4]; 4]; 2]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let compute_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Particle Compute Bind Group Layout"),
+                entries: &[
+                    StorageBuffer::<Particle>::bind_group_layout_entry(
+                        0,
+                        ShaderStages::COMPUTE,
+                        false,
+                    ),
+                    uniform_layout_entry(1, ShaderStages::COMPUTE),
+                    uniform_layout_entry(2, ShaderStages::COMPUTE),
+                ],
+            });
+        let render_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Particle Render Bind Group Layout"),
+                entries: &[
+                    uniform_layout_entry(0, ShaderStages::VERTEX),
+                    uniform_layout_entry(2, ShaderStages::VERTEX),
+                ],
+            });
+        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle Compute Bind Group"),
+            layout: &compute_bind_group_layout,
+            entries: &[
+                particles.bind_group_entry(0),
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: simulation_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: emitter_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle Render Bind Group"),
+            layout: &render_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: emitter_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = create_library_shader_module(
+            device,
+            "Particle Shader",
+            &[("shaders/particles.wgsl", PARTICLES_WGSL)],
+        );
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Particle Compute Pipeline Layout"),
+                bind_group_layouts: &[&compute_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let simulate_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Particle Simulate Pipeline"),
+            layout: Some(&compute_pipeline_layout),
+            module: &shader,
+            entry_point: "cs_simulate",
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Particle Render Pipeline Layout"),
+                bind_group_layouts: &[&render_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let create_render_pipeline = |label, entry_point, topology| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point,
+                    buffers: &[Particle::LAYOUT],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_particle",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::SrcAlpha,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::Zero,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    ..Default::default()
+                },
+                // Tested against the scene but not written, additive blending
+                // does not need sorting.
+                depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+            })
+        };
+        let billboard_pipeline = create_render_pipeline(
+            "Particle Billboard Pipeline",
+            "vs_billboard",
+            wgpu::PrimitiveTopology::TriangleStrip,
+        );
+        let point_pipeline = create_render_pipeline(
+            "Particle Point Pipeline",
+            "vs_point",
+            wgpu::PrimitiveTopology::PointList,
+        );
+
+        Self {
+            emitters: Vec::new(),
+            spawn_remainders: Vec::new(),
+            next_slot: 0,
+            gravity: [0.0, -9.81, 0.0],
+            drag: 0.0,
+            time: 0.0,
+            frame: 0,
+            render_mode: ParticleRenderMode::Billboards,
+            particles,
+            simulation_buffer,
+            emitter_buffer,
+            camera_buffer,
+            compute_bind_group,
+            render_bind_group,
+            simulate_pipeline,
+            billboard_pipeline,
+            point_pipeline,
+        }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.particles.len()
+    }
+    // Slots not yet given to an emitter.
+    pub fn free_slots(&self) -> u32 {
+        self.capacity() - self.next_slot
+    }
+    pub fn particles(&self) -> &StorageBuffer<Particle> {
+        &self.particles
+    }
+    pub fn emitter_count(&self) -> usize {
+        self.emitters.len()
+    }
+    pub fn emitter(&self, index: usize) -> Option<&Emitter> {
+        self.emitters.get(index)
+    }
+    pub fn gravity(&self) -> [f32; 3] {
+        self.gravity
+    }
+    pub fn drag(&self) -> f32 {
+        self.drag
+    }
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+    pub fn render_mode(&self) -> ParticleRenderMode {
+        self.render_mode
+    }
+
+    // Gives the emitter enough slots for `spawn_rate * max lifetime` particles,
+    // or whatever is left.
+    pub fn add_emitter(&mut self, mut emitter: Emitter) -> usize {
+        assert!(
+            self.emitters.len() < Self::MAX_EMITTERS,
+            "particle system to have room for another emitter"
+        );
+        let wanted = (emitter.spawn_rate * emitter.lifetime[1]).ceil() as u32 + 1;
+        let slot_count = wanted.min(self.free_slots());
+        assert!(slot_count > 0, "particle system to have free slots");
+
+        emitter.first_slot = self.next_slot;
+        emitter.slot_count = slot_count;
+        self.next_slot += slot_count;
+        self.emitters.push(emitter);
+        self.spawn_remainders.push(0.0);
+        self.emitters.len() - 1
+    }
+
+    // Keeps the emitter's slots, so raising the spawn rate or lifetime past what
+    // they hold recycles particles before they die.
+    pub fn set_emitter(&mut self, index: usize, emitter: Emitter) {
+        let slots = &self.emitters[index];
+        self.emitters[index] = Emitter {
+            first_slot: slots.first_slot,
+            slot_count: slots.slot_count,
+            spawn_offset: slots.spawn_offset,
+            spawn_count: slots.spawn_count,
+            ..emitter
+        };
+    }
+
+    // In units per second squared.
+    pub fn set_gravity(&mut self, gravity: [f32; 3]) {
+        self.gravity = gravity;
+    }
+
+    // The fraction of velocity lost per second.
+    pub fn set_drag(&mut self, drag: f32) {
+        self.drag = drag.max(0.0);
+    }
+
+    pub fn set_render_mode(&mut self, render_mode: ParticleRenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    pub fn update(&self, queue: &Queue, projection: &Projection) {
+        self.update_matrices(
+            queue,
+            *projection.view_matrix(),
+            *projection.projection_matrix(),
+        );
+    }
+
+    pub fn update_from_camera(&self, queue: &Queue, camera: &Camera, projection: &Projection) {
+        self.update_matrices(queue, camera.view_matrix(), *projection.projection_matrix());
+    }
+
+    pub fn update_matrices(
+        &self,
+        queue: &Queue,
+        view_matrix: Matrix4<f32>,
+        projection_matrix: Matrix4<f32>,
+    ) {
+        let matrices: [[[f32; 4]; 4]; 2] = [view_matrix.into(), projection_matrix.into()];
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&matrices));
+    }
+
+    // Spawns and moves the particles by `delta_time` seconds of the frame clock.
+    pub fn simulate(
+        &mut self,
+        queue: &Queue,
+        command_encoder: &mut CommandEncoder,
+        delta_time: f32,
+    ) {
+        let delta_time = delta_time.clamp(0.0, MAX_TIME_STEP);
+        self.time += delta_time;
+        self.frame = self.frame.wrapping_add(1);
+
+        for (emitter, remainder) in self.emitters.iter_mut().zip(&mut self.spawn_remainders) {
+            let spawns = *remainder + emitter.spawn_rate * delta_time;
+            let spawn_count = (spawns.floor() as u32).min(emitter.slot_count);
+            *remainder = spawns - spawns.floor();
+
+            emitter.spawn_offset =
+                (emitter.spawn_offset + emitter.spawn_count) % emitter.slot_count;
+            emitter.spawn_count = spawn_count;
+        }
+
+        let mut emitters = [Emitter::zeroed(); Self::MAX_EMITTERS];
+        emitters[..self.emitters.len()].copy_from_slice(&self.emitters);
+        queue.write_buffer(&self.emitter_buffer, 0, bytemuck::cast_slice(&emitters));
+        queue.write_buffer(
+            &self.simulation_buffer,
+            0,
+            bytemuck::bytes_of(&SimulationUniforms {
+                gravity: self.gravity,
+                drag: self.drag,
+                delta_time,
+                time: self.time,
+                seed: self.frame.wrapping_mul(0x9e37_79b9),
+                emitter_count: self.emitters.len() as u32,
+            }),
+        );
+
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Particle Simulate Pass"),
+        });
+        compute_pass.set_pipeline(&self.simulate_pipeline);
+        compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+        compute_pass.dispatch_workgroups(workgroup_count(self.capacity(), WORKGROUP_SIZE), 1, 1);
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        let (pipeline, vertices) = match self.render_mode {
+            ParticleRenderMode::Billboards => (&self.billboard_pipeline, 0..4),
+            ParticleRenderMode::Points => (&self.point_pipeline, 0..1),
+        };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.render_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.particles.buffer().slice(..));
+        render_pass.draw(vertices, 0..self.capacity());
+    }
+}
+
+fn uniform_layout_entry(binding: u32, visibility: ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn normalize(direction: [f32; 3]) -> [f32; 3] {
+    let direction = Vector3::from(direction);
+    if direction.magnitude2() > 0.0 {
+        direction.normalize().into()
+    } else {
+        [0.0, 1.0, 0.0]
+    }
+}
@@ -0,0 +1,335 @@
+pub mod ibl;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, BindGroupLayoutEntry, Buffer, BufferAddress,
+    BufferSize, BufferUsages, Device, Queue, ShaderStages,
+};
+
+use crate::{
+    loaders::gltf::{GltfScene, PbrMaterial as GltfMaterial, TextureRef},
+    texture::{mipmap::MipmapGenerator, ColorSpace, Texture, TextureBuilder},
+};
+
+// Includes `common/lighting.wgsl` for `Light` and `lightIncidence`, so it has to
+// go through the `ShaderPreprocessor`.
+pub const PBR_WGSL: &str = include_str!("shaders/pbr.wgsl");
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct PbrMaterialUniforms {
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 3],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub alpha_cutoff: f32,
+    pub flags: u32,
+    pub _padding: [u32; 3],
+}
+
+impl PbrMaterialUniforms {
+    pub const SIZE: BufferAddress = std::mem::size_of::<Self>() as BufferAddress;
+    // Set when a normal map is bound, matches `PBR_NORMAL_TEXTURE` in WGSL.
+    pub const NORMAL_TEXTURE: u32 = 1;
+}
+
+impl Default for PbrMaterialUniforms {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            emissive_factor: [0.0, 0.0, 0.0],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_cutoff: 0.5,
+            flags: 0,
+            _padding: [0; 3],
+        }
+    }
+}
+
+impl From<&GltfMaterial> for PbrMaterialUniforms {
+    fn from(material: &GltfMaterial) -> Self {
+        Self {
+            base_color_factor: material.base_color_factor,
+            emissive_factor: material.emissive_factor,
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            normal_scale: material.normal_scale,
+            occlusion_strength: material.occlusion_strength,
+            alpha_cutoff: material.alpha_cutoff,
+            ..Self::default()
+        }
+    }
+}
+
+// A metallic-roughness material laid out like glTF: the uniforms at binding 0,
+// then albedo, normal, metallic-roughness (blue and green), occlusion (red) and
+// emissive maps, each texture followed by its sampler.
+pub struct PbrMaterial {
+    uniforms: PbrMaterialUniforms,
+    uniform_buffer: Buffer,
+    textures: [Texture; 5],
+    bind_group: BindGroup,
+}
+
+impl PbrMaterial {
+    pub fn uniforms(&self) -> &PbrMaterialUniforms {
+        &self.uniforms
+    }
+    pub fn albedo_texture(&self) -> &Texture {
+        &self.textures[0]
+    }
+    pub fn normal_texture(&self) -> &Texture {
+        &self.textures[1]
+    }
+    pub fn metallic_roughness_texture(&self) -> &Texture {
+        &self.textures[2]
+    }
+    pub fn occlusion_texture(&self) -> &Texture {
+        &self.textures[3]
+    }
+    pub fn emissive_texture(&self) -> &Texture {
+        &self.textures[4]
+    }
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+
+    // The normal map flag is kept, it follows the bound textures.
+    pub fn set_uniforms(&mut self, queue: &Queue, mut uniforms: PbrMaterialUniforms) {
+        uniforms.flags = (uniforms.flags & !PbrMaterialUniforms::NORMAL_TEXTURE)
+            | (self.uniforms.flags & PbrMaterialUniforms::NORMAL_TEXTURE);
+        self.uniforms = uniforms;
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    // Pass these to `RendererBuilder::add_bind_group_layout` and build materials
+    // against the resulting layout.
+    pub fn bind_group_layout_entries(visibility: ShaderStages) -> Vec<BindGroupLayoutEntry> {
+        let mut entries = vec![BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(PbrMaterialUniforms::SIZE),
+            },
+            count: None,
+        }];
+        for index in 0..5 {
+            entries.extend(Texture::bind_group_layout_entries(
+                1 + index * 2,
+                visibility,
+            ));
+        }
+        entries
+    }
+}
+
+#[derive(Default)]
+pub struct PbrMaterialBuilder {
+    label: Option<String>,
+    uniforms: PbrMaterialUniforms,
+    albedo_texture: Option<Texture>,
+    normal_texture: Option<Texture>,
+    metallic_roughness_texture: Option<Texture>,
+    occlusion_texture: Option<Texture>,
+    emissive_texture: Option<Texture>,
+}
+
+impl PbrMaterialBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Uploads the textures the glTF material references. Only the first texture
+    // coordinate set is supported, so maps using another set are skipped. Pass the
+    // same `mipmap_generator` for every material of a scene.
+    pub fn from_gltf(
+        device: &Device,
+        queue: &Queue,
+        mipmap_generator: &mut MipmapGenerator,
+        scene: &GltfScene,
+        material: &GltfMaterial,
+    ) -> Self {
+        let mut load = |texture_ref: &Option<TextureRef>, color_space: ColorSpace| {
+            let texture_ref = texture_ref.as_ref()?;
+            if texture_ref.tex_coord != 0 {
+                log::warn!(
+                    "texture coordinate set {} is not supported, skipping the texture",
+                    texture_ref.tex_coord
+                );
+                return None;
+            }
+            let texture = scene.textures.get(texture_ref.texture)?;
+            let mut builder = TextureBuilder::new()
+                .set_color_space(color_space)
+                .set_address_modes(
+                    texture.address_mode_u,
+                    texture.address_mode_v,
+                    wgpu::AddressMode::Repeat,
+                )
+                .set_filter(texture.mag_filter, texture.min_filter)
+                .set_mipmap_filter(texture.mipmap_filter)
+                .set_mipmap_generator(mipmap_generator);
+            if let Some(name) = &texture.name {
+                builder = builder.set_label(name);
+            }
+            Some(builder.build_from_rgba8(
+                device,
+                queue,
+                texture.width,
+                texture.height,
+                &texture.rgba,
+            ))
+        };
+
+        Self {
+            label: material.name.clone(),
+            uniforms: material.into(),
+            albedo_texture: load(&material.base_color_texture, ColorSpace::Srgb),
+            normal_texture: load(&material.normal_texture, ColorSpace::Linear),
+            metallic_roughness_texture: load(
+                &material.metallic_roughness_texture,
+                ColorSpace::Linear,
+            ),
+            occlusion_texture: load(&material.occlusion_texture, ColorSpace::Linear),
+            emissive_texture: load(&material.emissive_texture, ColorSpace::Srgb),
+        }
+    }
+
+    pub fn set_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn set_uniforms(mut self, uniforms: PbrMaterialUniforms) -> Self {
+        self.uniforms = uniforms;
+        self
+    }
+
+    pub fn set_base_color_factor(mut self, base_color_factor: [f32; 4]) -> Self {
+        self.uniforms.base_color_factor = base_color_factor;
+        self
+    }
+
+    pub fn set_metallic_factor(mut self, metallic_factor: f32) -> Self {
+        self.uniforms.metallic_factor = metallic_factor;
+        self
+    }
+
+    pub fn set_roughness_factor(mut self, roughness_factor: f32) -> Self {
+        self.uniforms.roughness_factor = roughness_factor;
+        self
+    }
+
+    pub fn set_emissive_factor(mut self, emissive_factor: [f32; 3]) -> Self {
+        self.uniforms.emissive_factor = emissive_factor;
+        self
+    }
+
+    pub fn set_normal_scale(mut self, normal_scale: f32) -> Self {
+        self.uniforms.normal_scale = normal_scale;
+        self
+    }
+
+    pub fn set_occlusion_strength(mut self, occlusion_strength: f32) -> Self {
+        self.uniforms.occlusion_strength = occlusion_strength;
+        self
+    }
+
+    pub fn set_alpha_cutoff(mut self, alpha_cutoff: f32) -> Self {
+        self.uniforms.alpha_cutoff = alpha_cutoff;
+        self
+    }
+
+    // Should be sRGB.
+    pub fn set_albedo_texture(mut self, texture: Texture) -> Self {
+        self.albedo_texture = Some(texture);
+        self
+    }
+
+    // Should be linear, tangent space with +Y up as in glTF.
+    pub fn set_normal_texture(mut self, texture: Texture) -> Self {
+        self.normal_texture = Some(texture);
+        self
+    }
+
+    // Should be linear, roughness in green and metalness in blue.
+    pub fn set_metallic_roughness_texture(mut self, texture: Texture) -> Self {
+        self.metallic_roughness_texture = Some(texture);
+        self
+    }
+
+    // Should be linear, occlusion in red.
+    pub fn set_occlusion_texture(mut self, texture: Texture) -> Self {
+        self.occlusion_texture = Some(texture);
+        self
+    }
+
+    // Should be sRGB.
+    pub fn set_emissive_texture(mut self, texture: Texture) -> Self {
+        self.emissive_texture = Some(texture);
+        self
+    }
+
+    // Missing maps are replaced by 1x1 textures that leave the factors unchanged.
+    pub fn build(self, device: &Device, queue: &Queue, layout: &BindGroupLayout) -> PbrMaterial {
+        let mut uniforms = self.uniforms;
+        if self.normal_texture.is_some() {
+            uniforms.flags |= PbrMaterialUniforms::NORMAL_TEXTURE;
+        } else {
+            uniforms.flags &= !PbrMaterialUniforms::NORMAL_TEXTURE;
+        }
+
+        let fallback = |color_space: ColorSpace, pixel: [u8; 4]| {
+            TextureBuilder::new()
+                .set_label("Default Material Texture")
+                .set_color_space(color_space)
+                .set_mipmaps(false)
+                .build_from_rgba8(device, queue, 1, 1, &pixel)
+        };
+        let textures = [
+            self.albedo_texture
+                .unwrap_or_else(|| fallback(ColorSpace::Srgb, [255, 255, 255, 255])),
+            self.normal_texture
+                .unwrap_or_else(|| fallback(ColorSpace::Linear, [128, 128, 255, 255])),
+            self.metallic_roughness_texture
+                .unwrap_or_else(|| fallback(ColorSpace::Linear, [255, 255, 255, 255])),
+            self.occlusion_texture
+                .unwrap_or_else(|| fallback(ColorSpace::Linear, [255, 255, 255, 255])),
+            self.emissive_texture
+                .unwrap_or_else(|| fallback(ColorSpace::Srgb, [255, 255, 255, 255])),
+        ];
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: self.label.as_deref(),
+            contents: bytemuck::bytes_of(&uniforms),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniform_buffer.as_entire_binding(),
+        }];
+        for (index, texture) in textures.iter().enumerate() {
+            entries.extend(texture.bind_group_entries(1 + index as u32 * 2));
+        }
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: self.label.as_deref(),
+            layout,
+            entries: &entries,
+        });
+
+        PbrMaterial {
+            uniforms,
+            uniform_buffer,
+            textures,
+            bind_group,
+        }
+    }
+}
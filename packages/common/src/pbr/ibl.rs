@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    AddressMode, BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, CommandEncoder,
+    Device, FilterMode, PipelineLayout, Queue, RenderPipeline, ShaderModule, ShaderStages,
+    TextureFormat, TextureUsages, TextureView,
+};
+
+use crate::{
+    shader_preprocessor::create_library_shader_module,
+    texture::{Texture, TextureBuilder},
+    uniform_arena::UniformArena,
+};
+
+const IBL_WGSL: &str = include_str!("../shaders/ibl.wgsl");
+
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+const PREFILTERED_MIP_LEVELS: u32 = 5;
+const PREFILTER_SAMPLE_COUNT: u32 = 512;
+const BRDF_LUT_SIZE: u32 = 256;
+const BRDF_LUT_SAMPLE_COUNT: u32 = 1024;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct IblUniforms {
+    face: u32,
+    roughness: f32,
+    sample_count: u32,
+    source_size: f32,
+}
+
+// The textures the split sum approximation needs: a diffuse irradiance cubemap,
+// a specular cubemap prefiltered for increasing roughness along its mip chain and
+// the BRDF integration lookup table. All three are rendered on the GPU from an
+// environment cubemap when created.
+pub struct EnvironmentLighting {
+    irradiance: Texture,
+    prefiltered: Texture,
+    brdf_lut: Texture,
+}
+
+impl EnvironmentLighting {
+    // `environment` has to be a cubemap, ideally an HDR one with a full mip chain
+    // such as the result of `TextureBuilder::build_cubemap_from_equirectangular`.
+    pub fn new(device: &Device, queue: &Queue, environment: &Texture) -> Self {
+        assert_eq!(
+            environment.size().depth_or_array_layers,
+            6,
+            "environment texture to be a cubemap"
+        );
+        let source_size = environment.size().width as f32;
+        let prefiltered_size = PREFILTERED_SIZE.min(environment.size().width).max(1);
+        let prefiltered_mip_levels = PREFILTERED_MIP_LEVELS.min(prefiltered_size.ilog2() + 1);
+
+        let irradiance = create_target(
+            device,
+            "Irradiance Cubemap",
+            IRRADIANCE_SIZE,
+            1,
+            6,
+            TextureFormat::Rgba16Float,
+        );
+        let prefiltered = create_target(
+            device,
+            "Prefiltered Cubemap",
+            prefiltered_size,
+            prefiltered_mip_levels,
+            6,
+            TextureFormat::Rgba16Float,
+        );
+        let brdf_lut = create_target(
+            device,
+            "BRDF Lookup Table",
+            BRDF_LUT_SIZE,
+            1,
+            1,
+            TextureFormat::Rg16Float,
+        );
+
+        let mut generator = Generator::new(device, environment);
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Environment Lighting Encoder"),
+        });
+
+        let mut passes = Vec::new();
+        for face in 0..6 {
+            passes.push(Pass {
+                entry_point: "fs_irradiance",
+                texture: &irradiance,
+                mip_level: 0,
+                layer: face,
+                uniforms: IblUniforms {
+                    face,
+                    roughness: 1.0,
+                    sample_count: 0,
+                    source_size,
+                },
+            });
+        }
+        for mip_level in 0..prefiltered_mip_levels {
+            let roughness = mip_level as f32 / (prefiltered_mip_levels - 1).max(1) as f32;
+            for face in 0..6 {
+                passes.push(Pass {
+                    entry_point: "fs_prefilter",
+                    texture: &prefiltered,
+                    mip_level,
+                    layer: face,
+                    uniforms: IblUniforms {
+                        face,
+                        roughness,
+                        sample_count: PREFILTER_SAMPLE_COUNT,
+                        source_size,
+                    },
+                });
+            }
+        }
+        passes.push(Pass {
+            entry_point: "fs_brdf_lut",
+            texture: &brdf_lut,
+            mip_level: 0,
+            layer: 0,
+            uniforms: IblUniforms {
+                face: 0,
+                roughness: 0.0,
+                sample_count: BRDF_LUT_SAMPLE_COUNT,
+                source_size,
+            },
+        });
+        generator.run(device, queue, &mut command_encoder, &passes);
+        queue.submit(Some(command_encoder.finish()));
+
+        Self {
+            irradiance: wrap_target(device, irradiance, wgpu::TextureViewDimension::Cube),
+            prefiltered: wrap_target(device, prefiltered, wgpu::TextureViewDimension::Cube),
+            brdf_lut: wrap_target(device, brdf_lut, wgpu::TextureViewDimension::D2),
+        }
+    }
+
+    pub fn irradiance(&self) -> &Texture {
+        &self.irradiance
+    }
+    pub fn prefiltered(&self) -> &Texture {
+        &self.prefiltered
+    }
+    pub fn brdf_lut(&self) -> &Texture {
+        &self.brdf_lut
+    }
+
+    // Irradiance, prefiltered cubemap and lookup table, each followed by its
+    // sampler, starting at `binding`.
+    pub fn bind_group_layout_entries(
+        binding: u32,
+        visibility: ShaderStages,
+    ) -> [BindGroupLayoutEntry; 6] {
+        let [irradiance, irradiance_sampler] =
+            Texture::cube_bind_group_layout_entries(binding, visibility);
+        let [prefiltered, prefiltered_sampler] =
+            Texture::cube_bind_group_layout_entries(binding + 2, visibility);
+        let [brdf_lut, brdf_lut_sampler] =
+            Texture::bind_group_layout_entries(binding + 4, visibility);
+        [
+            irradiance,
+            irradiance_sampler,
+            prefiltered,
+            prefiltered_sampler,
+            brdf_lut,
+            brdf_lut_sampler,
+        ]
+    }
+
+    pub fn bind_group_entries(&self, binding: u32) -> [BindGroupEntry<'_>; 6] {
+        let [irradiance, irradiance_sampler] = self.irradiance.bind_group_entries(binding);
+        let [prefiltered, prefiltered_sampler] = self.prefiltered.bind_group_entries(binding + 2);
+        let [brdf_lut, brdf_lut_sampler] = self.brdf_lut.bind_group_entries(binding + 4);
+        [
+            irradiance,
+            irradiance_sampler,
+            prefiltered,
+            prefiltered_sampler,
+            brdf_lut,
+            brdf_lut_sampler,
+        ]
+    }
+
+    pub fn create_bind_group(
+        &self,
+        device: &Device,
+        layout: &BindGroupLayout,
+        binding: u32,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Environment Lighting Bind Group"),
+            layout,
+            entries: &self.bind_group_entries(binding),
+        })
+    }
+}
+
+struct Pass<'a> {
+    entry_point: &'static str,
+    texture: &'a wgpu::Texture,
+    mip_level: u32,
+    layer: u32,
+    uniforms: IblUniforms,
+}
+
+struct Generator {
+    shader: ShaderModule,
+    pipeline_layout: PipelineLayout,
+    uniform_layout: BindGroupLayout,
+    uniform_arena: UniformArena<IblUniforms>,
+    source_bind_group: BindGroup,
+}
+
+impl Generator {
+    fn new(device: &Device, environment: &Texture) -> Self {
+        let shader = create_library_shader_module(
+            device,
+            "Environment Lighting Shader",
+            &[("shaders/ibl.wgsl", IBL_WGSL)],
+        );
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Environment Lighting Uniform Layout"),
+            entries: &[UniformArena::<IblUniforms>::bind_group_layout_entry(
+                0,
+                ShaderStages::FRAGMENT,
+            )],
+        });
+        let source_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Environment Lighting Source Layout"),
+            entries: &Texture::cube_bind_group_layout_entries(0, ShaderStages::FRAGMENT),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Environment Lighting Pipeline Layout"),
+            bind_group_layouts: &[&uniform_layout, &source_layout],
+            push_constant_ranges: &[],
+        });
+
+        // Trilinear sampling of the source, the prefilter pass picks its mip level
+        // from each sample's solid angle.
+        let source_sampler = TextureBuilder::new()
+            .set_label("Environment Lighting Sampler")
+            .set_address_mode(AddressMode::ClampToEdge)
+            .set_filter(FilterMode::Linear, FilterMode::Linear)
+            .set_mipmap_filter(FilterMode::Linear)
+            .create_sampler(device);
+        let source_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Environment Lighting Source Bind Group"),
+            layout: &source_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(environment.view()),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&source_sampler),
+                },
+            ],
+        });
+
+        Self {
+            shader,
+            pipeline_layout,
+            uniform_layout,
+            uniform_arena: UniformArena::new(device, Some("Environment Lighting Uniforms"), 64),
+            source_bind_group,
+        }
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &Device,
+        entry_point: &str,
+        format: TextureFormat,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point,
+                targets: &[Some(format.into())],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        })
+    }
+
+    fn run(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        command_encoder: &mut CommandEncoder,
+        passes: &[Pass],
+    ) {
+        self.uniform_arena.clear();
+        for pass in passes {
+            self.uniform_arena.push(&pass.uniforms);
+        }
+        if self.uniform_arena.upload(device, queue) || self.uniform_arena.bind_group().is_none() {
+            self.uniform_arena
+                .create_bind_group(device, &self.uniform_layout, 0);
+        }
+
+        let mut pipelines: HashMap<&str, RenderPipeline> = HashMap::new();
+        for (index, pass) in passes.iter().enumerate() {
+            let pipeline = pipelines.entry(pass.entry_point).or_insert_with(|| {
+                self.create_pipeline(device, pass.entry_point, pass.texture.format())
+            });
+
+            let view = pass.texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_mip_level: pass.mip_level,
+                mip_level_count: Some(1),
+                base_array_layer: pass.layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            });
+            let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(pass.entry_point),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(
+                0,
+                self.uniform_arena
+                    .bind_group()
+                    .expect("uniform arena to have a bind group"),
+                &[self.uniform_arena.offset(index as u32)],
+            );
+            render_pass.set_bind_group(1, &self.source_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+fn create_target(
+    device: &Device,
+    label: &str,
+    size: u32,
+    mip_level_count: u32,
+    layers: u32,
+    format: TextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: layers,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn wrap_target(
+    device: &Device,
+    texture: wgpu::Texture,
+    dimension: wgpu::TextureViewDimension,
+) -> Texture {
+    let view: TextureView = texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(dimension),
+        ..Default::default()
+    });
+    let sampler = TextureBuilder::new()
+        .set_address_mode(AddressMode::ClampToEdge)
+        .set_filter(FilterMode::Linear, FilterMode::Linear)
+        .set_mipmap_filter(FilterMode::Linear)
+        .create_sampler(device);
+    Texture::new(texture, view, sampler)
+}
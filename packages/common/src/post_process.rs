@@ -0,0 +1,649 @@
+use std::collections::HashMap;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    BindGroupLayout, CommandEncoder, Device, PipelineLayout, Queue, RenderPipeline, Sampler,
+    ShaderModule, ShaderStages, Texture, TextureFormat, TextureView,
+};
+
+use crate::{shader_preprocessor::create_library_shader_module, uniform_arena::UniformArena};
+
+// Bindings, `vs_fullscreen` and helpers every effect is compiled with, included
+// as `common/post_process.wgsl`. A custom effect's WGSL is expanded after it and
+// defines
+// `@fragment fn fs_main(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32>`,
+// reading `sourceTexture` with `sourceSampler` and its parameters from `post`.
+pub const POST_PROCESS_PRELUDE_WGSL: &str = include_str!("shaders/post_process_prelude.wgsl");
+const POST_PROCESS_WGSL: &str = include_str!("shaders/post_process.wgsl");
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct PostUniforms {
+    pub params: [f32; 4],
+    pub texel_size: [f32; 2],
+    pub time: f32,
+    pub _padding: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TonemapOperator {
+    Reinhard,
+    Aces,
+}
+
+#[derive(Clone, Debug)]
+pub enum PostEffect {
+    Tonemap {
+        operator: TonemapOperator,
+        exposure: f32,
+    },
+    // Only for outputs that are not sRGB, which get gamma from the hardware.
+    Gamma(f32),
+    // Runs on tonemapped colors.
+    Fxaa,
+    // Blurs what is brighter than the threshold at half resolution and adds it
+    // back, before tonemapping.
+    Bloom {
+        threshold: f32,
+        knee: f32,
+        intensity: f32,
+    },
+    Vignette {
+        strength: f32,
+        radius: f32,
+        softness: f32,
+    },
+    Custom {
+        label: String,
+        source: String,
+        params: [f32; 4],
+    },
+}
+
+impl PostEffect {
+    pub fn reinhard(exposure: f32) -> Self {
+        PostEffect::Tonemap {
+            operator: TonemapOperator::Reinhard,
+            exposure,
+        }
+    }
+
+    pub fn aces(exposure: f32) -> Self {
+        PostEffect::Tonemap {
+            operator: TonemapOperator::Aces,
+            exposure,
+        }
+    }
+
+    pub fn bloom() -> Self {
+        PostEffect::Bloom {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.6,
+        }
+    }
+
+    pub fn vignette() -> Self {
+        PostEffect::Vignette {
+            strength: 0.5,
+            radius: 0.5,
+            softness: 0.5,
+        }
+    }
+
+    pub fn custom(label: &str, source: &str) -> Self {
+        PostEffect::Custom {
+            label: label.to_string(),
+            source: source.to_string(),
+            params: [0.0; 4],
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            PostEffect::Tonemap {
+                operator: TonemapOperator::Reinhard,
+                ..
+            } => "Reinhard Tonemap",
+            PostEffect::Tonemap {
+                operator: TonemapOperator::Aces,
+                ..
+            } => "ACES Tonemap",
+            PostEffect::Gamma(_) => "Gamma",
+            PostEffect::Fxaa => "FXAA",
+            PostEffect::Bloom { .. } => "Bloom",
+            PostEffect::Vignette { .. } => "Vignette",
+            PostEffect::Custom { label, .. } => label,
+        }
+    }
+}
+
+struct EffectSlot {
+    effect: PostEffect,
+    enabled: bool,
+    // Compiled from the prelude and the effect's WGSL, custom effects only.
+    module: Option<(u32, ShaderModule)>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum PipelineKey {
+    Builtin(&'static str),
+    Custom(u32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Target {
+    Hdr,
+    PingPong(usize),
+    Bloom(usize),
+    Output,
+}
+
+struct PostPass {
+    key: PipelineKey,
+    source: Target,
+    aux: Option<Target>,
+    target: Target,
+    params: [f32; 4],
+}
+
+struct RenderTarget {
+    texture: Texture,
+    view: TextureView,
+}
+
+// Render the scene into `hdr_view`, then `render` runs the enabled effects in
+// list order, ping-ponging between two HDR targets and writing the last one to
+// the output. With nothing enabled the HDR image is copied as is.
+pub struct PostProcessStack {
+    effects: Vec<EffectSlot>,
+    next_custom_id: u32,
+    output_format: TextureFormat,
+    size: (u32, u32),
+    time: f32,
+    hdr_target: RenderTarget,
+    ping_pong_targets: [RenderTarget; 2],
+    bloom_targets: [RenderTarget; 2],
+    sampler: Sampler,
+    texture_bind_group_layout: BindGroupLayout,
+    uniform_bind_group_layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    builtin_module: ShaderModule,
+    pipelines: HashMap<(PipelineKey, TextureFormat), RenderPipeline>,
+    uniforms: UniformArena<PostUniforms>,
+}
+
+impl PostProcessStack {
+    pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+    pub fn new(device: &Device, width: u32, height: u32, output_format: TextureFormat) -> Self {
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Process Texture Bind Group Layout"),
+                entries: &[
+                    texture_layout_entry(0),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    texture_layout_entry(2),
+                ],
+            });
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Process Uniform Bind Group Layout"),
+                entries: &[UniformArena::<PostUniforms>::bind_group_layout_entry(
+                    0,
+                    ShaderStages::FRAGMENT,
+                )],
+            });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Process Pipeline Layout"),
+            bind_group_layouts: &[&texture_bind_group_layout, &uniform_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let builtin_module = create_library_shader_module(
+            device,
+            "Post Process Shader",
+            &[("shaders/post_process.wgsl", POST_PROCESS_WGSL)],
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Process Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let (width, height) = (width.max(1), height.max(1));
+        Self {
+            effects: Vec::new(),
+            next_custom_id: 0,
+            output_format,
+            size: (width, height),
+            time: 0.0,
+            hdr_target: create_target(device, "HDR Target", width, height),
+            ping_pong_targets: [
+                create_target(device, "Post Process Target A", width, height),
+                create_target(device, "Post Process Target B", width, height),
+            ],
+            bloom_targets: [
+                create_target(device, "Bloom Target A", width / 2, height / 2),
+                create_target(device, "Bloom Target B", width / 2, height / 2),
+            ],
+            sampler,
+            texture_bind_group_layout,
+            uniform_bind_group_layout,
+            pipeline_layout,
+            builtin_module,
+            pipelines: HashMap::new(),
+            uniforms: UniformArena::new(device, Some("Post Process Uniforms"), 8),
+        }
+    }
+
+    // The scene's color attachment, in `HDR_FORMAT`.
+    pub fn hdr_view(&self) -> &TextureView {
+        &self.hdr_target.view
+    }
+    pub fn hdr_texture(&self) -> &Texture {
+        &self.hdr_target.texture
+    }
+    pub fn output_format(&self) -> TextureFormat {
+        self.output_format
+    }
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+    pub fn effect_count(&self) -> usize {
+        self.effects.len()
+    }
+    pub fn effect(&self, index: usize) -> Option<&PostEffect> {
+        self.effects.get(index).map(|slot| &slot.effect)
+    }
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.effects.get(index).is_some_and(|slot| slot.enabled)
+    }
+
+    // Seconds, passed to the effects as `post.time`.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    pub fn add_effect(&mut self, device: &Device, effect: PostEffect) -> usize {
+        let module = self.compile_custom(device, &effect);
+        self.effects.push(EffectSlot {
+            effect,
+            enabled: true,
+            module,
+        });
+        self.effects.len() - 1
+    }
+
+    // Replaces the effect at `index`, keeping whether it is enabled.
+    pub fn set_effect(&mut self, device: &Device, index: usize, effect: PostEffect) {
+        let module = self.compile_custom(device, &effect);
+        let slot = &mut self.effects[index];
+        if let Some((id, _)) = slot.module.take() {
+            self.pipelines
+                .retain(|(key, _), _| *key != PipelineKey::Custom(id));
+        }
+        slot.effect = effect;
+        slot.module = module;
+    }
+
+    pub fn remove_effect(&mut self, index: usize) -> PostEffect {
+        let slot = self.effects.remove(index);
+        if let Some((id, _)) = slot.module {
+            self.pipelines
+                .retain(|(key, _), _| *key != PipelineKey::Custom(id));
+        }
+        slot.effect
+    }
+
+    // Moves the effect at `from` so it ends up at `to`.
+    pub fn move_effect(&mut self, from: usize, to: usize) {
+        let slot = self.effects.remove(from);
+        self.effects.insert(to.min(self.effects.len()), slot);
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        self.effects[index].enabled = enabled;
+    }
+
+    // Returns whether the effect is now enabled.
+    pub fn toggle(&mut self, index: usize) -> bool {
+        let slot = &mut self.effects[index];
+        slot.enabled = !slot.enabled;
+        slot.enabled
+    }
+
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        if self.size == (width, height) {
+            return;
+        }
+        self.size = (width, height);
+        self.hdr_target = create_target(device, "HDR Target", width, height);
+        self.ping_pong_targets = [
+            create_target(device, "Post Process Target A", width, height),
+            create_target(device, "Post Process Target B", width, height),
+        ];
+        self.bloom_targets = [
+            create_target(device, "Bloom Target A", width / 2, height / 2),
+            create_target(device, "Bloom Target B", width / 2, height / 2),
+        ];
+    }
+
+    pub fn render(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        command_encoder: &mut CommandEncoder,
+        output_view: &TextureView,
+    ) {
+        let passes = self.plan_passes();
+
+        self.uniforms.clear();
+        for pass in &passes {
+            let source_size = self.target_size(pass.source);
+            self.uniforms.push(&PostUniforms {
+                params: pass.params,
+                texel_size: [1.0 / source_size.0 as f32, 1.0 / source_size.1 as f32],
+                time: self.time,
+                _padding: 0.0,
+            });
+        }
+        if self.uniforms.upload(device, queue) || self.uniforms.bind_group().is_none() {
+            self.uniforms
+                .create_bind_group(device, &self.uniform_bind_group_layout, 0);
+        }
+
+        for pass in &passes {
+            let format = self.target_format(pass.target);
+            if !self.pipelines.contains_key(&(pass.key, format)) {
+                let pipeline = self.create_pipeline(device, pass.key, format);
+                self.pipelines.insert((pass.key, format), pipeline);
+            }
+        }
+
+        let uniform_bind_group = self
+            .uniforms
+            .bind_group()
+            .expect("post process uniforms to have a bind group");
+        for (index, pass) in passes.iter().enumerate() {
+            let source = self.target_view(pass.source, output_view);
+            let aux = self.target_view(pass.aux.unwrap_or(pass.source), output_view);
+            let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Process Texture Bind Group"),
+                layout: &self.texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(aux),
+                    },
+                ],
+            });
+
+            let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Post Process Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.target_view(pass.target, output_view),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&self.pipelines[&(pass.key, self.target_format(pass.target))]);
+            render_pass.set_bind_group(0, &texture_bind_group, &[]);
+            render_pass.set_bind_group(
+                1,
+                uniform_bind_group,
+                &[self.uniforms.offset(index as u32)],
+            );
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    fn plan_passes(&self) -> Vec<PostPass> {
+        let enabled: Vec<&EffectSlot> = self.effects.iter().filter(|slot| slot.enabled).collect();
+        let mut passes = Vec::new();
+        let mut current = Target::Hdr;
+        if enabled.is_empty() {
+            passes.push(PostPass {
+                key: PipelineKey::Builtin("fs_copy"),
+                source: current,
+                aux: None,
+                target: Target::Output,
+                params: [0.0; 4],
+            });
+            return passes;
+        }
+
+        for (position, slot) in enabled.iter().enumerate() {
+            let target = if position + 1 == enabled.len() {
+                Target::Output
+            } else if current == Target::PingPong(0) {
+                Target::PingPong(1)
+            } else {
+                Target::PingPong(0)
+            };
+            let mut single = |key: PipelineKey, params: [f32; 4]| {
+                passes.push(PostPass {
+                    key,
+                    source: current,
+                    aux: None,
+                    target,
+                    params,
+                })
+            };
+
+            match &slot.effect {
+                PostEffect::Tonemap { operator, exposure } => {
+                    let entry_point = match operator {
+                        TonemapOperator::Reinhard => "fs_tonemap_reinhard",
+                        TonemapOperator::Aces => "fs_tonemap_aces",
+                    };
+                    single(
+                        PipelineKey::Builtin(entry_point),
+                        [*exposure, 0.0, 0.0, 0.0],
+                    );
+                }
+                PostEffect::Gamma(gamma) => {
+                    single(PipelineKey::Builtin("fs_gamma"), [*gamma, 0.0, 0.0, 0.0])
+                }
+                PostEffect::Fxaa => single(PipelineKey::Builtin("fs_fxaa"), [0.0; 4]),
+                PostEffect::Vignette {
+                    strength,
+                    radius,
+                    softness,
+                } => single(
+                    PipelineKey::Builtin("fs_vignette"),
+                    [*strength, *radius, *softness, 0.0],
+                ),
+                PostEffect::Custom { params, .. } => {
+                    let (id, _) = slot
+                        .module
+                        .as_ref()
+                        .expect("custom effect to have a shader module");
+                    single(PipelineKey::Custom(*id), *params);
+                }
+                PostEffect::Bloom {
+                    threshold,
+                    knee,
+                    intensity,
+                } => {
+                    let bloom_passes = [
+                        (
+                            "fs_bloom_threshold",
+                            current,
+                            Target::Bloom(0),
+                            [*threshold, *knee, 0.0, 0.0],
+                        ),
+                        (
+                            "fs_bloom_blur",
+                            Target::Bloom(0),
+                            Target::Bloom(1),
+                            [1.0, 0.0, 0.0, 0.0],
+                        ),
+                        (
+                            "fs_bloom_blur",
+                            Target::Bloom(1),
+                            Target::Bloom(0),
+                            [0.0, 1.0, 0.0, 0.0],
+                        ),
+                    ];
+                    for (entry_point, source, bloom_target, params) in bloom_passes {
+                        passes.push(PostPass {
+                            key: PipelineKey::Builtin(entry_point),
+                            source,
+                            aux: None,
+                            target: bloom_target,
+                            params,
+                        });
+                    }
+                    passes.push(PostPass {
+                        key: PipelineKey::Builtin("fs_bloom_composite"),
+                        source: current,
+                        aux: Some(Target::Bloom(0)),
+                        target,
+                        params: [*intensity, 0.0, 0.0, 0.0],
+                    });
+                }
+            }
+            current = target;
+        }
+        passes
+    }
+
+    fn compile_custom(
+        &mut self,
+        device: &Device,
+        effect: &PostEffect,
+    ) -> Option<(u32, ShaderModule)> {
+        let PostEffect::Custom { label, source, .. } = effect else {
+            return None;
+        };
+        let id = self.next_custom_id;
+        self.next_custom_id += 1;
+        let module = create_library_shader_module(
+            device,
+            label,
+            &[
+                ("common/post_process.wgsl", POST_PROCESS_PRELUDE_WGSL),
+                (label, source),
+            ],
+        );
+        Some((id, module))
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &Device,
+        key: PipelineKey,
+        format: TextureFormat,
+    ) -> RenderPipeline {
+        let (module, entry_point) = match key {
+            PipelineKey::Builtin(entry_point) => (&self.builtin_module, entry_point),
+            PipelineKey::Custom(id) => {
+                let (_, module) = self
+                    .effects
+                    .iter()
+                    .filter_map(|slot| slot.module.as_ref())
+                    .find(|(module_id, _)| *module_id == id)
+                    .expect("post process stack to have the custom effect");
+                (module, "fs_main")
+            }
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        })
+    }
+
+    fn target_view<'a>(&'a self, target: Target, output_view: &'a TextureView) -> &'a TextureView {
+        match target {
+            Target::Hdr => &self.hdr_target.view,
+            Target::PingPong(index) => &self.ping_pong_targets[index].view,
+            Target::Bloom(index) => &self.bloom_targets[index].view,
+            Target::Output => output_view,
+        }
+    }
+
+    fn target_format(&self, target: Target) -> TextureFormat {
+        match target {
+            Target::Output => self.output_format,
+            _ => Self::HDR_FORMAT,
+        }
+    }
+
+    fn target_size(&self, target: Target) -> (u32, u32) {
+        match target {
+            Target::Bloom(_) => ((self.size.0 / 2).max(1), (self.size.1 / 2).max(1)),
+            _ => self.size,
+        }
+    }
+}
+
+fn texture_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+fn create_target(device: &Device, label: &str, width: u32, height: u32) -> RenderTarget {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: PostProcessStack::HDR_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&Default::default());
+    RenderTarget { texture, view }
+}
@@ -1,12 +1,16 @@
 use cgmath::{Matrix4, Point3, Vector3};
 
-use crate::transform::{create_projection, create_transforms, create_view, create_view_projection};
+use crate::transform::{
+    create_normal_matrix, create_projection, create_transforms, create_view,
+    create_view_projection,
+};
 
 pub struct Projection {
     model_translation: Vector3<f32>,
     model_rotation: Vector3<f32>,
     model_scale: Vector3<f32>,
     model_matrix: Matrix4<f32>,
+    normal_matrix: Matrix4<f32>,
 
     camera_position: Point3<f32>,
     look_direction: Point3<f32>,
@@ -50,6 +54,7 @@ impl Projection {
             is_perspective,
         );
         let mvp_matrix = view_projection_matrix * model_matrix;
+        let normal_matrix = create_normal_matrix(model_matrix);
 
         Self {
             aspect_ratio,
@@ -61,6 +66,7 @@ impl Projection {
             model_translation,
             up_direction,
             model_matrix,
+            normal_matrix,
             view_matrix,
             projection_matrix,
             mvp_matrix,
@@ -79,6 +85,9 @@ impl Projection {
     pub fn model_matrix(&self) -> &Matrix4<f32> {
         &self.model_matrix
     }
+    pub fn normal_matrix(&self) -> &Matrix4<f32> {
+        &self.normal_matrix
+    }
 
     pub fn camera_position(&self) -> &Point3<f32> {
         &self.camera_position
@@ -110,6 +119,9 @@ impl Projection {
     pub fn model_matrix_slice(&self) -> &[f32; 16] {
         self.model_matrix.as_ref() as &[f32; 16]
     }
+    pub fn normal_matrix_slice(&self) -> &[f32; 16] {
+        self.normal_matrix.as_ref() as &[f32; 16]
+    }
     pub fn view_matrix_slice(&self) -> &[f32; 16] {
         self.view_matrix.as_ref() as &[f32; 16]
     }
@@ -139,6 +151,7 @@ impl Projection {
         scaling: [f32; 3],
     ) {
         self.model_matrix = create_transforms(translation, rotation, scaling);
+        self.normal_matrix = create_normal_matrix(self.model_matrix);
         self.update_mvp_matrix();
     }
 
@@ -183,6 +196,7 @@ impl Projection {
     }
     pub fn replace_model_matrix(&mut self, model_matrix: Matrix4<f32>) {
         self.model_matrix = model_matrix;
+        self.normal_matrix = create_normal_matrix(model_matrix);
         self.update_mvp_matrix();
     }
     pub fn replace_view_matrix(&mut self, view_matrix: Matrix4<f32>) {
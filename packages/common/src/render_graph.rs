@@ -0,0 +1,685 @@
+use std::collections::HashMap;
+
+use wgpu::{
+    Color, CommandEncoder, Device, LoadOp, RenderPass, Texture, TextureFormat, TextureUsages,
+    TextureView,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PassHandle(usize);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextureSize {
+    Absolute(u32, u32),
+    // A scale of the surface size, recreated on `RenderGraph::resize`.
+    Relative(f32),
+}
+
+impl TextureSize {
+    fn resolve(&self, surface_size: (u32, u32)) -> (u32, u32) {
+        match *self {
+            TextureSize::Absolute(width, height) => (width.max(1), height.max(1)),
+            TextureSize::Relative(scale) => (
+                ((surface_size.0 as f32 * scale).round() as u32).max(1),
+                ((surface_size.1 as f32 * scale).round() as u32).max(1),
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GraphTextureDescriptor {
+    label: String,
+    format: TextureFormat,
+    size: TextureSize,
+    usage: TextureUsages,
+    sample_count: u32,
+}
+
+impl GraphTextureDescriptor {
+    pub fn new(label: &str, format: TextureFormat, size: TextureSize) -> Self {
+        Self {
+            label: label.to_string(),
+            format,
+            size,
+            usage: TextureUsages::empty(),
+            sample_count: 1,
+        }
+    }
+
+    // Usages on top of `RENDER_ATTACHMENT`, and `TEXTURE_BINDING` when a pass
+    // reads the texture, which the graph adds itself.
+    pub fn set_usage(mut self, usage: TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn set_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+}
+
+// Declares what a pass touches. Several writers of one texture run in the order
+// they were added, so an overlay can load what an earlier pass drew. A read sees
+// the latest writer added before the pass, and writers added after it wait for
+// the read. When every writer was added after the pass, it reads what the last
+// of them wrote.
+pub struct GraphPass {
+    label: String,
+    reads: Vec<TextureHandle>,
+    color_attachments: Vec<(TextureHandle, LoadOp<Color>)>,
+    depth_attachment: Option<(TextureHandle, LoadOp<f32>)>,
+}
+
+impl GraphPass {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            reads: Vec::new(),
+            color_attachments: Vec::new(),
+            depth_attachment: None,
+        }
+    }
+
+    pub fn add_read(mut self, texture: TextureHandle) -> Self {
+        self.reads.push(texture);
+        self
+    }
+
+    pub fn add_color_attachment(mut self, texture: TextureHandle, load: LoadOp<Color>) -> Self {
+        self.color_attachments.push((texture, load));
+        self
+    }
+
+    pub fn set_depth_attachment(mut self, texture: TextureHandle, load: LoadOp<f32>) -> Self {
+        self.depth_attachment = Some((texture, load));
+        self
+    }
+
+    fn writes(&self) -> impl Iterator<Item = TextureHandle> + '_ {
+        self.color_attachments
+            .iter()
+            .map(|(texture, _)| *texture)
+            .chain(self.depth_attachment.map(|(texture, _)| texture))
+    }
+
+    fn uses(&self, texture: TextureHandle) -> bool {
+        self.reads.contains(&texture) || self.writes().any(|write| write == texture)
+    }
+}
+
+struct GraphTexture {
+    label: String,
+    // `None` for imported textures, whose views are passed to `execute`.
+    descriptor: Option<GraphTextureDescriptor>,
+    usage: TextureUsages,
+    physical: Option<usize>,
+    // Per position in the pass order, whether a later pass uses the contents.
+    store_after: Vec<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PhysicalKey {
+    format: TextureFormat,
+    size: (u32, u32),
+    usage: TextureUsages,
+    sample_count: u32,
+}
+
+struct PhysicalTexture {
+    key: PhysicalKey,
+    texture: Texture,
+    view: TextureView,
+}
+
+// Views of every texture in the graph for the pass being recorded.
+pub struct PassResources<'a> {
+    views: Vec<Option<&'a TextureView>>,
+}
+
+impl<'a> PassResources<'a> {
+    pub fn view(&self, texture: TextureHandle) -> Option<&'a TextureView> {
+        self.views.get(texture.0).copied().flatten()
+    }
+}
+
+// Textures and passes are declared once, `compile` orders the passes by their
+// dependencies and allocates the transient textures, letting textures with the
+// same descriptor share memory when their passes do not overlap. Each frame
+// `execute` records every pass into one command encoder.
+pub struct RenderGraph {
+    textures: Vec<GraphTexture>,
+    passes: Vec<GraphPass>,
+    order: Vec<usize>,
+    physical_textures: Vec<PhysicalTexture>,
+    surface_size: (u32, u32),
+    compiled: bool,
+}
+
+impl RenderGraph {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            textures: Vec::new(),
+            passes: Vec::new(),
+            order: Vec::new(),
+            physical_textures: Vec::new(),
+            surface_size: (width, height),
+            compiled: false,
+        }
+    }
+
+    pub fn surface_size(&self) -> (u32, u32) {
+        self.surface_size
+    }
+
+    // The passes in execution order, valid after `compile`.
+    pub fn order(&self) -> Vec<PassHandle> {
+        self.order.iter().map(|&index| PassHandle(index)).collect()
+    }
+
+    pub fn pass_label(&self, pass: PassHandle) -> &str {
+        &self.passes[pass.0].label
+    }
+
+    pub fn texture_label(&self, texture: TextureHandle) -> &str {
+        &self.textures[texture.0].label
+    }
+
+    // How many textures back the transient ones after aliasing.
+    pub fn physical_texture_count(&self) -> usize {
+        self.physical_textures.len()
+    }
+
+    // Transient textures only, valid after `compile` until the next `resize`.
+    pub fn texture(&self, texture: TextureHandle) -> Option<&Texture> {
+        let physical = self.textures.get(texture.0)?.physical?;
+        Some(&self.physical_textures[physical].texture)
+    }
+    pub fn view(&self, texture: TextureHandle) -> Option<&TextureView> {
+        let physical = self.textures.get(texture.0)?.physical?;
+        Some(&self.physical_textures[physical].view)
+    }
+
+    pub fn create_texture(&mut self, descriptor: GraphTextureDescriptor) -> TextureHandle {
+        self.compiled = false;
+        self.textures.push(GraphTexture {
+            label: descriptor.label.clone(),
+            descriptor: Some(descriptor),
+            usage: TextureUsages::empty(),
+            physical: None,
+            store_after: Vec::new(),
+        });
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    // For textures owned outside the graph, like the surface or a shadow map.
+    // Their contents are always stored.
+    pub fn import_texture(&mut self, label: &str) -> TextureHandle {
+        self.compiled = false;
+        self.textures.push(GraphTexture {
+            label: label.to_string(),
+            descriptor: None,
+            usage: TextureUsages::empty(),
+            physical: None,
+            store_after: Vec::new(),
+        });
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    pub fn add_pass(&mut self, pass: GraphPass) -> PassHandle {
+        for texture in pass.reads.iter().copied().chain(pass.writes()) {
+            assert!(
+                texture.0 < self.textures.len(),
+                "render graph to have the texture used by pass {}",
+                pass.label
+            );
+        }
+        self.compiled = false;
+        self.passes.push(pass);
+        PassHandle(self.passes.len() - 1)
+    }
+
+    pub fn compile(&mut self, device: &Device) {
+        self.order = self.sort_passes();
+        self.update_textures();
+        self.allocate(device);
+        self.compiled = true;
+    }
+
+    // Recreates the size-relative textures, so bind groups using `view` need to be
+    // created again.
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        self.surface_size = (width, height);
+        if self.compiled {
+            self.allocate(device);
+        }
+    }
+
+    // `imports` gives the view of every imported texture for this frame. `record`
+    // is called once per pass, in order, with the pass already begun on its
+    // attachments.
+    pub fn execute<'a, F>(
+        &'a self,
+        command_encoder: &mut CommandEncoder,
+        imports: &[(TextureHandle, &'a TextureView)],
+        mut record: F,
+    ) where
+        F: for<'p> FnMut(PassHandle, &'p PassResources<'a>, &mut RenderPass<'p>),
+    {
+        assert!(self.compiled, "render graph to be compiled");
+
+        let mut views: Vec<Option<&'a TextureView>> = (0..self.textures.len())
+            .map(|index| self.view(TextureHandle(index)))
+            .collect();
+        for (texture, view) in imports {
+            views[texture.0] = Some(*view);
+        }
+        let resources = PassResources { views };
+
+        for (position, &index) in self.order.iter().enumerate() {
+            let pass = &self.passes[index];
+            let view = |texture: TextureHandle| {
+                resources.view(texture).unwrap_or_else(|| {
+                    panic!(
+                        "render graph to have a view for {}",
+                        self.textures[texture.0].label
+                    )
+                })
+            };
+            let store = |texture: TextureHandle| self.textures[texture.0].store_after[position];
+
+            let color_attachments: Vec<Option<wgpu::RenderPassColorAttachment>> = pass
+                .color_attachments
+                .iter()
+                .map(|&(texture, load)| {
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: view(texture),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load,
+                            store: store(texture),
+                        },
+                    })
+                })
+                .collect();
+            let depth_stencil_attachment = pass.depth_attachment.map(|(texture, load)| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view: view(texture),
+                    depth_ops: Some(wgpu::Operations {
+                        load,
+                        store: store(texture),
+                    }),
+                    stencil_ops: None,
+                }
+            });
+
+            let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&pass.label),
+                color_attachments: &color_attachments,
+                depth_stencil_attachment,
+            });
+            record(PassHandle(index), &resources, &mut render_pass);
+        }
+    }
+
+    fn update_textures(&mut self) {
+        for (index, texture) in self.textures.iter_mut().enumerate() {
+            let handle = TextureHandle(index);
+            let read = self.passes.iter().any(|pass| pass.reads.contains(&handle));
+            texture.usage = match &texture.descriptor {
+                Some(descriptor) if read => {
+                    descriptor.usage
+                        | TextureUsages::RENDER_ATTACHMENT
+                        | TextureUsages::TEXTURE_BINDING
+                }
+                Some(descriptor) => descriptor.usage | TextureUsages::RENDER_ATTACHMENT,
+                None => TextureUsages::empty(),
+            };
+
+            // Transient contents no later pass uses are discarded.
+            texture.store_after = self
+                .order
+                .iter()
+                .enumerate()
+                .map(|(position, _)| {
+                    texture.descriptor.is_none()
+                        || self.order[position + 1..]
+                            .iter()
+                            .any(|&later| self.passes[later].uses(handle))
+                })
+                .collect();
+        }
+    }
+
+    // Kahn's algorithm, picking the earliest added pass whenever several are ready
+    // so independent passes keep their declaration order.
+    fn sort_passes(&self) -> Vec<usize> {
+        let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); self.passes.len()];
+        for (index, pass) in self.passes.iter().enumerate() {
+            for &read in &pass.reads {
+                let writers: Vec<usize> = (0..self.passes.len())
+                    .filter(|&other| {
+                        other != index && self.passes[other].writes().any(|write| write == read)
+                    })
+                    .collect();
+                match writers.iter().rev().find(|&&other| other < index) {
+                    Some(&writer) => {
+                        dependencies[index].push(writer);
+                        for &later in writers.iter().filter(|&&other| other > index) {
+                            dependencies[later].push(index);
+                        }
+                    }
+                    None => dependencies[index].extend(writers.last()),
+                }
+            }
+            for (other, other_pass) in self.passes[..index].iter().enumerate() {
+                if pass
+                    .writes()
+                    .any(|write| other_pass.writes().any(|other_write| other_write == write))
+                {
+                    dependencies[index].push(other);
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.passes.len());
+        let mut done = vec![false; self.passes.len()];
+        while order.len() < self.passes.len() {
+            let next = (0..self.passes.len()).find(|&index| {
+                !done[index] && dependencies[index].iter().all(|&other| done[other])
+            });
+            match next {
+                Some(index) => {
+                    done[index] = true;
+                    order.push(index);
+                }
+                None => {
+                    let stuck = (0..self.passes.len())
+                        .find(|&index| !done[index])
+                        .expect("render graph to have a pass left");
+                    panic!(
+                        "render graph has a dependency cycle through pass {}",
+                        self.passes[stuck].label
+                    );
+                }
+            }
+        }
+        order
+    }
+
+    // The physical textures needed for the current order, each with the
+    // transient textures sharing it.
+    fn assign_slots(&self) -> Vec<(PhysicalKey, Vec<usize>)> {
+        // First and last position in the order each transient texture is used at.
+        let mut lifetimes: Vec<(usize, usize, usize)> = Vec::new();
+        for (index, texture) in self.textures.iter().enumerate() {
+            if texture.descriptor.is_none() {
+                continue;
+            }
+            let positions: Vec<usize> = self
+                .order
+                .iter()
+                .enumerate()
+                .filter(|(_, &pass)| self.passes[pass].uses(TextureHandle(index)))
+                .map(|(position, _)| position)
+                .collect();
+            if let (Some(&first), Some(&last)) = (positions.first(), positions.last()) {
+                lifetimes.push((index, first, last));
+            }
+        }
+        lifetimes.sort_by_key(|&(_, first, _)| first);
+
+        // Each slot remembers the last position it is busy until.
+        let mut slots: Vec<(PhysicalKey, usize, Vec<usize>)> = Vec::new();
+        for (index, first, last) in lifetimes {
+            let texture = &self.textures[index];
+            let descriptor = texture
+                .descriptor
+                .as_ref()
+                .expect("transient texture to have a descriptor");
+            let key = PhysicalKey {
+                format: descriptor.format,
+                size: descriptor.size.resolve(self.surface_size),
+                usage: texture.usage,
+                sample_count: descriptor.sample_count,
+            };
+            let slot = slots
+                .iter_mut()
+                .find(|(slot_key, busy_until, _)| *slot_key == key && *busy_until < first);
+            match slot {
+                Some((_, busy_until, textures)) => {
+                    *busy_until = last;
+                    textures.push(index);
+                }
+                None => slots.push((key, last, vec![index])),
+            }
+        }
+        slots
+            .into_iter()
+            .map(|(key, _, textures)| (key, textures))
+            .collect()
+    }
+
+    fn allocate(&mut self, device: &Device) {
+        let slots = self.assign_slots();
+
+        let mut previous: HashMap<PhysicalKey, Vec<PhysicalTexture>> = HashMap::new();
+        for physical in self.physical_textures.drain(..) {
+            previous
+                .entry(physical.key.clone())
+                .or_default()
+                .push(physical);
+        }
+
+        for texture in &mut self.textures {
+            texture.physical = None;
+        }
+        for (slot, (key, textures)) in slots.into_iter().enumerate() {
+            let label = textures
+                .iter()
+                .map(|&index| self.textures[index].label.as_str())
+                .collect::<Vec<_>>()
+                .join(" / ");
+            let physical = previous
+                .get_mut(&key)
+                .and_then(|physical_textures| physical_textures.pop())
+                .unwrap_or_else(|| create_physical_texture(device, &label, key));
+            self.physical_textures.push(physical);
+            for index in textures {
+                self.textures[index].physical = Some(slot);
+            }
+        }
+    }
+}
+
+fn create_physical_texture(device: &Device, label: &str, key: PhysicalKey) -> PhysicalTexture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: key.size.0,
+            height: key.size.1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: key.sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: key.format,
+        usage: key.usage,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&Default::default());
+    PhysicalTexture { key, texture, view }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color_texture(graph: &mut RenderGraph, label: &str, format: TextureFormat) -> TextureHandle {
+        graph.create_texture(GraphTextureDescriptor::new(
+            label,
+            format,
+            TextureSize::Relative(1.0),
+        ))
+    }
+
+    fn labels(graph: &RenderGraph, order: &[usize]) -> Vec<String> {
+        order
+            .iter()
+            .map(|&index| graph.passes[index].label.clone())
+            .collect()
+    }
+
+    #[test]
+    fn reads_run_after_the_writers_they_depend_on() {
+        let mut graph = RenderGraph::new(64, 64);
+        let surface = graph.import_texture("Surface");
+        let scene = color_texture(&mut graph, "Scene", TextureFormat::Rgba16Float);
+        let blurred = color_texture(&mut graph, "Blurred", TextureFormat::Rgba16Float);
+        graph.add_pass(
+            GraphPass::new("Composite")
+                .add_read(scene)
+                .add_read(blurred)
+                .add_color_attachment(surface, LoadOp::Clear(Color::BLACK)),
+        );
+        graph.add_pass(
+            GraphPass::new("Blur")
+                .add_read(scene)
+                .add_color_attachment(blurred, LoadOp::Clear(Color::BLACK)),
+        );
+        graph.add_pass(
+            GraphPass::new("Scene").add_color_attachment(scene, LoadOp::Clear(Color::BLACK)),
+        );
+
+        let order = graph.sort_passes();
+        assert_eq!(labels(&graph, &order), ["Scene", "Blur", "Composite"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "dependency cycle")]
+    fn cycles_are_reported() {
+        let mut graph = RenderGraph::new(64, 64);
+        let a = color_texture(&mut graph, "A", TextureFormat::Rgba8Unorm);
+        let b = color_texture(&mut graph, "B", TextureFormat::Rgba8Unorm);
+        graph.add_pass(
+            GraphPass::new("First")
+                .add_read(b)
+                .add_color_attachment(a, LoadOp::Clear(Color::BLACK)),
+        );
+        graph.add_pass(
+            GraphPass::new("Second")
+                .add_read(a)
+                .add_color_attachment(b, LoadOp::Clear(Color::BLACK)),
+        );
+
+        graph.sort_passes();
+    }
+
+    #[test]
+    fn ties_keep_the_declaration_order() {
+        let mut graph = RenderGraph::new(64, 64);
+        let surface = graph.import_texture("Surface");
+        let shadow = color_texture(&mut graph, "Shadow", TextureFormat::R32Float);
+        graph.add_pass(
+            GraphPass::new("Scene").add_color_attachment(surface, LoadOp::Clear(Color::BLACK)),
+        );
+        graph.add_pass(GraphPass::new("Overlay").add_color_attachment(surface, LoadOp::Load));
+        graph.add_pass(
+            GraphPass::new("Shadow").add_color_attachment(shadow, LoadOp::Clear(Color::WHITE)),
+        );
+        graph.add_pass(GraphPass::new("Text").add_color_attachment(surface, LoadOp::Load));
+
+        let order = graph.sort_passes();
+        assert_eq!(
+            labels(&graph, &order),
+            ["Scene", "Overlay", "Shadow", "Text"]
+        );
+    }
+
+    #[test]
+    fn reads_see_the_latest_earlier_writer() {
+        let mut graph = RenderGraph::new(64, 64);
+        let target = color_texture(&mut graph, "Target", TextureFormat::Rgba8Unorm);
+        let late = color_texture(&mut graph, "Late", TextureFormat::Rgba8Unorm);
+        let output = graph.import_texture("Output");
+        graph.add_pass(
+            GraphPass::new("Draw").add_color_attachment(target, LoadOp::Clear(Color::BLACK)),
+        );
+        graph.add_pass(
+            GraphPass::new("Sample")
+                .add_read(target)
+                .add_read(late)
+                .add_color_attachment(output, LoadOp::Clear(Color::BLACK)),
+        );
+        // Drawing into the target again must not hold `Sample` back.
+        graph.add_pass(
+            GraphPass::new("Redraw").add_color_attachment(target, LoadOp::Clear(Color::WHITE)),
+        );
+        graph.add_pass(
+            GraphPass::new("Late").add_color_attachment(late, LoadOp::Clear(Color::BLACK)),
+        );
+
+        let order = graph.sort_passes();
+        assert_eq!(labels(&graph, &order), ["Draw", "Late", "Sample", "Redraw"]);
+    }
+
+    #[test]
+    fn transient_textures_share_slots_when_their_lifetimes_do_not_overlap() {
+        let mut graph = RenderGraph::new(64, 64);
+        let surface = graph.import_texture("Surface");
+        let first = color_texture(&mut graph, "First", TextureFormat::Rgba8Unorm);
+        let second = color_texture(&mut graph, "Second", TextureFormat::Rgba8Unorm);
+        let third = color_texture(&mut graph, "Third", TextureFormat::Rgba8Unorm);
+        let depth = color_texture(&mut graph, "Depth", TextureFormat::R32Float);
+        graph.add_pass(
+            GraphPass::new("One")
+                .add_color_attachment(first, LoadOp::Clear(Color::BLACK))
+                .add_color_attachment(depth, LoadOp::Clear(Color::BLACK)),
+        );
+        graph.add_pass(
+            GraphPass::new("Two")
+                .add_read(first)
+                .add_color_attachment(second, LoadOp::Clear(Color::BLACK)),
+        );
+        graph.add_pass(
+            GraphPass::new("Three")
+                .add_read(second)
+                .add_color_attachment(third, LoadOp::Clear(Color::BLACK)),
+        );
+        graph.add_pass(
+            GraphPass::new("Four")
+                .add_read(third)
+                .add_color_attachment(surface, LoadOp::Clear(Color::BLACK)),
+        );
+
+        graph.order = graph.sort_passes();
+        graph.update_textures();
+        let slots: Vec<Vec<usize>> = graph
+            .assign_slots()
+            .into_iter()
+            .map(|(_, textures)| textures)
+            .collect();
+        assert_eq!(
+            slots,
+            [vec![first.0, third.0], vec![depth.0], vec![second.0]]
+        );
+
+        // Only what a later pass reads is stored, imports always are.
+        assert_eq!(
+            graph.textures[first.0].store_after,
+            [true, false, false, false]
+        );
+        assert_eq!(graph.textures[depth.0].store_after, [false; 4]);
+        assert_eq!(graph.textures[surface.0].store_after, [true; 4]);
+        assert!(graph.textures[first.0]
+            .usage
+            .contains(TextureUsages::TEXTURE_BINDING));
+        assert!(!graph.textures[depth.0]
+            .usage
+            .contains(TextureUsages::TEXTURE_BINDING));
+    }
+}
@@ -1,11 +1,24 @@
+use std::path::Path;
+
+use bytemuck::Pod;
+use naga::valid::Capabilities;
 use wgpu::{
-    Adapter, Backends, BindGroupLayout, BindGroupLayoutEntry, DepthStencilState, Device,
-    DeviceDescriptor, IndexFormat, Instance, InstanceDescriptor, PipelineLayout, PrimitiveState,
-    PrimitiveTopology, Queue, RenderPipeline, ShaderModule, Surface, SurfaceConfiguration,
-    TextureUsages, VertexBufferLayout,
+    Adapter, Backends, BindGroup, BindGroupLayout, BindGroupLayoutEntry, Buffer, CommandEncoder,
+    ComputePipeline, DepthStencilState, Device, DeviceDescriptor, Features, IndexFormat, Instance,
+    InstanceDescriptor, PipelineLayout, PrimitiveState, PrimitiveTopology, Queue, RenderPipeline,
+    ShaderModule, ShaderStages, Surface, SurfaceConfiguration, TextureFormat, TextureUsages,
+    VertexBufferLayout,
 };
 use winit::window::Window;
 
+use crate::{
+    compute,
+    hdr::HdrPipeline,
+    hot_reload::{capture_validation_error, compile_shader, ShaderWatcher},
+    shader_preprocessor::ShaderPreprocessor,
+    shader_reflection::{device_capabilities, ShaderReflection},
+};
+
 pub struct Renderer<'a> {
     window: Option<Window>,
     instance: Option<Instance>,
@@ -17,8 +30,20 @@ pub struct Renderer<'a> {
     shader: Option<ShaderModule>,
     pipeline_layout: Option<PipelineLayout>,
     render_pipeline: Option<RenderPipeline>,
+    depth_only_pipeline: Option<RenderPipeline>,
+    hdr_pipeline: Option<HdrPipeline>,
     vertex_buffers_layout: Vec<VertexBufferLayout<'a>>,
     bind_group_layouts: Vec<BindGroupLayout>,
+    compute_shader: Option<ShaderModule>,
+    compute_bind_group_layouts: Vec<BindGroupLayout>,
+    compute_pipelines: Vec<ComputePipeline>,
+    shader_watcher: Option<ShaderWatcher>,
+    shader_preprocessor: ShaderPreprocessor,
+    shader_source: Option<WatchedSource>,
+    compute_shader_source: Option<WatchedSource>,
+    render_pipeline_settings: Option<RenderPipelineSettings>,
+    depth_only_pipeline_settings: Option<DepthOnlyPipelineSettings>,
+    compute_pipeline_settings: Vec<ComputePipelineSettings>,
 }
 
 impl<'a> Renderer<'a> {
@@ -92,6 +117,37 @@ impl<'a> Renderer<'a> {
         self.render_pipeline.as_mut()
     }
 
+    pub fn depth_only_pipeline(&self) -> Option<&RenderPipeline> {
+        self.depth_only_pipeline.as_ref()
+    }
+    pub fn mut_depth_only_pipeline(&mut self) -> Option<&mut RenderPipeline> {
+        self.depth_only_pipeline.as_mut()
+    }
+
+    pub fn hdr_pipeline(&self) -> Option<&HdrPipeline> {
+        self.hdr_pipeline.as_ref()
+    }
+    pub fn mut_hdr_pipeline(&mut self) -> Option<&mut HdrPipeline> {
+        self.hdr_pipeline.as_mut()
+    }
+
+    // Reconfigures the surface and resizes the HDR target along with it.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let device = self.device.as_ref().expect("renderer to have a device");
+        let surface = self.surface.as_ref().expect("renderer to have a surface");
+        let surface_configuration = self
+            .surface_configuration
+            .as_mut()
+            .expect("renderer to have a surface configuration");
+
+        surface_configuration.width = width;
+        surface_configuration.height = height;
+        surface.configure(device, surface_configuration);
+        if let Some(hdr_pipeline) = self.hdr_pipeline.as_mut() {
+            hdr_pipeline.resize(device, width, height);
+        }
+    }
+
     pub fn vertex_buffers_layout(&self) -> &Vec<VertexBufferLayout<'a>> {
         self.vertex_buffers_layout.as_ref() as &Vec<VertexBufferLayout<'a>>
     }
@@ -105,6 +161,243 @@ impl<'a> Renderer<'a> {
     pub fn mut_bind_group_layouts(&mut self) -> &mut Vec<BindGroupLayout> {
         self.bind_group_layouts.as_mut()
     }
+
+    pub fn compute_shader(&self) -> Option<&ShaderModule> {
+        self.compute_shader.as_ref()
+    }
+    pub fn mut_compute_shader(&mut self) -> Option<&mut ShaderModule> {
+        self.compute_shader.as_mut()
+    }
+
+    pub fn compute_bind_group_layouts(&self) -> &Vec<BindGroupLayout> {
+        self.compute_bind_group_layouts.as_ref()
+    }
+    pub fn mut_compute_bind_group_layouts(&mut self) -> &mut Vec<BindGroupLayout> {
+        self.compute_bind_group_layouts.as_mut()
+    }
+
+    pub fn compute_pipelines(&self) -> &Vec<ComputePipeline> {
+        self.compute_pipelines.as_ref()
+    }
+    pub fn mut_compute_pipelines(&mut self) -> &mut Vec<ComputePipeline> {
+        self.compute_pipelines.as_mut()
+    }
+
+    pub fn shader_watcher(&self) -> Option<&ShaderWatcher> {
+        self.shader_watcher.as_ref()
+    }
+    pub fn mut_shader_watcher(&mut self) -> Option<&mut ShaderWatcher> {
+        self.shader_watcher.as_mut()
+    }
+
+    // Recompiles the shaders loaded from a path whose files changed, and rebuilds
+    // the pipelines made from them. A shader or pipeline that fails to compile is
+    // logged and the previous one stays in use. Returns whether anything was
+    // replaced, call it once per frame.
+    pub fn reload_shaders(&mut self) -> bool {
+        let Some(shader_watcher) = self.shader_watcher.as_mut() else {
+            return false;
+        };
+        let changed = shader_watcher.poll();
+
+        let mut reloaded = false;
+        if self
+            .shader_source
+            .as_ref()
+            .is_some_and(|source| changed.contains(&source.index))
+        {
+            reloaded |= self.reload_shader();
+        }
+        if self
+            .compute_shader_source
+            .as_ref()
+            .is_some_and(|source| changed.contains(&source.index))
+        {
+            reloaded |= self.reload_compute_shader();
+        }
+        reloaded
+    }
+
+    fn reload_shader(&mut self) -> bool {
+        let device = self.device.as_ref().expect("renderer to have a device");
+        let shader_watcher = self
+            .shader_watcher
+            .as_mut()
+            .expect("renderer to have a shader watcher");
+        let source = self
+            .shader_source
+            .as_ref()
+            .expect("renderer to have a shader source");
+        let path = shader_watcher.path(source.index).display().to_string();
+
+        let loaded = load_watched_shader(
+            &self.shader_preprocessor,
+            shader_capabilities(self.adapter.as_ref(), device),
+            shader_watcher,
+            source.index,
+            source.label.as_deref(),
+        );
+        let result = loaded
+            .and_then(|(wgsl, _)| compile_shader(device, source.label.as_deref(), &wgsl))
+            .and_then(|shader| {
+                capture_validation_error(device, || {
+                    let render_pipeline = self.render_pipeline_settings.as_ref().map(|settings| {
+                        build_render_pipeline(
+                            device,
+                            &shader,
+                            self.pipeline_layout
+                                .as_ref()
+                                .expect("renderer to have a pipeline layout"),
+                            &self.vertex_buffers_layout,
+                            settings,
+                        )
+                    });
+                    let depth_only_pipeline =
+                        self.depth_only_pipeline_settings.as_ref().map(|settings| {
+                            build_depth_only_pipeline(
+                                device,
+                                &shader,
+                                &self.bind_group_layouts,
+                                &self.vertex_buffers_layout,
+                                settings,
+                            )
+                        });
+                    (shader, render_pipeline, depth_only_pipeline)
+                })
+            });
+
+        match result {
+            Ok((shader, render_pipeline, depth_only_pipeline)) => {
+                self.shader = Some(shader);
+                if render_pipeline.is_some() {
+                    self.render_pipeline = render_pipeline;
+                }
+                if depth_only_pipeline.is_some() {
+                    self.depth_only_pipeline = depth_only_pipeline;
+                }
+                log::info!("reloaded {}", path);
+                true
+            }
+            Err(error) => {
+                log::error!(
+                    "failed to reload {}, keeping the previous shader: {}",
+                    path,
+                    error
+                );
+                false
+            }
+        }
+    }
+
+    fn reload_compute_shader(&mut self) -> bool {
+        let device = self.device.as_ref().expect("renderer to have a device");
+        let shader_watcher = self
+            .shader_watcher
+            .as_mut()
+            .expect("renderer to have a shader watcher");
+        let source = self
+            .compute_shader_source
+            .as_ref()
+            .expect("renderer to have a compute shader source");
+        let path = shader_watcher.path(source.index).display().to_string();
+
+        let loaded = load_watched_shader(
+            &self.shader_preprocessor,
+            shader_capabilities(self.adapter.as_ref(), device),
+            shader_watcher,
+            source.index,
+            source.label.as_deref(),
+        );
+        let result = loaded
+            .and_then(|(wgsl, _)| compile_shader(device, source.label.as_deref(), &wgsl))
+            .and_then(|shader| {
+                capture_validation_error(device, || {
+                    let compute_pipelines: Vec<ComputePipeline> = self
+                        .compute_pipeline_settings
+                        .iter()
+                        .map(|settings| {
+                            build_compute_pipeline(
+                                device,
+                                &shader,
+                                &self.compute_bind_group_layouts,
+                                settings,
+                            )
+                        })
+                        .collect();
+                    (shader, compute_pipelines)
+                })
+            });
+
+        match result {
+            Ok((shader, compute_pipelines)) => {
+                self.compute_shader = Some(shader);
+                self.compute_pipelines = compute_pipelines;
+                log::info!("reloaded {}", path);
+                true
+            }
+            Err(error) => {
+                log::error!(
+                    "failed to reload {}, keeping the previous shader: {}",
+                    path,
+                    error
+                );
+                false
+            }
+        }
+    }
+
+    // Records one dispatch of the compute pipeline at `pipeline_index`, with
+    // `bind_groups` set from group 0 on.
+    pub fn encode_dispatch(
+        &self,
+        command_encoder: &mut CommandEncoder,
+        pipeline_index: usize,
+        bind_groups: &[&BindGroup],
+        workgroups: [u32; 3],
+    ) {
+        let compute_pipeline = self
+            .compute_pipelines
+            .get(pipeline_index)
+            .expect("renderer to have the compute pipeline");
+
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Compute Pass"),
+        });
+        compute_pass.set_pipeline(compute_pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            compute_pass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        compute_pass.dispatch_workgroups(workgroups[0], workgroups[1], workgroups[2]);
+    }
+
+    // Like `encode_dispatch`, submitted on its own.
+    pub fn dispatch(
+        &self,
+        pipeline_index: usize,
+        bind_groups: &[&BindGroup],
+        workgroups: [u32; 3],
+    ) {
+        let device = self.device.as_ref().expect("renderer to have a device");
+        let queue = self.queue.as_ref().expect("renderer to have a queue");
+
+        let mut command_encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.encode_dispatch(
+            &mut command_encoder,
+            pipeline_index,
+            bind_groups,
+            workgroups,
+        );
+        queue.submit(Some(command_encoder.finish()));
+    }
+
+    // Blocks until the GPU is done with `buffer` and returns its first `len`
+    // elements. The buffer needs `COPY_SRC` usage.
+    pub fn read_buffer<T: Pod>(&self, buffer: &Buffer, len: u32) -> Vec<T> {
+        let device = self.device.as_ref().expect("renderer to have a device");
+        let queue = self.queue.as_ref().expect("renderer to have a queue");
+        compute::read_buffer(device, queue, buffer, len)
+    }
 }
 
 pub struct RendererBuilder<'a> {
@@ -118,16 +411,45 @@ pub struct RendererBuilder<'a> {
     shader: Option<ShaderModule>,
     pipeline_layout: Option<PipelineLayout>,
     render_pipeline: Option<RenderPipeline>,
+    depth_only_pipeline: Option<RenderPipeline>,
+    hdr_pipeline: Option<HdrPipeline>,
     primitive_state: Option<PrimitiveState>,
     vertex_buffers_layout: Vec<VertexBufferLayout<'a>>,
     bind_group_layouts: Vec<BindGroupLayout>,
     depth_stencil_state: Option<DepthStencilState>,
+    color_target_format: Option<TextureFormat>,
+    features: Features,
+    backends: Backends,
+    force_fallback_adapter: bool,
+    compute_shader: Option<ShaderModule>,
+    compute_bind_group_layouts: Vec<BindGroupLayout>,
+    compute_pipelines: Vec<ComputePipeline>,
+    shader_watcher: Option<ShaderWatcher>,
+    shader_preprocessor: ShaderPreprocessor,
+    shader_source: Option<WatchedSource>,
+    compute_shader_source: Option<WatchedSource>,
+    render_pipeline_settings: Option<RenderPipelineSettings>,
+    depth_only_pipeline_settings: Option<DepthOnlyPipelineSettings>,
+    compute_pipeline_settings: Vec<ComputePipelineSettings>,
+    shader_reflection: Option<ShaderReflection>,
+    compute_shader_reflection: Option<ShaderReflection>,
+    bind_group_layout_entries: Vec<Vec<BindGroupLayoutEntry>>,
+    compute_bind_group_layout_entries: Vec<Vec<BindGroupLayoutEntry>>,
 }
 
 impl<'a> RendererBuilder<'a> {
     pub fn new(window: Window) -> Self {
         Self {
             window: Some(window),
+            ..Self::headless()
+        }
+    }
+
+    // Without a window or surface, for compute work and offscreen rendering.
+    // Skip `create_surface` and `create_surface_configuration`.
+    pub fn headless() -> Self {
+        Self {
+            window: None,
             instance: None,
             surface: None,
             adapter: None,
@@ -137,16 +459,48 @@ impl<'a> RendererBuilder<'a> {
             shader: None,
             pipeline_layout: None,
             render_pipeline: None,
+            depth_only_pipeline: None,
+            hdr_pipeline: None,
             primitive_state: None,
             vertex_buffers_layout: Vec::new(),
             bind_group_layouts: Vec::new(),
             depth_stencil_state: None,
+            color_target_format: None,
+            features: Features::empty(),
+            backends: Backends::VULKAN,
+            force_fallback_adapter: false,
+            compute_shader: None,
+            compute_bind_group_layouts: Vec::new(),
+            compute_pipelines: Vec::new(),
+            shader_watcher: None,
+            shader_preprocessor: ShaderPreprocessor::new(),
+            shader_source: None,
+            compute_shader_source: None,
+            render_pipeline_settings: None,
+            depth_only_pipeline_settings: None,
+            compute_pipeline_settings: Vec::new(),
+            shader_reflection: None,
+            compute_shader_reflection: None,
+            bind_group_layout_entries: Vec::new(),
+            compute_bind_group_layout_entries: Vec::new(),
         }
     }
 
+    // Call before `create_instance`.
+    pub fn set_backends(mut self, backends: Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    // Asks for a software adapter, which makes headless runs work without a GPU.
+    pub fn set_force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
     pub fn create_instance(mut self) -> Self {
         self.instance = Some(Instance::new(InstanceDescriptor {
-            backends: Backends::VULKAN,
+            backends: self.backends,
             dx12_shader_compiler: Default::default(),
         }));
         self
@@ -169,38 +523,55 @@ impl<'a> RendererBuilder<'a> {
             .instance
             .as_ref()
             .expect("renderer to have an instance");
-        let surface = self.surface.as_ref().expect("renderer to have a surface");
         self.adapter = Some(pollster::block_on(Self::request_adapter(
-            &instance, &surface,
+            instance,
+            self.surface.as_ref(),
+            self.force_fallback_adapter,
         )));
         self
     }
 
-    async fn request_adapter(instance: &Instance, surface: &Surface) -> Adapter {
+    async fn request_adapter(
+        instance: &Instance,
+        surface: Option<&Surface>,
+        force_fallback_adapter: bool,
+    ) -> Adapter {
         instance
             .request_adapter(&wgpu::RequestAdapterOptionsBase {
                 power_preference: wgpu::PowerPreference::None,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
+                force_fallback_adapter,
+                compatible_surface: surface,
             })
             .await
             .expect("instance to have a compatible adapter")
     }
 
+    // Features the adapter lacks are left out, so check `device().features()`
+    // before relying on one.
+    pub fn set_features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
     pub fn get_device(mut self, label: Option<&str>) -> Self {
         let adapter = self.adapter.as_ref().expect("renderer to have an adapter");
-        let (device, queue) = pollster::block_on(Self::request_device(&adapter, label));
+        let features = self.features & adapter.features();
+        let (device, queue) = pollster::block_on(Self::request_device(adapter, label, features));
         self.device = Some(device);
         self.queue = Some(queue);
         self
     }
 
-    async fn request_device(adapter: &Adapter, label: Option<&str>) -> (Device, Queue) {
+    async fn request_device(
+        adapter: &Adapter,
+        label: Option<&str>,
+        features: Features,
+    ) -> (Device, Queue) {
         adapter
             .request_device(
                 &DeviceDescriptor {
                     label,
-                    features: Default::default(),
+                    features,
                     limits: Default::default(),
                 },
                 None,
@@ -215,7 +586,7 @@ impl<'a> RendererBuilder<'a> {
         let adapter = self.adapter.as_ref().expect("renderer to have an adapter");
 
         let size = window.inner_size();
-        let surface_capabilities = surface.get_capabilities(&adapter);
+        let surface_capabilities = surface.get_capabilities(adapter);
         let format = surface_capabilities
             .formats
             .iter()
@@ -235,15 +606,56 @@ impl<'a> RendererBuilder<'a> {
         self
     }
 
-    pub fn create_shader_module(mut self, label: Option<&str>, shader_path: &str) -> Self {
+    // Includes, defines and `#ifdef` blocks are expanded by this preprocessor
+    // before the shaders are parsed. Set it before creating them.
+    pub fn set_shader_preprocessor(mut self, shader_preprocessor: ShaderPreprocessor) -> Self {
+        self.shader_preprocessor = shader_preprocessor;
+        self
+    }
+
+    // The shader is preprocessed, then parsed and validated with naga, so errors
+    // point at the line in the WGSL file it came from, and the pipelines made
+    // from it are checked against the vertex buffer and bind group layouts.
+    pub fn create_shader_module(self, label: Option<&str>, shader_path: &str) -> Self {
+        let shader = preprocess_shader(
+            &self.shader_preprocessor,
+            shader_capabilities(
+                self.adapter.as_ref(),
+                self.device.as_ref().expect("renderer to have a device"),
+            ),
+            label,
+            &[(label.unwrap_or("shader"), shader_path)],
+        )
+        .unwrap_or_else(|error| panic!("shader to be valid WGSL\n{}", error));
+        self.set_shader(label, shader)
+    }
+
+    fn set_shader(mut self, label: Option<&str>, shader: (String, ShaderReflection)) -> Self {
+        let (source, shader_reflection) = shader;
+        self.shader_reflection = Some(shader_reflection);
         let device = self.device.as_ref().expect("renderer to have a device");
         self.shader = Some(device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label,
-            source: wgpu::ShaderSource::Wgsl(shader_path.into()),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
         }));
         self
     }
 
+    // Loads the shader from `path` and watches the file and its includes, so
+    // `Renderer::reload_shaders` picks up edits without a rebuild.
+    pub fn create_shader_module_from_path<P: AsRef<Path>>(
+        mut self,
+        label: Option<&str>,
+        path: P,
+    ) -> Self {
+        let (index, shader) = self.watch_shader(label, path);
+        self.shader_source = Some(WatchedSource {
+            label: label.map(String::from),
+            index,
+        });
+        self.set_shader(label, shader)
+    }
+
     pub fn add_vertex_buffer_layout(mut self, buffer: VertexBufferLayout<'a>) -> Self {
         self.vertex_buffers_layout.push(buffer);
         self
@@ -258,6 +670,27 @@ impl<'a> RendererBuilder<'a> {
         let bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label, entries });
         self.bind_group_layouts.push(bind_group_layout);
+        self.bind_group_layout_entries.push(entries.to_vec());
+        self
+    }
+
+    // Adds a layout, generated from the shader, for each group `vs_main` and
+    // `fs_main` use past the layouts added so far. Hand written layouts can come
+    // first for groups that need dynamic offsets or unfilterable textures.
+    pub fn add_bind_group_layouts_from_shader(mut self, label: Option<&str>) -> Self {
+        let shader_reflection = self
+            .shader_reflection
+            .as_ref()
+            .expect("renderer to have a shader module");
+        let entry_points = ["vs_main", "fs_main"];
+        let groups: Vec<Vec<BindGroupLayoutEntry>> = (self.bind_group_layouts.len() as u32
+            ..shader_reflection.bind_group_count(&entry_points))
+            .map(|group| shader_reflection.bind_group_layout_entries(group, &entry_points))
+            .collect();
+
+        for entries in groups {
+            self = self.add_bind_group_layout(label, &entries);
+        }
         self
     }
 
@@ -295,6 +728,32 @@ impl<'a> RendererBuilder<'a> {
         self
     }
 
+    // Renders into this format instead of the surface's, for offscreen targets
+    // such as an HDR buffer.
+    pub fn set_color_target_format(mut self, format: TextureFormat) -> Self {
+        self.color_target_format = Some(format);
+        self
+    }
+
+    // Renders into a float target instead of the surface, with auto exposure
+    // and a tonemap to the surface done by the `HdrPipeline`. Call it after
+    // `create_surface_configuration` and before `create_render_pipeline`.
+    pub fn create_hdr_pipeline(mut self) -> Self {
+        let device = self.device.as_ref().expect("renderer to have a device");
+        let surface_configuration = self
+            .surface_configuration
+            .as_ref()
+            .expect("renderer to have a surface configuration");
+
+        self.hdr_pipeline = Some(HdrPipeline::new(
+            device,
+            surface_configuration.width,
+            surface_configuration.height,
+            surface_configuration.format,
+        ));
+        self
+    }
+
     pub fn create_render_pipeline(mut self, label: Option<&str>) -> Self {
         let device = self.device.as_ref().expect("renderer to have a device");
         let shader = self
@@ -309,9 +768,8 @@ impl<'a> RendererBuilder<'a> {
             .pipeline_layout
             .as_ref()
             .expect("renderer to have a pipeline layout");
-        let vertex_buffers_layout: &Vec<VertexBufferLayout> = self.vertex_buffers_layout.as_ref();
 
-        let primitive_state = match self.primitive_state.clone() {
+        let primitive_state = match self.primitive_state {
             Some(primitive_state) => primitive_state,
             None => wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
@@ -320,35 +778,238 @@ impl<'a> RendererBuilder<'a> {
             },
         };
 
-        let depth_stencil_state = self.depth_stencil_state.clone();
+        let color_target_format = match (self.color_target_format, &self.hdr_pipeline) {
+            (Some(format), _) => format,
+            (None, Some(_)) => HdrPipeline::FORMAT,
+            (None, None) => surface_configuration.format,
+        };
 
-        self.render_pipeline = Some(device.create_render_pipeline(
-            &wgpu::RenderPipelineDescriptor {
-                label,
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &vertex_buffers_layout,
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: surface_configuration.format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::all(),
-                    })],
-                }),
-                primitive: primitive_state,
-                depth_stencil: depth_stencil_state,
-                multisample: Default::default(),
-                multiview: Default::default(),
-            },
+        check_pipeline(
+            self.shader_reflection
+                .as_ref()
+                .expect("renderer to have a shader module"),
+            &["vs_main", "fs_main"],
+            &self.vertex_buffers_layout,
+            self.bind_group_layout_entries
+                .iter()
+                .map(Vec::as_slice)
+                .collect(),
+        );
+
+        let settings = RenderPipelineSettings {
+            label: label.map(String::from),
+            primitive_state,
+            depth_stencil_state: self.depth_stencil_state.clone(),
+            color_target_format,
+        };
+        self.render_pipeline = Some(build_render_pipeline(
+            device,
+            shader,
+            pipeline_layout,
+            &self.vertex_buffers_layout,
+            &settings,
+        ));
+        self.render_pipeline_settings = Some(settings);
+        self
+    }
+
+    // A vertex-only variant of the main pipeline for shadow maps and depth
+    // prepasses. It shares the shader module, vertex buffer layouts and primitive
+    // state, and gets its own pipeline layout made of the bind group layouts at
+    // `bind_group_layout_indices`, in that order.
+    pub fn create_depth_only_pipeline(
+        mut self,
+        label: Option<&str>,
+        vertex_entry_point: &str,
+        bind_group_layout_indices: &[usize],
+        depth_stencil_state: DepthStencilState,
+    ) -> Self {
+        let device = self.device.as_ref().expect("renderer to have a device");
+        let shader = self
+            .shader
+            .as_ref()
+            .expect("renderer to have a shader module");
+
+        check_pipeline(
+            self.shader_reflection
+                .as_ref()
+                .expect("renderer to have a shader module"),
+            &[vertex_entry_point],
+            &self.vertex_buffers_layout,
+            bind_group_layout_indices
+                .iter()
+                .map(|&index| {
+                    self.bind_group_layout_entries
+                        .get(index)
+                        .unwrap_or_else(|| {
+                            panic!("renderer to have a bind group layout at index {}", index)
+                        })
+                        .as_slice()
+                })
+                .collect(),
+        );
+
+        let settings = DepthOnlyPipelineSettings {
+            label: label.map(String::from),
+            vertex_entry_point: vertex_entry_point.to_string(),
+            bind_group_layout_indices: bind_group_layout_indices.to_vec(),
+            primitive_state: self.primitive_state.unwrap_or(wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                ..Default::default()
+            }),
+            depth_stencil_state,
+        };
+        self.depth_only_pipeline = Some(build_depth_only_pipeline(
+            device,
+            shader,
+            &self.bind_group_layouts,
+            &self.vertex_buffers_layout,
+            &settings,
         ));
+        self.depth_only_pipeline_settings = Some(settings);
+        self
+    }
+
+    pub fn create_compute_shader_module(self, label: Option<&str>, shader_path: &str) -> Self {
+        let shader = preprocess_shader(
+            &self.shader_preprocessor,
+            shader_capabilities(
+                self.adapter.as_ref(),
+                self.device.as_ref().expect("renderer to have a device"),
+            ),
+            label,
+            &[(label.unwrap_or("shader"), shader_path)],
+        )
+        .unwrap_or_else(|error| panic!("shader to be valid WGSL\n{}", error));
+        self.set_compute_shader(label, shader)
+    }
+
+    fn set_compute_shader(
+        mut self,
+        label: Option<&str>,
+        shader: (String, ShaderReflection),
+    ) -> Self {
+        let (source, compute_shader_reflection) = shader;
+        self.compute_shader_reflection = Some(compute_shader_reflection);
+        let device = self.device.as_ref().expect("renderer to have a device");
+        self.compute_shader = Some(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        }));
+        self
+    }
+
+    pub fn create_compute_shader_module_from_path<P: AsRef<Path>>(
+        mut self,
+        label: Option<&str>,
+        path: P,
+    ) -> Self {
+        let (index, shader) = self.watch_shader(label, path);
+        self.compute_shader_source = Some(WatchedSource {
+            label: label.map(String::from),
+            index,
+        });
+        self.set_compute_shader(label, shader)
+    }
+
+    pub fn add_compute_bind_group_layout(
+        mut self,
+        label: Option<&str>,
+        entries: &[BindGroupLayoutEntry],
+    ) -> Self {
+        let device = self.device.as_ref().expect("renderer to have a device");
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label, entries });
+        self.compute_bind_group_layouts.push(bind_group_layout);
+        self.compute_bind_group_layout_entries
+            .push(entries.to_vec());
         self
     }
 
+    // The same as `add_bind_group_layouts_from_shader` for all compute entry
+    // points of the compute shader.
+    pub fn add_compute_bind_group_layouts_from_shader(mut self, label: Option<&str>) -> Self {
+        let compute_shader_reflection = self
+            .compute_shader_reflection
+            .as_ref()
+            .expect("renderer to have a compute shader module");
+        let entry_points: Vec<&str> = compute_shader_reflection
+            .entry_points()
+            .iter()
+            .filter(|entry_point| entry_point.stage == ShaderStages::COMPUTE)
+            .map(|entry_point| entry_point.name.as_str())
+            .collect();
+        let groups: Vec<Vec<BindGroupLayoutEntry>> = (self.compute_bind_group_layouts.len() as u32
+            ..compute_shader_reflection.bind_group_count(&entry_points))
+            .map(|group| compute_shader_reflection.bind_group_layout_entries(group, &entry_points))
+            .collect();
+
+        for entries in groups {
+            self = self.add_compute_bind_group_layout(label, &entries);
+        }
+        self
+    }
+
+    // Each call adds a pipeline for `entry_point` of the compute shader, laid
+    // out with all compute bind group layouts added so far. `compute_pipelines`
+    // keeps them in the order they were created.
+    pub fn create_compute_pipeline(mut self, label: Option<&str>, entry_point: &str) -> Self {
+        let device = self.device.as_ref().expect("renderer to have a device");
+        let compute_shader = self
+            .compute_shader
+            .as_ref()
+            .expect("renderer to have a compute shader module");
+
+        check_pipeline(
+            self.compute_shader_reflection
+                .as_ref()
+                .expect("renderer to have a compute shader module"),
+            &[entry_point],
+            &[],
+            self.compute_bind_group_layout_entries
+                .iter()
+                .map(Vec::as_slice)
+                .collect(),
+        );
+
+        let settings = ComputePipelineSettings {
+            label: label.map(String::from),
+            entry_point: entry_point.to_string(),
+            bind_group_layout_count: self.compute_bind_group_layouts.len(),
+        };
+        self.compute_pipelines.push(build_compute_pipeline(
+            device,
+            compute_shader,
+            &self.compute_bind_group_layouts,
+            &settings,
+        ));
+        self.compute_pipeline_settings.push(settings);
+        self
+    }
+
+    fn watch_shader<P: AsRef<Path>>(
+        &mut self,
+        label: Option<&str>,
+        path: P,
+    ) -> (usize, (String, ShaderReflection)) {
+        let capabilities = shader_capabilities(
+            self.adapter.as_ref(),
+            self.device.as_ref().expect("renderer to have a device"),
+        );
+        let shader_watcher = self.shader_watcher.get_or_insert_with(ShaderWatcher::new);
+        let index = shader_watcher.watch(path);
+        let shader = load_watched_shader(
+            &self.shader_preprocessor,
+            capabilities,
+            shader_watcher,
+            index,
+            label,
+        )
+        .unwrap_or_else(|error| panic!("shader file to be readable and valid WGSL\n{}", error));
+        (index, shader)
+    }
+
     pub fn build(self) -> Renderer<'a> {
         Renderer {
             window: self.window,
@@ -361,8 +1022,293 @@ impl<'a> RendererBuilder<'a> {
             shader: self.shader,
             pipeline_layout: self.pipeline_layout,
             render_pipeline: self.render_pipeline,
+            depth_only_pipeline: self.depth_only_pipeline,
+            hdr_pipeline: self.hdr_pipeline,
             bind_group_layouts: self.bind_group_layouts,
             vertex_buffers_layout: self.vertex_buffers_layout,
+            compute_shader: self.compute_shader,
+            compute_bind_group_layouts: self.compute_bind_group_layouts,
+            compute_pipelines: self.compute_pipelines,
+            shader_watcher: self.shader_watcher,
+            shader_preprocessor: self.shader_preprocessor,
+            shader_source: self.shader_source,
+            compute_shader_source: self.compute_shader_source,
+            render_pipeline_settings: self.render_pipeline_settings,
+            depth_only_pipeline_settings: self.depth_only_pipeline_settings,
+            compute_pipeline_settings: self.compute_pipeline_settings,
         }
     }
 }
+
+// Shaders are validated against what the device can run, not everything naga
+// understands.
+fn shader_capabilities(adapter: Option<&Adapter>, device: &Device) -> Capabilities {
+    let adapter = adapter.expect("renderer to have an adapter");
+    device_capabilities(
+        device.features(),
+        adapter.get_downlevel_capabilities().flags,
+    )
+}
+
+// Returns the expanded source with its reflection, or the report of the first
+// error pointing at the original file and line.
+fn preprocess_shader(
+    shader_preprocessor: &ShaderPreprocessor,
+    capabilities: Capabilities,
+    label: Option<&str>,
+    sources: &[(&str, &str)],
+) -> Result<(String, ShaderReflection), String> {
+    shader_preprocessor
+        .process_sources(sources)
+        .and_then(|shader| {
+            let shader_reflection = shader.parse(label, capabilities)?;
+            Ok((shader.into_source(), shader_reflection))
+        })
+        .map_err(|error| error.report().to_string())
+}
+
+// Reads the file fresh and watches the files it includes from then on, even
+// when the shader fails to parse, so fixing an include reloads it too.
+fn load_watched_shader(
+    shader_preprocessor: &ShaderPreprocessor,
+    capabilities: Capabilities,
+    shader_watcher: &mut ShaderWatcher,
+    index: usize,
+    label: Option<&str>,
+) -> Result<(String, ShaderReflection), String> {
+    let shader = shader_preprocessor
+        .process_file(shader_watcher.path(index))
+        .map_err(|error| error.report().to_string())?;
+    shader_watcher.set_includes(index, shader.source_map().files());
+    let shader_reflection = shader
+        .parse(label, capabilities)
+        .map_err(|error| error.report().to_string())?;
+    Ok((shader.into_source(), shader_reflection))
+}
+
+// Panics with the binding or location at fault instead of leaving it to the
+// device's validation error.
+fn check_pipeline(
+    shader_reflection: &ShaderReflection,
+    entry_points: &[&str],
+    vertex_buffers_layout: &[VertexBufferLayout],
+    bind_group_layouts: Vec<&[BindGroupLayoutEntry]>,
+) {
+    let result = entry_points
+        .iter()
+        .filter(|&&entry_point| {
+            shader_reflection
+                .entry_point(entry_point)
+                .is_some_and(|entry_point| entry_point.stage == ShaderStages::VERTEX)
+        })
+        .try_for_each(|entry_point| {
+            shader_reflection.check_vertex_buffers(entry_point, vertex_buffers_layout)
+        })
+        .and_then(|_| {
+            shader_reflection.check_bind_group_layouts(entry_points, &bind_group_layouts)
+        });
+    if let Err(error) = result {
+        panic!("shader to match the pipeline layout: {}", error);
+    }
+}
+
+// A shader loaded through the `ShaderWatcher`.
+struct WatchedSource {
+    label: Option<String>,
+    index: usize,
+}
+
+// What the builder used to create each pipeline, kept so they can be rebuilt
+// from a reloaded shader.
+struct RenderPipelineSettings {
+    label: Option<String>,
+    primitive_state: PrimitiveState,
+    depth_stencil_state: Option<DepthStencilState>,
+    color_target_format: TextureFormat,
+}
+
+struct DepthOnlyPipelineSettings {
+    label: Option<String>,
+    vertex_entry_point: String,
+    bind_group_layout_indices: Vec<usize>,
+    primitive_state: PrimitiveState,
+    depth_stencil_state: DepthStencilState,
+}
+
+struct ComputePipelineSettings {
+    label: Option<String>,
+    entry_point: String,
+    bind_group_layout_count: usize,
+}
+
+fn build_render_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    pipeline_layout: &PipelineLayout,
+    vertex_buffers_layout: &[VertexBufferLayout],
+    settings: &RenderPipelineSettings,
+) -> RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: settings.label.as_deref(),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: vertex_buffers_layout,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: settings.color_target_format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::all(),
+            })],
+        }),
+        primitive: settings.primitive_state,
+        depth_stencil: settings.depth_stencil_state.clone(),
+        multisample: Default::default(),
+        multiview: Default::default(),
+    })
+}
+
+fn build_depth_only_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    bind_group_layouts: &[BindGroupLayout],
+    vertex_buffers_layout: &[VertexBufferLayout],
+    settings: &DepthOnlyPipelineSettings,
+) -> RenderPipeline {
+    let bind_group_layouts: Vec<&BindGroupLayout> = settings
+        .bind_group_layout_indices
+        .iter()
+        .map(|&index| {
+            bind_group_layouts
+                .get(index)
+                .expect("renderer to have the bind group layout")
+        })
+        .collect();
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: settings.label.as_deref(),
+        bind_group_layouts: &bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: settings.label.as_deref(),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: &settings.vertex_entry_point,
+            buffers: vertex_buffers_layout,
+        },
+        fragment: None,
+        primitive: settings.primitive_state,
+        depth_stencil: Some(settings.depth_stencil_state.clone()),
+        multisample: Default::default(),
+        multiview: Default::default(),
+    })
+}
+
+fn build_compute_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    bind_group_layouts: &[BindGroupLayout],
+    settings: &ComputePipelineSettings,
+) -> ComputePipeline {
+    let bind_group_layouts: Vec<&BindGroupLayout> = bind_group_layouts
+        .iter()
+        .take(settings.bind_group_layout_count)
+        .collect();
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: settings.label.as_deref(),
+        bind_group_layouts: &bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: settings.label.as_deref(),
+        layout: Some(&pipeline_layout),
+        module: shader,
+        entry_point: &settings.entry_point,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::BufferUsages;
+
+    use super::*;
+    use crate::compute::StorageBuffer;
+
+    const DOUBLE_WGSL: &str = "
+@group(0) @binding(0) var<storage, read_write> values: array<u32>;
+
+@compute @workgroup_size(64)
+fn double(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x < arrayLength(&values)) {
+        values[id.x] = values[id.x] * 2u + 1u;
+    }
+}
+";
+
+    // Machines without even a software adapter, such as bare CI containers, skip the test
+    // instead of failing on the builder's `expect`.
+    fn has_fallback_adapter() -> bool {
+        let instance = Instance::new(InstanceDescriptor {
+            backends: Backends::all(),
+            dx12_shader_compiler: Default::default(),
+        });
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::None,
+            force_fallback_adapter: true,
+            compatible_surface: None,
+        }))
+        .is_some_and(|adapter| {
+            adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        })
+    }
+
+    #[test]
+    fn headless_fallback_adapter_runs_compute() {
+        if !has_fallback_adapter() {
+            eprintln!("skipping: no fallback adapter with compute shaders");
+            return;
+        }
+
+        let renderer = RendererBuilder::headless()
+            .set_backends(Backends::all())
+            .set_force_fallback_adapter(true)
+            .create_instance()
+            .get_adapter()
+            .get_device(Some("Test Device"))
+            .create_compute_shader_module(Some("Double Shader"), DOUBLE_WGSL)
+            .add_compute_bind_group_layouts_from_shader(Some("Double Bind Group Layout"))
+            .create_compute_pipeline(Some("Double Pipeline"), "double")
+            .build();
+        let device = renderer.device().unwrap();
+
+        let input: Vec<u32> = (0..100).collect();
+        let values = StorageBuffer::new(device, Some("Values"), &input, BufferUsages::empty());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Double Bind Group"),
+            layout: &renderer.compute_bind_group_layouts()[0],
+            entries: &[values.bind_group_entry(0)],
+        });
+        renderer.dispatch(
+            0,
+            &[&bind_group],
+            [compute::workgroup_count(values.len(), 64), 1, 1],
+        );
+
+        let output: Vec<u32> = renderer.read_buffer(values.buffer(), values.len());
+        let expected: Vec<u32> = input.iter().map(|value| value * 2 + 1).collect();
+        assert_eq!(output, expected);
+
+        let prefix: Vec<u32> = renderer.read_buffer(values.buffer(), 3);
+        assert_eq!(prefix, expected[..3]);
+    }
+}
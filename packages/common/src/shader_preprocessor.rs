@@ -0,0 +1,565 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use naga::valid::Capabilities;
+use wgpu::{Device, DownlevelFlags, ShaderModule};
+
+use crate::{
+    lighting::LIGHTING_WGSL,
+    pbr::PBR_WGSL,
+    post_process::POST_PROCESS_PRELUDE_WGSL,
+    shader_reflection::{device_capabilities, ShaderError, ShaderReflection},
+    shadow::SHADOW_WGSL,
+    uniforms::SCENE_UNIFORMS_WGSL,
+};
+
+// Sources every preprocessor can include without reading the disk.
+const BUILTIN_SOURCES: [(&str, &str); 7] = [
+    (
+        "common/uniforms.wgsl",
+        include_str!("shaders/common/uniforms.wgsl"),
+    ),
+    (
+        "common/color_output.wgsl",
+        include_str!("shaders/common/color_output.wgsl"),
+    ),
+    ("common/scene_uniforms.wgsl", SCENE_UNIFORMS_WGSL),
+    ("common/lighting.wgsl", LIGHTING_WGSL),
+    ("common/shadow.wgsl", SHADOW_WGSL),
+    ("common/pbr.wgsl", PBR_WGSL),
+    ("common/post_process.wgsl", POST_PROCESS_PRELUDE_WGSL),
+];
+
+// Where each line of a preprocessed shader came from.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    files: Vec<String>,
+    lines: Vec<(usize, u32)>,
+}
+
+impl SourceMap {
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    // The file and line of `line` in the preprocessed shader, both 1-based.
+    pub fn locate(&self, line: u32) -> Option<(&str, u32)> {
+        let &(file, line) = self.lines.get((line as usize).checked_sub(1)?)?;
+        Some((&self.files[file], line))
+    }
+
+    fn push(&mut self, file: &str, line: u32) {
+        let index = match self.files.iter().position(|name| name == file) {
+            Some(index) => index,
+            None => {
+                self.files.push(file.to_string());
+                self.files.len() - 1
+            }
+        };
+        self.lines.push((index, line));
+    }
+}
+
+pub struct PreprocessedShader {
+    source: String,
+    source_map: SourceMap,
+}
+
+impl PreprocessedShader {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+    pub fn into_source(self) -> String {
+        self.source
+    }
+
+    // Validates the shader against the features of `device` before wgpu sees
+    // it. Only the adapter knows the downlevel flags, so shaders that need
+    // multisampled shading have to be created through the `Renderer`.
+    pub fn create_shader_module(
+        &self,
+        device: &Device,
+        label: Option<&str>,
+    ) -> Result<ShaderModule, ShaderError> {
+        let capabilities = device_capabilities(device.features(), DownlevelFlags::empty());
+        self.parse(label, capabilities)?;
+        Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label,
+            source: wgpu::ShaderSource::Wgsl(self.source.as_str().into()),
+        }))
+    }
+
+    // Errors point at the file and line the code was included from.
+    pub fn parse(
+        &self,
+        label: Option<&str>,
+        capabilities: Capabilities,
+    ) -> Result<ShaderReflection, ShaderError> {
+        ShaderReflection::parse(label, &self.source, capabilities)
+            .map_err(|error| error.remap(&self.source_map))
+    }
+}
+
+struct Condition {
+    active: bool,
+    has_else: bool,
+    line: u32,
+}
+
+struct Expansion {
+    defines: HashMap<String, String>,
+    included: HashSet<String>,
+    source: String,
+    source_map: SourceMap,
+}
+
+// Expands `#include "file"`, `#define NAME value`, `#undef`, `#ifdef`,
+// `#ifndef`, `#else` and `#endif` in WGSL. Each file is included once, and a
+// define with a value replaces the name wherever it appears as an identifier.
+#[derive(Clone)]
+pub struct ShaderPreprocessor {
+    sources: HashMap<String, String>,
+    include_dirs: Vec<PathBuf>,
+    defines: HashMap<String, String>,
+}
+
+impl Default for ShaderPreprocessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderPreprocessor {
+    pub fn new() -> Self {
+        Self {
+            sources: BUILTIN_SOURCES
+                .iter()
+                .map(|&(name, source)| (name.to_string(), source.to_string()))
+                .collect(),
+            include_dirs: Vec::new(),
+            defines: HashMap::new(),
+        }
+    }
+
+    pub fn add_source(mut self, name: &str, source: &str) -> Self {
+        self.sources.insert(name.to_string(), source.to_string());
+        self
+    }
+
+    // Includes that aren't added sources are looked up next to the including
+    // file, then in these directories in order.
+    pub fn add_include_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.include_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    // An empty `value` only makes `name` visible to `#ifdef`, which is how
+    // permutations of one shader are selected.
+    pub fn add_define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn process(&self, name: &str, source: &str) -> Result<PreprocessedShader, ShaderError> {
+        self.process_sources(&[(name, source)])
+    }
+
+    pub fn process_file<P: AsRef<Path>>(&self, path: P) -> Result<PreprocessedShader, ShaderError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| ShaderError::at(&name, None, error.to_string()))?;
+        self.process(&name, &source)
+    }
+
+    // Expands `sources` one after the other into one shader. Defines and
+    // includes carry over, so a prelude can be kept apart from the file.
+    pub fn process_sources(
+        &self,
+        sources: &[(&str, &str)],
+    ) -> Result<PreprocessedShader, ShaderError> {
+        let mut expansion = Expansion {
+            defines: self.defines.clone(),
+            included: HashSet::new(),
+            source: String::new(),
+            source_map: SourceMap::default(),
+        };
+        for (name, source) in sources {
+            self.expand(&mut expansion, name, source)?;
+        }
+
+        Ok(PreprocessedShader {
+            source: expansion.source,
+            source_map: expansion.source_map,
+        })
+    }
+
+    fn expand(
+        &self,
+        expansion: &mut Expansion,
+        name: &str,
+        source: &str,
+    ) -> Result<(), ShaderError> {
+        expansion.included.insert(name.to_string());
+
+        let mut conditions: Vec<Condition> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index as u32 + 1;
+            let error = |message: String| ShaderError::at(name, Some(line_number), message);
+            let active = conditions.iter().all(|condition| condition.active);
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    expansion
+                        .source
+                        .push_str(&substitute(line, &expansion.defines));
+                    expansion.source.push('\n');
+                    expansion.source_map.push(name, line_number);
+                }
+                continue;
+            };
+            let (keyword, argument) = match directive.split_once(char::is_whitespace) {
+                Some((keyword, argument)) => (keyword, argument.trim()),
+                None => (directive.trim(), ""),
+            };
+
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let define = identifier(argument)
+                        .ok_or_else(|| error(format!("expected a name after #{}", keyword)))?;
+                    conditions.push(Condition {
+                        active: expansion.defines.contains_key(define) == (keyword == "ifdef"),
+                        has_else: false,
+                        line: line_number,
+                    });
+                }
+                "else" => {
+                    let condition = conditions
+                        .last_mut()
+                        .ok_or_else(|| error("#else without #ifdef".to_string()))?;
+                    if condition.has_else {
+                        return Err(error("#else after #else".to_string()));
+                    }
+                    condition.active = !condition.active;
+                    condition.has_else = true;
+                }
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef".to_string()))?;
+                }
+                // Only the nesting matters in a skipped block.
+                _ if !active => (),
+                "define" => {
+                    let (define, value) = match argument.split_once(char::is_whitespace) {
+                        Some((define, value)) => (define, value.trim()),
+                        None => (argument, ""),
+                    };
+                    let define = identifier(define)
+                        .ok_or_else(|| error("expected a name after #define".to_string()))?;
+                    let value = substitute(value, &expansion.defines);
+                    expansion.defines.insert(define.to_string(), value);
+                }
+                "undef" => {
+                    let define = identifier(argument)
+                        .ok_or_else(|| error("expected a name after #undef".to_string()))?;
+                    expansion.defines.remove(define);
+                }
+                "include" => {
+                    let include = argument
+                        .strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                        .ok_or_else(|| error("expected #include \"file\"".to_string()))?;
+                    let (include_name, include_source) = self
+                        .resolve(name, include)
+                        .ok_or_else(|| error(format!("cannot find `{}` to include", include)))?;
+                    if !expansion.included.contains(&include_name) {
+                        self.expand(expansion, &include_name, &include_source)?;
+                    }
+                }
+                _ => return Err(error(format!("unknown directive #{}", keyword))),
+            }
+        }
+
+        match conditions.last() {
+            Some(condition) => Err(ShaderError::at(
+                name,
+                Some(condition.line),
+                "#ifdef without #endif",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn resolve(&self, including: &str, include: &str) -> Option<(String, String)> {
+        if let Some(source) = self.sources.get(include) {
+            return Some((include.to_string(), source.clone()));
+        }
+
+        let relative = Path::new(including)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| dir.join(include));
+        relative
+            .into_iter()
+            .chain(self.include_dirs.iter().map(|dir| dir.join(include)))
+            .find_map(|path| {
+                let source = std::fs::read_to_string(&path).ok()?;
+                Some((path.display().to_string(), source))
+            })
+    }
+}
+
+// The shaders of the library are fixed, an error in one is a bug and panics with
+// the report.
+pub(crate) fn create_library_shader_module(
+    device: &Device,
+    label: &str,
+    sources: &[(&str, &str)],
+) -> ShaderModule {
+    ShaderPreprocessor::new()
+        .process_sources(sources)
+        .and_then(|shader| shader.create_shader_module(device, Some(label)))
+        .unwrap_or_else(|error| panic!("shader to be valid WGSL\n{}", error.report()))
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn identifier(text: &str) -> Option<&str> {
+    let starts_with_letter = text
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_');
+    (starts_with_letter && text.chars().all(is_identifier_char)).then_some(text)
+}
+
+// Replaces defined names, but not parts of longer identifiers or number
+// suffixes such as the `e5` in `1.0e5`.
+fn substitute(line: &str, defines: &HashMap<String, String>) -> String {
+    if defines.values().all(String::is_empty) {
+        return line.to_string();
+    }
+
+    let mut output = String::with_capacity(line.len());
+    let mut chars = line.char_indices().peekable();
+    let mut previous = ' ';
+    while let Some((start, c)) = chars.next() {
+        if !(c.is_alphabetic() || c == '_') || is_identifier_char(previous) {
+            output.push(c);
+            previous = c;
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+        while let Some(&(index, next)) = chars.peek() {
+            if !is_identifier_char(next) {
+                break;
+            }
+            end = index + next.len_utf8();
+            chars.next();
+        }
+        let word = &line[start..end];
+        match defines.get(word) {
+            Some(value) if !value.is_empty() => output.push_str(value),
+            _ => output.push_str(word),
+        }
+        previous = '_';
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(preprocessor: &ShaderPreprocessor, source: &str) -> Result<String, ShaderError> {
+        preprocessor
+            .process("main.wgsl", source)
+            .map(PreprocessedShader::into_source)
+    }
+
+    fn error(preprocessor: &ShaderPreprocessor, source: &str) -> ShaderError {
+        preprocessor
+            .process("main.wgsl", source)
+            .err()
+            .expect("preprocessing to fail")
+    }
+
+    #[test]
+    fn includes_are_expanded_once_and_mapped_to_their_file() {
+        let preprocessor = ShaderPreprocessor::new()
+            .add_source("a.wgsl", "#include \"b.wgsl\"\nconst a = 1;")
+            .add_source("b.wgsl", "#include \"a.wgsl\"\nconst b = 2;");
+        let shader = preprocessor
+            .process(
+                "main.wgsl",
+                "#include \"a.wgsl\"\n#include \"b.wgsl\"\nconst c = 3;",
+            )
+            .unwrap();
+
+        // `b.wgsl` including `a.wgsl` back is skipped instead of recursing.
+        assert_eq!(
+            shader.source(),
+            "const b = 2;\nconst a = 1;\nconst c = 3;\n"
+        );
+        assert_eq!(shader.source_map().locate(1), Some(("b.wgsl", 2)));
+        assert_eq!(shader.source_map().locate(2), Some(("a.wgsl", 2)));
+        assert_eq!(shader.source_map().locate(3), Some(("main.wgsl", 3)));
+        assert_eq!(shader.source_map().locate(4), None);
+        assert_eq!(
+            shader.source_map().files(),
+            ["b.wgsl", "a.wgsl", "main.wgsl"]
+        );
+    }
+
+    #[test]
+    fn includes_are_found_next_to_the_file_and_in_include_dirs() {
+        let directory =
+            std::env::temp_dir().join(format!("shader-preprocessor-{}", std::process::id()));
+        let include_dir = directory.join("include");
+        std::fs::create_dir_all(&include_dir).unwrap();
+        std::fs::write(
+            directory.join("main.wgsl"),
+            "#include \"local.wgsl\"\n#include \"shared.wgsl\"\n",
+        )
+        .unwrap();
+        std::fs::write(directory.join("local.wgsl"), "const local = 1;\n").unwrap();
+        std::fs::write(include_dir.join("shared.wgsl"), "const shared = 2;\n").unwrap();
+
+        let shader = ShaderPreprocessor::new()
+            .add_include_dir(&include_dir)
+            .process_file(directory.join("main.wgsl"));
+        let missing = ShaderPreprocessor::new().process_file(directory.join("main.wgsl"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let shader = shader.unwrap();
+        assert_eq!(shader.source(), "const local = 1;\nconst shared = 2;\n");
+        assert_eq!(
+            shader.source_map().files()[1],
+            include_dir.join("shared.wgsl").display().to_string()
+        );
+        let missing = missing.err().expect("shared.wgsl to be missing");
+        assert_eq!(missing.line(), Some(2));
+        assert!(missing.message().contains("`shared.wgsl`"));
+    }
+
+    #[test]
+    fn defines_replace_whole_identifiers() {
+        let preprocessor = ShaderPreprocessor::new()
+            .add_define("SIZE", "4")
+            .add_define("e5", "oops");
+        let source = "#define HALF SIZE / 2\nconst a = SIZE + HALF + SIZE_2 + 1.0e5;\n#undef SIZE\nconst b = SIZE;";
+
+        assert_eq!(
+            expand(&preprocessor, source).unwrap(),
+            "const a = 4 + 4 / 2 + SIZE_2 + 1.0e5;\nconst b = SIZE;\n"
+        );
+    }
+
+    #[test]
+    fn conditions_nest() {
+        let preprocessor = ShaderPreprocessor::new().add_define("A", "");
+        let source = "\
+#ifdef A
+a
+#ifndef B
+not b
+#else
+b
+#endif
+#else
+not a
+#ifdef B
+#unknown is skipped
+#endif
+#endif
+#define B
+#ifdef B
+b
+#endif";
+
+        assert_eq!(expand(&preprocessor, source).unwrap(), "a\nnot b\nb\n");
+    }
+
+    #[test]
+    fn errors_point_at_the_directive() {
+        let preprocessor = ShaderPreprocessor::new();
+
+        let else_after_else = error(&preprocessor, "#ifdef A\n#else\n#else\n#endif");
+        assert_eq!(else_after_else.line(), Some(3));
+        assert_eq!(else_after_else.message(), "#else after #else");
+
+        let unterminated = error(&preprocessor, "#ifdef A\n#ifdef B\n#endif");
+        assert_eq!(unterminated.line(), Some(1));
+        assert_eq!(unterminated.message(), "#ifdef without #endif");
+
+        let unknown = error(&preprocessor, "const a = 1;\n#pragma once");
+        assert_eq!(unknown.label(), Some("main.wgsl"));
+        assert_eq!(unknown.line(), Some(2));
+        assert_eq!(unknown.message(), "unknown directive #pragma");
+        assert_eq!(
+            unknown.to_string(),
+            "main.wgsl:2:1: unknown directive #pragma"
+        );
+
+        assert_eq!(
+            error(&preprocessor, "#endif").message(),
+            "#endif without #ifdef"
+        );
+        assert_eq!(
+            error(&preprocessor, "#include common.wgsl").message(),
+            "expected #include \"file\""
+        );
+    }
+
+    #[test]
+    fn library_shaders_include_what_they_use() {
+        let preprocessor =
+            ShaderPreprocessor::new().add_include_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/src"));
+        let shaders = BUILTIN_SOURCES.iter().map(|&(name, _)| name).chain([
+            "shaders/hdr.wgsl",
+            "shaders/ibl.wgsl",
+            "shaders/mipmap_blit.wgsl",
+            "shaders/particles.wgsl",
+            "shaders/post_process.wgsl",
+            "shaders/skybox.wgsl",
+        ]);
+        for name in shaders {
+            let source = format!("#include \"{}\"", name);
+            let shader = preprocessor
+                .process(name, &source)
+                .and_then(|shader| shader.parse(Some(name), Capabilities::empty()));
+            if let Err(error) = shader {
+                panic!("{}", error.report());
+            }
+        }
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_included_file() {
+        let preprocessor =
+            ShaderPreprocessor::new().add_source("broken.wgsl", "const a = 1.0;\nconst b = a +;\n");
+        let shader = preprocessor
+            .process("main.wgsl", "#include \"broken.wgsl\"\nconst c = 2.0;\n")
+            .unwrap();
+
+        let error = shader
+            .parse(Some("main.wgsl"), Capabilities::empty())
+            .err()
+            .expect("shader to be invalid");
+        assert_eq!(error.label(), Some("broken.wgsl"));
+        assert_eq!(error.line(), Some(2));
+        assert!(
+            error.report().contains("broken.wgsl:2:"),
+            "{}",
+            error.report()
+        );
+    }
+}
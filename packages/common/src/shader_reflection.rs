@@ -0,0 +1,993 @@
+use std::{fmt, num::NonZeroU32};
+
+use naga::{
+    valid::{Capabilities, ModuleInfo, ValidationFlags, Validator},
+    AddressSpace, ImageClass, ImageDimension, Module, ScalarKind, StorageAccess, TypeInner,
+};
+use wgpu::{
+    BindGroupLayoutEntry, BindingType, BufferBindingType, BufferSize, DownlevelFlags, Features,
+    SamplerBindingType, ShaderStages, StorageTextureAccess, TextureFormat, TextureSampleType,
+    TextureViewDimension, VertexBufferLayout, VertexFormat,
+};
+
+use crate::shader_preprocessor::SourceMap;
+
+#[derive(Debug)]
+pub struct ShaderError {
+    label: Option<String>,
+    line: Option<u32>,
+    column: Option<u32>,
+    message: String,
+    report: String,
+}
+
+impl ShaderError {
+    fn new(
+        label: Option<&str>,
+        location: Option<naga::SourceLocation>,
+        message: String,
+        report: String,
+    ) -> Self {
+        Self {
+            label: label.map(String::from),
+            line: location.map(|location| location.line_number),
+            column: location.map(|location| location.line_position),
+            message,
+            report,
+        }
+    }
+
+    pub(crate) fn at(label: &str, line: Option<u32>, message: impl Into<String>) -> Self {
+        let mut error = Self {
+            label: Some(label.to_string()),
+            line,
+            column: line.map(|_| 1),
+            message: message.into(),
+            report: String::new(),
+        };
+        error.report = error.to_string();
+        error
+    }
+
+    // Moves the error from the preprocessed shader back to the file and line
+    // the code came from, in both the message and the report.
+    pub(crate) fn remap(mut self, source_map: &SourceMap) -> Self {
+        let Some((file, line)) = self.line.and_then(|line| source_map.locate(line)) else {
+            return self;
+        };
+
+        let report = self
+            .report
+            .lines()
+            .map(|report_line| remap_report_line(report_line, source_map))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.report = report;
+        self.label = Some(file.to_string());
+        self.line = Some(line);
+        self
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+    pub fn column(&self) -> Option<u32> {
+        self.column
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    // The message with the offending source lines underlined.
+    pub fn report(&self) -> &str {
+        &self.report
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = self.label.as_deref().unwrap_or("shader");
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "{}:{}:{}: {}", label, line, column, self.message)
+            }
+            _ => write!(f, "{}: {}", label, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+// Rewrites the `┌─ path:line:column` header and the `line │` gutter of a
+// codespan report.
+fn remap_report_line(report_line: &str, source_map: &SourceMap) -> String {
+    if let Some((indent, location)) = report_line.split_once("┌─ ") {
+        let mut parts = location.rsplitn(3, ':');
+        let (Some(column), Some(line), Some(_)) = (parts.next(), parts.next(), parts.next()) else {
+            return report_line.to_string();
+        };
+        if let Some((file, line)) = line.parse().ok().and_then(|line| source_map.locate(line)) {
+            return format!("{}┌─ {}:{}:{}", indent, file, line, column);
+        }
+    } else if let Some((gutter, code)) = report_line.split_once(" │") {
+        if let Some((_, line)) = gutter
+            .trim()
+            .parse()
+            .ok()
+            .and_then(|line| source_map.locate(line))
+        {
+            return format!("{:>width$} │{}", line, code, width = gutter.len());
+        }
+    }
+    report_line.to_string()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryPoint {
+    pub name: String,
+    pub stage: ShaderStages,
+    pub workgroup_size: [u32; 3],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VertexInput {
+    pub location: u32,
+    pub format: VertexFormat,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderBinding {
+    pub name: Option<String>,
+    pub group: u32,
+    pub binding: u32,
+    pub ty: BindingType,
+    pub count: Option<NonZeroU32>,
+    // The stages of the entry points that use the binding.
+    pub visibility: ShaderStages,
+}
+
+impl ShaderBinding {
+    pub fn layout_entry(&self) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility: self.visibility,
+            ty: self.ty,
+            count: self.count,
+        }
+    }
+
+    // Follows the rules wgpu applies when creating a pipeline.
+    pub fn check_layout_entry(&self, entry: &BindGroupLayoutEntry) -> Result<(), String> {
+        let matches = match (self.ty, entry.ty) {
+            (
+                BindingType::Buffer {
+                    ty: shader_ty,
+                    min_binding_size: size,
+                    ..
+                },
+                BindingType::Buffer {
+                    ty,
+                    min_binding_size,
+                    ..
+                },
+            ) => {
+                let ty_matches = match (shader_ty, ty) {
+                    (BufferBindingType::Uniform, BufferBindingType::Uniform) => true,
+                    // A read-only shader binding accepts a read-write layout.
+                    (
+                        BufferBindingType::Storage {
+                            read_only: shader_read_only,
+                        },
+                        BufferBindingType::Storage { read_only },
+                    ) => shader_read_only || !read_only,
+                    _ => false,
+                };
+                let size_matches = match (min_binding_size, size) {
+                    (Some(min_binding_size), Some(size)) => min_binding_size >= size,
+                    _ => true,
+                };
+                ty_matches && size_matches
+            }
+            (BindingType::Sampler(shader_ty), BindingType::Sampler(ty)) => {
+                (shader_ty == SamplerBindingType::Comparison)
+                    == (ty == SamplerBindingType::Comparison)
+            }
+            (
+                BindingType::Texture {
+                    sample_type: shader_sample_type,
+                    view_dimension: shader_view_dimension,
+                    multisampled: shader_multisampled,
+                },
+                BindingType::Texture {
+                    sample_type,
+                    view_dimension,
+                    multisampled,
+                },
+            ) => {
+                let sample_type_matches = match (shader_sample_type, sample_type) {
+                    (TextureSampleType::Float { .. }, TextureSampleType::Float { .. }) => true,
+                    (shader_sample_type, sample_type) => shader_sample_type == sample_type,
+                };
+                sample_type_matches
+                    && shader_view_dimension == view_dimension
+                    && shader_multisampled == multisampled
+            }
+            (shader_ty @ BindingType::StorageTexture { .. }, ty) => shader_ty == ty,
+            _ => false,
+        };
+        if !matches {
+            return Err(format!(
+                "{} is {:?} in the shader but {:?} in the bind group layout",
+                self.describe(),
+                self.ty,
+                entry.ty
+            ));
+        }
+        if !entry.visibility.contains(self.visibility) {
+            return Err(format!(
+                "{} is used in {:?} but the bind group layout makes it visible to {:?}",
+                self.describe(),
+                self.visibility,
+                entry.visibility
+            ));
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "`{}` @group({}) @binding({})",
+            self.name.as_deref().unwrap_or("binding"),
+            self.group,
+            self.binding
+        )
+    }
+}
+
+// A WGSL module parsed and validated with naga, so mistakes are reported with
+// their source location before wgpu sees the shader, and the pipeline layout
+// it expects can be checked or generated.
+pub struct ShaderReflection {
+    module: Module,
+    info: ModuleInfo,
+    entry_points: Vec<EntryPoint>,
+}
+
+impl ShaderReflection {
+    // `capabilities` are what the device supports, see `device_capabilities`.
+    pub fn parse(
+        label: Option<&str>,
+        source: &str,
+        capabilities: Capabilities,
+    ) -> Result<Self, ShaderError> {
+        let path = label.unwrap_or("shader");
+        let module = naga::front::wgsl::parse_str(source).map_err(|error| {
+            ShaderError::new(
+                label,
+                error.location(source),
+                error.message().to_string(),
+                error.emit_to_string_with_path(source, path),
+            )
+        })?;
+        let info = Validator::new(ValidationFlags::all(), capabilities)
+            .validate(&module)
+            .map_err(|error| {
+                ShaderError::new(
+                    label,
+                    error.location(source),
+                    error_chain(&error),
+                    error.emit_to_string_with_path(source, path),
+                )
+            })?;
+
+        let entry_points = module
+            .entry_points
+            .iter()
+            .map(|entry_point| EntryPoint {
+                name: entry_point.name.clone(),
+                stage: shader_stage(entry_point.stage),
+                workgroup_size: entry_point.workgroup_size,
+            })
+            .collect();
+
+        Ok(Self {
+            module,
+            info,
+            entry_points,
+        })
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+    pub fn entry_points(&self) -> &[EntryPoint] {
+        &self.entry_points
+    }
+    pub fn entry_point(&self, name: &str) -> Option<&EntryPoint> {
+        self.entry_points
+            .iter()
+            .find(|entry_point| entry_point.name == name)
+    }
+
+    // The `@location` inputs of a vertex entry point, including the members of
+    // struct arguments, ordered by location.
+    pub fn vertex_inputs(&self, entry_point: &str) -> Vec<VertexInput> {
+        let Some(entry_point) = self
+            .module
+            .entry_points
+            .iter()
+            .find(|candidate| candidate.name == entry_point)
+        else {
+            return Vec::new();
+        };
+
+        let mut vertex_inputs = Vec::new();
+        for argument in entry_point.function.arguments.iter() {
+            match (&argument.binding, &self.module.types[argument.ty].inner) {
+                (Some(binding), inner) => {
+                    vertex_inputs.extend(vertex_input(binding, inner));
+                }
+                (None, TypeInner::Struct { members, .. }) => {
+                    for member in members {
+                        if let Some(binding) = &member.binding {
+                            vertex_inputs
+                                .extend(vertex_input(binding, &self.module.types[member.ty].inner));
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        vertex_inputs.sort_by_key(|vertex_input| vertex_input.location);
+        vertex_inputs
+    }
+
+    // The resources used by any of `entry_points`, ordered by group and binding.
+    pub fn bindings(&self, entry_points: &[&str]) -> Vec<ShaderBinding> {
+        let mut bindings = Vec::new();
+        for (handle, variable) in self.module.global_variables.iter() {
+            let Some(resource_binding) = &variable.binding else {
+                continue;
+            };
+
+            let mut visibility = ShaderStages::NONE;
+            for (index, entry_point) in self.module.entry_points.iter().enumerate() {
+                if entry_points.contains(&entry_point.name.as_str())
+                    && !self.info.get_entry_point(index)[handle].is_empty()
+                {
+                    visibility |= shader_stage(entry_point.stage);
+                }
+            }
+            if visibility.is_empty() {
+                continue;
+            }
+
+            let (ty, count) = match self.module.types[variable.ty].inner {
+                TypeInner::BindingArray { base, size } => (
+                    base,
+                    match size {
+                        naga::ArraySize::Constant(size) => Some(size),
+                        naga::ArraySize::Dynamic => None,
+                    },
+                ),
+                _ => (variable.ty, None),
+            };
+            let Some(binding_type) = self.binding_type(variable.space, ty) else {
+                continue;
+            };
+
+            bindings.push(ShaderBinding {
+                name: variable.name.clone(),
+                group: resource_binding.group,
+                binding: resource_binding.binding,
+                ty: binding_type,
+                count,
+                visibility,
+            });
+        }
+        bindings.sort_by_key(|binding| (binding.group, binding.binding));
+        bindings
+    }
+
+    // One more than the highest group used, as groups below it need a layout
+    // even when they are empty.
+    pub fn bind_group_count(&self, entry_points: &[&str]) -> u32 {
+        self.bindings(entry_points)
+            .iter()
+            .map(|binding| binding.group + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn bind_group_layout_entries(
+        &self,
+        group: u32,
+        entry_points: &[&str],
+    ) -> Vec<BindGroupLayoutEntry> {
+        self.bindings(entry_points)
+            .iter()
+            .filter(|binding| binding.group == group)
+            .map(ShaderBinding::layout_entry)
+            .collect()
+    }
+
+    pub fn check_vertex_buffers(
+        &self,
+        entry_point: &str,
+        vertex_buffers_layout: &[VertexBufferLayout],
+    ) -> Result<(), String> {
+        self.check_entry_point(entry_point)?;
+        for vertex_input in self.vertex_inputs(entry_point) {
+            let attribute = vertex_buffers_layout
+                .iter()
+                .flat_map(|layout| layout.attributes.iter())
+                .find(|attribute| attribute.shader_location == vertex_input.location)
+                .ok_or_else(|| {
+                    format!(
+                        "`{}` reads @location({}) but no vertex buffer layout has that location",
+                        entry_point, vertex_input.location
+                    )
+                })?;
+            if vertex_format_kind(attribute.format) != vertex_format_kind(vertex_input.format) {
+                return Err(format!(
+                    "`{}` reads @location({}) as {:?} but the vertex buffer layout has {:?}",
+                    entry_point, vertex_input.location, vertex_input.format, attribute.format
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // `bind_group_layouts` holds the entries of each layout in the pipeline
+    // layout, indexed by group.
+    pub fn check_bind_group_layouts(
+        &self,
+        entry_points: &[&str],
+        bind_group_layouts: &[&[BindGroupLayoutEntry]],
+    ) -> Result<(), String> {
+        for entry_point in entry_points {
+            self.check_entry_point(entry_point)?;
+        }
+        for binding in self.bindings(entry_points) {
+            let entries = bind_group_layouts
+                .get(binding.group as usize)
+                .ok_or_else(|| {
+                    format!(
+                        "{} needs bind group {} but the pipeline layout has {}",
+                        binding.describe(),
+                        binding.group,
+                        bind_group_layouts.len()
+                    )
+                })?;
+            let entry = entries
+                .iter()
+                .find(|entry| entry.binding == binding.binding)
+                .ok_or_else(|| {
+                    format!(
+                        "{} is missing from bind group layout {}",
+                        binding.describe(),
+                        binding.group
+                    )
+                })?;
+            binding.check_layout_entry(entry)?;
+        }
+        Ok(())
+    }
+
+    fn check_entry_point(&self, entry_point: &str) -> Result<(), String> {
+        match self.entry_point(entry_point) {
+            Some(_) => Ok(()),
+            None => Err(format!("shader has no entry point `{}`", entry_point)),
+        }
+    }
+
+    fn binding_type(
+        &self,
+        space: AddressSpace,
+        ty: naga::Handle<naga::Type>,
+    ) -> Option<BindingType> {
+        let inner = &self.module.types[ty].inner;
+        let binding_type = match space {
+            AddressSpace::Uniform => BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(inner.size(self.module.to_ctx()) as u64),
+            },
+            AddressSpace::Storage { access } => BindingType::Buffer {
+                ty: BufferBindingType::Storage {
+                    read_only: !access.contains(StorageAccess::STORE),
+                },
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(inner.size(self.module.to_ctx()) as u64),
+            },
+            AddressSpace::Handle => match *inner {
+                TypeInner::Sampler { comparison } => BindingType::Sampler(if comparison {
+                    SamplerBindingType::Comparison
+                } else {
+                    SamplerBindingType::Filtering
+                }),
+                TypeInner::Image {
+                    dim,
+                    arrayed,
+                    class,
+                } => {
+                    let view_dimension = match (dim, arrayed) {
+                        (ImageDimension::D1, _) => TextureViewDimension::D1,
+                        (ImageDimension::D2, false) => TextureViewDimension::D2,
+                        (ImageDimension::D2, true) => TextureViewDimension::D2Array,
+                        (ImageDimension::D3, _) => TextureViewDimension::D3,
+                        (ImageDimension::Cube, false) => TextureViewDimension::Cube,
+                        (ImageDimension::Cube, true) => TextureViewDimension::CubeArray,
+                    };
+                    match class {
+                        ImageClass::Sampled { kind, multi } => BindingType::Texture {
+                            sample_type: match kind {
+                                // Multisampled textures can't be filtered.
+                                ScalarKind::Float => {
+                                    TextureSampleType::Float { filterable: !multi }
+                                }
+                                ScalarKind::Sint => TextureSampleType::Sint,
+                                ScalarKind::Uint => TextureSampleType::Uint,
+                                ScalarKind::Bool => return None,
+                            },
+                            view_dimension,
+                            multisampled: multi,
+                        },
+                        ImageClass::Depth { multi } => BindingType::Texture {
+                            sample_type: TextureSampleType::Depth,
+                            view_dimension,
+                            multisampled: multi,
+                        },
+                        ImageClass::Storage { format, access } => BindingType::StorageTexture {
+                            access: if access == StorageAccess::LOAD {
+                                StorageTextureAccess::ReadOnly
+                            } else if access == StorageAccess::STORE {
+                                StorageTextureAccess::WriteOnly
+                            } else {
+                                StorageTextureAccess::ReadWrite
+                            },
+                            format: texture_format(format),
+                            view_dimension,
+                        },
+                    }
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(binding_type)
+    }
+}
+
+// The shader capabilities a device with `features` and `downlevel_flags` can
+// run, the same way wgpu decides them when it creates a shader module.
+pub fn device_capabilities(features: Features, downlevel_flags: DownlevelFlags) -> Capabilities {
+    let mut capabilities = Capabilities::empty();
+    capabilities.set(
+        Capabilities::PUSH_CONSTANT,
+        features.contains(Features::PUSH_CONSTANTS),
+    );
+    capabilities.set(
+        Capabilities::FLOAT64,
+        features.contains(Features::SHADER_F64),
+    );
+    capabilities.set(
+        Capabilities::PRIMITIVE_INDEX,
+        features.contains(Features::SHADER_PRIMITIVE_INDEX),
+    );
+    capabilities.set(
+        Capabilities::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
+        features.contains(Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING),
+    );
+    capabilities.set(
+        Capabilities::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
+        features.contains(Features::UNIFORM_BUFFER_AND_STORAGE_TEXTURE_ARRAY_NON_UNIFORM_INDEXING),
+    );
+    // wgpu enables both with the one feature.
+    capabilities.set(
+        Capabilities::SAMPLER_NON_UNIFORM_INDEXING,
+        features.contains(Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING),
+    );
+    capabilities.set(
+        Capabilities::STORAGE_TEXTURE_16BIT_NORM_FORMATS,
+        features.contains(Features::TEXTURE_FORMAT_16BIT_NORM),
+    );
+    capabilities.set(
+        Capabilities::MULTIVIEW,
+        features.contains(Features::MULTIVIEW),
+    );
+    capabilities.set(
+        Capabilities::EARLY_DEPTH_TEST,
+        features.contains(Features::SHADER_EARLY_DEPTH_TEST),
+    );
+    capabilities.set(
+        Capabilities::MULTISAMPLED_SHADING,
+        downlevel_flags.contains(DownlevelFlags::MULTISAMPLED_SHADING),
+    );
+    capabilities
+}
+
+// naga nests the cause of a validation error, such as the expression at fault
+// inside the function that is invalid.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message = format!("{}: {}", message, error);
+        source = error.source();
+    }
+    message
+}
+
+fn shader_stage(stage: naga::ShaderStage) -> ShaderStages {
+    match stage {
+        naga::ShaderStage::Vertex => ShaderStages::VERTEX,
+        naga::ShaderStage::Fragment => ShaderStages::FRAGMENT,
+        naga::ShaderStage::Compute => ShaderStages::COMPUTE,
+    }
+}
+
+fn vertex_input(binding: &naga::Binding, inner: &TypeInner) -> Option<VertexInput> {
+    let naga::Binding::Location { location, .. } = *binding else {
+        return None;
+    };
+    let (kind, width, components) = match *inner {
+        TypeInner::Scalar { kind, width } => (kind, width, 1),
+        TypeInner::Vector { size, kind, width } => (kind, width, size as u8),
+        _ => return None,
+    };
+    let format = match (kind, width, components) {
+        (ScalarKind::Float, 4, 1) => VertexFormat::Float32,
+        (ScalarKind::Float, 4, 2) => VertexFormat::Float32x2,
+        (ScalarKind::Float, 4, 3) => VertexFormat::Float32x3,
+        (ScalarKind::Float, 4, 4) => VertexFormat::Float32x4,
+        (ScalarKind::Float, 8, 1) => VertexFormat::Float64,
+        (ScalarKind::Float, 8, 2) => VertexFormat::Float64x2,
+        (ScalarKind::Float, 8, 3) => VertexFormat::Float64x3,
+        (ScalarKind::Float, 8, 4) => VertexFormat::Float64x4,
+        (ScalarKind::Sint, 4, 1) => VertexFormat::Sint32,
+        (ScalarKind::Sint, 4, 2) => VertexFormat::Sint32x2,
+        (ScalarKind::Sint, 4, 3) => VertexFormat::Sint32x3,
+        (ScalarKind::Sint, 4, 4) => VertexFormat::Sint32x4,
+        (ScalarKind::Uint, 4, 1) => VertexFormat::Uint32,
+        (ScalarKind::Uint, 4, 2) => VertexFormat::Uint32x2,
+        (ScalarKind::Uint, 4, 3) => VertexFormat::Uint32x3,
+        (ScalarKind::Uint, 4, 4) => VertexFormat::Uint32x4,
+        _ => return None,
+    };
+    Some(VertexInput { location, format })
+}
+
+// Normalized formats are read as floats, only the scalar kind has to match the
+// shader.
+fn vertex_format_kind(format: VertexFormat) -> ScalarKind {
+    match format {
+        VertexFormat::Uint8x2
+        | VertexFormat::Uint8x4
+        | VertexFormat::Uint16x2
+        | VertexFormat::Uint16x4
+        | VertexFormat::Uint32
+        | VertexFormat::Uint32x2
+        | VertexFormat::Uint32x3
+        | VertexFormat::Uint32x4 => ScalarKind::Uint,
+        VertexFormat::Sint8x2
+        | VertexFormat::Sint8x4
+        | VertexFormat::Sint16x2
+        | VertexFormat::Sint16x4
+        | VertexFormat::Sint32
+        | VertexFormat::Sint32x2
+        | VertexFormat::Sint32x3
+        | VertexFormat::Sint32x4 => ScalarKind::Sint,
+        _ => ScalarKind::Float,
+    }
+}
+
+fn texture_format(format: naga::StorageFormat) -> TextureFormat {
+    use naga::StorageFormat as Sf;
+
+    match format {
+        Sf::R8Unorm => TextureFormat::R8Unorm,
+        Sf::R8Snorm => TextureFormat::R8Snorm,
+        Sf::R8Uint => TextureFormat::R8Uint,
+        Sf::R8Sint => TextureFormat::R8Sint,
+        Sf::R16Uint => TextureFormat::R16Uint,
+        Sf::R16Sint => TextureFormat::R16Sint,
+        Sf::R16Float => TextureFormat::R16Float,
+        Sf::Rg8Unorm => TextureFormat::Rg8Unorm,
+        Sf::Rg8Snorm => TextureFormat::Rg8Snorm,
+        Sf::Rg8Uint => TextureFormat::Rg8Uint,
+        Sf::Rg8Sint => TextureFormat::Rg8Sint,
+        Sf::R32Uint => TextureFormat::R32Uint,
+        Sf::R32Sint => TextureFormat::R32Sint,
+        Sf::R32Float => TextureFormat::R32Float,
+        Sf::Rg16Uint => TextureFormat::Rg16Uint,
+        Sf::Rg16Sint => TextureFormat::Rg16Sint,
+        Sf::Rg16Float => TextureFormat::Rg16Float,
+        Sf::Rgba8Unorm => TextureFormat::Rgba8Unorm,
+        Sf::Rgba8Snorm => TextureFormat::Rgba8Snorm,
+        Sf::Rgba8Uint => TextureFormat::Rgba8Uint,
+        Sf::Rgba8Sint => TextureFormat::Rgba8Sint,
+        Sf::Rgb10a2Unorm => TextureFormat::Rgb10a2Unorm,
+        Sf::Rg11b10Float => TextureFormat::Rg11b10Float,
+        Sf::Rg32Uint => TextureFormat::Rg32Uint,
+        Sf::Rg32Sint => TextureFormat::Rg32Sint,
+        Sf::Rg32Float => TextureFormat::Rg32Float,
+        Sf::Rgba16Uint => TextureFormat::Rgba16Uint,
+        Sf::Rgba16Sint => TextureFormat::Rgba16Sint,
+        Sf::Rgba16Float => TextureFormat::Rgba16Float,
+        Sf::Rgba32Uint => TextureFormat::Rgba32Uint,
+        Sf::Rgba32Sint => TextureFormat::Rgba32Sint,
+        Sf::Rgba32Float => TextureFormat::Rgba32Float,
+        Sf::R16Unorm => TextureFormat::R16Unorm,
+        Sf::R16Snorm => TextureFormat::R16Snorm,
+        Sf::Rg16Unorm => TextureFormat::Rg16Unorm,
+        Sf::Rg16Snorm => TextureFormat::Rg16Snorm,
+        Sf::Rgba16Unorm => TextureFormat::Rgba16Unorm,
+        Sf::Rgba16Snorm => TextureFormat::Rgba16Snorm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::{BufferAddress, VertexAttribute, VertexStepMode};
+
+    use super::*;
+
+    const SHADER: &str = "
+struct VertexInput {
+    @location(1) uv: vec2<f32>,
+    @location(0) position: vec3<f32>,
+};
+
+struct Scene {
+    viewProjection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> scene: Scene;
+@group(1) @binding(0) var colorTexture: texture_2d<f32>;
+@group(1) @binding(1) var colorSampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(input: VertexInput, @location(2) instance: u32) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = scene.viewProjection * vec4<f32>(input.position, f32(instance));
+    output.uv = input.uv;
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(colorTexture, colorSampler, input.uv);
+}
+";
+
+    fn parse(source: &str) -> Result<ShaderReflection, ShaderError> {
+        ShaderReflection::parse(Some("test.wgsl"), source, Capabilities::empty())
+    }
+
+    fn vertex_buffer_layout(attributes: &[VertexAttribute]) -> VertexBufferLayout<'_> {
+        VertexBufferLayout {
+            array_stride: 32 as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes,
+        }
+    }
+
+    fn texture_entry(binding: u32) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }
+    }
+
+    fn sampler_entry(binding: u32) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        }
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_line_and_column() {
+        let source = "fn main() {\n    let x: f32 = 1.0;\n    let y = x +;\n}\n";
+        let error = parse(source).err().expect("shader to be invalid");
+
+        assert_eq!(error.label(), Some("test.wgsl"));
+        assert_eq!(error.line(), Some(3));
+        assert_eq!(error.column(), Some(16));
+        assert!(error.to_string().starts_with("test.wgsl:3:16: "));
+        assert!(error.report().contains("test.wgsl:3:16"));
+    }
+
+    #[test]
+    fn vertex_inputs_are_ordered_by_location() {
+        let shader = parse(SHADER).unwrap();
+
+        assert_eq!(
+            shader.vertex_inputs("vs_main"),
+            vec![
+                VertexInput {
+                    location: 0,
+                    format: VertexFormat::Float32x3,
+                },
+                VertexInput {
+                    location: 1,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexInput {
+                    location: 2,
+                    format: VertexFormat::Uint32,
+                },
+            ]
+        );
+        assert!(shader.vertex_inputs("missing").is_empty());
+    }
+
+    #[test]
+    fn bindings_follow_the_entry_points_using_them() {
+        let shader = parse(SHADER).unwrap();
+
+        let bindings = shader.bindings(&["vs_main", "fs_main"]);
+        let names: Vec<_> = bindings
+            .iter()
+            .map(|binding| (binding.name.as_deref(), binding.group, binding.binding))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (Some("scene"), 0, 0),
+                (Some("colorTexture"), 1, 0),
+                (Some("colorSampler"), 1, 1),
+            ]
+        );
+        assert_eq!(bindings[0].visibility, ShaderStages::VERTEX);
+        assert_eq!(
+            bindings[0].ty,
+            BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(64),
+            }
+        );
+        assert_eq!(bindings[1].layout_entry(), texture_entry(0));
+        assert_eq!(bindings[2].layout_entry(), sampler_entry(1));
+
+        assert_eq!(shader.bindings(&["vs_main"]).len(), 1);
+        assert_eq!(shader.bind_group_count(&["vs_main"]), 1);
+        assert_eq!(shader.bind_group_count(&["vs_main", "fs_main"]), 2);
+    }
+
+    #[test]
+    fn check_vertex_buffers_names_the_location() {
+        let shader = parse(SHADER).unwrap();
+        let attributes = [
+            VertexAttribute {
+                format: VertexFormat::Float32x3,
+                offset: 0,
+                shader_location: 0,
+            },
+            VertexAttribute {
+                format: VertexFormat::Float32x2,
+                offset: 12,
+                shader_location: 1,
+            },
+        ];
+        let instance_attributes = [VertexAttribute {
+            format: VertexFormat::Uint32,
+            offset: 0,
+            shader_location: 2,
+        }];
+        let wrong_instance_attributes = [VertexAttribute {
+            format: VertexFormat::Float32,
+            offset: 0,
+            shader_location: 2,
+        }];
+
+        assert_eq!(
+            shader.check_vertex_buffers(
+                "vs_main",
+                &[
+                    vertex_buffer_layout(&attributes),
+                    vertex_buffer_layout(&instance_attributes),
+                ],
+            ),
+            Ok(())
+        );
+
+        let error = shader
+            .check_vertex_buffers("vs_main", &[vertex_buffer_layout(&attributes)])
+            .unwrap_err();
+        assert!(error.contains("@location(2)"), "{}", error);
+
+        let error = shader
+            .check_vertex_buffers(
+                "vs_main",
+                &[
+                    vertex_buffer_layout(&attributes),
+                    vertex_buffer_layout(&wrong_instance_attributes),
+                ],
+            )
+            .unwrap_err();
+        assert!(error.contains("@location(2) as Uint32"), "{}", error);
+
+        let error = shader.check_vertex_buffers("missing", &[]).unwrap_err();
+        assert!(error.contains("`missing`"), "{}", error);
+    }
+
+    #[test]
+    fn check_bind_group_layouts_names_the_binding() {
+        let shader = parse(SHADER).unwrap();
+        let entry_points = ["vs_main", "fs_main"];
+        let scene = shader.bind_group_layout_entries(0, &entry_points);
+
+        assert_eq!(
+            shader.check_bind_group_layouts(
+                &entry_points,
+                &[&scene, &[texture_entry(0), sampler_entry(1)]],
+            ),
+            Ok(())
+        );
+
+        let error = shader
+            .check_bind_group_layouts(&entry_points, &[&scene])
+            .unwrap_err();
+        assert!(error.contains("@group(1) @binding(0)"), "{}", error);
+
+        let error = shader
+            .check_bind_group_layouts(&entry_points, &[&scene, &[texture_entry(0)]])
+            .unwrap_err();
+        assert!(
+            error.contains("`colorSampler` @group(1) @binding(1)"),
+            "{}",
+            error
+        );
+
+        let error = shader
+            .check_bind_group_layouts(
+                &entry_points,
+                &[&scene, &[sampler_entry(0), texture_entry(1)]],
+            )
+            .unwrap_err();
+        assert!(
+            error.contains("`colorTexture` @group(1) @binding(0)"),
+            "{}",
+            error
+        );
+
+        let mut vertex_only = texture_entry(0);
+        vertex_only.visibility = ShaderStages::VERTEX;
+        let error = shader
+            .check_bind_group_layouts(&entry_points, &[&scene, &[vertex_only, sampler_entry(1)]])
+            .unwrap_err();
+        assert!(error.contains("visible to"), "{}", error);
+    }
+
+    #[test]
+    fn shaders_are_validated_against_the_device_capabilities() {
+        let source = "@vertex\nfn vs_main() -> @builtin(position) vec4<f32> {\n    return offset;\n}\n\nvar<push_constant> offset: vec4<f32>;\n";
+
+        let capabilities = device_capabilities(Features::empty(), DownlevelFlags::all());
+        let error = ShaderReflection::parse(None, source, capabilities)
+            .err()
+            .expect("push constants to need the feature");
+        assert_eq!(error.line(), Some(6));
+
+        let capabilities = device_capabilities(Features::PUSH_CONSTANTS, DownlevelFlags::all());
+        assert!(ShaderReflection::parse(None, source, capabilities).is_ok());
+    }
+}
@@ -0,0 +1,11 @@
+// The vertex output of the colored examples, which also pass the normal on
+// when VERTEX_NORMAL is defined.
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+#ifdef VERTEX_NORMAL
+    @location(0) vNormal: vec3<f32>,
+    @location(1) vColor: vec4<f32>,
+#else
+    @location(0) vColor: vec4<f32>,
+#endif
+};
@@ -0,0 +1,11 @@
+// The bindings of the examples that draw a single object: the shared scene
+// block and the object's model matrix.
+#include "common/scene_uniforms.wgsl"
+
+struct ModelUniforms {
+    modelMatrix: mat4x4<f32>,
+    normalMatrix: mat4x4<f32>,
+};
+
+@binding(0) @group(0) var<uniform> scene : SceneUniforms;
+@binding(0) @group(1) var<uniform> model : ModelUniforms;
@@ -0,0 +1,119 @@
+const HDR_EXPOSURE_AUTO: u32 = 0u;
+const HDR_EXPOSURE_MANUAL: u32 = 1u;
+const HDR_HISTOGRAM_BINS: u32 = 256u;
+
+struct HdrParams {
+    minLogLuminance: f32,
+    logLuminanceRange: f32,
+    adaptation: f32,
+    compensation: f32,
+    pixelCount: u32,
+    manualExposure: f32,
+    mode: u32,
+    _padding: u32,
+};
+
+struct ExposureState {
+    averageLuminance: f32,
+    exposure: f32,
+};
+
+@binding(0) @group(0) var hdrTexture : texture_2d<f32>;
+@binding(1) @group(0) var<storage, read_write> histogram : array<atomic<u32>, 256>;
+@binding(2) @group(0) var<uniform> params : HdrParams;
+@binding(3) @group(0) var<storage, read_write> exposureState : ExposureState;
+
+var<workgroup> localHistogram : array<atomic<u32>, 256>;
+var<workgroup> weightedCounts : array<f32, 256>;
+
+// Bin 0 holds near black pixels, which are left out of the average, the other
+// bins split the log2 luminance range evenly.
+fn luminanceBin(color: vec3<f32>) -> u32 {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if (luminance < 0.0001) {
+        return 0u;
+    }
+    let logLuminance = clamp((log2(luminance) - params.minLogLuminance) / params.logLuminanceRange, 0.0, 1.0);
+    return u32(logLuminance * 254.0 + 1.0);
+}
+
+@compute @workgroup_size(16, 16)
+fn cs_histogram(@builtin(global_invocation_id) id: vec3<u32>, @builtin(local_invocation_index) index: u32) {
+    atomicStore(&localHistogram[index], 0u);
+    workgroupBarrier();
+
+    let dimensions = textureDimensions(hdrTexture);
+    if (id.x < dimensions.x && id.y < dimensions.y) {
+        let color = textureLoad(hdrTexture, vec2<i32>(id.xy), 0).rgb;
+        atomicAdd(&localHistogram[luminanceBin(color)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[index], atomicLoad(&localHistogram[index]));
+}
+
+// One workgroup of 256 invocations, one per bin. Also clears the histogram for
+// the next frame.
+@compute @workgroup_size(256)
+fn cs_average(@builtin(local_invocation_index) index: u32) {
+    let count = atomicLoad(&histogram[index]);
+    weightedCounts[index] = f32(count) * f32(index);
+    atomicStore(&histogram[index], 0u);
+    workgroupBarrier();
+
+    for (var cutoff = HDR_HISTOGRAM_BINS >> 1u; cutoff > 0u; cutoff = cutoff >> 1u) {
+        if (index < cutoff) {
+            weightedCounts[index] = weightedCounts[index] + weightedCounts[index + cutoff];
+        }
+        workgroupBarrier();
+    }
+
+    if (index == 0u) {
+        // `count` is the number of black pixels here.
+        let lit = max(f32(params.pixelCount) - f32(count), 1.0);
+        let averageBin = weightedCounts[0] / lit - 1.0;
+        let logAverage = averageBin / 254.0 * params.logLuminanceRange + params.minLogLuminance;
+        let average = exp2(logAverage);
+
+        let previous = exposureState.averageLuminance;
+        var adapted = previous + (average - previous) * params.adaptation;
+        if (previous <= 0.0) {
+            adapted = average;
+        }
+        exposureState.averageLuminance = adapted;
+
+        if (params.mode == HDR_EXPOSURE_MANUAL) {
+            exposureState.exposure = params.manualExposure;
+        } else {
+            // Maps the average to middle grey.
+            exposureState.exposure = 0.18 / max(adapted, 0.0001) * exp2(params.compensation);
+        }
+    }
+}
+
+@binding(0) @group(0) var tonemapTexture : texture_2d<f32>;
+@binding(1) @group(0) var tonemapSampler : sampler;
+@binding(2) @group(0) var<storage, read> tonemapExposure : ExposureState;
+
+struct FullscreenOutput {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vUv: vec2<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var output: FullscreenOutput;
+    output.Position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.vUv = uv;
+    return output;
+}
+
+// ACES filmic fit, the sRGB target applies the gamma.
+@fragment
+fn fs_tonemap(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(tonemapTexture, tonemapSampler, vUv);
+    let x = color.rgb * tonemapExposure.exposure;
+    let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    return vec4<f32>(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
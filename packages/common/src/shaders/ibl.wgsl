@@ -0,0 +1,144 @@
+#include "common/pbr.wgsl"
+
+struct IblUniforms {
+    face: u32,
+    roughness: f32,
+    sampleCount: u32,
+    sourceSize: f32,
+};
+
+@group(0) @binding(0) var<uniform> ibl: IblUniforms;
+@group(1) @binding(0) var sourceTexture: texture_cube<f32>;
+@group(1) @binding(1) var sourceSampler: sampler;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vUv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertexIndex: u32) -> Output {
+    // A single triangle covering the whole target.
+    let uv = vec2<f32>(f32((vertexIndex << 1u) & 2u), f32(vertexIndex & 2u));
+    var output: Output;
+    output.Position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.vUv = uv;
+    return output;
+}
+
+// The sampling direction of a texel, in the cubemap's own left-handed space.
+fn faceDirection(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let s = uv.x * 2.0 - 1.0;
+    let t = uv.y * 2.0 - 1.0;
+    var direction: vec3<f32>;
+    switch face {
+        case 0u: { direction = vec3<f32>(1.0, -t, -s); }
+        case 1u: { direction = vec3<f32>(-1.0, -t, s); }
+        case 2u: { direction = vec3<f32>(s, 1.0, t); }
+        case 3u: { direction = vec3<f32>(s, -1.0, -t); }
+        case 4u: { direction = vec3<f32>(s, -t, 1.0); }
+        default: { direction = vec3<f32>(-s, -t, -1.0); }
+    }
+    return normalize(direction);
+}
+
+fn hammersley(index: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(index) / f32(count), f32(reverseBits(index)) * 2.3283064365386963e-10);
+}
+
+fn importanceSampleGgx(xi: vec2<f32>, normal: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let alpha = roughness * roughness;
+    let phi = 2.0 * PBR_PI * xi.x;
+    let cosTheta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    let sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    let halfway = vec3<f32>(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+
+    var up = vec3<f32>(0.0, 0.0, 1.0);
+    if (abs(normal.z) > 0.999) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return normalize(tangent * halfway.x + bitangent * halfway.y + normal * halfway.z);
+}
+
+@fragment
+fn fs_irradiance(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let normal = faceDirection(ibl.face, vUv);
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(normal.y) > 0.999) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    // Steps of about one texel of a 32x32 face, read from the matching mip level.
+    let delta = 0.05;
+    let lod = max(log2(ibl.sourceSize / 32.0), 0.0);
+    var irradiance = vec3<f32>(0.0);
+    var count = 0.0;
+    for (var phi = 0.0; phi < 2.0 * PBR_PI; phi = phi + delta) {
+        for (var theta = 0.0; theta < 0.5 * PBR_PI; theta = theta + delta) {
+            let tangentSample = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let direction = tangentSample.x * right + tangentSample.y * up + tangentSample.z * normal;
+            irradiance = irradiance + textureSampleLevel(sourceTexture, sourceSampler, direction, lod).rgb * cos(theta) * sin(theta);
+            count = count + 1.0;
+        }
+    }
+    return vec4<f32>(PBR_PI * irradiance / count, 1.0);
+}
+
+@fragment
+fn fs_prefilter(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let normal = faceDirection(ibl.face, vUv);
+    if (ibl.roughness <= 0.0) {
+        return vec4<f32>(textureSampleLevel(sourceTexture, sourceSampler, normal, 0.0).rgb, 1.0);
+    }
+
+    // Reads a blurrier mip for unlikely samples to avoid bright speckles.
+    let texelSolidAngle = 4.0 * PBR_PI / (6.0 * ibl.sourceSize * ibl.sourceSize);
+    var color = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < ibl.sampleCount; i = i + 1u) {
+        let halfway = importanceSampleGgx(hammersley(i, ibl.sampleCount), normal, ibl.roughness);
+        let lightDirection = normalize(2.0 * dot(normal, halfway) * halfway - normal);
+        let nDotL = dot(normal, lightDirection);
+        if (nDotL > 0.0) {
+            let nDotH = max(dot(normal, halfway), 0.0);
+            let pdf = distributionGgx(nDotH, ibl.roughness) * 0.25 + 0.0001;
+            let sampleSolidAngle = 1.0 / (f32(ibl.sampleCount) * pdf + 0.0001);
+            let lod = max(0.5 * log2(sampleSolidAngle / texelSolidAngle), 0.0);
+            color = color + textureSampleLevel(sourceTexture, sourceSampler, lightDirection, lod).rgb * nDotL;
+            weight = weight + nDotL;
+        }
+    }
+    return vec4<f32>(color / max(weight, 0.0001), 1.0);
+}
+
+// x is the cosine between normal and view direction, y the roughness.
+@fragment
+fn fs_brdf_lut(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let nDotV = max(vUv.x, 0.0001);
+    let roughness = vUv.y;
+    let viewDirection = vec3<f32>(sqrt(1.0 - nDotV * nDotV), 0.0, nDotV);
+    let normal = vec3<f32>(0.0, 0.0, 1.0);
+    let k = roughness * roughness / 2.0;
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < ibl.sampleCount; i = i + 1u) {
+        let halfway = importanceSampleGgx(hammersley(i, ibl.sampleCount), normal, roughness);
+        let lightDirection = normalize(2.0 * dot(viewDirection, halfway) * halfway - viewDirection);
+        let nDotL = max(lightDirection.z, 0.0);
+        if (nDotL > 0.0) {
+            let nDotH = max(halfway.z, 0.0);
+            let vDotH = max(dot(viewDirection, halfway), 0.0);
+            let visibility = geometrySmith(nDotV, nDotL, k) * vDotH / (nDotH * nDotV);
+            let fresnel = pow(1.0 - vDotH, 5.0);
+            scale = scale + (1.0 - fresnel) * visibility;
+            bias = bias + fresnel * visibility;
+        }
+    }
+    let count = f32(ibl.sampleCount);
+    return vec4<f32>(scale / count, bias / count, 0.0, 1.0);
+}
@@ -0,0 +1,79 @@
+const LIGHT_DIRECTIONAL: u32 = 0u;
+const LIGHT_POINT: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    attenuation: vec3<f32>,
+    innerConeCos: f32,
+    outerConeCos: f32,
+};
+
+struct Lights {
+    ambientColor: vec3<f32>,
+    count: u32,
+    lights: array<Light>,
+};
+
+struct BlinnPhongMaterial {
+    color: vec4<f32>,
+    ambient: f32,
+    diffuse: f32,
+    specular: f32,
+    shininess: f32,
+};
+
+fn lightAttenuation(light: Light, distance: f32) -> f32 {
+    let factors = light.attenuation;
+    var attenuation = 1.0 / max(factors.x + factors.y * distance + factors.z * distance * distance, 0.0001);
+    // Fade smoothly to zero at the range instead of cutting off.
+    if (light.range > 0.0) {
+        let ratio = distance / light.range;
+        let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+        attenuation = attenuation * window * window;
+    }
+    return attenuation;
+}
+
+// Returns the normalized direction from `position` towards the light in xyz and
+// the light's attenuation, including the spot cone, in w.
+fn lightIncidence(light: Light, position: vec3<f32>) -> vec4<f32> {
+    if (light.kind == LIGHT_DIRECTIONAL) {
+        return vec4<f32>(-normalize(light.direction), 1.0);
+    }
+    let toLight = light.position - position;
+    let distance = length(toLight);
+    let lightDirection = toLight / max(distance, 0.0001);
+    var attenuation = lightAttenuation(light, distance);
+    if (light.kind == LIGHT_SPOT) {
+        let cosAngle = dot(-lightDirection, normalize(light.direction));
+        attenuation = attenuation * smoothstep(light.outerConeCos, light.innerConeCos, cosAngle);
+    }
+    return vec4<f32>(lightDirection, attenuation);
+}
+
+// `normal` and `viewDirection` are normalized, `viewDirection` points from the
+// surface towards the eye. Returns the diffuse and specular contribution of one light.
+fn blinnPhongLight(light: Light, material: BlinnPhongMaterial, baseColor: vec3<f32>, position: vec3<f32>, normal: vec3<f32>, viewDirection: vec3<f32>) -> vec3<f32> {
+    let incidence = lightIncidence(light, position);
+    let lightDirection = incidence.xyz;
+    let attenuation = incidence.w;
+
+    let diffuse = max(dot(normal, lightDirection), 0.0);
+    var specular = 0.0;
+    if (diffuse > 0.0) {
+        let halfway = normalize(lightDirection + viewDirection);
+        specular = pow(max(dot(normal, halfway), 0.0), material.shininess);
+    }
+    let radiance = light.color * light.intensity * attenuation;
+    return radiance * (baseColor * material.diffuse * diffuse + vec3<f32>(material.specular * specular));
+}
+
+fn blinnPhongAmbient(ambientColor: vec3<f32>, material: BlinnPhongMaterial, baseColor: vec3<f32>) -> vec3<f32> {
+    return ambientColor * baseColor * material.ambient;
+}
@@ -0,0 +1,22 @@
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vUv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertexIndex: u32) -> Output {
+    // A single triangle covering the whole target.
+    let uv = vec2<f32>(f32((vertexIndex << 1u) & 2u), f32(vertexIndex & 2u));
+    var output: Output;
+    output.Position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.vUv = uv;
+    return output;
+}
+
+@group(0) @binding(0) var sourceTexture: texture_2d<f32>;
+@group(0) @binding(1) var sourceSampler: sampler;
+
+@fragment
+fn fs_main(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    return textureSample(sourceTexture, sourceSampler, vUv);
+}
@@ -0,0 +1,209 @@
+const PARTICLE_MAX_EMITTERS: u32 = 16u;
+const PARTICLE_SHAPE_POINT: u32 = 0u;
+const PARTICLE_SHAPE_SPHERE: u32 = 1u;
+const PARTICLE_SHAPE_CONE: u32 = 2u;
+const PARTICLE_PI: f32 = 3.14159265;
+
+struct Particle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+    // Zero for dead particles.
+    lifetime: f32,
+    emitter: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+};
+
+struct Emitter {
+    position: vec3<f32>,
+    shape: u32,
+    direction: vec3<f32>,
+    coneCos: f32,
+    startColor: vec4<f32>,
+    endColor: vec4<f32>,
+    speed: vec2<f32>,
+    lifetime: vec2<f32>,
+    radius: f32,
+    startSize: f32,
+    endSize: f32,
+    spawnRate: f32,
+    firstSlot: u32,
+    slotCount: u32,
+    spawnOffset: u32,
+    spawnCount: u32,
+};
+
+struct ParticleSimulation {
+    gravity: vec3<f32>,
+    drag: f32,
+    deltaTime: f32,
+    time: f32,
+    seed: u32,
+    emitterCount: u32,
+};
+
+struct ParticleCamera {
+    viewMatrix: mat4x4<f32>,
+    projectionMatrix: mat4x4<f32>,
+};
+
+@binding(0) @group(0) var<storage, read_write> particles : array<Particle>;
+@binding(1) @group(0) var<uniform> simulation : ParticleSimulation;
+@binding(2) @group(0) var<uniform> emitters : array<Emitter, PARTICLE_MAX_EMITTERS>;
+
+var<private> rngState : u32;
+
+fn pcgHash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random() -> f32 {
+    rngState = pcgHash(rngState);
+    return f32(rngState) / 4294967295.0;
+}
+
+fn randomRange(range: vec2<f32>) -> f32 {
+    return mix(range.x, range.y, random());
+}
+
+fn randomUnitVector() -> vec3<f32> {
+    let z = random() * 2.0 - 1.0;
+    let phi = random() * 2.0 * PARTICLE_PI;
+    let r = sqrt(max(1.0 - z * z, 0.0));
+    return vec3<f32>(r * cos(phi), r * sin(phi), z);
+}
+
+fn spawnParticle(emitterIndex: u32) -> Particle {
+    let emitter = emitters[emitterIndex];
+    var position = emitter.position;
+    var direction = randomUnitVector();
+
+    if (emitter.shape == PARTICLE_SHAPE_SPHERE) {
+        position = position + direction * emitter.radius * pow(random(), 1.0 / 3.0);
+    } else if (emitter.shape == PARTICLE_SHAPE_CONE) {
+        let axis = normalize(emitter.direction);
+        var up = vec3<f32>(0.0, 1.0, 0.0);
+        if (abs(axis.y) > 0.99) {
+            up = vec3<f32>(1.0, 0.0, 0.0);
+        }
+        let tangent = normalize(cross(up, axis));
+        let bitangent = cross(axis, tangent);
+
+        let cosTheta = mix(1.0, emitter.coneCos, random());
+        let sinTheta = sqrt(max(1.0 - cosTheta * cosTheta, 0.0));
+        let phi = random() * 2.0 * PARTICLE_PI;
+        direction = (tangent * cos(phi) + bitangent * sin(phi)) * sinTheta + axis * cosTheta;
+
+        let discAngle = random() * 2.0 * PARTICLE_PI;
+        let discRadius = emitter.radius * sqrt(random());
+        position = position + (tangent * cos(discAngle) + bitangent * sin(discAngle)) * discRadius;
+    }
+
+    var particle: Particle;
+    particle.position = position;
+    particle.age = 0.0;
+    particle.velocity = direction * randomRange(emitter.speed);
+    particle.lifetime = max(randomRange(emitter.lifetime), 0.001);
+    particle.emitter = emitterIndex;
+    return particle;
+}
+
+@compute @workgroup_size(64)
+fn cs_simulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= arrayLength(&particles)) {
+        return;
+    }
+    rngState = pcgHash(index ^ simulation.seed);
+
+    var particle = particles[index];
+    var step = simulation.deltaTime;
+
+    // Each emitter owns a ring of slots and respawns the next `spawnCount` of
+    // them every frame, overwriting the oldest particles.
+    for (var i = 0u; i < simulation.emitterCount; i = i + 1u) {
+        let emitter = emitters[i];
+        if (index >= emitter.firstSlot && index - emitter.firstSlot < emitter.slotCount) {
+            let ring = (index - emitter.firstSlot + emitter.slotCount - emitter.spawnOffset) % emitter.slotCount;
+            if (ring < emitter.spawnCount) {
+                particle = spawnParticle(i);
+                // Spreads the spawns over the frame instead of emitting in bursts.
+                step = simulation.deltaTime * random();
+            }
+            break;
+        }
+    }
+
+    if (particle.lifetime > 0.0) {
+        particle.velocity = (particle.velocity + simulation.gravity * step) * max(1.0 - simulation.drag * step, 0.0);
+        particle.position = particle.position + particle.velocity * step;
+        particle.age = particle.age + step;
+        if (particle.age >= particle.lifetime) {
+            particle.lifetime = 0.0;
+        }
+    }
+    particles[index] = particle;
+}
+
+@binding(0) @group(0) var<uniform> camera : ParticleCamera;
+
+struct ParticleOutput {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vColor: vec4<f32>,
+    @location(1) vUv: vec2<f32>,
+};
+
+fn particleOutput(positionAge: vec4<f32>, velocityLifetime: vec4<f32>, emitterIndex: u32, corner: vec2<f32>) -> ParticleOutput {
+    var output: ParticleOutput;
+    if (velocityLifetime.w <= 0.0) {
+        // Outside the clip volume, so dead particles are culled.
+        output.Position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        output.vColor = vec4<f32>(0.0);
+        output.vUv = vec2<f32>(0.0);
+        return output;
+    }
+
+    let emitter = emitters[emitterIndex];
+    let t = clamp(positionAge.w / velocityLifetime.w, 0.0, 1.0);
+    let size = mix(emitter.startSize, emitter.endSize, t);
+    // The first two rows of the view matrix are the camera's right and up axes.
+    let right = vec3<f32>(camera.viewMatrix[0].x, camera.viewMatrix[1].x, camera.viewMatrix[2].x);
+    let up = vec3<f32>(camera.viewMatrix[0].y, camera.viewMatrix[1].y, camera.viewMatrix[2].y);
+    let position = positionAge.xyz + (right * corner.x + up * corner.y) * size * 0.5;
+
+    output.Position = camera.projectionMatrix * camera.viewMatrix * vec4<f32>(position, 1.0);
+    output.vColor = mix(emitter.startColor, emitter.endColor, t);
+    output.vUv = corner * 0.5 + vec2<f32>(0.5);
+    return output;
+}
+
+// Four vertices per instance, drawn as a triangle strip.
+@vertex
+fn vs_billboard(
+    @builtin(vertex_index) index: u32,
+    @location(0) positionAge: vec4<f32>,
+    @location(1) velocityLifetime: vec4<f32>,
+    @location(2) emitterIndex: u32,
+) -> ParticleOutput {
+    let corner = vec2<f32>(f32(index & 1u) * 2.0 - 1.0, f32((index >> 1u) & 1u) * 2.0 - 1.0);
+    return particleOutput(positionAge, velocityLifetime, emitterIndex, corner);
+}
+
+@vertex
+fn vs_point(
+    @location(0) positionAge: vec4<f32>,
+    @location(1) velocityLifetime: vec4<f32>,
+    @location(2) emitterIndex: u32,
+) -> ParticleOutput {
+    return particleOutput(positionAge, velocityLifetime, emitterIndex, vec2<f32>(0.0));
+}
+
+@fragment
+fn fs_particle(@location(0) vColor: vec4<f32>, @location(1) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let falloff = 1.0 - smoothstep(0.0, 1.0, length(vUv * 2.0 - vec2<f32>(1.0)));
+    return vec4<f32>(vColor.rgb, vColor.a * falloff);
+}
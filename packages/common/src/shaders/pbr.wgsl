@@ -0,0 +1,99 @@
+#include "common/lighting.wgsl"
+
+const PBR_PI: f32 = 3.14159265359;
+const PBR_NORMAL_TEXTURE: u32 = 1u;
+
+struct PbrMaterialUniforms {
+    baseColorFactor: vec4<f32>,
+    emissiveFactor: vec3<f32>,
+    metallicFactor: f32,
+    roughnessFactor: f32,
+    normalScale: f32,
+    occlusionStrength: f32,
+    alphaCutoff: f32,
+    flags: u32,
+};
+
+fn distributionGgx(nDotH: f32, roughness: f32) -> f32 {
+    let alpha = roughness * roughness;
+    let alpha2 = alpha * alpha;
+    let denominator = nDotH * nDotH * (alpha2 - 1.0) + 1.0;
+    return alpha2 / max(PBR_PI * denominator * denominator, 0.0000001);
+}
+
+fn geometrySchlickGgx(nDotX: f32, k: f32) -> f32 {
+    return nDotX / (nDotX * (1.0 - k) + k);
+}
+
+// Direct lighting remaps roughness with k = (r + 1)^2 / 8, image based lighting
+// with k = r^2 / 2.
+fn geometrySmith(nDotV: f32, nDotL: f32, k: f32) -> f32 {
+    return geometrySchlickGgx(nDotV, k) * geometrySchlickGgx(nDotL, k);
+}
+
+fn fresnelSchlick(cosTheta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+fn fresnelSchlickRoughness(cosTheta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// Builds the tangent frame from screen space derivatives, so meshes need no
+// tangent attribute. `tangentNormal` is the normal map sample remapped to [-1, 1].
+fn perturbNormal(normal: vec3<f32>, position: vec3<f32>, uv: vec2<f32>, tangentNormal: vec3<f32>) -> vec3<f32> {
+    let dp1 = dpdx(position);
+    let dp2 = dpdy(position);
+    let duv1 = dpdx(uv);
+    let duv2 = dpdy(uv);
+    let dp2Perp = cross(dp2, normal);
+    let dp1Perp = cross(normal, dp1);
+    let tangent = dp2Perp * duv1.x + dp1Perp * duv2.x;
+    let bitangent = dp2Perp * duv1.y + dp1Perp * duv2.y;
+    let scale = max(dot(tangent, tangent), dot(bitangent, bitangent));
+    if (scale <= 0.0) {
+        return normal;
+    }
+    let invMax = inverseSqrt(scale);
+    return normalize(mat3x3<f32>(tangent * invMax, bitangent * invMax, normal) * tangentNormal);
+}
+
+// Cook-Torrance specular with a Lambertian diffuse term for one light direction.
+fn cookTorrance(lightDirection: vec3<f32>, radiance: vec3<f32>, albedo: vec3<f32>, metallic: f32, roughness: f32, normal: vec3<f32>, viewDirection: vec3<f32>) -> vec3<f32> {
+    let halfway = normalize(lightDirection + viewDirection);
+    let nDotL = max(dot(normal, lightDirection), 0.0);
+    let nDotV = max(dot(normal, viewDirection), 0.0001);
+    let nDotH = max(dot(normal, halfway), 0.0);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let fresnel = fresnelSchlick(max(dot(halfway, viewDirection), 0.0), f0);
+    let specular = distributionGgx(nDotH, roughness) * geometrySmith(nDotV, nDotL, k) * fresnel / (4.0 * nDotV * max(nDotL, 0.0001));
+    let diffuse = (vec3<f32>(1.0) - fresnel) * (1.0 - metallic) * albedo / PBR_PI;
+    return (diffuse + specular) * radiance * nDotL;
+}
+
+fn pbrLight(light: Light, albedo: vec3<f32>, metallic: f32, roughness: f32, position: vec3<f32>, normal: vec3<f32>, viewDirection: vec3<f32>) -> vec3<f32> {
+    let incidence = lightIncidence(light, position);
+    let radiance = light.color * light.intensity * incidence.w;
+    return cookTorrance(incidence.xyz, radiance, albedo, metallic, roughness, normal, viewDirection);
+}
+
+// Split sum image based lighting. The cubemaps are sampled with the left-handed
+// `vec3(d.x, d.y, -d.z)` convention of `commonlib::texture::cubemap`.
+fn pbrAmbient(irradianceTexture: texture_cube<f32>, prefilteredTexture: texture_cube<f32>, environmentSampler: sampler, brdfLut: texture_2d<f32>, brdfLutSampler: sampler, albedo: vec3<f32>, metallic: f32, roughness: f32, occlusion: f32, normal: vec3<f32>, viewDirection: vec3<f32>) -> vec3<f32> {
+    let nDotV = max(dot(normal, viewDirection), 0.0);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let fresnel = fresnelSchlickRoughness(nDotV, f0, roughness);
+
+    let irradiance = textureSampleLevel(irradianceTexture, environmentSampler, vec3<f32>(normal.x, normal.y, -normal.z), 0.0).rgb;
+    let diffuse = (vec3<f32>(1.0) - fresnel) * (1.0 - metallic) * irradiance * albedo;
+
+    let reflected = reflect(-viewDirection, normal);
+    let maxLod = f32(textureNumLevels(prefilteredTexture) - 1u);
+    let prefiltered = textureSampleLevel(prefilteredTexture, environmentSampler, vec3<f32>(reflected.x, reflected.y, -reflected.z), roughness * maxLod).rgb;
+    let brdf = textureSampleLevel(brdfLut, brdfLutSampler, vec2<f32>(nDotV, roughness), 0.0).rg;
+    let specular = prefiltered * (fresnel * brdf.x + brdf.y);
+
+    return (diffuse + specular) * occlusion;
+}
@@ -0,0 +1,107 @@
+#include "common/post_process.wgsl"
+
+@fragment
+fn fs_copy(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    return textureSample(sourceTexture, sourceSampler, vUv);
+}
+
+// params.x is the exposure.
+@fragment
+fn fs_tonemap_reinhard(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(sourceTexture, sourceSampler, vUv);
+    let exposed = color.rgb * post.params.x;
+    return vec4<f32>(exposed / (vec3<f32>(1.0) + exposed), color.a);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+@fragment
+fn fs_tonemap_aces(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(sourceTexture, sourceSampler, vUv);
+    let x = color.rgb * post.params.x;
+    let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    return vec4<f32>(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+}
+
+// params.x is the gamma.
+@fragment
+fn fs_gamma(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(sourceTexture, sourceSampler, vUv);
+    return vec4<f32>(pow(max(color.rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / post.params.x)), color.a);
+}
+
+const FXAA_REDUCE_MIN: f32 = 0.0078125;
+const FXAA_REDUCE_MUL: f32 = 0.125;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+// The lightweight FXAA variant: blur along the local edge direction and fall
+// back to a narrower blur when the wide one leaves the neighbourhood's range.
+// Expects tonemapped input.
+@fragment
+fn fs_fxaa(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let texel = post.texelSize;
+    let color = textureSample(sourceTexture, sourceSampler, vUv);
+    let lumaNw = postLuminance(textureSample(sourceTexture, sourceSampler, vUv + vec2<f32>(-1.0, -1.0) * texel).rgb);
+    let lumaNe = postLuminance(textureSample(sourceTexture, sourceSampler, vUv + vec2<f32>(1.0, -1.0) * texel).rgb);
+    let lumaSw = postLuminance(textureSample(sourceTexture, sourceSampler, vUv + vec2<f32>(-1.0, 1.0) * texel).rgb);
+    let lumaSe = postLuminance(textureSample(sourceTexture, sourceSampler, vUv + vec2<f32>(1.0, 1.0) * texel).rgb);
+    let lumaM = postLuminance(color.rgb);
+    let lumaMin = min(lumaM, min(min(lumaNw, lumaNe), min(lumaSw, lumaSe)));
+    let lumaMax = max(lumaM, max(max(lumaNw, lumaNe), max(lumaSw, lumaSe)));
+
+    var direction = vec2<f32>(-((lumaNw + lumaNe) - (lumaSw + lumaSe)), (lumaNw + lumaSw) - (lumaNe + lumaSe));
+    let reduce = max((lumaNw + lumaNe + lumaSw + lumaSe) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let inverseMin = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * inverseMin, vec2<f32>(-FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX)) * texel;
+
+    let narrow = 0.5 * (textureSample(sourceTexture, sourceSampler, vUv + direction * (1.0 / 3.0 - 0.5)).rgb
+        + textureSample(sourceTexture, sourceSampler, vUv + direction * (2.0 / 3.0 - 0.5)).rgb);
+    let wide = narrow * 0.5 + 0.25 * (textureSample(sourceTexture, sourceSampler, vUv - direction * 0.5).rgb
+        + textureSample(sourceTexture, sourceSampler, vUv + direction * 0.5).rgb);
+    let lumaWide = postLuminance(wide);
+    let outside = lumaWide < lumaMin || lumaWide > lumaMax;
+    return vec4<f32>(select(wide, narrow, outside), color.a);
+}
+
+// params.x is the threshold, params.y the soft knee around it.
+@fragment
+fn fs_bloom_threshold(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(sourceTexture, sourceSampler, vUv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = max(post.params.y, 0.0001);
+    var soft = clamp(brightness - post.params.x + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - post.params.x) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+// params.xy is the direction, in texels.
+@fragment
+fn fs_bloom_blur(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let step = post.params.xy * post.texelSize;
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    var color = textureSample(sourceTexture, sourceSampler, vUv).rgb * weights[0];
+    for (var i = 1; i < 5; i = i + 1) {
+        let offset = step * f32(i);
+        color = color + textureSample(sourceTexture, sourceSampler, vUv + offset).rgb * weights[i];
+        color = color + textureSample(sourceTexture, sourceSampler, vUv - offset).rgb * weights[i];
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+// params.x is the intensity.
+@fragment
+fn fs_bloom_composite(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(sourceTexture, sourceSampler, vUv);
+    let bloom = textureSample(auxTexture, sourceSampler, vUv).rgb;
+    return vec4<f32>(color.rgb + bloom * post.params.x, color.a);
+}
+
+// params.x is the strength, params.y the radius where darkening starts and
+// params.z the width of the falloff, both relative to the half diagonal.
+@fragment
+fn fs_vignette(@location(0) vUv: vec2<f32>) -> @location(0) vec4<f32> {
+    let color = textureSample(sourceTexture, sourceSampler, vUv);
+    let distance = length(vUv - vec2<f32>(0.5)) * 1.41421356;
+    let falloff = smoothstep(post.params.y, post.params.y + post.params.z, distance);
+    return vec4<f32>(color.rgb * (1.0 - post.params.x * falloff), color.a);
+}
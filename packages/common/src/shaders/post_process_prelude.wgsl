@@ -0,0 +1,30 @@
+struct PostUniforms {
+    params: vec4<f32>,
+    texelSize: vec2<f32>,
+    time: f32,
+    _padding: f32,
+};
+
+@binding(0) @group(0) var sourceTexture : texture_2d<f32>;
+@binding(1) @group(0) var sourceSampler : sampler;
+// The bloom texture for the bloom composite, the source again otherwise.
+@binding(2) @group(0) var auxTexture : texture_2d<f32>;
+@binding(0) @group(1) var<uniform> post : PostUniforms;
+
+struct FullscreenOutput {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vUv: vec2<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var output: FullscreenOutput;
+    output.Position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    output.vUv = uv;
+    return output;
+}
+
+fn postLuminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
@@ -0,0 +1,12 @@
+struct SceneUniforms {
+    viewMatrix: mat4x4<f32>,
+    projectionMatrix: mat4x4<f32>,
+    viewProjectionMatrix: mat4x4<f32>,
+    inverseViewMatrix: mat4x4<f32>,
+    inverseProjectionMatrix: mat4x4<f32>,
+    inverseViewProjectionMatrix: mat4x4<f32>,
+    cameraPosition: vec4<f32>,
+    viewportSize: vec2<f32>,
+    time: f32,
+    _padding: f32,
+};
@@ -0,0 +1,46 @@
+const SHADOW_MAX_CASCADES: u32 = 4u;
+
+struct ShadowUniforms {
+    lightViewProjections: array<mat4x4<f32>, 4>,
+    cascadeSplits: vec4<f32>,
+    cascadeCount: u32,
+    pcfRadius: i32,
+    normalBias: f32,
+    texelSize: f32,
+};
+
+// `viewDepth` is the positive distance along the camera's forward axis.
+fn shadowCascade(shadow: ShadowUniforms, viewDepth: f32) -> u32 {
+    var cascade = 0u;
+    for (var i = 0u; i + 1u < min(shadow.cascadeCount, SHADOW_MAX_CASCADES); i = i + 1u) {
+        if (viewDepth > shadow.cascadeSplits[i]) {
+            cascade = i + 1u;
+        }
+    }
+    return cascade;
+}
+
+// Returns how much of the light reaches `position`, 0 fully shadowed and 1 fully
+// lit, averaged over a (2 * pcfRadius + 1)² kernel of hardware compared samples.
+fn shadowFactor(shadowMap: texture_depth_2d_array, shadowSampler: sampler_comparison, shadow: ShadowUniforms, position: vec3<f32>, normal: vec3<f32>, viewDepth: f32) -> f32 {
+    let cascade = shadowCascade(shadow, viewDepth);
+    var matrices = shadow.lightViewProjections;
+    // Pushing the lookup out along the normal hides acne on surfaces at grazing angles.
+    let lightPosition = matrices[cascade] * vec4<f32>(position + normal * shadow.normalBias, 1.0);
+    let ndc = lightPosition.xyz / lightPosition.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, -ndc.y * 0.5 + 0.5);
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    var lit = 0.0;
+    var count = 0.0;
+    for (var y = -shadow.pcfRadius; y <= shadow.pcfRadius; y = y + 1) {
+        for (var x = -shadow.pcfRadius; x <= shadow.pcfRadius; x = x + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadow.texelSize;
+            lit = lit + textureSampleCompareLevel(shadowMap, shadowSampler, uv + offset, i32(cascade), ndc.z);
+            count = count + 1.0;
+        }
+    }
+    return lit / count;
+}
@@ -0,0 +1,32 @@
+struct SkyboxUniforms {
+    inverseViewProjectionMatrix: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> skybox: SkyboxUniforms;
+@group(0) @binding(1) var skyboxTexture: texture_cube<f32>;
+@group(0) @binding(2) var skyboxSampler: sampler;
+
+struct Output {
+    @builtin(position) Position: vec4<f32>,
+    @location(0) vNdc: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertexIndex: u32) -> Output {
+    // A single triangle covering the screen, pushed onto the far plane.
+    let uv = vec2<f32>(f32((vertexIndex << 1u) & 2u), f32(vertexIndex & 2u));
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    var output: Output;
+    output.Position = vec4<f32>(ndc, 1.0, 1.0);
+    output.vNdc = ndc;
+    return output;
+}
+
+@fragment
+fn fs_main(@location(0) vNdc: vec2<f32>) -> @location(0) vec4<f32> {
+    // The camera sits at the origin, so any point along the pixel's ray gives its
+    // direction. Depth 0.5 stays finite for infinite far planes too.
+    let point = skybox.inverseViewProjectionMatrix * vec4<f32>(vNdc, 0.5, 1.0);
+    let direction = point.xyz / point.w;
+    return textureSample(skyboxTexture, skyboxSampler, vec3<f32>(direction.x, direction.y, -direction.z));
+}
@@ -0,0 +1,467 @@
+use std::f32::consts::PI;
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::{InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3, Vector4};
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, Buffer,
+    BufferAddress, BufferSize, BufferUsages, CommandEncoder, DepthBiasState, DepthStencilState,
+    Device, Queue, RenderPass, Sampler, ShaderStages, Texture, TextureFormat, TextureView,
+};
+
+use crate::{
+    lighting::Light,
+    transform::{
+        create_orthographic, create_perspective, create_view, PERSPECTIVE_FAR, PERSPECTIVE_FOVY,
+        PERSPECTIVE_NEAR,
+    },
+    uniform_arena::UniformArena,
+};
+
+pub const SHADOW_WGSL: &str = include_str!("shaders/shadow.wgsl");
+
+pub const MAX_CASCADES: usize = 4;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct ShadowUniforms {
+    pub light_view_projections: [[[f32; 4]; 4]; MAX_CASCADES],
+    pub cascade_splits: [f32; 4],
+    pub cascade_count: u32,
+    pub pcf_radius: i32,
+    pub normal_bias: f32,
+    pub texel_size: f32,
+}
+
+impl ShadowUniforms {
+    pub const SIZE: BufferAddress = std::mem::size_of::<Self>() as BufferAddress;
+}
+
+// A depth texture array with one layer per cascade. Each frame, update the light
+// matrices, render every layer with a depth-only pipeline built against
+// `depth_stencil_state` and `pass_bind_group_layout_entry`, then sample it in the
+// main pass through `bind_group_entries` and `shadowFactor` from `SHADOW_WGSL`.
+pub struct ShadowMap {
+    uniforms: ShadowUniforms,
+    uniform_buffer: Buffer,
+    texture: Texture,
+    view: TextureView,
+    layer_views: Vec<TextureView>,
+    sampler: Sampler,
+    pass_arena: UniformArena<[[f32; 4]; 4]>,
+    size: u32,
+    split_lambda: f32,
+    shadow_distance: f32,
+}
+
+impl ShadowMap {
+    pub const FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+    pub fn new(device: &Device, size: u32, cascade_count: u32) -> Self {
+        let cascade_count = cascade_count.clamp(1, MAX_CASCADES as u32);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: cascade_count,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Map View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..cascade_count)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Shadow Map Layer View"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Map Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniforms = ShadowUniforms {
+            light_view_projections: [Matrix4::identity().into(); MAX_CASCADES],
+            cascade_splits: [f32::MAX; 4],
+            cascade_count,
+            pcf_radius: 1,
+            normal_bias: 0.02,
+            texel_size: 1.0 / size as f32,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Uniform Buffer"),
+            contents: bytemuck::bytes_of(&uniforms),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        Self {
+            uniforms,
+            uniform_buffer,
+            texture,
+            view,
+            layer_views,
+            sampler,
+            pass_arena: UniformArena::new(device, Some("Shadow Pass Arena"), cascade_count),
+            size,
+            split_lambda: 0.75,
+            shadow_distance: 40.0,
+        }
+    }
+
+    pub fn uniforms(&self) -> &ShadowUniforms {
+        &self.uniforms
+    }
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+    pub fn layer_view(&self, cascade: usize) -> &TextureView {
+        &self.layer_views[cascade]
+    }
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+    pub fn cascade_count(&self) -> usize {
+        self.uniforms.cascade_count as usize
+    }
+    pub fn pass_bind_group(&self) -> Option<&BindGroup> {
+        self.pass_arena.bind_group()
+    }
+    pub fn pass_offset(&self, cascade: usize) -> u32 {
+        self.pass_arena.offset(cascade as u32)
+    }
+
+    // Samples (2 * radius + 1)² texels per lookup, 0 takes a single sample.
+    pub fn set_pcf_radius(&mut self, queue: &Queue, pcf_radius: u32) {
+        self.uniforms.pcf_radius = pcf_radius as i32;
+        self.write_uniforms(queue);
+    }
+
+    // In world units, applied along the surface normal before the lookup.
+    pub fn set_normal_bias(&mut self, queue: &Queue, normal_bias: f32) {
+        self.uniforms.normal_bias = normal_bias;
+        self.write_uniforms(queue);
+    }
+
+    // Blends uniform (0) and logarithmic (1) cascade splits.
+    pub fn set_split_lambda(&mut self, split_lambda: f32) {
+        self.split_lambda = split_lambda.clamp(0.0, 1.0);
+    }
+
+    // How far from the camera the cascades reach, nothing is shadowed past it.
+    pub fn set_shadow_distance(&mut self, shadow_distance: f32) {
+        self.shadow_distance = shadow_distance.max(PERSPECTIVE_NEAR);
+    }
+
+    // Fits one orthographic cascade per slice of the camera frustum, using the
+    // perspective set up by `transform::create_projection`.
+    pub fn update_cascades(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        light_direction: [f32; 3],
+        view_matrix: Matrix4<f32>,
+        aspect_ratio: f32,
+    ) {
+        let inverse_view_matrix = view_matrix.invert().unwrap_or(Matrix4::identity());
+        let splits = cascade_splits(
+            PERSPECTIVE_NEAR,
+            self.shadow_distance,
+            self.layer_views.len(),
+            self.split_lambda,
+        );
+
+        let mut near = PERSPECTIVE_NEAR;
+        let mut matrices = Vec::with_capacity(splits.len());
+        for &far in &splits {
+            let (center, radius) = frustum_slice_bounds(
+                inverse_view_matrix,
+                PERSPECTIVE_FOVY,
+                aspect_ratio,
+                near,
+                far,
+            );
+            matrices.push(directional_light_view_projection(
+                light_direction.into(),
+                center,
+                radius,
+                self.size,
+            ));
+            near = far;
+        }
+        self.set_light_view_projections(device, queue, &matrices, &splits);
+    }
+
+    // A single cascade covering the sphere at `center`, for scenes of known size.
+    pub fn update_directional(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        light_direction: [f32; 3],
+        center: [f32; 3],
+        radius: f32,
+    ) {
+        let matrix = directional_light_view_projection(
+            light_direction.into(),
+            center.into(),
+            radius,
+            self.size,
+        );
+        self.set_light_view_projections(device, queue, &[matrix], &[f32::MAX]);
+    }
+
+    pub fn update_spot(&mut self, device: &Device, queue: &Queue, light: &Light, near: f32) {
+        let matrix = spot_light_view_projection(light, near);
+        self.set_light_view_projections(device, queue, &[matrix], &[f32::MAX]);
+    }
+
+    // `splits` holds the view distance each cascade ends at. Extra cascades of the
+    // texture are left unused.
+    pub fn set_light_view_projections(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        matrices: &[Matrix4<f32>],
+        splits: &[f32],
+    ) {
+        let count = matrices.len().clamp(1, self.layer_views.len());
+        self.pass_arena.clear();
+        for (cascade, matrix) in matrices.iter().take(count).enumerate() {
+            self.uniforms.light_view_projections[cascade] = (*matrix).into();
+            self.uniforms.cascade_splits[cascade] =
+                splits.get(cascade).copied().unwrap_or(f32::MAX);
+            self.pass_arena.push(&(*matrix).into());
+        }
+        self.uniforms.cascade_count = count as u32;
+        // The arena holds one slot per cascade and `count` is clamped to that, so
+        // it never grows and the pass bind group stays valid.
+        let reallocated = self.pass_arena.upload(device, queue);
+        debug_assert!(!reallocated);
+        self.write_uniforms(queue);
+    }
+
+    // For `RendererBuilder::create_depth_only_pipeline`. Constant bias is in depth
+    // buffer units, slope scale multiplies the polygon's depth slope.
+    pub fn depth_stencil_state(constant: i32, slope_scale: f32) -> DepthStencilState {
+        DepthStencilState {
+            format: Self::FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: DepthBiasState {
+                constant,
+                slope_scale,
+                clamp: 0.0,
+            },
+        }
+    }
+
+    // The light view-projection of the cascade being rendered, `mat4x4<f32>` in
+    // WGSL, bound with `pass_offset` as the dynamic offset.
+    pub fn pass_bind_group_layout_entry(binding: u32) -> BindGroupLayoutEntry {
+        UniformArena::<[[f32; 4]; 4]>::bind_group_layout_entry(binding, ShaderStages::VERTEX)
+    }
+
+    pub fn create_pass_bind_group(
+        &mut self,
+        device: &Device,
+        layout: &BindGroupLayout,
+        binding: u32,
+    ) {
+        self.pass_arena.create_bind_group(device, layout, binding);
+    }
+
+    // The `ShadowUniforms`, the `texture_depth_2d_array` and the
+    // `sampler_comparison`, at `binding` and the two after it.
+    pub fn bind_group_layout_entries(
+        binding: u32,
+        visibility: ShaderStages,
+    ) -> [BindGroupLayoutEntry; 3] {
+        [
+            BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(ShadowUniforms::SIZE),
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: binding + 1,
+                visibility,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: binding + 2,
+                visibility,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+        ]
+    }
+
+    pub fn bind_group_entries(&self, binding: u32) -> [BindGroupEntry<'_>; 3] {
+        [
+            BindGroupEntry {
+                binding,
+                resource: self.uniform_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::TextureView(&self.view),
+            },
+            BindGroupEntry {
+                binding: binding + 2,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ]
+    }
+
+    // Clears the cascade's layer and leaves the depth stored for the main pass.
+    pub fn begin_render_pass<'a>(
+        &'a self,
+        command_encoder: &'a mut CommandEncoder,
+        cascade: usize,
+    ) -> RenderPass<'a> {
+        command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.layer_views[cascade],
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+
+    fn write_uniforms(&self, queue: &Queue) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&self.uniforms));
+    }
+}
+
+// View distances where each of `count` cascades ends, between `near` and `far`.
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (1..=count)
+        .map(|index| {
+            let ratio = index as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(ratio);
+            let uniform = near + (far - near) * ratio;
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+// The bounding sphere of the camera frustum between `near` and `far`, in world
+// space. A sphere keeps the cascade size fixed while the camera rotates.
+pub fn frustum_slice_bounds(
+    inverse_view_matrix: Matrix4<f32>,
+    fovy: Rad<f32>,
+    aspect_ratio: f32,
+    near: f32,
+    far: f32,
+) -> (Point3<f32>, f32) {
+    let tan_half_fovy = (fovy.0 * 0.5).tan();
+    let mut corners = Vec::with_capacity(8);
+    for distance in [near, far] {
+        let half_height = distance * tan_half_fovy;
+        let half_width = half_height * aspect_ratio;
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            let corner =
+                inverse_view_matrix * Vector4::new(x * half_width, y * half_height, -distance, 1.0);
+            corners.push(corner.truncate());
+        }
+    }
+
+    let center = corners
+        .iter()
+        .fold(Vector3::new(0.0, 0.0, 0.0), |sum, corner| sum + corner)
+        / 8.0;
+    let radius = corners
+        .iter()
+        .map(|corner| (corner - center).magnitude())
+        .fold(0.0, f32::max);
+    // Rounding the radius keeps it from flickering with float error.
+    let radius = (radius * 16.0).ceil() / 16.0;
+    (Point3::new(center.x, center.y, center.z), radius)
+}
+
+// An orthographic light looking along `direction` at the sphere around `center`.
+// The light sits one more radius behind the sphere so casters just outside it
+// still land in the map, and the projection is snapped to whole texels so shadow
+// edges do not shimmer as the sphere moves.
+pub fn directional_light_view_projection(
+    direction: Vector3<f32>,
+    center: Point3<f32>,
+    radius: f32,
+    size: u32,
+) -> Matrix4<f32> {
+    let direction = direction.normalize();
+    let eye = center - direction * radius * 2.0;
+    let view_matrix = create_view(eye, center, up_direction(direction));
+    let mut projection_matrix =
+        create_orthographic(-radius, radius, -radius, radius, 0.0, radius * 3.0);
+
+    let origin = projection_matrix * view_matrix * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let texels = size as f32 * 0.5;
+    projection_matrix.w.x += ((origin.x * texels).round() - origin.x * texels) / texels;
+    projection_matrix.w.y += ((origin.y * texels).round() - origin.y * texels) / texels;
+    projection_matrix * view_matrix
+}
+
+// A perspective light at the spot's position covering its outer cone. Lights
+// without a range reach the default far plane.
+pub fn spot_light_view_projection(light: &Light, near: f32) -> Matrix4<f32> {
+    let direction = Vector3::from(light.direction).normalize();
+    let eye = Point3::from(light.position);
+    let view_matrix = create_view(eye, eye + direction, up_direction(direction));
+    let far = if light.range > 0.0 {
+        light.range
+    } else {
+        PERSPECTIVE_FAR
+    };
+    let fovy = (2.0 * light.outer_cone_cos.clamp(-1.0, 1.0).acos()).clamp(0.01, PI * 0.95);
+    create_perspective(Rad(fovy), 1.0, near, far) * view_matrix
+}
+
+fn up_direction(direction: Vector3<f32>) -> Vector3<f32> {
+    if direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
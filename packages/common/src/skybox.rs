@@ -0,0 +1,187 @@
+use cgmath::{Matrix4, SquareMatrix, Vector4};
+use wgpu::{
+    util::DeviceExt, BindGroup, BindGroupLayout, Buffer, BufferUsages, Device, Queue, RenderPass,
+    RenderPipeline, ShaderStages, TextureFormat,
+};
+
+use crate::{
+    camera::Camera, projection::Projection, shader_preprocessor::create_library_shader_module,
+    texture::Texture, transform::create_projection,
+};
+
+const SKYBOX_WGSL: &str = include_str!("shaders/skybox.wgsl");
+
+// Draws a cubemap behind everything else. The triangle sits on the far plane and
+// never writes depth, so it can be drawn before or after the opaque geometry of
+// a pass that uses `depth_format`.
+pub struct Skybox {
+    cubemap: Texture,
+    uniform_buffer: Buffer,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    render_pipeline: RenderPipeline,
+}
+
+impl Skybox {
+    pub fn new(
+        device: &Device,
+        cubemap: Texture,
+        color_format: TextureFormat,
+        depth_format: Option<TextureFormat>,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skybox Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[[[0.0f32; 4]; 4]]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let [texture_entry, sampler_entry] =
+            Texture::cube_bind_group_layout_entries(1, ShaderStages::FRAGMENT);
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Skybox Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry,
+                sampler_entry,
+            ],
+        });
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, &uniform_buffer, &cubemap);
+
+        let shader = create_library_shader_module(
+            device,
+            "Skybox Shader",
+            &[("shaders/skybox.wgsl", SKYBOX_WGSL)],
+        );
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(color_format.into())],
+            }),
+            primitive: Default::default(),
+            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: None,
+        });
+
+        Self {
+            cubemap,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            render_pipeline,
+        }
+    }
+
+    // Bind it with `Texture::cube_bind_group_layout_entries` for reflections. The
+    // cube is stored left-handed, so sample it with `vec3(d.x, d.y, -d.z)` for a
+    // world space direction `d`.
+    pub fn cubemap(&self) -> &Texture {
+        &self.cubemap
+    }
+
+    pub fn set_cubemap(&mut self, device: &Device, cubemap: Texture) {
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &cubemap,
+        );
+        self.cubemap = cubemap;
+    }
+
+    // An orthographic projection has no vanishing point, so the sky is drawn with
+    // the default perspective projection at the same aspect ratio instead.
+    pub fn update(&self, queue: &Queue, projection: &Projection) {
+        self.update_matrices(queue, *projection.view_matrix(), sky_projection(projection));
+    }
+
+    pub fn update_from_camera(&self, queue: &Queue, camera: &Camera, projection: &Projection) {
+        self.update_matrices(queue, camera.view_matrix(), sky_projection(projection));
+    }
+
+    pub fn update_matrices(
+        &self,
+        queue: &Queue,
+        view_matrix: Matrix4<f32>,
+        projection_matrix: Matrix4<f32>,
+    ) {
+        let view_projection_matrix = projection_matrix * remove_translation(view_matrix);
+        let inverse = view_projection_matrix
+            .invert()
+            .unwrap_or_else(Matrix4::identity);
+        let inverse: [[f32; 4]; 4] = inverse.into();
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&inverse));
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        uniform_buffer: &Buffer,
+        cubemap: &Texture,
+    ) -> BindGroup {
+        let [texture_entry, sampler_entry] = cubemap.bind_group_entries(1);
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Skybox Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                texture_entry,
+                sampler_entry,
+            ],
+        })
+    }
+}
+
+fn sky_projection(projection: &Projection) -> Matrix4<f32> {
+    if projection.is_perspective() {
+        *projection.projection_matrix()
+    } else {
+        create_projection(projection.aspect_ratio(), true)
+    }
+}
+
+// Keeps the camera's orientation but moves it back to the origin, so the sky
+// stays infinitely far away however the camera moves.
+pub fn remove_translation(view_matrix: Matrix4<f32>) -> Matrix4<f32> {
+    let mut view_matrix = view_matrix;
+    view_matrix.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+    view_matrix
+}
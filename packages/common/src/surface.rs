@@ -0,0 +1,131 @@
+use std::ops::Range;
+
+use wgpu::PrimitiveTopology;
+
+use crate::{
+    mesh::{compute_vertex_normals, MeshData},
+    vertices::{primitives::grid_indices, Vertex3DNormalColored},
+};
+
+pub struct Surface {
+    pub mesh: MeshData<Vertex3DNormalColored>,
+    pub wireframe: Option<MeshData<Vertex3DNormalColored>>,
+}
+
+pub struct SurfaceBuilder {
+    u_range: Range<f32>,
+    v_range: Range<f32>,
+    u_segments: u32,
+    v_segments: u32,
+    color: [f32; 4],
+    colormap: Option<Box<dyn Fn(f32) -> [f32; 4]>>,
+    wireframe: bool,
+}
+
+impl SurfaceBuilder {
+    pub fn new(u_range: Range<f32>, v_range: Range<f32>, u_segments: u32, v_segments: u32) -> Self {
+        Self {
+            u_range,
+            v_range,
+            u_segments: u_segments.max(1),
+            v_segments: v_segments.max(1),
+            color: [1.0, 1.0, 1.0, 1.0],
+            colormap: None,
+            wireframe: false,
+        }
+    }
+
+    pub fn set_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn set_colormap<F: Fn(f32) -> [f32; 4] + 'static>(mut self, colormap: F) -> Self {
+        self.colormap = Some(Box::new(colormap));
+        self
+    }
+
+    pub fn set_wireframe(mut self, wireframe: bool) -> Self {
+        self.wireframe = wireframe;
+        self
+    }
+
+    // Plots `z = f(x, y)` with the height on the world y axis, as the cameras here are y-up.
+    pub fn build_height_field<F: Fn(f32, f32) -> f32>(&self, height: F) -> Surface {
+        self.build_parametric(|x, y| [x, height(x, y), y])
+    }
+
+    pub fn build_parametric<F: Fn(f32, f32) -> [f32; 3]>(&self, surface: F) -> Surface {
+        let mut positions =
+            Vec::with_capacity(((self.u_segments + 1) * (self.v_segments + 1)) as usize);
+        for row in 0..=self.v_segments {
+            let v = lerp(&self.v_range, row as f32 / self.v_segments as f32);
+            for column in 0..=self.u_segments {
+                let u = lerp(&self.u_range, column as f32 / self.u_segments as f32);
+                positions.push(surface(u, v));
+            }
+        }
+
+        let indices = grid_indices(self.v_segments + 1, self.u_segments, false, false);
+        let normals = compute_vertex_normals(&positions, &indices);
+
+        let (min_height, max_height) = positions.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min, max), position| (min.min(position[1]), max.max(position[1])),
+        );
+        let height_span = max_height - min_height;
+
+        let vertices: Vec<Vertex3DNormalColored> = positions
+            .iter()
+            .zip(normals)
+            .map(|(position, normal)| {
+                let color = match &self.colormap {
+                    Some(colormap) if height_span > 0.0 => {
+                        colormap((position[1] - min_height) / height_span)
+                    }
+                    Some(colormap) => colormap(0.0),
+                    None => self.color,
+                };
+                Vertex3DNormalColored {
+                    position: *position,
+                    normal,
+                    color,
+                }
+            })
+            .collect();
+
+        let wireframe = self.wireframe.then(|| {
+            MeshData::indexed(
+                vertices.clone(),
+                self.wireframe_indices(),
+                PrimitiveTopology::LineList,
+            )
+        });
+
+        Surface {
+            mesh: MeshData::indexed(vertices, indices, PrimitiveTopology::TriangleList),
+            wireframe,
+        }
+    }
+
+    fn wireframe_indices(&self) -> Vec<u32> {
+        let stride = self.u_segments + 1;
+        let mut indices = Vec::new();
+        for row in 0..=self.v_segments {
+            for column in 0..=self.u_segments {
+                let index = row * stride + column;
+                if column < self.u_segments {
+                    indices.extend_from_slice(&[index, index + 1]);
+                }
+                if row < self.v_segments {
+                    indices.extend_from_slice(&[index, index + stride]);
+                }
+            }
+        }
+        indices
+    }
+}
+
+fn lerp(range: &Range<f32>, t: f32) -> f32 {
+    range.start + (range.end - range.start) * t
+}
@@ -0,0 +1,481 @@
+pub mod compressed;
+pub mod cubemap;
+pub mod mipmap;
+
+use std::path::Path;
+
+use wgpu::{
+    AddressMode, BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, Device,
+    Extent3d, FilterMode, Queue, Sampler, ShaderStages, TextureFormat, TextureUsages, TextureView,
+};
+
+use crate::{
+    colormap::srgb_to_linear,
+    loaders::LoadError,
+    texture::{
+        compressed::CompressedImage,
+        mipmap::{generate_mipmaps, MipmapGenerator},
+    },
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    // Color data such as albedo or UI images.
+    Srgb,
+    // Non-color data such as normal, roughness or height maps.
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn rgba8_format(&self) -> TextureFormat {
+        match self {
+            ColorSpace::Srgb => TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+pub struct Texture {
+    texture: wgpu::Texture,
+    view: TextureView,
+    sampler: Sampler,
+}
+
+impl Texture {
+    // Wraps textures created elsewhere, such as render targets, so they can be
+    // bound through the same helpers.
+    pub fn new(texture: wgpu::Texture, view: TextureView, sampler: Sampler) -> Self {
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+    pub fn size(&self) -> Extent3d {
+        self.texture.size()
+    }
+    pub fn format(&self) -> TextureFormat {
+        self.texture.format()
+    }
+    pub fn mip_level_count(&self) -> u32 {
+        self.texture.mip_level_count()
+    }
+
+    pub fn bind_group_layout_entries(
+        binding: u32,
+        visibility: ShaderStages,
+    ) -> [BindGroupLayoutEntry; 2] {
+        layout_entries(binding, visibility, wgpu::TextureViewDimension::D2)
+    }
+
+    pub fn cube_bind_group_layout_entries(
+        binding: u32,
+        visibility: ShaderStages,
+    ) -> [BindGroupLayoutEntry; 2] {
+        layout_entries(binding, visibility, wgpu::TextureViewDimension::Cube)
+    }
+
+    pub fn bind_group_entries(&self, binding: u32) -> [BindGroupEntry<'_>; 2] {
+        [
+            BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(&self.view),
+            },
+            BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ]
+    }
+
+    pub fn create_bind_group(
+        &self,
+        device: &Device,
+        layout: &BindGroupLayout,
+        binding: u32,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout,
+            entries: &self.bind_group_entries(binding),
+        })
+    }
+}
+
+fn layout_entries(
+    binding: u32,
+    visibility: ShaderStages,
+    view_dimension: wgpu::TextureViewDimension,
+) -> [BindGroupLayoutEntry; 2] {
+    [
+        BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        },
+        BindGroupLayoutEntry {
+            binding: binding + 1,
+            visibility,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+    ]
+}
+
+pub struct TextureBuilder<'a> {
+    label: Option<String>,
+    color_space: ColorSpace,
+    mipmaps: bool,
+    address_modes: [AddressMode; 3],
+    mag_filter: FilterMode,
+    min_filter: FilterMode,
+    mipmap_filter: FilterMode,
+    anisotropy_clamp: u16,
+    mipmap_generator: Option<&'a mut MipmapGenerator>,
+}
+
+impl Default for TextureBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> TextureBuilder<'a> {
+    pub fn new() -> Self {
+        Self {
+            label: None,
+            color_space: ColorSpace::Srgb,
+            mipmaps: true,
+            address_modes: [AddressMode::Repeat; 3],
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            anisotropy_clamp: 1,
+            mipmap_generator: None,
+        }
+    }
+
+    pub fn set_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn set_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    pub fn set_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn set_address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_modes = [address_mode; 3];
+        self
+    }
+
+    pub fn set_address_modes(mut self, u: AddressMode, v: AddressMode, w: AddressMode) -> Self {
+        self.address_modes = [u, v, w];
+        self
+    }
+
+    pub fn set_filter(mut self, mag_filter: FilterMode, min_filter: FilterMode) -> Self {
+        self.mag_filter = mag_filter;
+        self.min_filter = min_filter;
+        self
+    }
+
+    pub fn set_mipmap_filter(mut self, mipmap_filter: FilterMode) -> Self {
+        self.mipmap_filter = mipmap_filter;
+        self
+    }
+
+    pub fn set_anisotropy(mut self, anisotropy_clamp: u16) -> Self {
+        self.anisotropy_clamp = anisotropy_clamp.clamp(1, 16);
+        self
+    }
+
+    // Lets textures built in a row share the generator's pipelines. Without one, a
+    // generator is created for the texture and dropped after it.
+    pub fn set_mipmap_generator(mut self, mipmap_generator: &'a mut MipmapGenerator) -> Self {
+        self.mipmap_generator = Some(mipmap_generator);
+        self
+    }
+
+    pub fn build_from_path(
+        self,
+        device: &Device,
+        queue: &Queue,
+        path: impl AsRef<Path>,
+    ) -> Result<Texture, LoadError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| LoadError::io(path, error))?;
+        let builder = match self.label {
+            Some(_) => self,
+            None => self.set_label(&path.display().to_string()),
+        };
+        if CompressedImage::is_container(&bytes) {
+            let image = CompressedImage::parse(&bytes, path)?;
+            return builder
+                .build_from_compressed(device, queue, &image)
+                .map_err(|message| LoadError::new(path, None, message));
+        }
+        builder
+            .build_from_bytes(device, queue, &bytes)
+            .map_err(|error| LoadError::new(path, None, error.to_string()))
+    }
+
+    // Faces are ordered +X, -X, +Y, -Y, +Z, -Z and must be square and of equal size.
+    pub fn build_cubemap_from_paths<P: AsRef<Path>>(
+        self,
+        device: &Device,
+        queue: &Queue,
+        paths: [P; 6],
+    ) -> Result<Texture, LoadError> {
+        let mut size = None;
+        let mut pixels = Vec::new();
+        for path in &paths {
+            let path = path.as_ref();
+            let face = image::open(path)
+                .map_err(|error| LoadError::new(path, None, error.to_string()))?
+                .into_rgba8();
+            let face_size = face.width();
+            if face.height() != face_size || size.is_some_and(|size| size != face_size) {
+                return Err(LoadError::new(
+                    path,
+                    None,
+                    format!(
+                        "cubemap face is {}x{}, faces must be square and equally sized",
+                        face.width(),
+                        face.height()
+                    ),
+                ));
+            }
+            size = Some(face_size);
+            pixels.extend_from_slice(&face);
+        }
+        let size = size.unwrap_or(1);
+
+        let image = CompressedImage {
+            format: self.color_space.rgba8_format(),
+            width: size,
+            height: size,
+            array_layers: 6,
+            is_cubemap: true,
+            levels: vec![pixels],
+        };
+        Ok(self.create_texture(device, queue, &image))
+    }
+
+    // Projects a latitude-longitude panorama (usually a Radiance .hdr file) onto
+    // the six faces of an `Rgba16Float` cubemap. LDR images are treated as sRGB.
+    pub fn build_cubemap_from_equirectangular(
+        self,
+        device: &Device,
+        queue: &Queue,
+        path: impl AsRef<Path>,
+    ) -> Result<Texture, LoadError> {
+        let path = path.as_ref();
+        let panorama =
+            image::open(path).map_err(|error| LoadError::new(path, None, error.to_string()))?;
+        let is_linear = matches!(
+            panorama,
+            image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_)
+        );
+        let panorama = panorama.into_rgb32f();
+        let texels: Vec<[f32; 3]> = panorama
+            .pixels()
+            .map(|pixel| {
+                if is_linear {
+                    pixel.0
+                } else {
+                    pixel.0.map(srgb_to_linear)
+                }
+            })
+            .collect();
+
+        let face_size = (panorama.width() / 4).max(1);
+        let faces = cubemap::equirectangular_to_cube_faces(
+            &texels,
+            panorama.width(),
+            panorama.height(),
+            face_size,
+        );
+        let image = CompressedImage {
+            format: TextureFormat::Rgba16Float,
+            width: face_size,
+            height: face_size,
+            array_layers: 6,
+            is_cubemap: true,
+            levels: vec![bytemuck::cast_slice(&faces).to_vec()],
+        };
+        Ok(self.create_texture(device, queue, &image))
+    }
+
+    pub fn build_from_bytes(
+        self,
+        device: &Device,
+        queue: &Queue,
+        bytes: &[u8],
+    ) -> Result<Texture, image::ImageError> {
+        let image = image::load_from_memory(bytes)?.into_rgba8();
+        Ok(self.build_from_rgba8(device, queue, image.width(), image.height(), &image))
+    }
+
+    pub fn build_from_rgba8(
+        self,
+        device: &Device,
+        queue: &Queue,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Texture {
+        assert_eq!(
+            pixels.len(),
+            width as usize * height as usize * 4,
+            "pixel data to be {}x{} RGBA8",
+            width,
+            height
+        );
+        let image = CompressedImage {
+            format: self.color_space.rgba8_format(),
+            width,
+            height,
+            array_layers: 1,
+            is_cubemap: false,
+            levels: vec![pixels.to_vec()],
+        };
+        self.create_texture(device, queue, &image)
+    }
+
+    // Uses the container's own format (and its sRGB flag) when the device supports
+    // it, otherwise decodes to RGBA8 on the CPU.
+    pub fn build_from_compressed(
+        self,
+        device: &Device,
+        queue: &Queue,
+        image: &CompressedImage,
+    ) -> Result<Texture, String> {
+        if image.is_supported(device.features()) {
+            return Ok(self.create_texture(device, queue, image));
+        }
+        log::info!(
+            "{:?} is not supported by the device, decoding on the CPU",
+            image.format
+        );
+        let image = image.decompress()?;
+        Ok(self.create_texture(device, queue, &image))
+    }
+
+    fn create_texture(
+        mut self,
+        device: &Device,
+        queue: &Queue,
+        image: &CompressedImage,
+    ) -> Texture {
+        let size = image.size();
+        let generates_mipmaps = self.mipmaps
+            && image.mip_level_count() == 1
+            && MipmapGenerator::is_supported(device, image.format);
+        let mip_level_count = if generates_mipmaps {
+            size.max_mips(wgpu::TextureDimension::D2)
+        } else {
+            image.mip_level_count()
+        };
+        let mut usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
+        if generates_mipmaps {
+            usage |= TextureUsages::RENDER_ATTACHMENT;
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: self.label.as_deref(),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: image.format,
+            usage,
+            view_formats: &[],
+        });
+
+        let (block_width, block_height) = image.format.block_dimensions();
+        let block_size = image.format.block_size(None).unwrap_or(4);
+        for (mip_level, data) in image.levels.iter().enumerate() {
+            let mip_size = size.mip_level_size(mip_level as u32, wgpu::TextureDimension::D2);
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(mip_size.width.div_ceil(block_width) * block_size),
+                    rows_per_image: Some(mip_size.height.div_ceil(block_height)),
+                },
+                mip_size.physical_size(image.format),
+            );
+        }
+        if generates_mipmaps {
+            match self.mipmap_generator.as_deref_mut() {
+                Some(mipmap_generator) => {
+                    generate_mipmaps(device, queue, mipmap_generator, &texture)
+                }
+                None => {
+                    generate_mipmaps(device, queue, &mut MipmapGenerator::new(device), &texture)
+                }
+            }
+        }
+
+        let dimension = if image.is_cubemap && image.array_layers == 6 {
+            Some(wgpu::TextureViewDimension::Cube)
+        } else {
+            None
+        };
+        Texture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension,
+                ..Default::default()
+            }),
+            sampler: self.create_sampler(device),
+            texture,
+        }
+    }
+
+    pub fn create_sampler(&self, device: &Device) -> Sampler {
+        let is_linear = self.mag_filter == FilterMode::Linear
+            && self.min_filter == FilterMode::Linear
+            && self.mipmap_filter == FilterMode::Linear;
+        let [address_mode_u, address_mode_v, address_mode_w] = self.address_modes;
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: self.label.as_deref(),
+            address_mode_u,
+            address_mode_v,
+            address_mode_w,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            // wgpu rejects anisotropic sampling unless every filter is linear.
+            anisotropy_clamp: if is_linear { self.anisotropy_clamp } else { 1 },
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,472 @@
+use std::path::Path;
+
+use wgpu::{AstcBlock, AstcChannel, Extent3d, Features, TextureFormat};
+
+use crate::loaders::LoadError;
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const DDS_MAGIC: [u8; 4] = *b"DDS ";
+
+// Pre-compressed image data as stored in a KTX2 or DDS container. Each entry of
+// `levels` holds one mip level with all of its array layers (and cube faces)
+// packed back to back, largest level first.
+#[derive(Clone, Debug)]
+pub struct CompressedImage {
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub array_layers: u32,
+    pub is_cubemap: bool,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.starts_with(&KTX2_IDENTIFIER) || bytes.starts_with(&DDS_MAGIC)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| LoadError::io(path, error))?;
+        Self::parse(&bytes, path)
+    }
+
+    pub fn parse(bytes: &[u8], file: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let result = if bytes.starts_with(&KTX2_IDENTIFIER) {
+            parse_ktx2(bytes)
+        } else if bytes.starts_with(&DDS_MAGIC) {
+            parse_dds(bytes)
+        } else {
+            Err("not a KTX2 or DDS file".to_string())
+        };
+        result.map_err(|message| LoadError::new(file, None, message))
+    }
+
+    pub fn size(&self) -> Extent3d {
+        Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: self.array_layers,
+        }
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    pub fn is_supported(&self, features: Features) -> bool {
+        features.contains(self.format.required_features())
+    }
+
+    // Decodes every level and layer to RGBA8, for devices that lack the
+    // compression feature the image needs.
+    pub fn decompress(&self) -> Result<Self, String> {
+        if !self.format.is_compressed() {
+            return Ok(self.clone());
+        }
+        let format = if self.format.is_srgb() {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        };
+
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(mip_level, data)| {
+                let size = self
+                    .size()
+                    .mip_level_size(mip_level as u32, wgpu::TextureDimension::D2);
+                let layer_size = level_layer_size(self.format, size.width, size.height);
+                let mut pixels = vec![0u32; (size.width * size.height) as usize];
+                let mut level = Vec::with_capacity(pixels.len() * 4 * self.array_layers as usize);
+                for layer in data.chunks_exact(layer_size) {
+                    decode(self.format, layer, size.width, size.height, &mut pixels)?;
+                    // The decoder packs pixels as BGRA.
+                    level.extend(pixels.iter().flat_map(|pixel| {
+                        let [b, g, r, a] = pixel.to_le_bytes();
+                        [r, g, b, a]
+                    }));
+                }
+                Ok(level)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            format,
+            levels,
+            ..self.clone()
+        })
+    }
+}
+
+pub(crate) fn level_layer_size(format: TextureFormat, width: u32, height: u32) -> usize {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_size(None).unwrap_or(4);
+    (width.div_ceil(block_width) * height.div_ceil(block_height) * block_size) as usize
+}
+
+fn decode(
+    format: TextureFormat,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    pixels: &mut [u32],
+) -> Result<(), String> {
+    use texture2ddecoder::*;
+
+    let (width, height) = (width as usize, height as usize);
+    match format {
+        TextureFormat::Bc1RgbaUnorm | TextureFormat::Bc1RgbaUnormSrgb => {
+            decode_bc1a(data, width, height, pixels)
+        }
+        TextureFormat::Bc2RgbaUnorm | TextureFormat::Bc2RgbaUnormSrgb => {
+            decode_bc2(data, width, height, pixels)
+        }
+        TextureFormat::Bc3RgbaUnorm | TextureFormat::Bc3RgbaUnormSrgb => {
+            decode_bc3(data, width, height, pixels)
+        }
+        TextureFormat::Bc4RUnorm => decode_bc4(data, width, height, pixels),
+        TextureFormat::Bc5RgUnorm => decode_bc5(data, width, height, pixels),
+        // HDR data is clamped to the 0..1 range on the way down to RGBA8.
+        TextureFormat::Bc6hRgbUfloat => decode_bc6_unsigned(data, width, height, pixels),
+        TextureFormat::Bc6hRgbFloat => decode_bc6_signed(data, width, height, pixels),
+        TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb => {
+            decode_bc7(data, width, height, pixels)
+        }
+        TextureFormat::Etc2Rgb8Unorm | TextureFormat::Etc2Rgb8UnormSrgb => {
+            decode_etc2_rgb(data, width, height, pixels)
+        }
+        TextureFormat::Etc2Rgb8A1Unorm | TextureFormat::Etc2Rgb8A1UnormSrgb => {
+            decode_etc2_rgba1(data, width, height, pixels)
+        }
+        TextureFormat::Etc2Rgba8Unorm | TextureFormat::Etc2Rgba8UnormSrgb => {
+            decode_etc2_rgba8(data, width, height, pixels)
+        }
+        TextureFormat::EacR11Unorm => decode_eacr(data, width, height, pixels),
+        TextureFormat::EacR11Snorm => decode_eacr_signed(data, width, height, pixels),
+        TextureFormat::EacRg11Unorm => decode_eacrg(data, width, height, pixels),
+        TextureFormat::EacRg11Snorm => decode_eacrg_signed(data, width, height, pixels),
+        TextureFormat::Astc {
+            channel: AstcChannel::Unorm | AstcChannel::UnormSrgb,
+            ..
+        } => {
+            let (block_width, block_height) = format.block_dimensions();
+            decode_astc(
+                data,
+                width,
+                height,
+                block_width as usize,
+                block_height as usize,
+                pixels,
+            )
+        }
+        format => return Err(format!("no CPU decoder for {:?}", format)),
+    }
+    .map_err(String::from)
+}
+
+// Offsets and lengths come from the file, so every range is built with checked
+// arithmetic and a bad value is reported instead of overflowing.
+fn byte_range(offset: usize, length: usize) -> Result<std::ops::Range<usize>, String> {
+    let end = offset
+        .checked_add(length)
+        .ok_or_else(|| format!("range {}+{} overflows", offset, length))?;
+    Ok(offset..end)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    bytes
+        .get(byte_range(offset, 4)?)
+        .map(|value| u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+        .ok_or_else(|| "unexpected end of file".to_string())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<usize, String> {
+    let low = read_u32(bytes, offset)?;
+    // The low half was in bounds, so `offset + 4` cannot overflow.
+    Ok(low as usize | (read_u32(bytes, offset + 4)? as usize) << 32)
+}
+
+fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage, String> {
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?.max(1);
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?.max(1);
+    let face_count = read_u32(bytes, 36)?.max(1);
+    let level_count = read_u32(bytes, 40)?.max(1);
+    let supercompression = read_u32(bytes, 44)?;
+
+    let format = vk_format_to_wgpu(vk_format)
+        .ok_or_else(|| format!("unsupported VkFormat {}", vk_format))?;
+    if supercompression != 0 {
+        return Err(format!(
+            "supercompression scheme {} is not supported",
+            supercompression
+        ));
+    }
+    if depth > 1 {
+        return Err("3D textures are not supported".to_string());
+    }
+
+    let array_layers = layer_count * face_count;
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: array_layers,
+    };
+    // The level index follows the 48 byte header and the 32 byte section index.
+    let levels = (0..level_count as usize)
+        .map(|mip_level| {
+            let entry = 80 + mip_level * 24;
+            let offset = read_u64(bytes, entry)?;
+            let length = read_u64(bytes, entry + 8)?;
+            let mip_size = size.mip_level_size(mip_level as u32, wgpu::TextureDimension::D2);
+            let expected =
+                level_layer_size(format, mip_size.width, mip_size.height) * array_layers as usize;
+            if length != expected {
+                return Err(format!(
+                    "level {} holds {} bytes, expected {}",
+                    mip_level, length, expected
+                ));
+            }
+            bytes
+                .get(byte_range(offset, length)?)
+                .map(|level| level.to_vec())
+                .ok_or_else(|| format!("level {} is out of bounds", mip_level))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(CompressedImage {
+        format,
+        width,
+        height,
+        array_layers,
+        is_cubemap: face_count == 6,
+        levels,
+    })
+}
+
+fn vk_format_to_wgpu(vk_format: u32) -> Option<TextureFormat> {
+    let astc = |block, srgb: bool| TextureFormat::Astc {
+        block,
+        channel: if srgb {
+            AstcChannel::UnormSrgb
+        } else {
+            AstcChannel::Unorm
+        },
+    };
+    let astc_blocks = [
+        AstcBlock::B4x4,
+        AstcBlock::B5x4,
+        AstcBlock::B5x5,
+        AstcBlock::B6x5,
+        AstcBlock::B6x6,
+        AstcBlock::B8x5,
+        AstcBlock::B8x6,
+        AstcBlock::B8x8,
+        AstcBlock::B10x5,
+        AstcBlock::B10x6,
+        AstcBlock::B10x8,
+        AstcBlock::B10x10,
+        AstcBlock::B12x10,
+        AstcBlock::B12x12,
+    ];
+
+    Some(match vk_format {
+        37 => TextureFormat::Rgba8Unorm,
+        43 => TextureFormat::Rgba8UnormSrgb,
+        44 => TextureFormat::Bgra8Unorm,
+        50 => TextureFormat::Bgra8UnormSrgb,
+        97 => TextureFormat::Rgba16Float,
+        109 => TextureFormat::Rgba32Float,
+        131 | 133 => TextureFormat::Bc1RgbaUnorm,
+        132 | 134 => TextureFormat::Bc1RgbaUnormSrgb,
+        135 => TextureFormat::Bc2RgbaUnorm,
+        136 => TextureFormat::Bc2RgbaUnormSrgb,
+        137 => TextureFormat::Bc3RgbaUnorm,
+        138 => TextureFormat::Bc3RgbaUnormSrgb,
+        139 => TextureFormat::Bc4RUnorm,
+        140 => TextureFormat::Bc4RSnorm,
+        141 => TextureFormat::Bc5RgUnorm,
+        142 => TextureFormat::Bc5RgSnorm,
+        143 => TextureFormat::Bc6hRgbUfloat,
+        144 => TextureFormat::Bc6hRgbFloat,
+        145 => TextureFormat::Bc7RgbaUnorm,
+        146 => TextureFormat::Bc7RgbaUnormSrgb,
+        147 => TextureFormat::Etc2Rgb8Unorm,
+        148 => TextureFormat::Etc2Rgb8UnormSrgb,
+        149 => TextureFormat::Etc2Rgb8A1Unorm,
+        150 => TextureFormat::Etc2Rgb8A1UnormSrgb,
+        151 => TextureFormat::Etc2Rgba8Unorm,
+        152 => TextureFormat::Etc2Rgba8UnormSrgb,
+        153 => TextureFormat::EacR11Unorm,
+        154 => TextureFormat::EacR11Snorm,
+        155 => TextureFormat::EacRg11Unorm,
+        156 => TextureFormat::EacRg11Snorm,
+        // ASTC formats come in UNORM/SRGB pairs ordered by block size.
+        157..=184 => {
+            let index = (vk_format - 157) as usize;
+            astc(astc_blocks[index / 2], index % 2 == 1)
+        }
+        _ => return None,
+    })
+}
+
+fn parse_dds(bytes: &[u8]) -> Result<CompressedImage, String> {
+    if read_u32(bytes, 4)? != 124 {
+        return Err("invalid DDS header size".to_string());
+    }
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let level_count = read_u32(bytes, 28)?.max(1);
+    let pixel_format_flags = read_u32(bytes, 80)?;
+    let four_cc = bytes.get(84..88).ok_or("unexpected end of file")?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    const DDPF_FOURCC: u32 = 0x4;
+    const DDPF_RGB: u32 = 0x40;
+    const DDSCAPS2_CUBEMAP: u32 = 0x200;
+    const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+    let mut data_offset = 128;
+    let mut array_layers = 1;
+    let mut is_cubemap = caps2 & DDSCAPS2_CUBEMAP != 0;
+
+    let format = if pixel_format_flags & DDPF_FOURCC != 0 && four_cc == b"DX10" {
+        let dxgi_format = read_u32(bytes, 128)?;
+        is_cubemap = read_u32(bytes, 136)? & DDS_RESOURCE_MISC_TEXTURECUBE != 0;
+        array_layers = read_u32(bytes, 140)?.max(1);
+        data_offset = 148;
+        dxgi_format_to_wgpu(dxgi_format)
+            .ok_or_else(|| format!("unsupported DXGI format {}", dxgi_format))?
+    } else if pixel_format_flags & DDPF_FOURCC != 0 {
+        match four_cc {
+            b"DXT1" => TextureFormat::Bc1RgbaUnorm,
+            b"DXT2" | b"DXT3" => TextureFormat::Bc2RgbaUnorm,
+            b"DXT4" | b"DXT5" => TextureFormat::Bc3RgbaUnorm,
+            b"ATI1" | b"BC4U" => TextureFormat::Bc4RUnorm,
+            b"BC4S" => TextureFormat::Bc4RSnorm,
+            b"ATI2" | b"BC5U" => TextureFormat::Bc5RgUnorm,
+            b"BC5S" => TextureFormat::Bc5RgSnorm,
+            four_cc => {
+                return Err(format!(
+                    "unsupported FourCC `{}`",
+                    String::from_utf8_lossy(four_cc)
+                ))
+            }
+        }
+    } else if pixel_format_flags & DDPF_RGB != 0 && read_u32(bytes, 88)? == 32 {
+        match read_u32(bytes, 92)? {
+            0x0000_00FF => TextureFormat::Rgba8Unorm,
+            0x00FF_0000 => TextureFormat::Bgra8Unorm,
+            _ => return Err("unsupported RGB channel layout".to_string()),
+        }
+    } else {
+        return Err("unsupported DDS pixel format".to_string());
+    };
+    if is_cubemap {
+        array_layers *= 6;
+    }
+
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: array_layers,
+    };
+    let layer_sizes: Vec<usize> = (0..level_count)
+        .map(|mip_level| {
+            let mip_size = size.mip_level_size(mip_level, wgpu::TextureDimension::D2);
+            level_layer_size(format, mip_size.width, mip_size.height)
+        })
+        .collect();
+
+    // DDS stores every level of the first layer before moving on to the next,
+    // the opposite of the per-level layout used here.
+    let mut levels: Vec<Vec<u8>> = layer_sizes
+        .iter()
+        .map(|layer_size| Vec::with_capacity(layer_size * array_layers as usize))
+        .collect();
+    let mut offset = data_offset;
+    for _ in 0..array_layers {
+        for (level, layer_size) in levels.iter_mut().zip(&layer_sizes) {
+            let data = bytes
+                .get(byte_range(offset, *layer_size)?)
+                .ok_or("unexpected end of file")?;
+            level.extend_from_slice(data);
+            offset += layer_size;
+        }
+    }
+
+    Ok(CompressedImage {
+        format,
+        width,
+        height,
+        array_layers,
+        is_cubemap,
+        levels,
+    })
+}
+
+fn dxgi_format_to_wgpu(dxgi_format: u32) -> Option<TextureFormat> {
+    Some(match dxgi_format {
+        2 => TextureFormat::Rgba32Float,
+        10 => TextureFormat::Rgba16Float,
+        28 => TextureFormat::Rgba8Unorm,
+        29 => TextureFormat::Rgba8UnormSrgb,
+        87 => TextureFormat::Bgra8Unorm,
+        91 => TextureFormat::Bgra8UnormSrgb,
+        71 => TextureFormat::Bc1RgbaUnorm,
+        72 => TextureFormat::Bc1RgbaUnormSrgb,
+        74 => TextureFormat::Bc2RgbaUnorm,
+        75 => TextureFormat::Bc2RgbaUnormSrgb,
+        77 => TextureFormat::Bc3RgbaUnorm,
+        78 => TextureFormat::Bc3RgbaUnormSrgb,
+        80 => TextureFormat::Bc4RUnorm,
+        81 => TextureFormat::Bc4RSnorm,
+        83 => TextureFormat::Bc5RgUnorm,
+        84 => TextureFormat::Bc5RgSnorm,
+        95 => TextureFormat::Bc6hRgbUfloat,
+        96 => TextureFormat::Bc6hRgbFloat,
+        98 => TextureFormat::Bc7RgbaUnorm,
+        99 => TextureFormat::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 1x1 RGBA8 KTX2 file whose single level sits at `offset`.
+    fn ktx2(offset: u64) -> Vec<u8> {
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+        // VkFormat, type size, width, height, depth, layers, faces, levels, supercompression.
+        for value in [37u32, 1, 1, 1, 0, 0, 1, 1, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(80, 0);
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&4u64.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&[10, 20, 30, 255]);
+        bytes
+    }
+
+    #[test]
+    fn ktx2_level_is_read() {
+        let image = CompressedImage::parse(&ktx2(104), "level.ktx2").unwrap();
+        assert_eq!(image.format, TextureFormat::Rgba8Unorm);
+        assert_eq!(image.levels, vec![vec![10, 20, 30, 255]]);
+    }
+
+    #[test]
+    fn ktx2_overflowing_level_offset_is_an_error() {
+        let error = CompressedImage::parse(&ktx2(u64::MAX - 1), "overflow.ktx2").unwrap_err();
+        assert!(error.message().contains("overflows"), "{}", error);
+    }
+}
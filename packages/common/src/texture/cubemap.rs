@@ -0,0 +1,71 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector3};
+use half::f16;
+
+// Cubemaps follow the Vulkan convention, which is left-handed. Sampling with
+// `vec3(d.x, d.y, -d.z)` for a right-handed world direction `d` puts the +Z face
+// in front of a camera looking down -Z and keeps every face unmirrored.
+pub fn face_direction(face: usize, s: f32, t: f32) -> Vector3<f32> {
+    // `s` and `t` run from -1 to 1, left to right and top to bottom.
+    let cube_direction = match face {
+        0 => Vector3::new(1.0, -t, -s),
+        1 => Vector3::new(-1.0, -t, s),
+        2 => Vector3::new(s, 1.0, t),
+        3 => Vector3::new(s, -1.0, -t),
+        4 => Vector3::new(s, -t, 1.0),
+        _ => Vector3::new(-s, -t, -1.0),
+    };
+    Vector3::new(cube_direction.x, cube_direction.y, -cube_direction.z).normalize()
+}
+
+// The panorama's center column faces -Z and its top row faces +Y.
+pub fn equirectangular_uv(direction: Vector3<f32>) -> [f32; 2] {
+    let u = direction.x.atan2(-direction.z) / (2.0 * PI) + 0.5;
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+    [u, v]
+}
+
+// Returns the six faces as RGBA half floats, ordered +X, -X, +Y, -Y, +Z, -Z.
+pub fn equirectangular_to_cube_faces(
+    texels: &[[f32; 3]],
+    width: u32,
+    height: u32,
+    face_size: u32,
+) -> Vec<f16> {
+    let sample = |x: i64, y: i64| -> [f32; 3] {
+        let x = x.rem_euclid(width as i64) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        texels[(y * width + x) as usize]
+    };
+
+    let mut faces = Vec::with_capacity((face_size * face_size * 6 * 4) as usize);
+    for face in 0..6 {
+        for y in 0..face_size {
+            for x in 0..face_size {
+                let s = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+                let t = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+                let [u, v] = equirectangular_uv(face_direction(face, s, t));
+
+                // Bilinear filtering, wrapping horizontally across the seam.
+                let px = u * width as f32 - 0.5;
+                let py = v * height as f32 - 0.5;
+                let (x0, y0) = (px.floor() as i64, py.floor() as i64);
+                let (fx, fy) = (px - px.floor(), py - py.floor());
+                let [a, b, c, d] = [
+                    sample(x0, y0),
+                    sample(x0 + 1, y0),
+                    sample(x0, y0 + 1),
+                    sample(x0 + 1, y0 + 1),
+                ];
+                for channel in 0..3 {
+                    let top = a[channel] + (b[channel] - a[channel]) * fx;
+                    let bottom = c[channel] + (d[channel] - c[channel]) * fx;
+                    faces.push(f16::from_f32(top + (bottom - top) * fy));
+                }
+                faces.push(f16::ONE);
+            }
+        }
+    }
+    faces
+}
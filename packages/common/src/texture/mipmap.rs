@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use wgpu::{
+    BindGroupLayout, CommandEncoder, Device, FilterMode, PipelineLayout, Queue, RenderPipeline,
+    Sampler, ShaderModule, ShaderStages, TextureFormat, TextureFormatFeatureFlags, TextureUsages,
+    TextureView,
+};
+
+use crate::{shader_preprocessor::create_library_shader_module, texture::Texture};
+
+const MIPMAP_BLIT_WGSL: &str = include_str!("../shaders/mipmap_blit.wgsl");
+
+// Fills every level after the first by rendering the previous level through a
+// linear sampler, halving the size each time. Pipelines are cached per format so
+// one generator can be shared by every texture a scene loads.
+pub struct MipmapGenerator {
+    shader: ShaderModule,
+    sampler: Sampler,
+    bind_group_layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    pipelines: HashMap<TextureFormat, RenderPipeline>,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &Device) -> Self {
+        let shader = create_library_shader_module(
+            device,
+            "Mipmap Shader",
+            &[("shaders/mipmap_blit.wgsl", MIPMAP_BLIT_WGSL)],
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mipmap Bind Group Layout"),
+            entries: &Texture::bind_group_layout_entries(0, ShaderStages::FRAGMENT),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            sampler,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    pub fn is_supported(device: &Device, format: TextureFormat) -> bool {
+        let features = format.guaranteed_format_features(device.features());
+        features
+            .allowed_usages
+            .contains(TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING)
+            && features
+                .flags
+                .contains(TextureFormatFeatureFlags::FILTERABLE)
+    }
+
+    // The texture needs `RENDER_ATTACHMENT` and `TEXTURE_BINDING` usage. Every
+    // array layer is filled, so cubemaps get their six faces mipmapped.
+    pub fn generate(
+        &mut self,
+        device: &Device,
+        command_encoder: &mut CommandEncoder,
+        texture: &wgpu::Texture,
+    ) {
+        let format = texture.format();
+        assert!(
+            Self::is_supported(device, format),
+            "texture format {:?} to be renderable and filterable",
+            format
+        );
+        let pipeline = self.pipelines.entry(format).or_insert_with(|| {
+            create_pipeline(device, &self.shader, &self.pipeline_layout, format)
+        });
+
+        for layer in 0..texture.depth_or_array_layers() {
+            let views: Vec<TextureView> = (0..texture.mip_level_count())
+                .map(|mip_level| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("Mipmap View"),
+                        dimension: Some(wgpu::TextureViewDimension::D2),
+                        base_mip_level: mip_level,
+                        mip_level_count: Some(1),
+                        base_array_layer: layer,
+                        array_layer_count: Some(1),
+                        ..Default::default()
+                    })
+                })
+                .collect();
+
+            for level in views.windows(2) {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Mipmap Bind Group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&level[0]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                    ],
+                });
+                let mut render_pass =
+                    command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Mipmap Pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &level[1],
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+        }
+    }
+}
+
+pub fn generate_mipmaps(
+    device: &Device,
+    queue: &Queue,
+    mipmap_generator: &mut MipmapGenerator,
+    texture: &wgpu::Texture,
+) {
+    let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Mipmap Encoder"),
+    });
+    mipmap_generator.generate(device, &mut command_encoder, texture);
+    queue.submit(Some(command_encoder.finish()));
+}
+
+fn create_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    pipeline_layout: &PipelineLayout,
+    format: TextureFormat,
+) -> RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Mipmap Pipeline"),
+        layout: Some(pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(format.into())],
+        }),
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: None,
+    })
+}
@@ -1,6 +1,6 @@
 use std::f32::consts::PI;
 
-use cgmath::{ortho, perspective, Matrix4, Point3, Rad, Vector3};
+use cgmath::{ortho, perspective, Matrix, Matrix4, Point3, Rad, SquareMatrix, Vector3, Vector4};
 
 #[rustfmt::skip]
 #[allow(unused)]
@@ -19,14 +19,38 @@ pub fn create_view(
     Matrix4::look_at_rh(camera_position, look_direction, up_direction)
 }
 
+pub const PERSPECTIVE_FOVY: Rad<f32> = Rad(2.0 * PI / 5.0);
+pub const PERSPECTIVE_NEAR: f32 = 0.1;
+pub const PERSPECTIVE_FAR: f32 = 100.0;
+
 pub fn create_projection(aspect_ratio: f32, is_perspective: bool) -> Matrix4<f32> {
     if is_perspective {
-        OPENGL_TO_WGPU_MATRIX * perspective(Rad(2.0 * PI / 5.0), aspect_ratio, 0.1, 100.0)
+        create_perspective(
+            PERSPECTIVE_FOVY,
+            aspect_ratio,
+            PERSPECTIVE_NEAR,
+            PERSPECTIVE_FAR,
+        )
     } else {
-        OPENGL_TO_WGPU_MATRIX * ortho(-4.0, 4.0, -3.0, 3.0, -1.0, 6.0)
+        create_orthographic(-4.0, 4.0, -3.0, 3.0, -1.0, 6.0)
     }
 }
 
+pub fn create_perspective(fovy: Rad<f32>, aspect_ratio: f32, near: f32, far: f32) -> Matrix4<f32> {
+    OPENGL_TO_WGPU_MATRIX * perspective(fovy, aspect_ratio, near, far)
+}
+
+pub fn create_orthographic(
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near: f32,
+    far: f32,
+) -> Matrix4<f32> {
+    OPENGL_TO_WGPU_MATRIX * ortho(left, right, bottom, top, near, far)
+}
+
 pub fn create_view_projection(
     camera_position: Point3<f32>,
     look_direction: Point3<f32>,
@@ -53,7 +77,19 @@ pub fn create_transforms(
     let rotate_mat_z = Matrix4::from_angle_z(Rad(rotation[2]));
     let scale_mat = Matrix4::from_nonuniform_scale(scaling[0], scaling[1], scaling[2]);
 
-    let model_mat = trans_mat * rotate_mat_z * rotate_mat_y * rotate_mat_x * scale_mat;
+    trans_mat * rotate_mat_z * rotate_mat_y * rotate_mat_x * scale_mat
+}
 
-    model_mat
+// The inverse transpose of the model matrix, so normals stay perpendicular to
+// surfaces under non-uniform scaling. Translation is dropped.
+pub fn create_normal_matrix(model_matrix: Matrix4<f32>) -> Matrix4<f32> {
+    let mut normal_matrix = model_matrix
+        .invert()
+        .unwrap_or_else(Matrix4::identity)
+        .transpose();
+    normal_matrix.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+    normal_matrix.x.w = 0.0;
+    normal_matrix.y.w = 0.0;
+    normal_matrix.z.w = 0.0;
+    normal_matrix
 }
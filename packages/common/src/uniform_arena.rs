@@ -0,0 +1,138 @@
+use std::marker::PhantomData;
+
+use bytemuck::Pod;
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutEntry, Buffer, BufferAddress, BufferSize,
+    BufferUsages, Device, Queue, ShaderStages,
+};
+
+pub struct UniformArena<T: Pod> {
+    label: Option<String>,
+    buffer: Buffer,
+    bind_group: Option<BindGroup>,
+    staging: Vec<u8>,
+    stride: BufferAddress,
+    capacity: u32,
+    len: u32,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> UniformArena<T> {
+    pub fn new(device: &Device, label: Option<&str>, capacity: u32) -> Self {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as BufferAddress;
+        let stride = align_to(std::mem::size_of::<T>() as BufferAddress, alignment);
+        let capacity = capacity.max(1);
+
+        Self {
+            label: label.map(String::from),
+            buffer: Self::create_buffer(device, label, stride, capacity),
+            bind_group: None,
+            staging: Vec::with_capacity((stride * capacity as BufferAddress) as usize),
+            stride,
+            capacity,
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+    pub fn bind_group(&self) -> Option<&BindGroup> {
+        self.bind_group.as_ref()
+    }
+    pub fn stride(&self) -> BufferAddress {
+        self.stride
+    }
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bind_group_layout_entry(binding: u32, visibility: ShaderStages) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: BufferSize::new(std::mem::size_of::<T>() as BufferAddress),
+            },
+            count: None,
+        }
+    }
+
+    pub fn create_bind_group(&mut self, device: &Device, layout: &BindGroupLayout, binding: u32) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: self.label.as_deref(),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &self.buffer,
+                    offset: 0,
+                    size: BufferSize::new(std::mem::size_of::<T>() as BufferAddress),
+                }),
+            }],
+        }));
+    }
+
+    pub fn clear(&mut self) {
+        self.staging.clear();
+        self.len = 0;
+    }
+
+    pub fn push(&mut self, value: &T) -> u32 {
+        let offset = self.staging.len();
+        self.staging.extend_from_slice(bytemuck::bytes_of(value));
+        self.staging.resize(offset + self.stride as usize, 0);
+        self.len += 1;
+        offset as u32
+    }
+
+    pub fn offset(&self, index: u32) -> u32 {
+        (index as BufferAddress * self.stride) as u32
+    }
+
+    // Returns true when the pushed values no longer fit and the buffer was
+    // reallocated. The old bind group is dropped with it, so `create_bind_group`
+    // has to be called again before the arena is bound for drawing.
+    #[must_use]
+    pub fn upload(&mut self, device: &Device, queue: &Queue) -> bool {
+        let reallocated = self.len > self.capacity;
+        if reallocated {
+            self.capacity = self.len.next_power_of_two();
+            self.buffer =
+                Self::create_buffer(device, self.label.as_deref(), self.stride, self.capacity);
+            self.bind_group = None;
+        }
+
+        if !self.staging.is_empty() {
+            queue.write_buffer(&self.buffer, 0, &self.staging);
+        }
+        reallocated
+    }
+
+    fn create_buffer(
+        device: &Device,
+        label: Option<&str>,
+        stride: BufferAddress,
+        capacity: u32,
+    ) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label,
+            size: stride * capacity as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+}
+
+pub fn align_to(value: BufferAddress, alignment: BufferAddress) -> BufferAddress {
+    value.div_ceil(alignment) * alignment
+}
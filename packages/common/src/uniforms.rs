@@ -0,0 +1,149 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Point3, SquareMatrix};
+use wgpu::{BindGroupLayoutEntry, BufferAddress, ShaderStages};
+
+use crate::{camera::Camera, projection::Projection, transform::create_normal_matrix};
+
+pub const SCENE_UNIFORMS_WGSL: &str = include_str!("shaders/scene_uniforms.wgsl");
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct SceneUniforms {
+    pub view_matrix: [[f32; 4]; 4],
+    pub projection_matrix: [[f32; 4]; 4],
+    pub view_projection_matrix: [[f32; 4]; 4],
+    pub inverse_view_matrix: [[f32; 4]; 4],
+    pub inverse_projection_matrix: [[f32; 4]; 4],
+    pub inverse_view_projection_matrix: [[f32; 4]; 4],
+    pub camera_position: [f32; 4],
+    pub viewport_size: [f32; 2],
+    pub time: f32,
+    pub _padding: f32,
+}
+
+impl SceneUniforms {
+    pub const SIZE: BufferAddress = std::mem::size_of::<Self>() as BufferAddress;
+
+    pub fn new(
+        view_matrix: Matrix4<f32>,
+        projection_matrix: Matrix4<f32>,
+        camera_position: Point3<f32>,
+        viewport_size: [f32; 2],
+        time: f32,
+    ) -> Self {
+        let view_projection_matrix = projection_matrix * view_matrix;
+
+        Self {
+            view_matrix: view_matrix.into(),
+            projection_matrix: projection_matrix.into(),
+            view_projection_matrix: view_projection_matrix.into(),
+            inverse_view_matrix: invert_or_identity(view_matrix).into(),
+            inverse_projection_matrix: invert_or_identity(projection_matrix).into(),
+            inverse_view_projection_matrix: invert_or_identity(view_projection_matrix).into(),
+            camera_position: [camera_position.x, camera_position.y, camera_position.z, 1.0],
+            viewport_size,
+            time,
+            _padding: 0.0,
+        }
+    }
+
+    pub fn from_camera(
+        camera: &Camera,
+        projection: &Projection,
+        viewport_size: [f32; 2],
+        time: f32,
+    ) -> Self {
+        Self::new(
+            camera.view_matrix(),
+            *projection.projection_matrix(),
+            *camera.position(),
+            viewport_size,
+            time,
+        )
+    }
+
+    pub fn from_projection(projection: &Projection, viewport_size: [f32; 2], time: f32) -> Self {
+        Self::new(
+            *projection.view_matrix(),
+            *projection.projection_matrix(),
+            *projection.camera_position(),
+            viewport_size,
+            time,
+        )
+    }
+
+    pub fn bind_group_layout_entry(binding: u32, visibility: ShaderStages) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(Self::SIZE),
+            },
+            count: None,
+        }
+    }
+}
+
+impl Default for SceneUniforms {
+    fn default() -> Self {
+        Self::new(
+            Matrix4::identity(),
+            Matrix4::identity(),
+            Point3::new(0.0, 0.0, 0.0),
+            [1.0, 1.0],
+            0.0,
+        )
+    }
+}
+
+// The per-object block declared next to `SceneUniforms` by
+// `common/uniforms.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct ModelUniforms {
+    pub model_matrix: [[f32; 4]; 4],
+    pub normal_matrix: [[f32; 4]; 4],
+}
+
+impl ModelUniforms {
+    pub const SIZE: BufferAddress = std::mem::size_of::<Self>() as BufferAddress;
+
+    pub fn new(model_matrix: Matrix4<f32>) -> Self {
+        Self {
+            model_matrix: model_matrix.into(),
+            normal_matrix: create_normal_matrix(model_matrix).into(),
+        }
+    }
+
+    pub fn from_projection(projection: &Projection) -> Self {
+        Self {
+            model_matrix: (*projection.model_matrix()).into(),
+            normal_matrix: (*projection.normal_matrix()).into(),
+        }
+    }
+
+    pub fn bind_group_layout_entry(binding: u32, visibility: ShaderStages) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(Self::SIZE),
+            },
+            count: None,
+        }
+    }
+}
+
+impl Default for ModelUniforms {
+    fn default() -> Self {
+        Self::new(Matrix4::identity())
+    }
+}
+
+fn invert_or_identity(matrix: Matrix4<f32>) -> Matrix4<f32> {
+    matrix.invert().unwrap_or_else(Matrix4::identity)
+}
@@ -1,31 +1,111 @@
+pub mod primitives;
 pub mod vertex_data;
-use bytemuck::{Pod, Zeroable};
+pub use commonlib_derive::Vertex;
+use wgpu::VertexBufferLayout;
+
+pub trait Vertex: bytemuck::Pod {
+    const LAYOUT: VertexBufferLayout<'static>;
+}
+
+pub trait VertexPosition {
+    fn position(&self) -> [f32; 3];
+}
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Vertex)]
 pub struct Vertex2DColored {
     pub position: [f32; 2],
     pub color: [f32; 3],
 }
 
-unsafe impl Zeroable for Vertex2DColored {}
-unsafe impl Pod for Vertex2DColored {}
-
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Vertex)]
 pub struct Vertex3D {
     pub position: [f32; 3],
 }
 
-unsafe impl Zeroable for Vertex3D {}
-unsafe impl Pod for Vertex3D {}
-
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Vertex)]
 pub struct Vertex4DColored {
     pub position: [f32; 4],
     pub color: [f32; 4],
 }
 
-unsafe impl Zeroable for Vertex4DColored {}
-unsafe impl Pod for Vertex4DColored {}
\ No newline at end of file
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Vertex)]
+pub struct Vertex3DNormalUv {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Vertex)]
+pub struct Vertex3DNormalColored {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub color: [f32; 4],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::{VertexAttribute, VertexFormat, VertexStepMode};
+
+    fn attribute(format: VertexFormat, offset: u64, shader_location: u32) -> VertexAttribute {
+        VertexAttribute {
+            format,
+            offset,
+            shader_location,
+        }
+    }
+
+    #[test]
+    fn fields_are_laid_out_in_order() {
+        assert_eq!(
+            Vertex3DNormalUv::ATTRIBUTES,
+            [
+                attribute(VertexFormat::Float32x3, 0, 0),
+                attribute(VertexFormat::Float32x3, 12, 1),
+                attribute(VertexFormat::Float32x2, 24, 2),
+            ]
+        );
+        assert_eq!(
+            Vertex2DColored::ATTRIBUTES,
+            [
+                attribute(VertexFormat::Float32x2, 0, 0),
+                attribute(VertexFormat::Float32x3, 8, 1),
+            ]
+        );
+        assert_eq!(
+            Vertex4DColored::ATTRIBUTES,
+            [
+                attribute(VertexFormat::Float32x4, 0, 0),
+                attribute(VertexFormat::Float32x4, 16, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn layout_uses_the_struct_size_and_steps_per_vertex() {
+        let layout = Vertex3DNormalColored::LAYOUT;
+        assert_eq!(layout.array_stride, 40);
+        assert_eq!(layout.step_mode, VertexStepMode::Vertex);
+        assert_eq!(layout.attributes, &Vertex3DNormalColored::ATTRIBUTES);
+        assert_eq!(<Vertex3D as Vertex>::LAYOUT.array_stride, 12);
+    }
+
+    #[test]
+    fn position_is_widened_to_three_components() {
+        let vertex = Vertex2DColored {
+            position: [1.0, 2.0],
+            color: [0.0; 3],
+        };
+        assert_eq!(vertex.position(), [1.0, 2.0, 0.0]);
+        let vertex = Vertex4DColored {
+            position: [1.0, 2.0, 3.0, 4.0],
+            color: [0.0; 4],
+        };
+        assert_eq!(vertex.position(), [1.0, 2.0, 3.0]);
+    }
+}
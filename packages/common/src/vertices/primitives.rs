@@ -0,0 +1,529 @@
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+};
+
+use cgmath::{InnerSpace, Vector3};
+use wgpu::PrimitiveTopology;
+
+use crate::{
+    mesh::MeshData,
+    vertices::{vertex_data, Vertex3DNormalUv},
+};
+
+fn vertex(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Vertex3DNormalUv {
+    Vertex3DNormalUv {
+        position,
+        normal,
+        uv,
+    }
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    Vector3::from(v).normalize().into()
+}
+
+// Connects `rows` rings of `columns + 1` vertices (the last column duplicates the first to carry
+// the uv seam). Rings are ordered from top to bottom and wound counter-clockwise seen from outside.
+pub(crate) fn grid_indices(rows: u32, columns: u32, skip_first: bool, skip_last: bool) -> Vec<u32> {
+    let mut indices = Vec::with_capacity((rows as usize) * (columns as usize) * 6);
+    for row in 0..rows - 1 {
+        for column in 0..columns {
+            let a = row * (columns + 1) + column;
+            let b = a + columns + 1;
+            if !(skip_first && row == 0) {
+                indices.extend_from_slice(&[a, b, a + 1]);
+            }
+            if !(skip_last && row == rows - 2) {
+                indices.extend_from_slice(&[a + 1, b, b + 1]);
+            }
+        }
+    }
+    indices
+}
+
+pub fn cube(half_extent: f32) -> MeshData<Vertex3DNormalUv> {
+    let (positions, _colors, uvs, normals) = vertex_data::cube_data();
+    let vertices = positions
+        .iter()
+        .zip(uvs.iter())
+        .zip(normals.iter())
+        .map(|((position, uv), normal)| {
+            vertex(
+                [
+                    position[0] as f32 * half_extent,
+                    position[1] as f32 * half_extent,
+                    position[2] as f32 * half_extent,
+                ],
+                [normal[0] as f32, normal[1] as f32, normal[2] as f32],
+                [uv[0] as f32, uv[1] as f32],
+            )
+        })
+        .collect::<Vec<_>>();
+    let indices = (0..vertices.len() as u32).collect();
+
+    MeshData::indexed(vertices, indices, PrimitiveTopology::TriangleList)
+}
+
+pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> MeshData<Vertex3DNormalUv> {
+    let sectors = sectors.max(3);
+    let stacks = stacks.max(2);
+
+    let mut vertices = Vec::with_capacity(((stacks + 1) * (sectors + 1)) as usize);
+    for stack in 0..=stacks {
+        let phi = PI * stack as f32 / stacks as f32;
+        for sector in 0..=sectors {
+            let theta = TAU * sector as f32 / sectors as f32;
+            let normal = [phi.sin() * theta.cos(), phi.cos(), -phi.sin() * theta.sin()];
+            vertices.push(vertex(
+                [normal[0] * radius, normal[1] * radius, normal[2] * radius],
+                normal,
+                [sector as f32 / sectors as f32, stack as f32 / stacks as f32],
+            ));
+        }
+    }
+
+    MeshData::indexed(
+        vertices,
+        grid_indices(stacks + 1, sectors, true, true),
+        PrimitiveTopology::TriangleList,
+    )
+}
+
+pub fn icosphere(radius: f32, subdivisions: u32) -> MeshData<Vertex3DNormalUv> {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut positions: Vec<[f32; 3]> = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .iter()
+    .map(|position| normalize(*position))
+    .collect();
+
+    #[rustfmt::skip]
+    let mut faces: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32, positions: &mut Vec<[f32; 3]>| -> u32 {
+            let key = (a.min(b), a.max(b));
+            *midpoints.entry(key).or_insert_with(|| {
+                let (pa, pb) = (positions[a as usize], positions[b as usize]);
+                positions.push(normalize([pa[0] + pb[0], pa[1] + pb[1], pa[2] + pb[2]]));
+                positions.len() as u32 - 1
+            })
+        };
+
+        faces = faces
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let ab = midpoint(a, b, &mut positions);
+                let bc = midpoint(b, c, &mut positions);
+                let ca = midpoint(c, a, &mut positions);
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let vertices = positions
+        .iter()
+        .map(|normal| {
+            vertex(
+                [normal[0] * radius, normal[1] * radius, normal[2] * radius],
+                *normal,
+                [
+                    0.5 + (-normal[2]).atan2(normal[0]) / TAU,
+                    normal[1].clamp(-1.0, 1.0).acos() / PI,
+                ],
+            )
+        })
+        .collect();
+
+    MeshData::indexed(
+        vertices,
+        faces.into_iter().flatten().collect(),
+        PrimitiveTopology::TriangleList,
+    )
+}
+
+fn push_cap(
+    vertices: &mut Vec<Vertex3DNormalUv>,
+    indices: &mut Vec<u32>,
+    radius: f32,
+    y: f32,
+    segments: u32,
+    facing_up: bool,
+) {
+    let normal = if facing_up {
+        [0.0, 1.0, 0.0]
+    } else {
+        [0.0, -1.0, 0.0]
+    };
+    let center = vertices.len() as u32;
+    vertices.push(vertex([0.0, y, 0.0], normal, [0.5, 0.5]));
+    for segment in 0..=segments {
+        let theta = TAU * segment as f32 / segments as f32;
+        let (x, z) = (theta.cos(), -theta.sin());
+        vertices.push(vertex(
+            [x * radius, y, z * radius],
+            normal,
+            [0.5 + x * 0.5, 0.5 + z * 0.5],
+        ));
+    }
+    for segment in 0..segments {
+        let current = center + 1 + segment;
+        if facing_up {
+            indices.extend_from_slice(&[center, current, current + 1]);
+        } else {
+            indices.extend_from_slice(&[center, current + 1, current]);
+        }
+    }
+}
+
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshData<Vertex3DNormalUv> {
+    let segments = segments.max(3);
+    let half_height = height / 2.0;
+
+    let mut vertices = Vec::with_capacity((4 * (segments + 2)) as usize);
+    for (row, y) in [half_height, -half_height].iter().enumerate() {
+        for segment in 0..=segments {
+            let theta = TAU * segment as f32 / segments as f32;
+            let normal = [theta.cos(), 0.0, -theta.sin()];
+            vertices.push(vertex(
+                [normal[0] * radius, *y, normal[2] * radius],
+                normal,
+                [segment as f32 / segments as f32, row as f32],
+            ));
+        }
+    }
+    let mut indices = grid_indices(2, segments, false, false);
+
+    push_cap(
+        &mut vertices,
+        &mut indices,
+        radius,
+        half_height,
+        segments,
+        true,
+    );
+    push_cap(
+        &mut vertices,
+        &mut indices,
+        radius,
+        -half_height,
+        segments,
+        false,
+    );
+
+    MeshData::indexed(vertices, indices, PrimitiveTopology::TriangleList)
+}
+
+pub fn cone(radius: f32, height: f32, segments: u32) -> MeshData<Vertex3DNormalUv> {
+    let segments = segments.max(3);
+    let half_height = height / 2.0;
+    let slant = Vector3::new(height, radius, 0.0).normalize();
+
+    let mut vertices = Vec::with_capacity((3 * (segments + 1) + 1) as usize);
+    // The apex is duplicated per segment so each side face gets its own smooth normal.
+    for segment in 0..segments {
+        let theta = TAU * (segment as f32 + 0.5) / segments as f32;
+        vertices.push(vertex(
+            [0.0, half_height, 0.0],
+            [slant.x * theta.cos(), slant.y, -slant.x * theta.sin()],
+            [(segment as f32 + 0.5) / segments as f32, 0.0],
+        ));
+    }
+    for segment in 0..=segments {
+        let theta = TAU * segment as f32 / segments as f32;
+        vertices.push(vertex(
+            [theta.cos() * radius, -half_height, -theta.sin() * radius],
+            [slant.x * theta.cos(), slant.y, -slant.x * theta.sin()],
+            [segment as f32 / segments as f32, 1.0],
+        ));
+    }
+
+    let mut indices = Vec::with_capacity((6 * segments) as usize);
+    for segment in 0..segments {
+        let base = segments + segment;
+        indices.extend_from_slice(&[segment, base, base + 1]);
+    }
+
+    push_cap(
+        &mut vertices,
+        &mut indices,
+        radius,
+        -half_height,
+        segments,
+        false,
+    );
+
+    MeshData::indexed(vertices, indices, PrimitiveTopology::TriangleList)
+}
+
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> MeshData<Vertex3DNormalUv> {
+    let major_segments = major_segments.max(3);
+    let minor_segments = minor_segments.max(3);
+
+    let mut vertices = Vec::with_capacity(((major_segments + 1) * (minor_segments + 1)) as usize);
+    for minor in 0..=minor_segments {
+        let phi = TAU * minor as f32 / minor_segments as f32;
+        for major in 0..=major_segments {
+            let theta = TAU * major as f32 / major_segments as f32;
+            let normal = [phi.cos() * theta.cos(), phi.sin(), -phi.cos() * theta.sin()];
+            let ring = major_radius + minor_radius * phi.cos();
+            vertices.push(vertex(
+                [
+                    ring * theta.cos(),
+                    minor_radius * phi.sin(),
+                    -ring * theta.sin(),
+                ],
+                normal,
+                [
+                    major as f32 / major_segments as f32,
+                    minor as f32 / minor_segments as f32,
+                ],
+            ));
+        }
+    }
+
+    // Rings advance from the outer equator over the top, so they run opposite to the sphere's.
+    let mut indices = grid_indices(minor_segments + 1, major_segments, false, false);
+    for triangle in indices.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+    }
+
+    MeshData::indexed(vertices, indices, PrimitiveTopology::TriangleList)
+}
+
+pub fn plane(
+    width: f32,
+    depth: f32,
+    subdivisions_x: u32,
+    subdivisions_z: u32,
+) -> MeshData<Vertex3DNormalUv> {
+    let columns = subdivisions_x.max(1);
+    let rows = subdivisions_z.max(1);
+
+    let mut vertices = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+    for row in 0..=rows {
+        let v = row as f32 / rows as f32;
+        for column in 0..=columns {
+            let u = column as f32 / columns as f32;
+            vertices.push(vertex(
+                [(u - 0.5) * width, 0.0, (v - 0.5) * depth],
+                [0.0, 1.0, 0.0],
+                [u, v],
+            ));
+        }
+    }
+
+    MeshData::indexed(
+        vertices,
+        grid_indices(rows + 1, columns, false, false),
+        PrimitiveTopology::TriangleList,
+    )
+}
+
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> MeshData<Vertex3DNormalUv> {
+    let segments = segments.max(3);
+    let rings = rings.max(1);
+    let half_height = height / 2.0;
+    let total_length = height + PI * radius;
+
+    let mut vertices = Vec::with_capacity((2 * (rings + 1) * (segments + 1)) as usize);
+    for ring in 0..2 * (rings + 1) {
+        let (phi, y_offset, arc) = if ring <= rings {
+            let phi = PI / 2.0 * ring as f32 / rings as f32;
+            (phi, half_height, phi * radius)
+        } else {
+            let phi = PI / 2.0 + PI / 2.0 * (ring - rings - 1) as f32 / rings as f32;
+            (phi, -half_height, phi * radius + height)
+        };
+        for segment in 0..=segments {
+            let theta = TAU * segment as f32 / segments as f32;
+            let normal = [phi.sin() * theta.cos(), phi.cos(), -phi.sin() * theta.sin()];
+            vertices.push(vertex(
+                [
+                    normal[0] * radius,
+                    normal[1] * radius + y_offset,
+                    normal[2] * radius,
+                ],
+                normal,
+                [segment as f32 / segments as f32, arc / total_length],
+            ));
+        }
+    }
+
+    MeshData::indexed(
+        vertices,
+        grid_indices(2 * (rings + 1), segments, true, true),
+        PrimitiveTopology::TriangleList,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Merges vertices that share a position, so seams, poles and the separate cap rims close up
+    // and the mesh can be checked as a surface.
+    fn welded_triangles(mesh: &MeshData<Vertex3DNormalUv>) -> Vec<[u32; 3]> {
+        let mut welded: HashMap<[i32; 3], u32> = HashMap::new();
+        let remap: Vec<u32> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let key = vertex.position.map(|value| (value * 1.0e4).round() as i32);
+                let next = welded.len() as u32;
+                *welded.entry(key).or_insert(next)
+            })
+            .collect();
+
+        mesh.indices
+            .as_ref()
+            .unwrap()
+            .chunks_exact(3)
+            .map(|triangle| {
+                [
+                    remap[triangle[0] as usize],
+                    remap[triangle[1] as usize],
+                    remap[triangle[2] as usize],
+                ]
+            })
+            .collect()
+    }
+
+    fn assert_closed(name: &str, mesh: &MeshData<Vertex3DNormalUv>) {
+        let mut edges: HashMap<(u32, u32), u32> = HashMap::new();
+        for [a, b, c] in welded_triangles(mesh) {
+            assert!(
+                a != b && b != c && c != a,
+                "{}: degenerate triangle after welding",
+                name
+            );
+            for (start, end) in [(a, b), (b, c), (c, a)] {
+                *edges.entry((start.min(end), start.max(end))).or_insert(0) += 1;
+            }
+        }
+        for (edge, count) in edges {
+            assert_eq!(
+                count, 2,
+                "{}: edge {:?} is shared by {} faces",
+                name, edge, count
+            );
+        }
+    }
+
+    fn assert_unit_normals(name: &str, mesh: &MeshData<Vertex3DNormalUv>) {
+        for vertex in &mesh.vertices {
+            let length = Vector3::from(vertex.normal).magnitude();
+            assert!(
+                (length - 1.0).abs() <= 1.0e-4,
+                "{}: normal {:?} has length {}",
+                name,
+                vertex.normal,
+                length
+            );
+        }
+    }
+
+    fn assert_counts(mesh: &MeshData<Vertex3DNormalUv>, vertices: usize, indices: usize) {
+        assert_eq!(mesh.vertices.len(), vertices);
+        assert_eq!(mesh.indices.as_ref().unwrap().len(), indices);
+        assert!(mesh
+            .indices
+            .as_ref()
+            .unwrap()
+            .iter()
+            .all(|&index| (index as usize) < vertices));
+    }
+
+    #[test]
+    fn cube_counts_and_surface() {
+        let mesh = cube(1.0);
+        assert_counts(&mesh, 36, 36);
+        assert_closed("cube", &mesh);
+        assert_unit_normals("cube", &mesh);
+    }
+
+    #[test]
+    fn uv_sphere_counts_and_surface() {
+        let mesh = uv_sphere(1.0, 16, 8);
+        assert_counts(&mesh, 9 * 17, 16 * 7 * 6);
+        assert_closed("uv_sphere", &mesh);
+        assert_unit_normals("uv_sphere", &mesh);
+    }
+
+    #[test]
+    fn icosphere_counts_and_surface() {
+        let mesh = icosphere(1.0, 2);
+        assert_counts(&mesh, 162, 20 * 16 * 3);
+        assert_closed("icosphere", &mesh);
+        assert_unit_normals("icosphere", &mesh);
+    }
+
+    #[test]
+    fn cylinder_counts_and_surface() {
+        let mesh = cylinder(1.0, 2.0, 16);
+        assert_counts(&mesh, 2 * 17 + 2 * 18, 16 * 6 + 2 * 16 * 3);
+        assert_closed("cylinder", &mesh);
+        assert_unit_normals("cylinder", &mesh);
+    }
+
+    #[test]
+    fn cone_counts_and_surface() {
+        let mesh = cone(1.0, 2.0, 16);
+        assert_counts(&mesh, 16 + 17 + 18, 16 * 3 + 16 * 3);
+        assert_closed("cone", &mesh);
+        assert_unit_normals("cone", &mesh);
+    }
+
+    #[test]
+    fn torus_counts_and_surface() {
+        let mesh = torus(2.0, 0.5, 24, 12);
+        assert_counts(&mesh, 25 * 13, 24 * 12 * 6);
+        assert_closed("torus", &mesh);
+        assert_unit_normals("torus", &mesh);
+    }
+
+    #[test]
+    fn capsule_counts_and_surface() {
+        let mesh = capsule(0.5, 1.0, 16, 4);
+        assert_counts(&mesh, 10 * 17, 16 * 8 * 6);
+        assert_closed("capsule", &mesh);
+        assert_unit_normals("capsule", &mesh);
+    }
+
+    #[test]
+    fn plane_counts_and_normals() {
+        let mesh = plane(1.0, 1.0, 4, 3);
+        assert_counts(&mesh, 5 * 4, 4 * 3 * 6);
+        assert_unit_normals("plane", &mesh);
+    }
+
+    #[test]
+    fn segment_counts_are_clamped() {
+        assert_counts(&uv_sphere(1.0, 0, 0), 3 * 4, 3 * 6);
+        assert_counts(&cylinder(1.0, 1.0, 1), 2 * 4 + 2 * 5, 3 * 6 + 2 * 3 * 3);
+    }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::type_complexity)]
 pub fn cube_data() -> (Vec<[i8; 3]>, Vec<[i8; 3]>, Vec<[i8; 2]>, Vec<[i8; 3]>) {
     let positions = [
         // front (0, 0, 1)